@@ -79,8 +79,9 @@ pub fn expand(_attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
             use super::*;
             use ::tola_vdom::core::{
                 Family, HasStableId, Phase, PhaseExt, ElementExt, IndexedExt as IndexedExtTrait,
-                NoneFamily, NoneIndexed, NoneProcessed, ExtractFamily,
+                NoneFamily, NoneIndexed, NoneProcessed, ExtractFamily, ExtChange, DocDiff,
             };
+            use ::tola_vdom::algo::StableHasher;
             use ::tola_vdom::id::StableId;
             use ::tola_vdom::span::SourceSpan;
             use ::tola_vdom::transform::IndexStats;
@@ -134,6 +135,27 @@ pub fn expand(_attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
                         #(Self::#variant_names(_) => <#field_types as Family>::NAME),*
                     }
                 }
+
+                fn diff_ext(&self, new: &Self) -> ExtChange {
+                    match (self, new) {
+                        (Self::None(_), Self::None(_)) => ExtChange::Unchanged,
+                        #(
+                            (Self::#variant_names(old), Self::#variant_names(new)) => {
+                                <#field_types as Family>::diff_indexed(old, new)
+                            }
+                        )*
+                        // Changed family: its data may render differently
+                        #[allow(unreachable_patterns)]
+                        _ => ExtChange::Replace,
+                    }
+                }
+
+                fn hash_ext(&self, hasher: StableHasher) -> StableHasher {
+                    match self {
+                        Self::None(_) => hasher,
+                        #(Self::#variant_names(data) => <#field_types as Family>::hash_indexed(data, hasher)),*
+                    }
+                }
             }
 
             impl HasStableId for IndexedExt {
@@ -171,6 +193,27 @@ pub fn expand(_attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
                         #(Self::#variant_names(_) => <#field_types as Family>::NAME),*
                     }
                 }
+
+                fn diff_ext(&self, new: &Self) -> ExtChange {
+                    match (self, new) {
+                        (Self::None(_), Self::None(_)) => ExtChange::Unchanged,
+                        #(
+                            (Self::#variant_names(old), Self::#variant_names(new)) => {
+                                <#field_types as Family>::diff_processed(old, new)
+                            }
+                        )*
+                        // Changed family: its data may render differently
+                        #[allow(unreachable_patterns)]
+                        _ => ExtChange::Replace,
+                    }
+                }
+
+                fn hash_ext(&self, hasher: StableHasher) -> StableHasher {
+                    match self {
+                        Self::None(_) => hasher,
+                        #(Self::#variant_names(data) => <#field_types as Family>::hash_processed(data, hasher)),*
+                    }
+                }
             }
 
             impl HasStableId for ProcessedExt {
@@ -257,6 +300,10 @@ pub fn expand(_attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
                 pub node_count: usize,
             }
 
+            impl DocDiff for RawDocExt {}
+            impl DocDiff for IndexedDocExt {}
+            impl DocDiff for ProcessedDocExt {}
+
             // =================================================================
             // Text Extensions
             // =================================================================
@@ -267,10 +314,11 @@ pub fn expand(_attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
                 pub span: Option<SourceSpan>,
             }
 
-            /// Text extension for Indexed phase - StableId.
+            /// Text extension for Indexed phase - StableId and source span.
             #[derive(Debug, Clone, Default)]
             pub struct IndexedTextExt {
                 pub stable_id: StableId,
+                pub span: Option<SourceSpan>,
             }
 
             impl HasStableId for IndexedTextExt {
@@ -279,6 +327,29 @@ pub fn expand(_attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
                 }
             }
 
+            impl ::tola_vdom::span::SpannedExt for RawTextExt {
+                fn from_span(span: SourceSpan) -> Self {
+                    Self { span: Some(span) }
+                }
+
+                fn span(&self) -> Option<SourceSpan> {
+                    self.span
+                }
+            }
+
+            impl ::tola_vdom::span::SpannedExt for IndexedTextExt {
+                fn from_span(span: SourceSpan) -> Self {
+                    Self {
+                        stable_id: StableId::default(),
+                        span: Some(span),
+                    }
+                }
+
+                fn span(&self) -> Option<SourceSpan> {
+                    self.span
+                }
+            }
+
             // =================================================================
             // Family Identification
             // =================================================================
@@ -369,6 +440,7 @@ pub fn expand(_attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
             type IndexDocExtFn = fn(RawDocExt, ::tola_vdom::transform::IndexStats) -> IndexedDocExt;
             type ProcessExtFn = fn(&IndexedExt) -> ProcessedExt;
             type ProcessDocExtFn = fn(&IndexedDocExt) -> ProcessedDocExt;
+            type ParseExtFn = fn(&str, &::tola_vdom::attr::Attrs) -> RawExt;
 
             /// Create an Indexer configured for this site's phases.
             ///
@@ -380,8 +452,11 @@ pub fn expand(_attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
                 fn do_index_ext(ext: RawExt, id: StableId) -> IndexedExt {
                     index_ext(ext, id)
                 }
-                fn do_index_text(_text_ext: RawTextExt, id: StableId) -> IndexedTextExt {
-                    IndexedTextExt { stable_id: id }
+                fn do_index_text(text_ext: RawTextExt, id: StableId) -> IndexedTextExt {
+                    IndexedTextExt {
+                        stable_id: id,
+                        span: text_ext.span,
+                    }
                 }
                 fn do_index_doc(doc_ext: RawDocExt, stats: ::tola_vdom::transform::IndexStats) -> IndexedDocExt {
                     IndexedDocExt {
@@ -410,6 +485,24 @@ pub fn expand(_attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
                 ::tola_vdom::Processor::new(do_process_ext, do_process_doc)
             }
 
+            /// Create an HTML parser producing Raw nodes for this site.
+            ///
+            /// ```ignore
+            /// let nodes = MySite::parser().parse_fragment("<p>Hello</p>");
+            /// ```
+            pub fn parser() -> ::tola_vdom::parse::Parser<Raw, ParseExtFn> {
+                ::tola_vdom::parse::Parser::new(create_raw_ext)
+            }
+
+            /// Parse an HTML document into a Raw document.
+            ///
+            /// ```ignore
+            /// let doc = MySite::parse("<html><body><p>Hello</p></body></html>");
+            /// ```
+            pub fn parse(html: &str) -> ::tola_vdom::Document<Raw> {
+                parser().parse_document(html)
+            }
+
             // =================================================================
             // Serialization Trait Implementations
             // =================================================================
@@ -455,6 +548,7 @@ pub fn expand(_attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
                 fn from_stable_id(id: u64) -> Self {
                     Self {
                         stable_id: StableId::from_raw(id),
+                        span: None,
                     }
                 }
             }
@@ -0,0 +1,478 @@
+//! Patch Application
+//!
+//! Applies `PatchOp`s produced by [`diff`](super::diff) to a `Document` on the
+//! Rust side, so a cached VDOM stays in sync with what the browser holds
+//! without re-indexing:
+//!
+//! ```text
+//! let result = diff(&cached, &new);
+//! apply_patches(&mut cached, &result.ops)?;  // cached now matches new
+//! ```
+//!
+//! Operations run in order. Targets and anchors are resolved by StableId
+//! against the document as it stands when each operation runs, the same way
+//! the browser runtime resolves them.
+//!
+//! `UpdateText` only carries the new text, so the resulting text node gets a
+//! default `TextExt`. `UpdateDoc` leaves `Document::meta` alone, since a
+//! `DocChange` cannot rebuild it; copy the new document's `meta` over.
+
+use thiserror::Error;
+
+use crate::core::{HasStableId, PhaseExt};
+use crate::id::StableId;
+use crate::node::{Document, Element, Node, Text};
+
+use super::diff::{Anchor, PatchOp};
+
+/// Error applying a patch operation.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ApplyError {
+    /// No element with the operation's target StableId.
+    #[error("patch target {0} not found")]
+    TargetNotFound(StableId),
+
+    /// No element with the anchor's StableId.
+    #[error("anchor element {0} not found")]
+    AnchorNotFound(StableId),
+
+    /// `Remove` or `Move` targeted the root element.
+    #[error("cannot detach the root element {0}")]
+    DetachRoot(StableId),
+
+    /// `Before`/`After` anchor referenced the root element.
+    #[error("cannot place a node beside the root element {0}")]
+    RootSibling(StableId),
+
+    /// `Move` anchor lies inside the moved element.
+    #[error("cannot move element {0} into its own subtree")]
+    MoveIntoSelf(StableId),
+
+    /// Text op index does not refer to a text node.
+    #[error("child {index} of {parent} is not a text node")]
+    NotText {
+        /// Parent element
+        parent: StableId,
+        /// Child index
+        index: usize,
+    },
+
+    /// `ChildIndex` anchor is past the end of the parent's children.
+    #[error("child index {index} is out of range for {parent}")]
+    IndexOutOfRange {
+        /// Parent element
+        parent: StableId,
+        /// Child index
+        index: usize,
+    },
+}
+
+/// Apply patch operations to a document in order.
+///
+/// On error, the operations before the failing one remain applied.
+///
+/// Each operation locates its target with a document walk, so applying
+/// `k` operations costs O(k * n).
+pub fn apply_patches<P>(doc: &mut Document<P>, ops: &[PatchOp<P>]) -> Result<(), ApplyError>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId + Clone,
+{
+    ops.iter().try_for_each(|op| apply_patch(doc, op))
+}
+
+/// Apply a single patch operation to a document.
+pub fn apply_patch<P>(doc: &mut Document<P>, op: &PatchOp<P>) -> Result<(), ApplyError>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId + Clone,
+{
+    let root = &mut doc.root;
+    match op {
+        PatchOp::Replace { target, element, .. } => {
+            *target_mut(root, *target)? = Element::clone(element);
+        }
+        PatchOp::UpdateText { target, text } => {
+            // Same as `element.textContent = text`
+            let elem = target_mut(root, *target)?;
+            elem.children.clear();
+            if !text.is_empty() {
+                elem.children.push(Node::Text(Text::new(text.as_str())));
+            }
+        }
+        PatchOp::UpdateTextAt {
+            parent,
+            index,
+            text,
+        }
+        | PatchOp::SpliceText {
+            parent,
+            index,
+            text,
+            ..
+        } => {
+            let children = &mut target_mut(root, *parent)?.children;
+            match children.get_mut(*index) {
+                Some(Node::Text(node)) => node.content = text.as_str().into(),
+                _ => return Err(ApplyError::NotText { parent: *parent, index: *index }),
+            }
+        }
+        PatchOp::RemoveTextAt { parent, index } => {
+            let children = &mut target_mut(root, *parent)?.children;
+            if !matches!(children.get(*index), Some(Node::Text(_))) {
+                return Err(ApplyError::NotText { parent: *parent, index: *index });
+            }
+            children.remove(*index);
+        }
+        PatchOp::ReplaceChildren { target, children, .. } => {
+            target_mut(root, *target)?.children = children.iter().cloned().collect();
+        }
+        PatchOp::Remove { target } => {
+            let path = find_path(root, *target).ok_or(ApplyError::TargetNotFound(*target))?;
+            detach(root, &path).ok_or(ApplyError::DetachRoot(*target))?;
+        }
+        PatchOp::Insert { anchor, node, .. } => {
+            insert_at(root, *anchor, node.clone())?;
+        }
+        PatchOp::Move { target, to } => {
+            let path = find_path(root, *target).ok_or(ApplyError::TargetNotFound(*target))?;
+            let anchor_id = to.target_id();
+            let anchor_path = find_path(root, anchor_id).ok_or(ApplyError::AnchorNotFound(anchor_id))?;
+            if anchor_path.starts_with(&path) {
+                return Err(ApplyError::MoveIntoSelf(*target));
+            }
+            let node = detach(root, &path).ok_or(ApplyError::DetachRoot(*target))?;
+            insert_at(root, *to, node)?;
+        }
+        PatchOp::UpdateAttrs { target, changes } => {
+            let elem = target_mut(root, *target)?;
+            for (name, value) in changes {
+                match value {
+                    Some(value) => elem.set_attr(name.clone(), value.clone()),
+                    None => {
+                        elem.remove_attr(name);
+                    }
+                }
+            }
+        }
+        PatchOp::UpdateClasses { target, value, .. } => {
+            target_mut(root, *target)?.set_attr("class", value.clone());
+        }
+        PatchOp::UpdateStyles { target, value, .. } => {
+            target_mut(root, *target)?.set_attr("style", value.clone());
+        }
+        PatchOp::UpdateExt { target, ext, .. } => {
+            target_mut(root, *target)?.ext = ext.clone();
+        }
+        PatchOp::Rekey { target, element } => {
+            // Same content, so this only swaps the StableIds
+            *target_mut(root, *target)? = Element::clone(element);
+        }
+        PatchOp::UpdateDoc { .. } => {}
+    }
+    Ok(())
+}
+
+// =============================================================================
+// Helpers
+// =============================================================================
+
+/// Insert `node` at the position described by `anchor`.
+fn insert_at<P>(root: &mut Element<P>, anchor: Anchor, node: Node<P>) -> Result<(), ApplyError>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    let id = anchor.target_id();
+    let path = find_path(root, id).ok_or(ApplyError::AnchorNotFound(id))?;
+    match anchor {
+        Anchor::FirstChildOf(_) => descend_mut(root, &path).children.insert(0, node),
+        Anchor::LastChildOf(_) => descend_mut(root, &path).children.push(node),
+        Anchor::ChildIndex(_, index) => {
+            let children = &mut descend_mut(root, &path).children;
+            if index > children.len() {
+                return Err(ApplyError::IndexOutOfRange { parent: id, index });
+            }
+            children.insert(index, node);
+        }
+        Anchor::Before(_) | Anchor::After(_) => {
+            let (&index, parent) = path.split_last().ok_or(ApplyError::RootSibling(id))?;
+            let index = if matches!(anchor, Anchor::After(_)) { index + 1 } else { index };
+            descend_mut(root, parent).children.insert(index, node);
+        }
+    }
+    Ok(())
+}
+
+/// The element with StableId `id`, or `TargetNotFound`.
+fn target_mut<P>(root: &mut Element<P>, id: StableId) -> Result<&mut Element<P>, ApplyError>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    let path = find_path(root, id).ok_or(ApplyError::TargetNotFound(id))?;
+    Ok(descend_mut(root, &path))
+}
+
+/// Remove and return the node at `path` (`None` for the root).
+fn detach<P: PhaseExt>(root: &mut Element<P>, path: &[usize]) -> Option<Node<P>> {
+    let (&index, parent) = path.split_last()?;
+    Some(descend_mut(root, parent).children.remove(index))
+}
+
+/// Child indices from `root` to the element with StableId `id`.
+pub(super) fn find_path<P>(root: &Element<P>, id: StableId) -> Option<Vec<usize>>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    if root.ext.stable_id() == id {
+        return Some(Vec::new());
+    }
+
+    // Open elements and the index of the next child to visit in each
+    let mut stack = vec![(root, 0)];
+    while let Some((elem, next)) = stack.last_mut() {
+        let elem = *elem;
+        let Some(child) = elem.children.get(*next) else {
+            stack.pop();
+            continue;
+        };
+        *next += 1;
+        if let Node::Element(child) = child {
+            if child.ext.stable_id() == id {
+                return Some(stack.iter().map(|(_, next)| next - 1).collect());
+            }
+            stack.push((child, 0));
+        }
+    }
+    None
+}
+
+/// Follow a path produced by `find_path`.
+///
+/// Every caller modifies the subtree at the end of the path, so the cached
+/// content hashes of the elements passed are cleared on the way.
+fn descend_mut<'a, P: PhaseExt>(root: &'a mut Element<P>, path: &[usize]) -> &'a mut Element<P> {
+    root.invalidate_hash();
+    path.iter().fold(root, |elem, &index| {
+        let child = elem.children[index]
+            .as_element_mut()
+            .expect("path only passes through elements");
+        child.invalidate_hash();
+        child
+    })
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+    use crate::algo::test_rng::Rng;
+    use crate::algo::{diff, diff_with_config, invert, DiffConfig};
+    use crate::families::SvgFamily;
+    use crate::node::Comment;
+    use crate::render::{render_document, RenderConfig};
+    use crate::transform::Transform;
+    use crate::vdom;
+
+    #[vdom::families]
+    pub struct ApplyTestSite {
+        svg: SvgFamily,
+    }
+
+    type Indexed = ApplyTestSite::Indexed;
+
+    fn elem(tag: &str, id: u64) -> Element<Indexed> {
+        let ext = ApplyTestSite::IndexedExt::None(crate::core::NoneIndexed {
+            stable_id: StableId::from_raw(id),
+        });
+        Element::with_ext(tag, ext)
+    }
+
+    fn id(raw: u64) -> StableId {
+        StableId::from_raw(raw)
+    }
+
+    fn html(doc: &Document<Indexed>) -> String {
+        render_document(doc, &RenderConfig::new(true, false))
+    }
+
+    fn list(items: &[u64]) -> Document<Indexed> {
+        let mut root = elem("ul", 0);
+        for &item in items {
+            root.children.push(Node::Element(Box::new(elem("li", item))));
+        }
+        Document::new(root)
+    }
+
+    #[test]
+    fn test_apply_basic_ops() {
+        let mut doc = list(&[1, 2, 3]);
+        let ops = vec![
+            PatchOp::Remove { target: id(2) },
+            PatchOp::Move { target: id(3), to: Anchor::FirstChildOf(id(0)) },
+            PatchOp::Insert {
+                anchor: Anchor::After(id(1)),
+                node: Node::Element(Box::new(elem("li", 4))),
+                is_svg: false,
+            },
+            PatchOp::UpdateText { target: id(4), text: "new".into() },
+            PatchOp::UpdateAttrs {
+                target: id(0),
+                changes: vec![("class".into(), Some("items".into()))],
+            },
+        ];
+        apply_patches(&mut doc, &ops).unwrap();
+
+        let mut expected = list(&[3, 1]);
+        expected.root.set_attr("class", "items");
+        let mut item = elem("li", 4);
+        item.children.push(Node::Text(Text::new("new")));
+        expected.root.children.push(Node::Element(Box::new(item)));
+        assert_eq!(html(&doc), html(&expected));
+    }
+
+    #[test]
+    fn test_apply_errors() {
+        let mut doc = list(&[1, 2]);
+        let missing = PatchOp::Remove { target: id(9) };
+        assert_eq!(apply_patch(&mut doc, &missing), Err(ApplyError::TargetNotFound(id(9))));
+
+        let root = PatchOp::Remove { target: id(0) };
+        assert_eq!(apply_patch(&mut doc, &root), Err(ApplyError::DetachRoot(id(0))));
+
+        let beside_root = PatchOp::Insert {
+            anchor: Anchor::Before(id(0)),
+            node: Node::Element(Box::new(elem("li", 3))),
+            is_svg: false,
+        };
+        assert_eq!(apply_patch(&mut doc, &beside_root), Err(ApplyError::RootSibling(id(0))));
+
+        let into_self = PatchOp::Move { target: id(1), to: Anchor::LastChildOf(id(1)) };
+        assert_eq!(apply_patch(&mut doc, &into_self), Err(ApplyError::MoveIntoSelf(id(1))));
+
+        let no_anchor = PatchOp::Move { target: id(1), to: Anchor::After(id(7)) };
+        assert_eq!(apply_patch(&mut doc, &no_anchor), Err(ApplyError::AnchorNotFound(id(7))));
+
+        // Failed operations leave the document untouched
+        assert_eq!(html(&doc), html(&list(&[1, 2])));
+    }
+
+    const TAGS: &[&str] = &["div", "p", "span", "section", "li", "svg"];
+    const WORDS: &[&str] = &["alpha", "beta", "gamma", "delta", ""];
+
+    fn random_elem(rng: &mut Rng, next_id: &mut u64, depth: usize) -> Element<Indexed> {
+        *next_id += 1;
+        let mut elem = elem(rng.pick(TAGS), *next_id);
+        if rng.chance(40) {
+            elem.set_attr("class", rng.pick(WORDS));
+        }
+        if depth < 4 {
+            for _ in 0..rng.below(5) {
+                elem.children.push(random_node(rng, next_id, depth + 1));
+            }
+        }
+        elem
+    }
+
+    fn random_node(rng: &mut Rng, next_id: &mut u64, depth: usize) -> Node<Indexed> {
+        match rng.below(10) {
+            0..=5 => Node::Element(Box::new(random_elem(rng, next_id, depth))),
+            6..=8 => Node::Text(Text::new(rng.pick(WORDS))),
+            _ => Node::Comment(Comment::new(rng.pick(WORDS))),
+        }
+    }
+
+    /// Derive an edited copy of `elem` that keeps most StableIds.
+    fn mutate(rng: &mut Rng, elem: &Element<Indexed>, next_id: &mut u64, depth: usize) -> Element<Indexed> {
+        let mut out = elem.clone();
+        out.children.clear();
+        if rng.chance(5) {
+            out.tag = rng.pick(TAGS).into();
+        }
+        if rng.chance(20) {
+            out.set_attr("title", rng.pick(WORDS));
+        }
+        if rng.chance(15) {
+            out.remove_attr("class");
+        }
+
+        for child in &elem.children {
+            if rng.chance(15) {
+                continue;
+            }
+            let child = match child {
+                Node::Element(child) => Node::Element(Box::new(mutate(rng, child, next_id, depth + 1))),
+                Node::Text(_) if rng.chance(25) => Node::Text(Text::new(rng.pick(WORDS))),
+                other => other.clone(),
+            };
+            out.children.push(child);
+            if rng.chance(10) {
+                out.children.push(random_node(rng, next_id, depth + 1));
+            }
+        }
+        if out.children.len() > 1 && rng.chance(30) {
+            let a = rng.below(out.children.len() as u64) as usize;
+            let b = rng.below(out.children.len() as u64) as usize;
+            out.children.swap(a, b);
+        }
+        if rng.chance(10) {
+            out.children.push(random_node(rng, next_id, depth + 1));
+        }
+        out
+    }
+
+    /// Apply the inverse of `ops` to `patched`, which must then match `old`.
+    fn undo(patched: &mut Document<Indexed>, old: &Document<Indexed>, ops: &[PatchOp<Indexed>], seed: u64) {
+        let undo = invert(old, ops).unwrap_or_else(|e| panic!("seed {seed} (invert): {e}"));
+        apply_patches(patched, &undo).unwrap_or_else(|e| panic!("seed {seed} (undo): {e}\nundo: {undo:?}"));
+        assert_eq!(html(patched), html(old), "seed {seed} (undo)\nops: {ops:?}\nundo: {undo:?}");
+    }
+
+    #[test]
+    fn test_apply_diff_roundtrip_random_trees() {
+        for seed in 1..=500u64 {
+            let mut rng = Rng::new(seed);
+            let mut next_id = 0;
+            let old = Document::new(random_elem(&mut rng, &mut next_id, 0));
+            let new = Document::new(mutate(&mut rng, &old.root, &mut next_id, 0));
+
+            let result = diff(&old, &new);
+            assert!(!result.should_reload, "seed {seed}: {:?}", result.reload_reason);
+
+            let mut patched = old.clone();
+            apply_patches(&mut patched, &result.ops)
+                .unwrap_or_else(|e| panic!("seed {seed}: {e}\nops: {:?}", result.ops));
+            assert_eq!(html(&patched), html(&new), "seed {seed}\nold: {}\nops: {:?}", html(&old), result.ops);
+            undo(&mut patched, &old, &result.ops, seed);
+
+            // Again on indexed copies, whose cached content hashes let the
+            // diff skip unchanged subtrees
+            let reindex = |doc: &Document<Indexed>| {
+                let markup = render_document(doc, &RenderConfig::new(false, false));
+                ApplyTestSite::indexer().transform(ApplyTestSite::parse(&markup))
+            };
+            let (old, new) = (reindex(&old), reindex(&new));
+            let result = diff(&old, &new);
+            let mut patched = old.clone();
+            apply_patches(&mut patched, &result.ops)
+                .unwrap_or_else(|e| panic!("seed {seed} (indexed): {e}\nops: {:?}", result.ops));
+            assert_eq!(html(&patched), html(&new), "seed {seed} (indexed)\nops: {:?}", result.ops);
+            assert_eq!(patched.content_hash(), new.content_hash(), "seed {seed} (indexed)");
+            undo(&mut patched, &old, &result.ops, seed);
+
+            // And with removed subtrees moved to where they were reinserted
+            let config = DiffConfig::default().with_reparent_detection(true);
+            let result = diff_with_config(&old, &new, config);
+            let mut patched = old.clone();
+            apply_patches(&mut patched, &result.ops)
+                .unwrap_or_else(|e| panic!("seed {seed} (reparent): {e}\nops: {:?}", result.ops));
+            assert_eq!(html(&patched), html(&new), "seed {seed} (reparent)\nops: {:?}", result.ops);
+            undo(&mut patched, &old, &result.ops, seed);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Patch Cost Model
+//!
+//! Estimates how many bytes patches take on the wire once rendered, so the
+//! diff can weigh a patch set against reloading the page (see
+//! `DiffConfig::max_patch_ratio`). Op counts alone misjudge both ways: one
+//! `Replace` may carry megabytes, while thousands of attribute updates fit
+//! in a few kilobytes.
+//!
+//! Estimates follow the markup `render` produces with ids
+//! (`RenderConfig::DEV`) but skip escaping and void elements, and price the
+//! JSON envelope of each op at a flat [`OP_BYTES`].
+
+use crate::core::{DocChange, ElementExt, PhaseExt};
+use crate::node::{Element, Node};
+
+use super::diff::PatchOpRef;
+
+/// JSON framing of one op: name, target id, anchor and punctuation.
+const OP_BYTES: usize = 48;
+
+/// An id attribute as rendered: ` data-tola-id="…"` with a 16-digit id.
+const ID_ATTR_BYTES: usize = 32;
+
+/// A quoted id in an id list, with its separator.
+const ID_BYTES: usize = 19;
+
+/// A splice as `[start,delete,""]`, with its separator.
+pub(super) const SPLICE_BYTES: usize = 16;
+
+/// Estimated rendered size of a node and its descendants.
+pub fn estimate_node_bytes<P: PhaseExt>(node: &Node<P>) -> usize {
+    match node {
+        Node::Element(elem) => estimate_element_bytes(elem),
+        Node::Text(text) => text.content.len(),
+        // <!--…-->, <!DOCTYPE …>, <?… …?>
+        Node::Comment(comment) => comment.content.len() + 7,
+        Node::Doctype(doctype) => doctype.content.len() + 11,
+        Node::ProcessingInstruction(pi) => pi.target.len() + pi.data.len() + 5,
+    }
+}
+
+/// Estimated rendered size of an element and its descendants.
+pub fn estimate_element_bytes<P: PhaseExt>(elem: &Element<P>) -> usize {
+    let mut bytes = 0;
+    let mut stack = vec![elem];
+    while let Some(elem) = stack.pop() {
+        // <tag …></tag>
+        bytes += 2 * elem.tag.len() + 5 + ID_ATTR_BYTES;
+        bytes += elem.attrs.iter().map(|(name, value)| attr_bytes(name, Some(value))).sum::<usize>();
+        for child in &elem.children {
+            match child {
+                Node::Element(child) => stack.push(child),
+                other => bytes += estimate_node_bytes(other),
+            }
+        }
+    }
+    bytes
+}
+
+/// Estimated size of patches rendered from `ops` (`PatchOp`s or
+/// `PatchOpRef`s), as JSON.
+pub fn estimate_patch_bytes<'o, P, O>(ops: &'o [O]) -> usize
+where
+    P: PhaseExt,
+    &'o O: Into<PatchOpRef<'o, P>>,
+{
+    ops.iter().map(|op| OP_BYTES + payload_bytes(&op.into())).sum()
+}
+
+fn payload_bytes<P: PhaseExt>(op: &PatchOpRef<'_, P>) -> usize {
+    match op {
+        PatchOpRef::Replace { element, .. } => estimate_element_bytes(element),
+        PatchOpRef::UpdateText { text, .. } | PatchOpRef::UpdateTextAt { text, .. } => text.len(),
+        PatchOpRef::SpliceText { splices, .. } => {
+            splices.iter().map(|splice| SPLICE_BYTES + splice.insert.len()).sum()
+        }
+        PatchOpRef::ReplaceChildren { children, .. } => children.iter().map(estimate_node_bytes).sum(),
+        PatchOpRef::Insert { node, .. } => estimate_node_bytes(node),
+        PatchOpRef::UpdateAttrs { changes, .. } => {
+            changes.iter().map(|(name, value)| attr_bytes(name, value.as_deref())).sum()
+        }
+        PatchOpRef::UpdateClasses { add, remove, .. } => {
+            add.iter().chain(remove.iter()).map(|token| token.len() + 3).sum()
+        }
+        PatchOpRef::UpdateStyles { set, remove, .. } => {
+            let set: usize = set.iter().map(|(name, value)| attr_bytes(name, Some(value))).sum();
+            set + remove.iter().map(|name| name.len() + 3).sum::<usize>()
+        }
+        PatchOpRef::UpdateExt { ext, data, .. } => ext.family_name().len() + data.len(),
+        PatchOpRef::UpdateDoc { change, .. } => doc_change_bytes(change),
+        PatchOpRef::Rekey { element, .. } => {
+            let mut ids = 0;
+            let mut stack = vec![&**element];
+            while let Some(elem) = stack.pop() {
+                ids += 1;
+                stack.extend(elem.children.iter().filter_map(Node::as_element));
+            }
+            ids * ID_BYTES
+        }
+        PatchOpRef::RemoveTextAt { .. } | PatchOpRef::Remove { .. } | PatchOpRef::Move { .. } => 0,
+    }
+}
+
+/// ` name="value"` in markup, `["name","value"]` in an attribute list.
+fn attr_bytes(name: &str, value: Option<&str>) -> usize {
+    name.len() + value.map_or(4, str::len) + 4
+}
+
+fn doc_change_bytes(change: &DocChange) -> usize {
+    match change {
+        DocChange::Title { new, .. } => new.len(),
+        DocChange::Meta { name, new, .. } => attr_bytes(name, new.as_deref()),
+        DocChange::AddLink { rel, href } | DocChange::RemoveLink { rel, href } => attr_bytes(rel, Some(href)),
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+    use crate::algo::{diff, diff_with_config, DiffConfig, ReloadReason};
+    use crate::algo::test_site::doc;
+    use crate::render::{patches_to_json, render_document, render_patches, RenderConfig};
+
+    /// Whether `estimate` is within a quarter of `actual`.
+    fn close(estimate: usize, actual: usize) -> bool {
+        estimate.abs_diff(actual) * 4 <= actual
+    }
+
+    #[test]
+    fn test_estimates_track_rendered_size() {
+        let page = doc(r#"<main class="page"><h1>Title</h1><p>Some <b>bold</b> text</p><!-- c --></main>"#);
+        let rendered = render_document(&page, &RenderConfig::DEV).len();
+        let estimate = estimate_element_bytes(&page.root);
+        assert!(close(estimate, rendered), "estimate {estimate}, rendered {rendered}");
+
+        let new = doc(r#"<main class="wide"><h1>New title</h1><p>Other text</p><ul><li>a</li></ul></main>"#);
+        let result = diff(&page, &new);
+        let json = patches_to_json(&render_patches(&result.ops, &RenderConfig::DEV)).len();
+        assert_eq!(result.stats.patch_bytes, estimate_patch_bytes(&result.ops));
+        assert!(close(result.stats.patch_bytes, json), "estimate {}, json {json}", result.stats.patch_bytes);
+        // Not computed without a ratio
+        assert_eq!(result.stats.page_bytes, 0);
+    }
+
+    #[test]
+    fn test_patch_ratio_decides_reload() {
+        let item = |i: usize, class: &str| {
+            format!(r#"<li class="{class}" key="{i}">Item {i}, <em>described</em> at some length</li>"#)
+        };
+        let list = |class: &str| {
+            format!("<ul>{}</ul>", (0..3000).map(|i| item(i, class)).collect::<String>())
+        };
+        let (old, new) = (doc(&list("a")), doc(&list("b")));
+
+        // Many small updates: too many ops, but a fraction of the page
+        let result = diff(&old, &new);
+        assert!(matches!(
+            result.reload_reason,
+            Some(ReloadReason::OpsLimitExceeded { limit: 2000, reached: 2001 })
+        ));
+        // The diff stopped early, so the count is only a lower bound
+        let reason = result.reload_reason.unwrap().to_string();
+        assert_eq!(reason, "too many patch operations (at least 2001, limit 2000)");
+        let config = DiffConfig::default().with_max_patch_ratio(0.5);
+        let result = diff_with_config(&old, &new, config);
+        assert!(!result.should_reload);
+        assert!(result.stats.patch_bytes < result.stats.page_bytes / 2, "{:?}", result.stats);
+
+        // One op carrying most of the page
+        let old = doc("<main><p>small</p></main>");
+        let new = doc(&format!("<main><section>{}</section></main>", list("b")));
+        let config = DiffConfig::default().with_max_patch_ratio(0.5);
+        let result = diff_with_config(&old, &new, config);
+        assert_eq!(result.ops.len(), 0);
+        assert!(matches!(result.reload_reason, Some(ReloadReason::PatchTooLarge { .. })));
+        assert!(result.stats.patch_bytes > result.stats.page_bytes / 2, "{:?}", result.stats);
+    }
+}
@@ -12,7 +12,9 @@
 //! render_patches(ops, emit_ids) -> Vec<Patch>  // HTML rendering
 //! ```
 //!
-//! `PatchOp` stores node references, `Patch` stores rendered HTML.
+//! `PatchOp` stores node data, `Patch` stores rendered HTML. `diff_ref`
+//! returns `PatchOpRef`s instead, which borrow inserted and replaced
+//! subtrees from the new document rather than cloning them.
 //! This separation enables:
 //! - Testing diff logic without render
 //! - Different render strategies (minified, pretty-printed)
@@ -26,9 +28,19 @@
 //!
 //! # Key Features
 //!
-//! - **Move Detection**: Reordered nodes generate `Move` ops, not Delete+Insert
+//! - **Move Detection**: Reordered nodes generate `Move` ops, not Delete+Insert;
+//!   with `DiffConfig::detect_reparents`, also across parents
 //! - **Stable Identity**: Same StableId = same node across edits
 //! - **Incremental Updates**: Only changed subtrees are patched
+//! - **Family Data**: Families compare their own data through
+//!   `Family::diff_indexed`/`diff_processed`, producing `UpdateExt` or
+//!   `Replace` for changes the markup does not show
+//! - **Document Metadata**: `DocDiff` on the phase's `DocExt` compares
+//!   `Document::meta`, producing `UpdateDoc` ops for the title and head
+//!   `<meta>`/`<link>` changes
+//! - **Subtree Skipping**: Elements with equal cached `ContentHash`es and
+//!   StableIds are skipped without walking them, so diffing an unchanged
+//!   indexed page is O(1)
 //!
 //! # Complexity
 //!
@@ -36,18 +48,33 @@
 //! - Space: O(n + m) for patch list
 
 use crate::attr::{AttrKey, AttrValue};
-use crate::core::{HasStableId, PhaseExt};
+use crate::core::{DocChange, DocDiff, ElementExt, ExtChange, HasStableId, PhaseExt};
 use crate::id::StableId;
 use crate::node::{Document, Element, Node};
 
-use super::myers::{diff_sequences, Edit};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
 
-/// Default maximum depth for recursive diffing before fallback to full replace.
+use rustc_hash::FxHashSet;
+
+use super::cost::{estimate_element_bytes, estimate_patch_bytes, SPLICE_BYTES};
+use super::ignore::IgnoreRules;
+use super::myers::{diff_sequences_with, Edit, DEFAULT_MAX_EDIT_DISTANCE};
+use super::policy::{AttrChange, DefaultPolicy, DiffPolicy, ElementInfo, PolicyAction};
+use super::reparent::detect_reparents;
+use super::tokens::{self, token_update};
+use super::text::{text_splices, TextSplice};
+
+/// Default maximum depth for recursive diffing before fallback to subtree replace.
 const DEFAULT_MAX_DIFF_DEPTH: usize = 500;
 
 /// Default maximum number of operations before fallback to full reload.
 const DEFAULT_MAX_OPS: usize = 2000;
 
+/// Default splice-to-text size ratio below which changed text is spliced.
+const DEFAULT_MAX_SPLICE_RATIO: f64 = 0.5;
+
 // =============================================================================
 // Public Types
 // =============================================================================
@@ -57,14 +84,41 @@ const DEFAULT_MAX_OPS: usize = 2000;
 /// Use this to tune diff behavior for specific document types:
 /// - Increase limits for large documents (generated docs, long tables)
 /// - Decrease limits for faster fallback on complex changes
-#[derive(Debug, Clone, Copy)]
+/// - Swap the [`DiffPolicy`] to change how attribute changes are patched
+#[derive(Debug, Clone)]
 pub struct DiffConfig {
-    /// Maximum recursion depth before fallback to full replace.
+    /// Maximum depth diffed node by node. Elements nested deeper are compared
+    /// with a deep equality check and replaced whole if they differ.
     /// Default: 500
     pub max_depth: usize,
     /// Maximum number of patch operations before fallback to full reload.
+    /// With `max_patch_ratio` set, the patch size decides instead: past this
+    /// many operations, the diff only stops once the patches outweigh the page.
     /// Default: 2000
     pub max_ops: usize,
+    /// Edit distance between two sibling lists past which the Myers search
+    /// gives way to patience diff (see [`diff_sequences_with`](super::diff_sequences_with)).
+    /// Default: 512
+    pub max_edit_distance: usize,
+    /// Rules for patching changed elements.
+    /// Default: [`DefaultPolicy`]
+    pub policy: Arc<dyn DiffPolicy>,
+    /// Match removed and inserted subtrees across parents by content hash
+    /// and move them instead (see `Patch::Rekey`).
+    /// Default: false
+    pub detect_reparents: bool,
+    /// Reload when the estimated patch size exceeds this fraction of the
+    /// estimated page size (see [`estimate_patch_bytes`](super::estimate_patch_bytes)).
+    /// Default: None (patch size is not limited)
+    pub max_patch_ratio: Option<f64>,
+    /// Volatile content left alone by the diff.
+    /// Default: empty
+    pub ignore: IgnoreRules,
+    /// Send a changed text as `SpliceText` when the splices weigh at most
+    /// this fraction of the new text; larger edits send it whole. `0.0`
+    /// always sends whole texts.
+    /// Default: 0.5
+    pub max_splice_ratio: f64,
 }
 
 impl Default for DiffConfig {
@@ -72,6 +126,12 @@ impl Default for DiffConfig {
         Self {
             max_depth: DEFAULT_MAX_DIFF_DEPTH,
             max_ops: DEFAULT_MAX_OPS,
+            max_edit_distance: DEFAULT_MAX_EDIT_DISTANCE,
+            policy: Arc::new(DefaultPolicy),
+            detect_reparents: false,
+            max_patch_ratio: None,
+            ignore: IgnoreRules::default(),
+            max_splice_ratio: DEFAULT_MAX_SPLICE_RATIO,
         }
     }
 }
@@ -79,7 +139,47 @@ impl Default for DiffConfig {
 impl DiffConfig {
     /// Create config with custom limits.
     pub fn new(max_depth: usize, max_ops: usize) -> Self {
-        Self { max_depth, max_ops }
+        Self {
+            max_depth,
+            max_ops,
+            ..Self::default()
+        }
+    }
+
+    /// Set the sibling-list edit distance ceiling (builder pattern).
+    pub fn with_max_edit_distance(mut self, max_edit_distance: usize) -> Self {
+        self.max_edit_distance = max_edit_distance;
+        self
+    }
+
+    /// Enable or disable reparent detection (builder pattern).
+    pub fn with_reparent_detection(mut self, enabled: bool) -> Self {
+        self.detect_reparents = enabled;
+        self
+    }
+
+    /// Reload when patches outweigh `ratio` times the page (builder pattern).
+    pub fn with_max_patch_ratio(mut self, ratio: f64) -> Self {
+        self.max_patch_ratio = Some(ratio);
+        self
+    }
+
+    /// Set the splice size threshold for text updates (builder pattern).
+    pub fn with_max_splice_ratio(mut self, ratio: f64) -> Self {
+        self.max_splice_ratio = ratio;
+        self
+    }
+
+    /// Set the ignore rules (builder pattern).
+    pub fn with_ignore(mut self, rules: IgnoreRules) -> Self {
+        self.ignore = rules;
+        self
+    }
+
+    /// Set the diff policy (builder pattern).
+    pub fn with_policy(mut self, policy: impl DiffPolicy + 'static) -> Self {
+        self.policy = Arc::new(policy);
+        self
     }
 
     /// Create config for large documents (higher limits).
@@ -87,6 +187,8 @@ impl DiffConfig {
         Self {
             max_depth: 1000,
             max_ops: 5000,
+            max_edit_distance: 4096,
+            ..Self::default()
         }
     }
 
@@ -95,6 +197,8 @@ impl DiffConfig {
         Self {
             max_depth: 100,
             max_ops: 500,
+            max_edit_distance: 128,
+            ..Self::default()
         }
     }
 }
@@ -111,12 +215,84 @@ pub struct DiffStats {
     pub nodes_kept: usize,
     /// Number of nodes moved
     pub nodes_moved: usize,
+    /// Number of nodes moved to another parent (see `DiffConfig::detect_reparents`)
+    pub nodes_reparented: usize,
     /// Number of nodes replaced
     pub nodes_replaced: usize,
     /// Number of text updates
     pub text_updates: usize,
     /// Number of attribute updates
     pub attr_updates: usize,
+    /// Number of family data updates
+    pub ext_updates: usize,
+    /// Number of document-level changes (see [`DocDiff`])
+    pub doc_updates: usize,
+    /// Number of attribute changes and elements skipped by `DiffConfig::ignore`
+    pub ignored: usize,
+    /// Estimated rendered size of the patches in bytes
+    pub patch_bytes: usize,
+    /// Estimated rendered size of the new page in bytes
+    /// (only computed with `DiffConfig::max_patch_ratio` set)
+    pub page_bytes: usize,
+}
+
+/// Why a diff gave up on patching and asks for a full reload.
+///
+/// `Display` gives a short message for logs; [`PatchBatch`](crate::wire::PatchBatch)
+/// sends the variant and its fields.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReloadReason {
+    /// The doctype changed, which decides quirks mode
+    DoctypeChanged,
+    /// A comment or processing instruction before the root changed, which
+    /// no patch can reach
+    PrologChanged,
+    /// A `<script src>` changed; re-running scripts has side effects
+    ScriptSrcChanged { target: StableId },
+    /// A [`DiffPolicy`] asked for a reload of this element
+    PolicyRequested {
+        target: StableId,
+        reason: Cow<'static, str>,
+    },
+    /// The diff needed more than `DiffConfig::max_ops` operations.
+    ///
+    /// The diff stops as soon as it passes the limit, so `reached` is a
+    /// lower bound (usually `limit + 1`), not the size of the full patch.
+    OpsLimitExceeded { limit: usize, reached: usize },
+    /// The estimated patch size outweighed the page
+    /// (see `DiffConfig::max_patch_ratio`)
+    PatchTooLarge { patch_bytes: usize, page_bytes: usize },
+}
+
+impl ReloadReason {
+    /// StableId of the element that caused the reload, if any
+    /// (see [`Document::span_of`] for its source location)
+    pub fn target(&self) -> Option<StableId> {
+        match self {
+            Self::ScriptSrcChanged { target } | Self::PolicyRequested { target, .. } => Some(*target),
+            Self::DoctypeChanged
+            | Self::PrologChanged
+            | Self::OpsLimitExceeded { .. }
+            | Self::PatchTooLarge { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for ReloadReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DoctypeChanged => f.write_str("doctype changed"),
+            Self::PrologChanged => f.write_str("content before the root element changed"),
+            Self::ScriptSrcChanged { target } => write!(f, "script src changed ({target})"),
+            Self::PolicyRequested { target, reason } => write!(f, "{reason} ({target})"),
+            Self::OpsLimitExceeded { limit, reached } => {
+                write!(f, "too many patch operations (at least {reached}, limit {limit})")
+            }
+            Self::PatchTooLarge { patch_bytes, page_bytes } => {
+                write!(f, "patch larger than page (~{patch_bytes} of ~{page_bytes} bytes)")
+            }
+        }
+    }
 }
 
 /// Result of StableId-based diff operation
@@ -127,19 +303,50 @@ pub struct DiffResult<P: PhaseExt> {
     pub ops: Vec<PatchOp<P>>,
     /// Whether diff exceeded limits and should fallback to reload
     pub should_reload: bool,
-    /// Reason for reload (if should_reload is true)
-    pub reload_reason: Option<String>,
+    /// Reason for reload (set exactly when should_reload is true)
+    pub reload_reason: Option<ReloadReason>,
     /// Statistics about the diff
     pub stats: DiffStats,
 }
 
 impl<P: PhaseExt> DiffResult<P> {
     /// Create a result that triggers reload
-    pub fn reload(reason: impl Into<String>) -> Self {
+    pub fn reload(reason: ReloadReason) -> Self {
+        Self {
+            ops: vec![],
+            should_reload: true,
+            reload_reason: Some(reason),
+            stats: DiffStats::default(),
+        }
+    }
+
+    /// Check if any changes were detected
+    pub fn has_changes(&self) -> bool {
+        !self.ops.is_empty() || self.should_reload
+    }
+}
+
+/// Result of [`diff_ref`], with ops borrowing from the new document
+#[derive(Debug)]
+#[must_use]
+pub struct DiffResultRef<'a, P: PhaseExt> {
+    /// Generated patch operations, borrowing from the new document
+    pub ops: Vec<PatchOpRef<'a, P>>,
+    /// Whether diff exceeded limits and should fallback to reload
+    pub should_reload: bool,
+    /// Reason for reload (set exactly when should_reload is true)
+    pub reload_reason: Option<ReloadReason>,
+    /// Statistics about the diff
+    pub stats: DiffStats,
+}
+
+impl<P: PhaseExt> DiffResultRef<'_, P> {
+    /// Create a result that triggers reload
+    pub fn reload(reason: ReloadReason) -> Self {
         Self {
             ops: vec![],
             should_reload: true,
-            reload_reason: Some(reason.into()),
+            reload_reason: Some(reason),
             stats: DiffStats::default(),
         }
     }
@@ -148,6 +355,16 @@ impl<P: PhaseExt> DiffResult<P> {
     pub fn has_changes(&self) -> bool {
         !self.ops.is_empty() || self.should_reload
     }
+
+    /// Copy the ops into owned [`PatchOp`]s
+    pub fn into_owned(self) -> DiffResult<P> {
+        DiffResult {
+            ops: self.ops.into_iter().map(PatchOpRef::into_owned).collect(),
+            should_reload: self.should_reload,
+            reload_reason: self.reload_reason,
+            stats: self.stats,
+        }
+    }
 }
 
 // =============================================================================
@@ -156,8 +373,9 @@ impl<P: PhaseExt> DiffResult<P> {
 
 /// Anchor for insert/move operations
 ///
-/// Specifies WHERE to place an element relative to existing nodes.
-/// All anchors reference elements by StableId, never by position.
+/// Specifies WHERE to place a node relative to existing nodes.
+/// Anchors reference elements by StableId; only `ChildIndex` adds a
+/// position, for placing nodes next to text.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Anchor {
     /// Insert/move after an element
@@ -168,15 +386,21 @@ pub enum Anchor {
     FirstChildOf(StableId),
     /// Insert/move as last child
     LastChildOf(StableId),
+    /// Insert/move so the node becomes child `index` of the parent.
+    /// For a move, the index counts children after the moved node is
+    /// detached.
+    ChildIndex(StableId, usize),
 }
 
 impl Anchor {
     /// Get the StableId referenced by this anchor
     pub fn target_id(&self) -> StableId {
         match self {
-            Self::After(id) | Self::Before(id) | Self::FirstChildOf(id) | Self::LastChildOf(id) => {
-                *id
-            }
+            Self::After(id)
+            | Self::Before(id)
+            | Self::FirstChildOf(id)
+            | Self::LastChildOf(id)
+            | Self::ChildIndex(id, _) => *id,
         }
     }
 }
@@ -198,11 +422,32 @@ pub enum PatchOp<P: PhaseExt> {
     Replace {
         target: StableId,
         element: Box<Element<P>>,
+        /// Whether the element lies inside an `<svg>` (parsed as SVG)
+        is_svg: bool,
     },
 
     /// Update text content (for single-text-child elements)
     UpdateText { target: StableId, text: String },
 
+    /// Update the text node at child `index` of `parent`
+    UpdateTextAt {
+        parent: StableId,
+        index: usize,
+        text: String,
+    },
+
+    /// Remove the text node at child `index` of `parent`
+    RemoveTextAt { parent: StableId, index: usize },
+
+    /// Splice the text node at child `index` of `parent`
+    SpliceText {
+        parent: StableId,
+        index: usize,
+        splices: Vec<TextSplice>,
+        /// New text, stored by `apply_patches`
+        text: String,
+    },
+
     /// Replace all children
     ReplaceChildren {
         target: StableId,
@@ -218,6 +463,8 @@ pub enum PatchOp<P: PhaseExt> {
     Insert {
         anchor: Anchor,
         node: Node<P>,
+        /// Whether the node goes inside an `<svg>` (parsed as SVG)
+        is_svg: bool,
     },
 
     /// Move existing element to new anchor position
@@ -229,6 +476,44 @@ pub enum PatchOp<P: PhaseExt> {
         /// Attribute changes: (name, Some(value)) for set, (name, None) for remove
         changes: Vec<(AttrKey, Option<AttrValue>)>,
     },
+
+    /// Add and remove individual class tokens
+    UpdateClasses {
+        target: StableId,
+        add: Vec<AttrValue>,
+        remove: Vec<AttrValue>,
+        /// New `class` value, stored by `apply_patches`
+        value: AttrValue,
+    },
+
+    /// Set and remove individual inline style properties
+    UpdateStyles {
+        target: StableId,
+        set: Vec<(AttrKey, AttrValue)>,
+        remove: Vec<AttrKey>,
+        /// New `style` value, stored by `apply_patches`
+        value: AttrValue,
+    },
+
+    /// Update family data reported by [`ExtChange::Update`]
+    UpdateExt {
+        target: StableId,
+        /// New extension, stored by `apply_patches`
+        ext: P::Ext,
+        /// Payload for the page runtime
+        data: String,
+    },
+
+    /// Give a moved element the StableIds of `element`, which has the same
+    /// content (emitted after a cross-parent `Move`)
+    Rekey {
+        target: StableId,
+        element: Box<Element<P>>,
+    },
+
+    /// Apply a document-level change reported by [`DocDiff`];
+    /// `target` is the root element
+    UpdateDoc { target: StableId, change: DocChange },
 }
 
 impl<P: PhaseExt> PatchOp<P> {
@@ -237,11 +522,19 @@ impl<P: PhaseExt> PatchOp<P> {
         match self {
             Self::Replace { target, .. } => *target,
             Self::UpdateText { target, .. } => *target,
+            Self::UpdateTextAt { parent, .. } => *parent,
+            Self::RemoveTextAt { parent, .. } => *parent,
+            Self::SpliceText { parent, .. } => *parent,
             Self::ReplaceChildren { target, .. } => *target,
             Self::Remove { target } => *target,
             Self::Insert { anchor, .. } => anchor.target_id(),
             Self::Move { target, .. } => *target,
             Self::UpdateAttrs { target, .. } => *target,
+            Self::UpdateClasses { target, .. } => *target,
+            Self::UpdateStyles { target, .. } => *target,
+            Self::UpdateExt { target, .. } => *target,
+            Self::Rekey { target, .. } => *target,
+            Self::UpdateDoc { target, .. } => *target,
         }
     }
 
@@ -250,6 +543,11 @@ impl<P: PhaseExt> PatchOp<P> {
         match self {
             Self::Replace { target, .. } => format!("Replace({})", target),
             Self::UpdateText { target, .. } => format!("Text({})", target),
+            Self::UpdateTextAt { parent, index, .. } => format!("Text({}[{}])", parent, index),
+            Self::RemoveTextAt { parent, index } => format!("RemoveText({}[{}])", parent, index),
+            Self::SpliceText { parent, index, splices, .. } => {
+                format!("Splice({}[{}],{})", parent, index, splices.len())
+            }
             Self::ReplaceChildren { target, .. } => format!("Children({})", target),
             Self::Remove { target } => format!("Remove({})", target),
             Self::Insert { anchor, .. } => format!("Insert({:?})", anchor),
@@ -258,10 +556,381 @@ impl<P: PhaseExt> PatchOp<P> {
                 let keys: Vec<&str> = changes.iter().map(|(k, _)| k.as_str()).collect();
                 format!("Attrs({},{:?})", target, keys)
             }
+            Self::UpdateClasses { target, add, remove, .. } => {
+                format!("Classes({},+{:?},-{:?})", target, add, remove)
+            }
+            Self::UpdateStyles { target, set, remove, .. } => {
+                let keys: Vec<&str> = set.iter().map(|(k, _)| k.as_str()).collect();
+                format!("Styles({},{:?},-{:?})", target, keys, remove)
+            }
+            Self::UpdateExt { target, ext, .. } => format!("Ext({},{})", target, ext.family_name()),
+            Self::Rekey { target, .. } => format!("Rekey({})", target),
+            Self::UpdateDoc { target, change } => format!("Doc({},{:?})", target, change),
+        }
+    }
+
+    /// Borrow this op as a [`PatchOpRef`], without copying its data
+    pub fn to_ref(&self) -> PatchOpRef<'_, P> {
+        PatchOpRef::from(self)
+    }
+}
+
+// =============================================================================
+// PatchOpRef: Borrowed diff output
+// =============================================================================
+
+/// Patch operation borrowing its data, usually from the new document.
+///
+/// The counterpart of [`PatchOp`] produced by [`diff_ref`]: inserted and
+/// replaced subtrees are borrowed instead of cloned, so a large insertion
+/// costs nothing until it is rendered. Ops that carry content the new
+/// document does not contain (such as subtrees pruned by reparent
+/// detection) own it. Use [`into_owned`](Self::into_owned) to keep an op
+/// past the document's lifetime.
+#[derive(Debug, Clone)]
+pub enum PatchOpRef<'a, P: PhaseExt> {
+    /// Replace entire element
+    Replace {
+        target: StableId,
+        element: Cow<'a, Element<P>>,
+        /// Whether the element lies inside an `<svg>` (parsed as SVG)
+        is_svg: bool,
+    },
+
+    /// Update text content (for single-text-child elements)
+    UpdateText { target: StableId, text: Cow<'a, str> },
+
+    /// Update the text node at child `index` of `parent`
+    UpdateTextAt {
+        parent: StableId,
+        index: usize,
+        text: Cow<'a, str>,
+    },
+
+    /// Remove the text node at child `index` of `parent`
+    RemoveTextAt { parent: StableId, index: usize },
+
+    /// Splice the text node at child `index` of `parent`
+    SpliceText {
+        parent: StableId,
+        index: usize,
+        splices: Cow<'a, [TextSplice]>,
+        /// New text
+        text: Cow<'a, str>,
+    },
+
+    /// Replace all children
+    ReplaceChildren {
+        target: StableId,
+        children: Cow<'a, [Node<P>]>,
+        /// Whether parent is SVG (affects text escaping)
+        is_svg: bool,
+    },
+
+    /// Remove element by ID
+    Remove { target: StableId },
+
+    /// Insert new node at anchor position
+    Insert {
+        anchor: Anchor,
+        node: Cow<'a, Node<P>>,
+        /// Whether the node goes inside an `<svg>` (parsed as SVG)
+        is_svg: bool,
+    },
+
+    /// Move existing element to new anchor position
+    Move { target: StableId, to: Anchor },
+
+    /// Update attributes
+    UpdateAttrs {
+        target: StableId,
+        /// Attribute changes: (name, Some(value)) for set, (name, None) for remove
+        changes: Cow<'a, [(AttrKey, Option<AttrValue>)]>,
+    },
+
+    /// Add and remove individual class tokens
+    UpdateClasses {
+        target: StableId,
+        add: Cow<'a, [AttrValue]>,
+        remove: Cow<'a, [AttrValue]>,
+        /// New `class` value
+        value: Cow<'a, AttrValue>,
+    },
+
+    /// Set and remove individual inline style properties
+    UpdateStyles {
+        target: StableId,
+        set: Cow<'a, [(AttrKey, AttrValue)]>,
+        remove: Cow<'a, [AttrKey]>,
+        /// New `style` value
+        value: Cow<'a, AttrValue>,
+    },
+
+    /// Update family data reported by [`ExtChange::Update`]
+    UpdateExt {
+        target: StableId,
+        /// New extension
+        ext: Cow<'a, P::Ext>,
+        /// Payload for the page runtime
+        data: Cow<'a, str>,
+    },
+
+    /// Give a moved element the StableIds of `element`, which has the same
+    /// content (emitted after a cross-parent `Move`)
+    Rekey {
+        target: StableId,
+        element: Cow<'a, Element<P>>,
+    },
+
+    /// Apply a document-level change reported by [`DocDiff`];
+    /// `target` is the root element
+    UpdateDoc {
+        target: StableId,
+        change: Cow<'a, DocChange>,
+    },
+}
+
+impl<P: PhaseExt> PatchOpRef<'_, P> {
+    /// Get the primary target StableId of this patch
+    pub fn target(&self) -> StableId {
+        match self {
+            Self::Replace { target, .. } => *target,
+            Self::UpdateText { target, .. } => *target,
+            Self::UpdateTextAt { parent, .. } => *parent,
+            Self::RemoveTextAt { parent, .. } => *parent,
+            Self::SpliceText { parent, .. } => *parent,
+            Self::ReplaceChildren { target, .. } => *target,
+            Self::Remove { target } => *target,
+            Self::Insert { anchor, .. } => anchor.target_id(),
+            Self::Move { target, .. } => *target,
+            Self::UpdateAttrs { target, .. } => *target,
+            Self::UpdateClasses { target, .. } => *target,
+            Self::UpdateStyles { target, .. } => *target,
+            Self::UpdateExt { target, .. } => *target,
+            Self::Rekey { target, .. } => *target,
+            Self::UpdateDoc { target, .. } => *target,
+        }
+    }
+
+    /// Copy borrowed data into an owned [`PatchOp`]
+    pub fn into_owned(self) -> PatchOp<P> {
+        match self {
+            Self::Replace { target, element, is_svg } => PatchOp::Replace {
+                target,
+                element: Box::new(element.into_owned()),
+                is_svg,
+            },
+            Self::UpdateText { target, text } => PatchOp::UpdateText {
+                target,
+                text: text.into_owned(),
+            },
+            Self::UpdateTextAt { parent, index, text } => PatchOp::UpdateTextAt {
+                parent,
+                index,
+                text: text.into_owned(),
+            },
+            Self::RemoveTextAt { parent, index } => PatchOp::RemoveTextAt { parent, index },
+            Self::SpliceText { parent, index, splices, text } => PatchOp::SpliceText {
+                parent,
+                index,
+                splices: splices.into_owned(),
+                text: text.into_owned(),
+            },
+            Self::ReplaceChildren { target, children, is_svg } => PatchOp::ReplaceChildren {
+                target,
+                children: children.into_owned(),
+                is_svg,
+            },
+            Self::Remove { target } => PatchOp::Remove { target },
+            Self::Insert { anchor, node, is_svg } => PatchOp::Insert {
+                anchor,
+                node: node.into_owned(),
+                is_svg,
+            },
+            Self::Move { target, to } => PatchOp::Move { target, to },
+            Self::UpdateAttrs { target, changes } => PatchOp::UpdateAttrs {
+                target,
+                changes: changes.into_owned(),
+            },
+            Self::UpdateClasses { target, add, remove, value } => PatchOp::UpdateClasses {
+                target,
+                add: add.into_owned(),
+                remove: remove.into_owned(),
+                value: value.into_owned(),
+            },
+            Self::UpdateStyles { target, set, remove, value } => PatchOp::UpdateStyles {
+                target,
+                set: set.into_owned(),
+                remove: remove.into_owned(),
+                value: value.into_owned(),
+            },
+            Self::UpdateExt { target, ext, data } => PatchOp::UpdateExt {
+                target,
+                ext: ext.into_owned(),
+                data: data.into_owned(),
+            },
+            Self::Rekey { target, element } => PatchOp::Rekey {
+                target,
+                element: Box::new(element.into_owned()),
+            },
+            Self::UpdateDoc { target, change } => PatchOp::UpdateDoc {
+                target,
+                change: change.into_owned(),
+            },
+        }
+    }
+}
+
+impl<'a, P: PhaseExt> From<&'a PatchOp<P>> for PatchOpRef<'a, P> {
+    fn from(op: &'a PatchOp<P>) -> Self {
+        match op {
+            PatchOp::Replace { target, element, is_svg } => Self::Replace {
+                target: *target,
+                element: Cow::Borrowed(element),
+                is_svg: *is_svg,
+            },
+            PatchOp::UpdateText { target, text } => Self::UpdateText {
+                target: *target,
+                text: Cow::Borrowed(text),
+            },
+            PatchOp::UpdateTextAt { parent, index, text } => Self::UpdateTextAt {
+                parent: *parent,
+                index: *index,
+                text: Cow::Borrowed(text),
+            },
+            PatchOp::RemoveTextAt { parent, index } => Self::RemoveTextAt {
+                parent: *parent,
+                index: *index,
+            },
+            PatchOp::SpliceText { parent, index, splices, text } => Self::SpliceText {
+                parent: *parent,
+                index: *index,
+                splices: Cow::Borrowed(splices),
+                text: Cow::Borrowed(text),
+            },
+            PatchOp::ReplaceChildren { target, children, is_svg } => Self::ReplaceChildren {
+                target: *target,
+                children: Cow::Borrowed(children),
+                is_svg: *is_svg,
+            },
+            PatchOp::Remove { target } => Self::Remove { target: *target },
+            PatchOp::Insert { anchor, node, is_svg } => Self::Insert {
+                anchor: *anchor,
+                node: Cow::Borrowed(node),
+                is_svg: *is_svg,
+            },
+            PatchOp::Move { target, to } => Self::Move { target: *target, to: *to },
+            PatchOp::UpdateAttrs { target, changes } => Self::UpdateAttrs {
+                target: *target,
+                changes: Cow::Borrowed(changes),
+            },
+            PatchOp::UpdateClasses { target, add, remove, value } => Self::UpdateClasses {
+                target: *target,
+                add: Cow::Borrowed(add),
+                remove: Cow::Borrowed(remove),
+                value: Cow::Borrowed(value),
+            },
+            PatchOp::UpdateStyles { target, set, remove, value } => Self::UpdateStyles {
+                target: *target,
+                set: Cow::Borrowed(set),
+                remove: Cow::Borrowed(remove),
+                value: Cow::Borrowed(value),
+            },
+            PatchOp::UpdateExt { target, ext, data } => Self::UpdateExt {
+                target: *target,
+                ext: Cow::Borrowed(ext),
+                data: Cow::Borrowed(data),
+            },
+            PatchOp::Rekey { target, element } => Self::Rekey {
+                target: *target,
+                element: Cow::Borrowed(element),
+            },
+            PatchOp::UpdateDoc { target, change } => Self::UpdateDoc {
+                target: *target,
+                change: Cow::Borrowed(change),
+            },
+        }
+    }
+}
+
+impl<'a, 'b, P: PhaseExt> From<&'b PatchOpRef<'a, P>> for PatchOpRef<'b, P> {
+    fn from(op: &'b PatchOpRef<'a, P>) -> Self {
+        match op {
+            PatchOpRef::Replace { target, element, is_svg } => Self::Replace {
+                target: *target,
+                element: Cow::Borrowed(&**element),
+                is_svg: *is_svg,
+            },
+            PatchOpRef::UpdateText { target, text } => Self::UpdateText {
+                target: *target,
+                text: Cow::Borrowed(&**text),
+            },
+            PatchOpRef::UpdateTextAt { parent, index, text } => Self::UpdateTextAt {
+                parent: *parent,
+                index: *index,
+                text: Cow::Borrowed(&**text),
+            },
+            PatchOpRef::RemoveTextAt { parent, index } => Self::RemoveTextAt {
+                parent: *parent,
+                index: *index,
+            },
+            PatchOpRef::SpliceText { parent, index, splices, text } => Self::SpliceText {
+                parent: *parent,
+                index: *index,
+                splices: Cow::Borrowed(&**splices),
+                text: Cow::Borrowed(&**text),
+            },
+            PatchOpRef::ReplaceChildren { target, children, is_svg } => Self::ReplaceChildren {
+                target: *target,
+                children: Cow::Borrowed(&**children),
+                is_svg: *is_svg,
+            },
+            PatchOpRef::Remove { target } => Self::Remove { target: *target },
+            PatchOpRef::Insert { anchor, node, is_svg } => Self::Insert {
+                anchor: *anchor,
+                node: Cow::Borrowed(&**node),
+                is_svg: *is_svg,
+            },
+            PatchOpRef::Move { target, to } => Self::Move { target: *target, to: *to },
+            PatchOpRef::UpdateAttrs { target, changes } => Self::UpdateAttrs {
+                target: *target,
+                changes: Cow::Borrowed(&**changes),
+            },
+            PatchOpRef::UpdateClasses { target, add, remove, value } => Self::UpdateClasses {
+                target: *target,
+                add: Cow::Borrowed(&**add),
+                remove: Cow::Borrowed(&**remove),
+                value: Cow::Borrowed(&**value),
+            },
+            PatchOpRef::UpdateStyles { target, set, remove, value } => Self::UpdateStyles {
+                target: *target,
+                set: Cow::Borrowed(&**set),
+                remove: Cow::Borrowed(&**remove),
+                value: Cow::Borrowed(&**value),
+            },
+            PatchOpRef::UpdateExt { target, ext, data } => Self::UpdateExt {
+                target: *target,
+                ext: Cow::Borrowed(&**ext),
+                data: Cow::Borrowed(&**data),
+            },
+            PatchOpRef::Rekey { target, element } => Self::Rekey {
+                target: *target,
+                element: Cow::Borrowed(&**element),
+            },
+            PatchOpRef::UpdateDoc { target, change } => Self::UpdateDoc {
+                target: *target,
+                change: Cow::Borrowed(&**change),
+            },
         }
     }
 }
 
+impl<P: PhaseExt> From<PatchOpRef<'_, P>> for PatchOp<P> {
+    fn from(op: PatchOpRef<'_, P>) -> Self {
+        op.into_owned()
+    }
+}
+
 // =============================================================================
 // Patch: Rendered output (with HTML)
 // =============================================================================
@@ -269,14 +938,34 @@ impl<P: PhaseExt> PatchOp<P> {
 /// Rendered patch operation ready for client execution.
 ///
 /// This is the **output of `render_patches()`**. Contains pre-rendered HTML.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Patch {
     /// Replace entire element's outerHTML
-    Replace { target: StableId, html: String },
+    /// `is_svg` indicates content should be parsed as SVG namespace
+    Replace { target: StableId, html: String, is_svg: bool },
 
     /// Update text content (element.textContent = text)
     UpdateText { target: StableId, text: String },
 
+    /// Update the text node at child `index` of `parent`
+    /// (parent.childNodes[index].data = text)
+    UpdateTextAt {
+        parent: StableId,
+        index: usize,
+        text: String,
+    },
+
+    /// Remove the text node at child `index` of `parent`
+    RemoveTextAt { parent: StableId, index: usize },
+
+    /// Splice the text node at child `index` of `parent`, last splice first
+    /// (parent.childNodes[index].replaceData(start, delete, insert))
+    SpliceText {
+        parent: StableId,
+        index: usize,
+        splices: Vec<TextSplice>,
+    },
+
     /// Replace inner HTML (element.innerHTML = html)
     /// `is_svg` indicates content should be parsed as SVG namespace
     ReplaceChildren { target: StableId, html: String, is_svg: bool },
@@ -285,7 +974,8 @@ pub enum Patch {
     Remove { target: StableId },
 
     /// Insert new content at anchor position
-    Insert { anchor: Anchor, html: String },
+    /// `is_svg` indicates content should be parsed as SVG namespace
+    Insert { anchor: Anchor, html: String, is_svg: bool },
 
     /// Move existing element to new anchor position
     Move { target: StableId, to: Anchor },
@@ -295,6 +985,57 @@ pub enum Patch {
         target: StableId,
         attrs: Vec<(AttrKey, Option<AttrValue>)>,
     },
+
+    /// Remove, then add class tokens (element.classList)
+    UpdateClasses {
+        target: StableId,
+        add: Vec<AttrValue>,
+        remove: Vec<AttrValue>,
+    },
+
+    /// Remove, then set inline style properties (element.style)
+    UpdateStyles {
+        target: StableId,
+        set: Vec<(AttrKey, AttrValue)>,
+        remove: Vec<AttrKey>,
+    },
+
+    /// Hand a family data payload to the element
+    /// (dispatches a `tola:update-ext` event with `{ family, data }`)
+    UpdateExt {
+        target: StableId,
+        family: String,
+        data: String,
+    },
+
+    /// Set the id attributes of the element and its descendant elements,
+    /// in document order
+    Rekey { target: StableId, ids: Vec<StableId> },
+
+    /// Set the document title (document.title = title)
+    UpdateTitle { target: StableId, title: String },
+
+    /// Set the content of the head `<meta>` with this `name` (or `property`),
+    /// creating it if needed; remove it when `content` is `None`
+    UpdateMeta {
+        target: StableId,
+        name: String,
+        content: Option<String>,
+    },
+
+    /// Append `<link rel href>` to the head
+    AddLink {
+        target: StableId,
+        rel: String,
+        href: String,
+    },
+
+    /// Remove the head `<link>`s with this `rel` and `href`
+    RemoveLink {
+        target: StableId,
+        rel: String,
+        href: String,
+    },
 }
 
 impl Patch {
@@ -303,11 +1044,22 @@ impl Patch {
         match self {
             Self::Replace { target, .. } => *target,
             Self::UpdateText { target, .. } => *target,
+            Self::UpdateTextAt { parent, .. } => *parent,
+            Self::RemoveTextAt { parent, .. } => *parent,
+            Self::SpliceText { parent, .. } => *parent,
             Self::ReplaceChildren { target, .. } => *target,
             Self::Remove { target } => *target,
             Self::Insert { anchor, .. } => anchor.target_id(),
             Self::Move { target, .. } => *target,
             Self::UpdateAttrs { target, .. } => *target,
+            Self::UpdateClasses { target, .. } => *target,
+            Self::UpdateStyles { target, .. } => *target,
+            Self::UpdateExt { target, .. } => *target,
+            Self::Rekey { target, .. } => *target,
+            Self::UpdateTitle { target, .. } => *target,
+            Self::UpdateMeta { target, .. } => *target,
+            Self::AddLink { target, .. } => *target,
+            Self::RemoveLink { target, .. } => *target,
         }
     }
 }
@@ -351,41 +1103,125 @@ where
     P: PhaseExt,
     P::Ext: HasStableId + Clone,
 {
+    diff_ref(old, new, config).into_owned()
+}
+
+/// Diff two VDOM documents into ops borrowing from `new`.
+///
+/// Like [`diff_with_config`], but inserted and replaced subtrees are not
+/// cloned; render the ops while both documents are alive.
+///
+/// # Example
+///
+/// ```ignore
+/// let result = diff_ref(&old_doc, &new_doc, DiffConfig::default());
+/// let patches = render_patches(&result.ops, &RenderConfig::DEV);
+/// ```
+pub fn diff_ref<'a, P>(
+    old: &'a Document<P>,
+    new: &'a Document<P>,
+    config: DiffConfig,
+) -> DiffResultRef<'a, P>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId + Clone,
+{
+    // The doctype decides quirks mode and cannot be patched in place
+    let old_doctype = old.doctype().map(|d| d.content.as_str());
+    let new_doctype = new.doctype().map(|d| d.content.as_str());
+    if old_doctype != new_doctype {
+        return DiffResultRef::reload(ReloadReason::DoctypeChanged);
+    }
+    // No patch reaches comments or processing instructions outside the root
+    if !same_prolog(&old.prolog, &new.prolog) {
+        return DiffResultRef::reload(ReloadReason::PrologChanged);
+    }
+
     let mut ctx = DiffContext::<P>::new(config);
-    ctx.diff_element(&old.root, &new.root);
+    ctx.ignored = ctx.config.ignore.matched_elements(old, new);
+    if ctx.config.max_patch_ratio.is_some() {
+        ctx.stats.page_bytes = estimate_element_bytes(&new.root);
+    }
+    ctx.diff_tree(&old.root, &new.root);
+    if ctx.config.detect_reparents && !ctx.should_abort() {
+        let ops = std::mem::take(&mut ctx.ops);
+        ctx.ops = detect_reparents(ops, &old.root, &mut ctx.stats);
+    }
+    ctx.diff_doc(old, new);
+    ctx.weigh_patch();
     ctx.into_result()
 }
 
+/// Whether two prologs hold the same nodes, compared by content.
+fn same_prolog<P: PhaseExt>(old: &[Node<P>], new: &[Node<P>]) -> bool {
+    old.len() == new.len()
+        && old.iter().zip(new).all(|pair| match pair {
+            (Node::Doctype(old), Node::Doctype(new)) => old.content == new.content,
+            (Node::Comment(old), Node::Comment(new)) => old.content == new.content,
+            (Node::ProcessingInstruction(old), Node::ProcessingInstruction(new)) => {
+                old.target == new.target && old.data == new.data
+            }
+            (Node::Text(old), Node::Text(new)) => old.content == new.content,
+            _ => false,
+        })
+}
+
 // =============================================================================
 // Internal Context
 // =============================================================================
 
-struct DiffContext<P: PhaseExt> {
-    ops: Vec<PatchOp<P>>,
+struct DiffContext<'a, P: PhaseExt> {
+    ops: Vec<PatchOpRef<'a, P>>,
+    /// Matched child element pairs of the element being diffed, in order
+    matched: Vec<(&'a Element<P>, &'a Element<P>)>,
     depth: usize,
+    /// Whether the element being diffed lies inside an `<svg>` (and not in
+    /// a `<foreignObject>` there); once its children are diffed, whether they do
+    in_svg: bool,
     should_reload: bool,
-    reload_reason: Option<String>,
+    reload_reason: Option<ReloadReason>,
     stats: DiffStats,
     config: DiffConfig,
+    /// Op count past which the diff stops, or with `max_patch_ratio` set,
+    /// weighs the patches again
+    ops_limit: usize,
+    /// Elements matched by the ignore rules' selectors
+    ignored: FxHashSet<StableId>,
 }
 
-impl<P: PhaseExt> DiffContext<P>
+impl<'a, P: PhaseExt> DiffContext<'a, P>
 where
     P::Ext: HasStableId + Clone,
 {
     fn new(config: DiffConfig) -> Self {
         Self {
             ops: Vec::new(),
+            matched: Vec::new(),
             depth: 0,
+            in_svg: false,
             should_reload: false,
             reload_reason: None,
             stats: DiffStats::default(),
+            ops_limit: config.max_ops,
             config,
+            ignored: FxHashSet::default(),
         }
     }
 
-    fn into_result(self) -> DiffResult<P> {
-        DiffResult {
+    fn into_result(mut self) -> DiffResultRef<'a, P> {
+        // An aborted op list is incomplete and must not be applied
+        let limited = self.config.max_patch_ratio.is_none();
+        if !self.should_reload && limited && self.ops.len() > self.config.max_ops {
+            self.should_reload = true;
+            self.reload_reason = Some(ReloadReason::OpsLimitExceeded {
+                limit: self.config.max_ops,
+                reached: self.ops.len(),
+            });
+        }
+        if self.should_reload {
+            self.ops.clear();
+        }
+        DiffResultRef {
             ops: self.ops,
             should_reload: self.should_reload,
             reload_reason: self.reload_reason,
@@ -393,33 +1229,162 @@ where
         }
     }
 
-    fn should_abort(&self) -> bool {
-        self.should_reload || self.ops.len() > self.config.max_ops || self.depth > self.config.max_depth
+    /// Append the changes between the documents' metadata
+    fn diff_doc(&mut self, old: &Document<P>, new: &Document<P>) {
+        if self.should_abort() {
+            return;
+        }
+        let target = old.root.ext.stable_id();
+        for change in old.meta.diff_doc(&new.meta) {
+            self.stats.doc_updates += 1;
+            self.ops.push(PatchOpRef::UpdateDoc { target, change: Cow::Owned(change) });
+        }
     }
 
-    /// Diff two elements by StableId
-    fn diff_element(&mut self, old: &Element<P>, new: &Element<P>) {
+    /// Estimate the size of the patches, and reload if they outweigh the
+    /// page by more than `max_patch_ratio`.
+    fn weigh_patch(&mut self) {
         if self.should_abort() {
             return;
         }
+        self.stats.patch_bytes = estimate_patch_bytes(&self.ops);
+        self.check_patch_ratio();
+    }
 
-        self.stats.elements_compared += 1;
-        let old_id = old.ext.stable_id();
-
-        // If tags differ, must replace entirely
-        if old.tag != new.tag {
-            self.ops.push(PatchOp::Replace {
-                target: old_id,
-                element: Box::new(new.clone()),
+    /// Reload if `stats.patch_bytes` outweighs the page by more than
+    /// `max_patch_ratio`.
+    fn check_patch_ratio(&mut self) {
+        if let Some(ratio) = self.config.max_patch_ratio
+            && self.stats.patch_bytes as f64 > ratio * self.stats.page_bytes as f64
+        {
+            self.should_reload = true;
+            self.reload_reason = Some(ReloadReason::PatchTooLarge {
+                patch_bytes: self.stats.patch_bytes,
+                page_bytes: self.stats.page_bytes,
             });
-            self.stats.nodes_replaced += 1;
-            return;
         }
+    }
 
-        // Diff attributes
-        self.diff_attrs(old, new);
-
-        // Check if this is an SVG element - SVG text children contain raw markup,
+    fn should_abort(&mut self) -> bool {
+        if self.should_reload || self.ops.len() <= self.ops_limit {
+            return self.should_reload;
+        }
+        if self.config.max_patch_ratio.is_none() {
+            return true;
+        }
+        // The patch size decides: weigh the ops so far, and again each time
+        // their count doubles
+        self.stats.patch_bytes = estimate_patch_bytes(&self.ops);
+        self.check_patch_ratio();
+        self.ops_limit = self.ops.len() * 2;
+        self.should_reload
+    }
+
+    /// Diff two trees, descending through matched elements with an explicit
+    /// stack so that document depth never grows the call stack.
+    fn diff_tree(&mut self, old: &'a Element<P>, new: &'a Element<P>) {
+        // (old, new, depth, inside <svg>) pairs still to diff, next pair on top
+        let mut stack = vec![(old, new, 0, false)];
+        while let Some((old, new, depth, in_svg)) = stack.pop() {
+            if self.should_abort() {
+                return;
+            }
+            self.depth = depth;
+            self.in_svg = in_svg;
+            self.diff_element(old, new);
+            let in_svg = self.in_svg;
+            let children = self.matched.drain(..).rev();
+            stack.extend(children.map(|(old, new)| (old, new, depth + 1, in_svg)));
+        }
+    }
+
+    /// Diff two elements by StableId
+    ///
+    /// Matched child elements are queued in `matched` for `diff_tree`.
+    fn diff_element(&mut self, old: &'a Element<P>, new: &'a Element<P>) {
+        if self.should_abort() {
+            return;
+        }
+
+        self.stats.elements_compared += 1;
+        if hashes_match(old, new) {
+            self.stats.nodes_kept += 1;
+            return;
+        }
+        let old_id = old.ext.stable_id();
+
+        // If tags differ, must replace entirely
+        if old.tag != new.tag {
+            self.ops.push(PatchOpRef::Replace {
+                target: old_id,
+                element: Cow::Borrowed(new),
+                is_svg: self.in_svg,
+            });
+            self.stats.nodes_replaced += 1;
+            return;
+        }
+
+        let info = ElementInfo {
+            id: old_id,
+            tag: &new.tag,
+            family: new.family_name(),
+            old_attrs: &old.attrs,
+            new_attrs: &new.attrs,
+        };
+        if self.ignored.contains(&old_id) || self.config.ignore.ignores_subtree(&info) {
+            self.stats.ignored += 1;
+            self.stats.nodes_kept += 1;
+            return;
+        }
+        match self.config.policy.element(&info) {
+            PolicyAction::Keep => {
+                self.stats.nodes_kept += 1;
+                return;
+            }
+            PolicyAction::Update => {}
+            action => {
+                self.escalate(old_id, new, action);
+                return;
+            }
+        }
+
+        match old.ext.diff_ext(&new.ext) {
+            ExtChange::Unchanged => {}
+            ExtChange::Update(data) => {
+                self.ops.push(PatchOpRef::UpdateExt {
+                    target: old_id,
+                    ext: Cow::Borrowed(&new.ext),
+                    data: Cow::Owned(data),
+                });
+                self.stats.ext_updates += 1;
+            }
+            ExtChange::Replace => {
+                self.escalate(old_id, new, PolicyAction::Replace);
+                return;
+            }
+        }
+
+        // Too deep to diff recursively: replace the subtree if anything changed
+        if self.depth > self.config.max_depth {
+            if subtrees_equal(old, new) {
+                self.stats.nodes_kept += 1;
+            } else {
+                self.ops.push(PatchOpRef::Replace {
+                    target: old_id,
+                    element: Cow::Borrowed(new),
+                    is_svg: self.in_svg,
+                });
+                self.stats.nodes_replaced += 1;
+            }
+            return;
+        }
+
+        // Diff attributes
+        if !self.diff_attrs(old, new, &info) {
+            return;
+        }
+
+        // Check if this is an SVG element - SVG text children may be raw markup,
         // must use innerHTML (ReplaceChildren) not textContent (UpdateText)
         let is_svg = old.tag == "svg";
 
@@ -434,10 +1399,13 @@ where
                 // Both have single text child
                 (Some(old_text), Some(new_text)) => {
                     if old_text != new_text {
-                        self.ops.push(PatchOp::UpdateText {
-                            target: old_id,
-                            text: new_text.to_string(),
-                        });
+                        let op = self
+                            .splice_text(old_id, 0, old_text, new_text)
+                            .unwrap_or(PatchOpRef::UpdateText {
+                                target: old_id,
+                                text: Cow::Borrowed(new_text),
+                            });
+                        self.ops.push(op);
                         self.stats.text_updates += 1;
                     }
                     self.stats.nodes_kept += 1;
@@ -445,9 +1413,9 @@ where
                 }
                 // Old has text, new is empty
                 (Some(_), None) if new.children.is_empty() => {
-                    self.ops.push(PatchOp::UpdateText {
+                    self.ops.push(PatchOpRef::UpdateText {
                         target: old_id,
-                        text: String::new(),
+                        text: Cow::Borrowed(""),
                     });
                     self.stats.text_updates += 1;
                     self.stats.nodes_kept += 1;
@@ -455,9 +1423,9 @@ where
                 }
                 // Old is empty, new has text
                 (None, Some(new_text)) if old.children.is_empty() => {
-                    self.ops.push(PatchOp::UpdateText {
+                    self.ops.push(PatchOpRef::UpdateText {
                         target: old_id,
-                        text: new_text.to_string(),
+                        text: Cow::Borrowed(new_text),
                     });
                     self.stats.text_updates += 1;
                     self.stats.nodes_kept += 1;
@@ -467,72 +1435,141 @@ where
             }
         }
 
-        // Diff children
-        self.depth += 1;
+        // Diff children; those of an <svg> are created in the SVG namespace,
+        // those of a <foreignObject> in the HTML one again
+        self.in_svg = is_svg || (self.in_svg && old.tag != "foreignObject");
         self.diff_children(&old.children, &new.children, old_id, &old.tag);
-        self.depth -= 1;
 
         self.stats.nodes_kept += 1;
     }
 
-    /// Diff element attributes
+    /// `SpliceText` for the text node at `index` of `parent` changing from
+    /// `old` to `new`, or `None` if the splices would weigh more than
+    /// `max_splice_ratio` of the new text.
+    fn splice_text(
+        &self,
+        parent: StableId,
+        index: usize,
+        old: &str,
+        new: &'a str,
+    ) -> Option<PatchOpRef<'a, P>> {
+        let budget = self.config.max_splice_ratio * new.len() as f64;
+        if budget < SPLICE_BYTES as f64 {
+            return None;
+        }
+        let splices = text_splices(old, new, self.config.max_edit_distance);
+        let bytes: usize = splices.iter().map(|splice| SPLICE_BYTES + splice.insert.len()).sum();
+        (bytes as f64 <= budget).then(|| PatchOpRef::SpliceText {
+            parent,
+            index,
+            splices: Cow::Owned(splices),
+            text: Cow::Borrowed(new),
+        })
+    }
+
+    /// Diff element attributes, consulting the policy on each change
     ///
-    /// Special handling for resource URL changes:
-    /// - `<link href>` change: Use `Replace` to trigger CSS reload
-    /// - `<script src>` change: Trigger full reload (re-execution has side effects)
-    fn diff_attrs(&mut self, old: &Element<P>, new: &Element<P>) {
+    /// With the default policy, a `<link href>` change replaces the element
+    /// and a `<script src>` change triggers a full reload. Returns `false`
+    /// when the policy replaced the element or asked for a reload, which
+    /// ends the diff of this element.
+    fn diff_attrs(&mut self, old: &Element<P>, new: &'a Element<P>, info: &ElementInfo<'_>) -> bool {
         if self.should_abort() {
-            return;
+            return false;
         }
 
+        let policy = &self.config.policy;
         let mut changes: Vec<(AttrKey, Option<AttrValue>)> = Vec::new();
+        let mut action = PolicyAction::Keep;
 
         // Check for changed/added attributes
         for (name, value) in &new.attrs {
             let old_value = old.get_attr(name);
             if old_value != Some(value.as_str()) {
-                changes.push((name.clone(), Some(value.clone())));
+                if self.config.ignore.ignores_attr(name, old_value, Some(value)) {
+                    self.stats.ignored += 1;
+                    continue;
+                }
+                let change = AttrChange { name, old: old_value, new: Some(value) };
+                let decided = policy.attr(info, &change);
+                if decided == PolicyAction::Update {
+                    changes.push((name.clone(), Some(value.clone())));
+                }
+                action = action.max(decided);
             }
         }
 
         // Check for removed attributes
-        for (name, _) in &old.attrs {
+        for (name, value) in &old.attrs {
             if new.get_attr(name).is_none() {
-                changes.push((name.clone(), None));
+                if self.config.ignore.ignores_attr(name, Some(value), None) {
+                    self.stats.ignored += 1;
+                    continue;
+                }
+                let change = AttrChange { name, old: Some(value), new: None };
+                let decided = policy.attr(info, &change);
+                if decided == PolicyAction::Update {
+                    changes.push((name.clone(), None));
+                }
+                action = action.max(decided);
             }
         }
 
-        if !changes.is_empty() {
-            // Check for resource URL changes that need special handling
-            let href_changed = old.tag == "link" && changes.iter().any(|(n, _)| n == "href");
-            let src_changed = old.tag == "script" && changes.iter().any(|(n, _)| n == "src");
+        match action {
+            PolicyAction::Keep => true,
+            PolicyAction::Update => {
+                let target = old.ext.stable_id();
+                // Class and style changes go token by token, keeping what
+                // page scripts added
+                let mut token_ops = Vec::new();
+                changes.retain(|(name, value)| {
+                    let (Some(old_value), Some(new_value)) = (old.get_attr(name), value) else {
+                        return true;
+                    };
+                    let op = token_update(target, name, old_value, new_value);
+                    let whole = op.is_none();
+                    token_ops.extend(op.filter(|op| !tokens::is_noop(op)));
+                    whole
+                });
+                if !changes.is_empty() {
+                    self.ops.push(PatchOpRef::UpdateAttrs { target, changes: Cow::Owned(changes) });
+                    self.stats.attr_updates += 1;
+                }
+                self.stats.attr_updates += token_ops.len();
+                self.ops.extend(token_ops);
+                true
+            }
+            action => {
+                self.escalate(old.ext.stable_id(), new, action);
+                false
+            }
+        }
+    }
 
-            if href_changed {
-                // <link href> changed: use Replace to trigger CSS reload
-                self.ops.push(PatchOp::Replace {
-                    target: old.ext.stable_id(),
-                    element: Box::new(new.clone()),
+    /// Carry out a policy's `Replace` or `Reload` for an element.
+    fn escalate(&mut self, target: StableId, new: &'a Element<P>, action: PolicyAction) {
+        match action {
+            PolicyAction::Replace => {
+                self.ops.push(PatchOpRef::Replace {
+                    target,
+                    element: Cow::Borrowed(new),
+                    is_svg: self.in_svg,
                 });
                 self.stats.nodes_replaced += 1;
-            } else if src_changed {
-                // <script src> changed: trigger reload (re-execution has side effects)
+            }
+            PolicyAction::Reload(reason) => {
                 self.should_reload = true;
-                self.reload_reason = Some("script src changed".to_string());
-            } else {
-                self.ops.push(PatchOp::UpdateAttrs {
-                    target: old.ext.stable_id(),
-                    changes,
-                });
-                self.stats.attr_updates += 1;
+                self.reload_reason = Some(reason);
             }
+            PolicyAction::Keep | PolicyAction::Update => {}
         }
     }
 
     /// Diff child nodes
     fn diff_children(
         &mut self,
-        old_children: &[Node<P>],
-        new_children: &[Node<P>],
+        old_children: &'a [Node<P>],
+        new_children: &'a [Node<P>],
         parent_id: StableId,
         parent_tag: &str,
     ) {
@@ -545,13 +1582,12 @@ where
             return;
         }
 
-        // SVG: must use innerHTML, deep compare entire subtree
-        let is_svg = parent_tag == "svg";
-        if is_svg {
+        // SVG with a raw markup body: must use innerHTML, deep compare entire subtree
+        if parent_tag == "svg" && (has_raw_text(old_children) || has_raw_text(new_children)) {
             if !svg_subtrees_equal(old_children, new_children) {
-                self.ops.push(PatchOp::ReplaceChildren {
+                self.ops.push(PatchOpRef::ReplaceChildren {
                     target: parent_id,
-                    children: new_children.to_vec(),
+                    children: Cow::Borrowed(new_children),
                     is_svg: true,
                 });
                 self.stats.nodes_replaced += 1;
@@ -559,50 +1595,50 @@ where
             return;
         }
 
-        // Non-SVG: use optimized paths
+        // Element children, SVG included: use optimized paths
         if old_children.is_empty() {
             self.insert_all_children(new_children, parent_id);
             return;
         }
 
         if new_children.is_empty() {
-            self.remove_all_element_children(old_children);
+            if old_children.iter().all(Node::is_element) {
+                self.remove_all_element_children(old_children);
+            } else {
+                // Text and comments have no StableId to remove them by
+                self.ops.push(PatchOpRef::ReplaceChildren {
+                    target: parent_id,
+                    children: Cow::Borrowed(&[]),
+                    is_svg: self.in_svg,
+                });
+                self.stats.nodes_replaced += 1;
+            }
             return;
         }
 
-        // Check content types
-        let old_has_text = old_children.iter().any(|n| matches!(n, Node::Text(_)));
-        let new_has_text = new_children.iter().any(|n| matches!(n, Node::Text(_)));
+        // Check content types (text, comments etc. take the mixed path)
+        let old_has_text = old_children.iter().any(|n| !n.is_element());
+        let new_has_text = new_children.iter().any(|n| !n.is_element());
 
         if !old_has_text && !new_has_text {
             self.diff_element_children(old_children, new_children, parent_id);
         } else {
-            self.diff_mixed_children(old_children, new_children, parent_id, parent_tag);
+            self.diff_mixed_children(old_children, new_children, parent_id);
         }
     }
 
-    /// Insert all children
-    fn insert_all_children(&mut self, children: &[Node<P>], parent_id: StableId) {
-        let mut last_element_id: Option<StableId> = None;
-
+    /// Insert all children into an empty parent, in order
+    fn insert_all_children(&mut self, children: &'a [Node<P>], parent_id: StableId) {
         for child in children {
             if self.should_abort() {
                 return;
             }
 
-            let anchor = match last_element_id {
-                Some(prev_id) => Anchor::After(prev_id),
-                None => Anchor::FirstChildOf(parent_id),
-            };
-
-            self.ops.push(PatchOp::Insert {
-                anchor,
-                node: child.clone(),
+            self.ops.push(PatchOpRef::Insert {
+                anchor: Anchor::LastChildOf(parent_id),
+                node: Cow::Borrowed(child),
+                is_svg: self.in_svg,
             });
-
-            if let Node::Element(elem) = child {
-                last_element_id = Some(elem.ext.stable_id());
-            }
         }
     }
 
@@ -614,7 +1650,7 @@ where
             }
 
             if let Node::Element(elem) = child {
-                self.ops.push(PatchOp::Remove {
+                self.ops.push(PatchOpRef::Remove {
                     target: elem.ext.stable_id(),
                 });
             }
@@ -624,14 +1660,14 @@ where
     /// Diff pure element children using LCS
     fn diff_element_children(
         &mut self,
-        old_children: &[Node<P>],
-        new_children: &[Node<P>],
+        old_children: &'a [Node<P>],
+        new_children: &'a [Node<P>],
         parent_id: StableId,
     ) {
         let old_ids: Vec<StableId> = old_children.iter().map(get_node_stable_id).collect();
         let new_ids: Vec<StableId> = new_children.iter().map(get_node_stable_id).collect();
 
-        let lcs_result = diff_sequences(&old_ids, &new_ids);
+        let lcs_result = diff_sequences_with(&old_ids, &new_ids, self.config.max_edit_distance);
 
         let mut keeps: Vec<(usize, usize)> = Vec::new();
         let mut moves: Vec<(usize, usize)> = Vec::new();
@@ -652,39 +1688,41 @@ where
             if self.should_abort() {
                 return;
             }
-            self.ops.push(PatchOp::Remove {
+            self.ops.push(PatchOpRef::Remove {
                 target: old_ids[*old_idx],
             });
         }
 
-        // 2. Apply moves
-        moves.sort_unstable_by_key(|(_, new_idx)| *new_idx);
-        for (old_idx, new_idx) in &moves {
+        // 2. Place moved and inserted elements in new order, so each anchor
+        //    is already in its final position when it is referenced
+        let mut placements: Vec<(usize, Option<usize>)> = moves
+            .iter()
+            .map(|&(old_idx, new_idx)| (new_idx, Some(old_idx)))
+            .chain(inserts.iter().map(|&new_idx| (new_idx, None)))
+            .collect();
+        placements.sort_unstable_by_key(|(new_idx, _)| *new_idx);
+        for (new_idx, moved_from) in placements {
             if self.should_abort() {
                 return;
             }
-            let anchor = self.compute_anchor(*new_idx, new_children, parent_id);
-            self.ops.push(PatchOp::Move {
-                target: old_ids[*old_idx],
-                to: anchor,
-            });
-            self.stats.nodes_moved += 1;
-        }
-
-        // 3. Insert new elements
-        inserts.sort_unstable();
-        for new_idx in &inserts {
-            if self.should_abort() {
-                return;
+            let anchor = self.compute_anchor(new_idx, new_children, parent_id);
+            match moved_from {
+                Some(old_idx) => {
+                    self.ops.push(PatchOpRef::Move {
+                        target: old_ids[old_idx],
+                        to: anchor,
+                    });
+                    self.stats.nodes_moved += 1;
+                }
+                None => self.ops.push(PatchOpRef::Insert {
+                    anchor,
+                    node: Cow::Borrowed(&new_children[new_idx]),
+                    is_svg: self.in_svg,
+                }),
             }
-            let anchor = self.compute_anchor(*new_idx, new_children, parent_id);
-            self.ops.push(PatchOp::Insert {
-                anchor,
-                node: new_children[*new_idx].clone(),
-            });
         }
 
-        // 4. Recursively diff kept and moved elements
+        // 3. Recursively diff kept and moved elements
         for (old_idx, new_idx) in keeps.iter().chain(moves.iter()) {
             self.diff_nodes(&old_children[*old_idx], &new_children[*new_idx]);
         }
@@ -705,30 +1743,33 @@ where
         Anchor::FirstChildOf(parent_id)
     }
 
-    /// Diff mixed children (contains text nodes)
+    /// Diff mixed children (contains text, comment or other non-element nodes)
+    ///
+    /// Element and text children are diffed like element children, with text
+    /// nodes addressed by index. Children with comments or other markup are
+    /// matched by position or replaced whole.
     fn diff_mixed_children(
         &mut self,
-        old_children: &[Node<P>],
-        new_children: &[Node<P>],
+        old_children: &'a [Node<P>],
+        new_children: &'a [Node<P>],
         parent_id: StableId,
-        parent_tag: &str,
     ) {
-        // Check if parent is SVG - affects text escaping in render
-        let is_svg = parent_tag == "svg";
+        if text_children_addressable(old_children) && text_children_addressable(new_children) {
+            self.diff_text_children(old_children, new_children, parent_id);
+            return;
+        }
 
         if self.children_structure_matches(old_children, new_children) {
-            let text_changed = old_children.iter().zip(new_children.iter()).any(|(old, new)| {
-                matches!(
-                    (old, new),
-                    (Node::Text(old_t), Node::Text(new_t)) if old_t.content != new_t.content
-                )
-            });
+            let text_changed = old_children
+                .iter()
+                .zip(new_children.iter())
+                .any(|(old, new)| !old.is_element() && !leaf_nodes_equal(old, new));
 
             if text_changed {
-                self.ops.push(PatchOp::ReplaceChildren {
+                self.ops.push(PatchOpRef::ReplaceChildren {
                     target: parent_id,
-                    children: new_children.to_vec(),
-                    is_svg,
+                    children: Cow::Borrowed(new_children),
+                    is_svg: self.in_svg,
                 });
                 self.stats.text_updates += 1;
             } else {
@@ -737,37 +1778,156 @@ where
                 }
             }
         } else {
-            self.ops.push(PatchOp::ReplaceChildren {
+            self.ops.push(PatchOpRef::ReplaceChildren {
                 target: parent_id,
-                children: new_children.to_vec(),
-                is_svg,
+                children: Cow::Borrowed(new_children),
+                is_svg: self.in_svg,
             });
             self.stats.nodes_replaced += 1;
         }
     }
 
-    /// Check if structure matches
+    /// Diff element and text children using LCS
+    ///
+    /// Text nodes are keyed by the element before them, so edited text keeps
+    /// its slot and becomes `UpdateTextAt`. Indices are computed against a
+    /// simulation of the child list, so each is valid when its op runs.
+    fn diff_text_children(
+        &mut self,
+        old_children: &'a [Node<P>],
+        new_children: &'a [Node<P>],
+        parent_id: StableId,
+    ) {
+        let old_keys = text_child_keys(old_children);
+        let new_keys = text_child_keys(new_children);
+        let lcs_result = diff_sequences_with(&old_keys, &new_keys, self.config.max_edit_distance);
+
+        let mut matched: Vec<(usize, usize)> = Vec::new();
+        let mut deletes: Vec<usize> = Vec::new();
+        // (new index, old index if moved)
+        let mut placements: Vec<(usize, Option<usize>)> = Vec::new();
+
+        for edit in &lcs_result.edits {
+            match *edit {
+                Edit::Keep { old_idx, new_idx } => matched.push((old_idx, new_idx)),
+                // Text has no identity to move; recreate it instead
+                Edit::Move { old_idx, new_idx } if !old_children[old_idx].is_element() => {
+                    deletes.push(old_idx);
+                    placements.push((new_idx, None));
+                }
+                Edit::Move { old_idx, new_idx } => {
+                    matched.push((old_idx, new_idx));
+                    placements.push((new_idx, Some(old_idx)));
+                }
+                Edit::Delete { old_idx } => deletes.push(old_idx),
+                Edit::Insert { new_idx } => placements.push((new_idx, None)),
+            }
+        }
+
+        // Keys of the live child list as ops are applied
+        let mut current = old_keys.clone();
+        let position = |current: &[StableId], key| {
+            current
+                .iter()
+                .position(|&k| k == key)
+                .expect("key is in the child list")
+        };
+
+        // 1. Remove deleted nodes
+        for old_idx in deletes {
+            if self.should_abort() {
+                return;
+            }
+            let key = old_keys[old_idx];
+            let index = position(&current, key);
+            current.retain(|&k| k != key);
+            self.ops.push(match &old_children[old_idx] {
+                Node::Element(_) => PatchOpRef::Remove { target: key },
+                _ => PatchOpRef::RemoveTextAt {
+                    parent: parent_id,
+                    index,
+                },
+            });
+        }
+
+        // 2. Place moved and inserted nodes in new order, each right after
+        //    its new predecessor
+        placements.sort_unstable_by_key(|(new_idx, _)| *new_idx);
+        for (new_idx, moved_from) in placements {
+            if self.should_abort() {
+                return;
+            }
+            let key = new_keys[new_idx];
+            if moved_from.is_some() {
+                current.retain(|&k| k != key);
+            }
+            let (anchor, index) = match new_idx.checked_sub(1) {
+                None => (Anchor::FirstChildOf(parent_id), 0),
+                Some(prev) => {
+                    let index = position(&current, new_keys[prev]) + 1;
+                    match &new_children[prev] {
+                        Node::Element(_) => (Anchor::After(new_keys[prev]), index),
+                        _ => (Anchor::ChildIndex(parent_id, index), index),
+                    }
+                }
+            };
+            current.insert(index, key);
+
+            if moved_from.is_some() {
+                self.ops.push(PatchOpRef::Move { target: key, to: anchor });
+                self.stats.nodes_moved += 1;
+            } else {
+                self.ops.push(PatchOpRef::Insert {
+                    anchor,
+                    node: Cow::Borrowed(&new_children[new_idx]),
+                    is_svg: self.in_svg,
+                });
+            }
+        }
+
+        // 3. Update edited text in place and diff kept and moved elements
+        for (old_idx, new_idx) in matched {
+            match (&old_children[old_idx], &new_children[new_idx]) {
+                (Node::Text(old_text), Node::Text(new_text)) => {
+                    self.stats.text_nodes_compared += 1;
+                    if old_text.content != new_text.content {
+                        let op = self
+                            .splice_text(parent_id, new_idx, &old_text.content, &new_text.content)
+                            .unwrap_or(PatchOpRef::UpdateTextAt {
+                                parent: parent_id,
+                                index: new_idx,
+                                text: Cow::Borrowed(&new_text.content),
+                            });
+                        self.ops.push(op);
+                        self.stats.text_updates += 1;
+                    }
+                }
+                (old, new) => self.diff_nodes(old, new),
+            }
+        }
+    }
+
+    /// Check if structure matches: same node kinds and element StableIds
+    /// at every position
     fn children_structure_matches(&self, old: &[Node<P>], new: &[Node<P>]) -> bool {
         if old.len() != new.len() {
             return false;
         }
-        old.iter().zip(new.iter()).all(|(o, n)| {
-            matches!(
-                (o, n),
-                (Node::Element(_), Node::Element(_)) | (Node::Text(_), Node::Text(_))
-            )
+        old.iter().zip(new.iter()).all(|(o, n)| match (o, n) {
+            (Node::Element(o), Node::Element(n)) => o.ext.stable_id() == n.ext.stable_id(),
+            _ => std::mem::discriminant(o) == std::mem::discriminant(n),
         })
     }
 
     /// Diff two nodes
-    fn diff_nodes(&mut self, old: &Node<P>, new: &Node<P>) {
+    fn diff_nodes(&mut self, old: &'a Node<P>, new: &'a Node<P>) {
         if self.should_abort() {
             return;
         }
 
         match (old, new) {
             (Node::Element(old_elem), Node::Element(new_elem)) => {
-                self.diff_element(old_elem, new_elem);
+                self.matched.push((old_elem, new_elem));
             }
             (Node::Text(old_text), Node::Text(new_text)) => {
                 self.stats.text_nodes_compared += 1;
@@ -776,6 +1936,12 @@ where
                     self.stats.text_updates += 1;
                 }
             }
+            (old, new) if old.is_markup() => {
+                debug_assert!(
+                    leaf_nodes_equal(old, new),
+                    "Comment/doctype/PI diff should be handled by parent"
+                );
+            }
             _ => {
                 debug_assert!(false, "diff_nodes called with mismatched types");
             }
@@ -808,28 +1974,146 @@ where
             let hash = StableHasher::new().update_str(&text.content).finish();
             StableId::from_raw(hash)
         }
+        Node::Comment(comment) => {
+            use crate::algo::StableHasher;
+            let hash = StableHasher::new()
+                .update_str("__comment__")
+                .update_str(&comment.content)
+                .finish();
+            StableId::from_raw(hash)
+        }
+        Node::Doctype(doctype) => {
+            use crate::algo::StableHasher;
+            let hash = StableHasher::new()
+                .update_str("__doctype__")
+                .update_str(&doctype.content)
+                .finish();
+            StableId::from_raw(hash)
+        }
+        Node::ProcessingInstruction(pi) => {
+            use crate::algo::StableHasher;
+            let hash = StableHasher::new()
+                .update_str("__pi__")
+                .update_str(&pi.target)
+                .update_str(&pi.data)
+                .finish();
+            StableId::from_raw(hash)
+        }
     }
 }
 
-/// Deep compare SVG subtrees (SVG requires innerHTML, no fine-grained patch).
-fn svg_subtrees_equal<P: PhaseExt>(old: &[Node<P>], new: &[Node<P>]) -> bool
+/// Whether text children map one-to-one onto DOM text nodes, so they can
+/// be addressed by child index: only elements and non-empty text, with no
+/// two text nodes adjacent (the browser merges those when parsing).
+fn text_children_addressable<P: PhaseExt>(children: &[Node<P>]) -> bool {
+    children
+        .iter()
+        .all(|child| child.is_element() || matches!(child, Node::Text(text) if !text.content.is_empty()))
+        && children
+            .windows(2)
+            .all(|pair| pair[0].is_element() || pair[1].is_element())
+}
+
+/// LCS keys for element and text children: elements by StableId, text by
+/// the element before it (unique, as text nodes are never adjacent).
+fn text_child_keys<P: PhaseExt>(children: &[Node<P>]) -> Vec<StableId>
 where
     P::Ext: HasStableId,
 {
-    if old.len() != new.len() {
-        return false;
+    use crate::algo::StableHasher;
+
+    let mut prev_element: u64 = 0;
+    children
+        .iter()
+        .map(|child| match child {
+            Node::Element(elem) => {
+                let id = elem.ext.stable_id();
+                prev_element = id.as_raw();
+                id
+            }
+            _ => {
+                let hash = StableHasher::new()
+                    .update_str("__text_after__")
+                    .update_u64(prev_element)
+                    .finish();
+                StableId::from_raw(hash)
+            }
+        })
+        .collect()
+}
+
+/// Compare two non-element nodes by kind and content.
+fn leaf_nodes_equal<P: PhaseExt>(old: &Node<P>, new: &Node<P>) -> bool {
+    match (old, new) {
+        (Node::Text(o), Node::Text(n)) => o.content == n.content,
+        (Node::Comment(o), Node::Comment(n)) => o.content == n.content,
+        (Node::Doctype(o), Node::Doctype(n)) => o.content == n.content,
+        (Node::ProcessingInstruction(o), Node::ProcessingInstruction(n)) => {
+            o.target == n.target && o.data == n.data
+        }
+        _ => false,
     }
+}
+
+/// Whether cached content hashes show two subtrees are identical.
+///
+/// StableIds derive from the parent's id and the node's content, so equal
+/// root ids and equal content hashes imply equal ids throughout.
+fn hashes_match<P: PhaseExt>(old: &Element<P>, new: &Element<P>) -> bool
+where
+    P::Ext: HasStableId,
+{
+    let hash = old.cached_hash();
+    hash.is_some() && hash == new.cached_hash() && old.ext.stable_id() == new.ext.stable_id()
+}
+
+/// Whether children include raw text, such as an SVG body of unparsed markup.
+fn has_raw_text<P: PhaseExt>(children: &[Node<P>]) -> bool {
+    children.iter().any(|node| matches!(node, Node::Text(text) if text.is_raw()))
+}
+
+/// Deep compare raw SVG bodies (raw markup requires innerHTML, no fine-grained patch).
+fn svg_subtrees_equal<P: PhaseExt>(old: &[Node<P>], new: &[Node<P>]) -> bool
+where
+    P::Ext: HasStableId,
+{
+    old.len() == new.len()
+        && old.iter().zip(new.iter()).all(|(o, n)| match (o, n) {
+            (Node::Element(old_e), Node::Element(new_e)) => subtrees_equal(old_e, new_e),
+            _ => leaf_nodes_equal(o, n), // Different node types compare unequal
+        })
+}
 
-    old.iter().zip(new.iter()).all(|(o, n)| match (o, n) {
-        (Node::Text(old_t), Node::Text(new_t)) => old_t.content == new_t.content,
-        (Node::Element(old_e), Node::Element(new_e)) => {
-            old_e.tag == new_e.tag
-                && old_e.attrs == new_e.attrs
-                && old_e.ext.stable_id() == new_e.ext.stable_id()
-                && svg_subtrees_equal(&old_e.children, &new_e.children)
+/// Deep compare two element subtrees by tag, attributes, StableId, family
+/// data and content.
+///
+/// Uses an explicit stack, so it is safe for subtrees of any depth.
+fn subtrees_equal<P: PhaseExt>(old: &Element<P>, new: &Element<P>) -> bool
+where
+    P::Ext: HasStableId,
+{
+    let mut stack = vec![(old, new)];
+    while let Some((old, new)) = stack.pop() {
+        if hashes_match(old, new) {
+            continue;
         }
-        _ => false, // Different node types
-    })
+        if old.tag != new.tag
+            || old.attrs != new.attrs
+            || old.ext.stable_id() != new.ext.stable_id()
+            || old.children.len() != new.children.len()
+            || old.ext.diff_ext(&new.ext) != ExtChange::Unchanged
+        {
+            return false;
+        }
+        for pair in old.children.iter().zip(new.children.iter()) {
+            match pair {
+                (Node::Element(o), Node::Element(n)) => stack.push((o, n)),
+                (o, n) if !leaf_nodes_equal(o, n) => return false,
+                _ => {}
+            }
+        }
+    }
+    true
 }
 
 // =============================================================================
@@ -848,6 +2132,54 @@ mod tests {
         heading: HeadingFamily,
         svg: SvgFamily,
         media: MediaFamily,
+        chart: ChartFamily,
+    }
+
+    /// `<x-chart>` whose spec is family data, rendered client-side.
+    pub struct ChartFamily;
+
+    #[derive(Debug, Clone, Default)]
+    pub struct ChartIndexed {
+        stable_id: StableId,
+        spec: String,
+    }
+
+    impl HasStableId for ChartIndexed {
+        fn stable_id(&self) -> StableId {
+            self.stable_id
+        }
+    }
+
+    impl crate::core::Family for ChartFamily {
+        const NAME: &'static str = "chart";
+
+        type Raw = String;
+        type Indexed = ChartIndexed;
+        type Processed = crate::core::NoneProcessed;
+
+        fn identify(tag: &str, _attrs: &crate::attr::Attrs) -> bool {
+            tag == "x-chart"
+        }
+
+        fn index(spec: String, stable_id: StableId) -> ChartIndexed {
+            ChartIndexed { stable_id, spec }
+        }
+
+        fn process(indexed: &ChartIndexed) -> Self::Processed {
+            crate::core::NoneProcessed { stable_id: indexed.stable_id }
+        }
+
+        fn diff_indexed(old: &ChartIndexed, new: &ChartIndexed) -> ExtChange {
+            if old.spec == new.spec {
+                ExtChange::Unchanged
+            } else {
+                ExtChange::Update(new.spec.clone())
+            }
+        }
+
+        fn hash_indexed(indexed: &ChartIndexed, hasher: crate::algo::StableHasher) -> crate::algo::StableHasher {
+            hasher.update_str(&indexed.spec)
+        }
     }
 
     fn indexed_elem(tag: &str, id: u64) -> Element<DiffTestSite::Indexed> {
@@ -866,6 +2198,7 @@ mod tests {
         let patch: PatchOp<DiffTestSite::Indexed> = PatchOp::Replace {
             target: StableId::from_raw(42),
             element: Box::new(indexed_elem("div", 42)),
+            is_svg: false,
         };
         assert_eq!(patch.target().as_raw(), 42);
     }
@@ -875,15 +2208,17 @@ mod tests {
         let patch = Patch::Replace {
             target: StableId::from_raw(42),
             html: "<div></div>".to_string(),
+            is_svg: false,
         };
         assert_eq!(patch.target().as_raw(), 42);
     }
 
     #[test]
     fn test_diff_result_reload() {
-        let result: DiffResult<DiffTestSite::Indexed> = DiffResult::reload("test reason");
+        let result: DiffResult<DiffTestSite::Indexed> = DiffResult::reload(ReloadReason::DoctypeChanged);
         assert!(result.should_reload);
-        assert_eq!(result.reload_reason, Some("test reason".to_string()));
+        assert_eq!(result.reload_reason, Some(ReloadReason::DoctypeChanged));
+        assert_eq!(result.reload_reason.as_ref().unwrap().to_string(), "doctype changed");
         assert!(result.has_changes());
     }
 
@@ -1057,11 +2392,533 @@ mod tests {
 
         let result = diff(&old, &new);
 
-        // Should detect the change and generate ReplaceChildren for SVG
+        // Element-structured SVG is diffed like HTML
+        assert!(
+            matches!(
+                result.ops.as_slice(),
+                [PatchOp::UpdateAttrs { target, changes }] if target.as_raw() == 3 && changes[0].0 == "d"
+            ),
+            "got: {:?}",
+            result.ops
+        );
+    }
+
+    #[test]
+    fn test_svg_ops_are_flagged() {
+        fn build_svg_doc(shapes: &[(&str, u64)]) -> Document<DiffTestSite::Indexed> {
+            let mut root = indexed_elem("body", 0);
+            let mut svg = indexed_elem("svg", 1);
+            let mut g = indexed_elem("g", 2);
+            for &(tag, id) in shapes {
+                g.children.push(Node::Element(Box::new(indexed_elem(tag, id))));
+            }
+            svg.children.push(Node::Element(Box::new(g)));
+            root.children.push(Node::Element(Box::new(svg)));
+            root.children.push(Node::Element(Box::new(indexed_elem("p", 9))));
+            Document::new(root)
+        }
+
+        let old = build_svg_doc(&[("rect", 3)]);
+        let new = build_svg_doc(&[("circle", 3), ("path", 4)]);
+        let result = diff(&old, &new);
+        assert!(
+            matches!(
+                result.ops.as_slice(),
+                [
+                    PatchOp::Insert { anchor: Anchor::After(after), is_svg: true, .. },
+                    PatchOp::Replace { target, is_svg: true, .. },
+                ] if after.as_raw() == 3 && target.as_raw() == 3
+            ),
+            "got: {:?}",
+            result.ops
+        );
+
+        // Outside the <svg> nothing is flagged
+        let mut html = build_svg_doc(&[("rect", 3)]);
+        html.root.children[1] = Node::Element(Box::new(indexed_elem("div", 9)));
+        let result = diff(&old, &html);
+        let ops = result.ops.as_slice();
+        assert!(matches!(ops, [PatchOp::Replace { is_svg: false, .. }]), "{ops:?}");
+
+        // Nor is HTML inside a <foreignObject>
+        let embed = |paragraphs: &[u64]| {
+            let mut doc = build_svg_doc(&[("foreignObject", 5)]);
+            let svg = doc.root.children[0].as_element_mut().unwrap();
+            let g = svg.children[0].as_element_mut().unwrap();
+            let object = g.children[0].as_element_mut().unwrap();
+            for &id in paragraphs {
+                object.children.push(Node::Element(Box::new(indexed_elem("p", id))));
+            }
+            doc
+        };
+        let result = diff(&embed(&[6]), &embed(&[6, 7]));
+        let ops = result.ops.as_slice();
+        assert!(matches!(ops, [PatchOp::Insert { is_svg: false, .. }]), "{ops:?}");
+    }
+
+    #[test]
+    fn test_raw_svg_body_replaces_children() {
+        fn build_svg_doc(markup: &str) -> Document<DiffTestSite::Indexed> {
+            let mut root = indexed_elem("body", 0);
+            let mut svg = indexed_elem("svg", 1);
+            svg.children.push(Node::Text(crate::node::Text::raw_with_ext(markup, Default::default())));
+            root.children.push(Node::Element(Box::new(svg)));
+            Document::new(root)
+        }
+
+        let unchanged = diff(&build_svg_doc("<path d=\"M0\"/>"), &build_svg_doc("<path d=\"M0\"/>"));
+        assert!(!unchanged.has_changes(), "got: {:?}", unchanged.ops);
+
+        let result = diff(&build_svg_doc("<path d=\"M0\"/>"), &build_svg_doc("<path d=\"M1\"/>"));
+        assert!(
+            matches!(
+                result.ops.as_slice(),
+                [PatchOp::ReplaceChildren { target, is_svg: true, .. }] if target.as_raw() == 1
+            ),
+            "got: {:?}",
+            result.ops
+        );
+    }
+
+    #[test]
+    fn test_comment_change_replaces_children() {
+        fn build_doc(comment: &str) -> Document<DiffTestSite::Indexed> {
+            let mut root = indexed_elem("body", 0);
+            root.children.push(Node::Element(Box::new(indexed_elem("p", 1))));
+            root.children.push(Node::Comment(crate::node::Comment::new(comment)));
+            Document::new(root)
+        }
+
+        let unchanged = diff(&build_doc("a"), &build_doc("a"));
+        assert!(!unchanged.has_changes(), "got: {:?}", unchanged.ops);
+
+        let result = diff(&build_doc("a"), &build_doc("b"));
+        assert!(matches!(
+            result.ops.as_slice(),
+            [PatchOp::ReplaceChildren { target, children, .. }]
+                if target.as_raw() == 0 && children[1].is_comment()
+        ));
+    }
+
+    #[test]
+    fn test_doctype_change_reloads() {
+        let html5 = Document::new(indexed_elem("html", 0))
+            .with_prolog([Node::Doctype(crate::node::Doctype::html())]);
+        let legacy = Document::new(indexed_elem("html", 0))
+            .with_prolog([Node::Doctype(crate::node::Doctype::new("html PUBLIC \"-//W3C//DTD HTML 4.01//EN\""))]);
+
+        assert!(!diff(&html5, &html5.clone()).has_changes());
+        let result = diff(&html5, &legacy);
+        assert!(result.should_reload);
+        assert_eq!(result.reload_reason, Some(ReloadReason::DoctypeChanged));
+    }
+
+    #[test]
+    fn test_prolog_change_reloads() {
+        use crate::node::{Comment, ProcessingInstruction};
+
+        fn build_doc(prolog: Vec<Node<DiffTestSite::Indexed>>) -> Document<DiffTestSite::Indexed> {
+            Document::new(indexed_elem("html", 0)).with_prolog(prolog)
+        }
+        let comment = |text: &str| Node::Comment(Comment::new(text));
+        let pi = |data: &str| Node::ProcessingInstruction(ProcessingInstruction::new("xml", data));
+
+        let old = build_doc(vec![comment("build 1"), pi("version=\"1.0\"")]);
+        assert!(!diff(&old, &old.clone()).has_changes());
+        for prolog in [
+            vec![comment("build 2"), pi("version=\"1.0\"")],
+            vec![comment("build 1"), pi("version=\"1.1\"")],
+            vec![comment("build 1")],
+            vec![comment("build 1"), pi("version=\"1.0\""), comment("extra")],
+        ] {
+            let new = build_doc(prolog);
+            assert_ne!(old.content_hash(), new.content_hash());
+            let result = diff(&old, &new);
+            assert_eq!(result.reload_reason, Some(ReloadReason::PrologChanged), "{:?}", new.prolog);
+        }
+    }
+
+    #[test]
+    fn test_content_hashes_skip_unchanged_subtrees() {
+        use crate::transform::Transform;
+
+        fn page(title: &str) -> Document<DiffTestSite::Indexed> {
+            let sections: String = (0..20)
+                .map(|i| format!("<section><h2>Part {i}</h2><p>Body <b>{i}</b></p></section>"))
+                .collect();
+            let html = format!("<!DOCTYPE html><html><body><h1>{title}</h1>{sections}</body></html>");
+            DiffTestSite::indexer().transform(DiffTestSite::parse(&html))
+        }
+
+        let old = page("Old");
+        let same = page("Old");
+        assert!(old.root.cached_hash().is_some());
+        assert_eq!(old.content_hash(), same.content_hash());
+
+        let result = diff(&old, &same);
+        assert!(!result.has_changes());
+        assert_eq!(result.stats.elements_compared, 1);
+
+        // Only the path to the changed heading is walked
+        let new = page("New");
+        assert_ne!(old.content_hash(), new.content_hash());
+        let result = diff(&old, &new);
+        assert!(matches!(result.ops.as_slice(), [PatchOp::UpdateText { text, .. }] if text == "New"));
+        assert!(result.stats.elements_compared < 30, "{:?}", result.stats);
+
+        // A hash computed from scratch matches the cached one
+        let mut uncached = old.clone();
+        uncached.root.invalidate_hash();
+        assert_eq!(uncached.content_hash(), old.content_hash());
+    }
+
+    #[test]
+    fn test_in_place_edits_clear_cached_hashes() {
+        use crate::node::CursorMut;
+        use crate::transform::Transform;
+
+        let html = "<html><body><section><p>One <b>two</b></p></section></body></html>";
+        let old = DiffTestSite::indexer().transform(DiffTestSite::parse(html));
+        let is_b = |elem: &Element<DiffTestSite::Indexed>| elem.tag == "b";
+        let edits: [fn(&mut Document<DiffTestSite::Indexed>); 5] = [
+            |doc| doc.for_each_mut(|elem| elem.set_attr("title", "b")),
+            |doc| doc.find_mut(|elem| elem.tag == "b").unwrap().push_text("!"),
+            |doc| {
+                let mut cursor = CursorMut::new(&mut doc.root);
+                cursor.move_to_first_child();
+                cursor.move_to_first_child();
+                cursor.append_child(Node::Text(indexed_text("more")));
+            },
+            |doc| {
+                let mut cursor = CursorMut::new(&mut doc.root);
+                while cursor.move_to_first_child() {}
+                cursor.text_mut().unwrap().content = "Uno ".into();
+            },
+            |doc| {
+                let p = doc.find_mut(|elem| elem.tag == "p").unwrap();
+                p.first_child_mut().unwrap().remove_attr("id");
+                p.first_child_mut().unwrap().clear();
+            },
+        ];
+        for (i, edit) in edits.into_iter().enumerate() {
+            let mut new = old.clone();
+            edit(&mut new);
+            assert!(new.root.cached_hash().is_none(), "edit {i}");
+            assert!(diff(&old, &new).has_changes(), "edit {i}");
+        }
+        assert!(old.find(is_b).unwrap().cached_hash().is_some());
+    }
+
+    #[test]
+    fn test_select_mut_clears_cached_hashes() {
+        use crate::transform::Transform;
+
+        let html = r#"<html><body><nav><a class="external" href="/x">x</a></nav></body></html>"#;
+        let old = DiffTestSite::indexer().transform(DiffTestSite::parse(html));
+        let mut new = old.clone();
+        let count = new.select_mut("a.external", |a| a.set_attr("rel", "noopener")).unwrap();
+        assert_eq!(count, 1);
+
+        let result = diff(&old, &new);
+        assert_eq!(result.ops.len(), 1);
+        assert!(matches!(&result.ops[0], PatchOp::UpdateAttrs { changes, .. } if changes[0].0 == "rel"));
+    }
+
+    #[test]
+    fn test_family_data_changes_are_patched() {
+        use crate::algo::apply_patches;
+        use crate::core::ExtractFamily;
+        use crate::families::{HeadingRaw, MediaProcessed};
+        use crate::transform::Transform;
+        use DiffTestSite::RawExt;
+
+        /// Same markup every time; only the family data differs.
+        fn raw(spec: &str, anchor: &str) -> Document<DiffTestSite::Raw> {
+            let mut doc = DiffTestSite::parse("<main><h2>Intro</h2><x-chart></x-chart><video></video></main>");
+            doc.for_each_mut(|elem| match &mut elem.ext {
+                RawExt::Chart(data) => *data = spec.to_string(),
+                RawExt::Heading(data) => *data = HeadingRaw::with_id(2, anchor),
+                _ => {}
+            });
+            doc
+        }
+        let indexed = |spec, anchor| DiffTestSite::indexer().transform(raw(spec, anchor));
+        let processed = |spec, anchor| DiffTestSite::processor().transform(indexed(spec, anchor));
+
+        // Indexed: the chart spec is hashed, so the change is not skipped
+        let mut old = indexed("bar", "intro");
+        let new = indexed("line", "intro");
+        assert_ne!(old.content_hash(), new.content_hash());
+        let result = diff(&old, &new);
+        assert!(
+            matches!(result.ops.as_slice(), [PatchOp::UpdateExt { data, .. }] if data == "line"),
+            "{:?}",
+            result.ops
+        );
+        assert_eq!(result.stats.ext_updates, 1);
+        apply_patches(&mut old, &result.ops).unwrap();
+        let chart = old.find_by::<ChartFamily>()[0];
+        assert_eq!(ExtractFamily::<ChartFamily>::get(&chart.ext).unwrap().spec, "line");
+
+        // Processed: a heading anchor change is sent to the page
+        let result = diff(&processed("bar", "intro"), &processed("bar", "overview"));
+        assert!(matches!(
+            result.ops.as_slice(),
+            [PatchOp::UpdateExt { data, .. }] if data == "overview"
+        ));
+
+        // Processed: media size changes re-render the element
+        let old = processed("bar", "intro");
+        let mut new = processed("bar", "intro");
+        new.modify_by::<MediaFamily, _>(|video| {
+            let data: &mut MediaProcessed = ExtractFamily::<MediaFamily>::get_mut(&mut video.ext).unwrap();
+            data.width = Some(640);
+        });
+        let result = diff(&old, &new);
+        assert!(matches!(
+            result.ops.as_slice(),
+            [PatchOp::Replace { element, .. }] if element.tag == "video"
+        ));
+    }
+
+    #[test]
+    fn test_class_and_style_changes_are_tokenized() {
+        use crate::algo::apply_patches;
+        use crate::transform::Transform;
+
+        let index = |html: &str| DiffTestSite::indexer().transform(DiffTestSite::parse(html));
+        let mut old = index(r#"<div class="card active" style="color: red; margin: 0" title="a">x</div>"#);
+        let new = index(r#"<div class="card featured" style="color: blue" title="b">x</div>"#);
+        let result = diff(&old, &new);
+        let [
+            PatchOp::UpdateAttrs { changes, .. },
+            PatchOp::UpdateClasses { add, remove, .. },
+            PatchOp::UpdateStyles { set, remove: removed, .. },
+        ] = result.ops.as_slice()
+        else {
+            panic!("{:?}", result.ops);
+        };
+        assert_eq!(changes.as_slice(), [("title".into(), Some("b".into()))]);
+        assert_eq!(add.as_slice(), [AttrValue::from("featured")]);
+        assert_eq!(remove.as_slice(), [AttrValue::from("active")]);
+        assert_eq!(set.as_slice(), [("color".into(), "blue".into())]);
+        assert_eq!(removed.as_slice(), [AttrKey::from("margin")]);
+        assert_eq!(result.stats.attr_updates, 3);
+
+        // The cached copy takes the new values as written
+        apply_patches(&mut old, &result.ops).unwrap();
+        assert_eq!(old.content_hash(), new.content_hash());
+
+        // Added or removed attributes, and styles whose declarations would
+        // end up out of order, are set whole
+        for (old, new) in [
+            (r#"<p>x</p>"#, r#"<p class="a">x</p>"#),
+            (r#"<p class="a">x</p>"#, r#"<p>x</p>"#),
+            (
+                r#"<p style="margin-top: 1px; margin: 0">x</p>"#,
+                r#"<p style="margin: 0; margin-top: 1px">x</p>"#,
+            ),
+        ] {
+            let result = diff(&index(old), &index(new));
+            let ops = result.ops.as_slice();
+            assert!(matches!(ops, [PatchOp::UpdateAttrs { .. }]), "{old}: {ops:?}");
+        }
+
+        // Reordered or respaced tokens leave nothing to patch
+        let old = index(r#"<p class="a b" style="color: red">x</p>"#);
+        let new = index(r#"<p class="b  a" style="color:red;">x</p>"#);
+        let result = diff(&old, &new);
+        assert!(result.ops.is_empty(), "{:?}", result.ops);
+        assert_eq!(result.stats.attr_updates, 0);
+    }
+
+    #[test]
+    fn test_mixed_content_text_change_is_granular() {
+        fn build_doc(tail: &str) -> Document<DiffTestSite::Indexed> {
+            let mut b = indexed_elem("b", 2);
+            b.children.push(Node::Text(indexed_text("x")));
+            let mut p = indexed_elem("p", 1);
+            p.children.push(Node::Text(indexed_text("Hello ")));
+            p.children.push(Node::Element(Box::new(b)));
+            p.children.push(Node::Text(indexed_text(tail)));
+            Document::new(p)
+        }
+
+        let result = diff(&build_doc(" world"), &build_doc(" there"));
         assert!(
-            result.has_changes(),
-            "Expected changes for SVG nested attr change, got: {:?}",
+            matches!(
+                result.ops.as_slice(),
+                [PatchOp::UpdateTextAt { parent, index: 2, text }]
+                    if parent.as_raw() == 1 && text == " there"
+            ),
+            "got: {:?}",
             result.ops
         );
     }
+
+    #[test]
+    fn test_small_edit_to_long_text_is_spliced() {
+        fn build_doc(text: &str) -> Document<DiffTestSite::Indexed> {
+            let mut pre = indexed_elem("pre", 1);
+            pre.children.push(Node::Text(indexed_text(text)));
+            Document::new(pre)
+        }
+
+        let old = build_doc("fn main() {\n    println!(\"one\");\n}");
+        let new = build_doc("fn main() {\n    eprintln!(\"one\");\n}");
+        let result = diff(&old, &new);
+        assert!(
+            matches!(
+                result.ops.as_slice(),
+                [PatchOp::SpliceText { parent, index: 0, splices, text }]
+                    if parent.as_raw() == 1
+                        && *splices == [TextSplice { start: 16, delete: 0, insert: "e".into() }]
+                        && text.starts_with("fn main() {\n    eprintln!")
+            ),
+            "got: {:?}",
+            result.ops
+        );
+        assert_eq!(result.stats.text_updates, 1);
+
+        // Short texts, rewrites and a zero ratio send the whole text
+        let rewritten = build_doc("fn other() -> usize { 42 }");
+        let config = DiffConfig::default().with_max_splice_ratio(0.0);
+        for (old, new, config) in [
+            (build_doc("Hi"), build_doc("Ho"), DiffConfig::default()),
+            (old.clone(), rewritten, DiffConfig::default()),
+            (old, new, config),
+        ] {
+            let result = diff_with_config(&old, &new, config);
+            assert!(matches!(result.ops.as_slice(), [PatchOp::UpdateText { .. }]), "{:?}", result.ops);
+        }
+    }
+
+    #[test]
+    fn test_doc_meta_changes_become_doc_ops() {
+        /// Indexed phase whose documents carry a title and head metadata
+        #[derive(Debug, Clone, Copy, Default)]
+        struct Paged;
+
+        impl crate::core::Phase for Paged {
+            const NAME: &'static str = "Paged";
+        }
+
+        impl PhaseExt for Paged {
+            type Ext = DiffTestSite::IndexedExt;
+            type DocExt = PageMeta;
+            type TextExt = DiffTestSite::IndexedTextExt;
+        }
+
+        #[derive(Debug, Clone, Default)]
+        struct PageMeta {
+            title: String,
+            metas: Vec<(String, String)>,
+            links: Vec<(String, String)>,
+        }
+
+        impl DocDiff for PageMeta {
+            fn diff_doc(&self, new: &Self) -> Vec<DocChange> {
+                let mut changes = Vec::new();
+                if self.title != new.title {
+                    changes.push(DocChange::Title { old: self.title.clone(), new: new.title.clone() });
+                }
+                changes.extend(DocChange::metas(&self.metas, &new.metas));
+                changes.extend(DocChange::links(&self.links, &new.links));
+                changes
+            }
+
+            fn hash_doc(&self, hasher: crate::algo::StableHasher) -> crate::algo::StableHasher {
+                let pairs = self.metas.iter().chain(&self.links);
+                pairs.fold(hasher.update_str(&self.title), |h, (a, b)| h.update_str(a).update_str(b))
+            }
+        }
+
+        fn build_doc(title: &str, description: Option<&str>, css: &str) -> Document<Paged> {
+            let root = DiffTestSite::IndexedExt::None(crate::core::NoneIndexed {
+                stable_id: StableId::from_raw(1),
+            });
+            let pair = |a: &str, b: &str| (a.to_string(), b.to_string());
+            let meta = PageMeta {
+                title: title.into(),
+                metas: description.map(|d| pair("description", d)).into_iter().collect(),
+                links: vec![pair("icon", "/icon.png"), pair("stylesheet", css)],
+            };
+            Document::with_meta(Element::with_ext("html", root), meta)
+        }
+
+        let old = build_doc("Home", Some("Welcome"), "/a.css");
+        assert!(!diff(&old, &old.clone()).has_changes());
+        // A title-only change is not skipped by comparing document hashes
+        let retitled = build_doc("About", Some("Welcome"), "/a.css");
+        assert_eq!(old.content_hash(), build_doc("Home", Some("Welcome"), "/a.css").content_hash());
+        assert_ne!(old.content_hash(), retitled.content_hash());
+        assert_eq!(diff(&old, &retitled).ops.len(), 1);
+
+        let new = build_doc("About", None, "/b.css");
+        let result = diff(&old, &new);
+        let changes: Vec<&DocChange> = result
+            .ops
+            .iter()
+            .map(|op| match op {
+                PatchOp::UpdateDoc { target, change } if target.as_raw() == 1 => change,
+                other => panic!("unexpected op {other:?}"),
+            })
+            .collect();
+        let link = |rel: &str, href: &str| (rel.to_string(), href.to_string());
+        let (rel, href) = link("stylesheet", "/a.css");
+        let removed = DocChange::RemoveLink { rel, href };
+        let (rel, href) = link("stylesheet", "/b.css");
+        let added = DocChange::AddLink { rel, href };
+        assert_eq!(
+            changes,
+            [
+                &DocChange::Title { old: "Home".into(), new: "About".into() },
+                &DocChange::Meta { name: "description".into(), old: Some("Welcome".into()), new: None },
+                &removed,
+                &added,
+            ]
+        );
+        assert_eq!(result.stats.doc_updates, 4);
+        assert_eq!(changes[0].inverse(), DocChange::Title { old: "About".into(), new: "Home".into() });
+
+        let patches = crate::render::render_patches(&result.ops, &crate::render::RenderConfig::DEV);
+        let id = StableId::from_raw(1);
+        assert_eq!(patches[0], Patch::UpdateTitle { target: id, title: "About".into() });
+        assert_eq!(patches[1], Patch::UpdateMeta { target: id, name: "description".into(), content: None });
+    }
+
+    #[test]
+    fn test_diff_ref_borrows_from_new_document() {
+        use crate::render::{render_patches, RenderConfig};
+        use crate::transform::Transform;
+
+        fn page(body: &str) -> Document<DiffTestSite::Indexed> {
+            let html = format!("<html><body>{body}</body></html>");
+            DiffTestSite::indexer().transform(DiffTestSite::parse(&html))
+        }
+
+        let old = page("<p>Title</p>");
+        let table: String = (0..50).map(|i| format!("<tr><td>{i}</td></tr>")).collect();
+        let new = page(&format!("<p>New</p><table>{table}</table>"));
+
+        let result = diff_ref(&old, &new, DiffConfig::default());
+        let body = new.root.children[0].as_element().unwrap();
+        assert!(
+            matches!(
+                result.ops.as_slice(),
+                [PatchOpRef::Insert { node, .. }, PatchOpRef::UpdateText { text: Cow::Borrowed("New"), .. }]
+                    if matches!(node, Cow::Borrowed(node) if std::ptr::eq(*node, &body.children[1]))
+            ),
+            "{:?}",
+            result.ops.iter().map(|op| op.clone().into_owned().summary()).collect::<Vec<_>>()
+        );
+
+        // Borrowed, owned and re-borrowed ops render the same
+        let owned = diff(&old, &new);
+        let patches = render_patches(&result.ops, &RenderConfig::DEV);
+        assert_eq!(patches, render_patches(&owned.ops, &RenderConfig::DEV));
+        let reborrowed: Vec<PatchOpRef<'_, _>> = owned.ops.iter().map(PatchOp::to_ref).collect();
+        assert_eq!(patches, render_patches(&reborrowed, &RenderConfig::DEV));
+        assert_eq!(patches, render_patches(&result.into_owned().ops, &RenderConfig::DEV));
+    }
 }
@@ -0,0 +1,229 @@
+//! Ignore Rules
+//!
+//! Some content changes on every build without anyone editing it: build
+//! timestamps, cache-busting query strings, generated nonces. Diffing it
+//! sends patches (and sometimes reloads) for nothing. [`IgnoreRules`] tell
+//! the diff to leave such content alone:
+//!
+//! ```ignore
+//! let rules = IgnoreRules::new()
+//!     .attr("data-build*")                       // attribute name patterns
+//!     .query_string("href")                      // compare URLs without ?query
+//!     .query_string("src")
+//!     .elements(Selector::parse("time[data-build], :family(math)")?)
+//!     .subtree_if(|el| el.tag == "x-clock");     // ElementInfo predicates
+//!
+//! let config = DiffConfig::default().with_ignore(rules);
+//! ```
+//!
+//! Ignored attribute changes are dropped from `UpdateAttrs`. Ignored
+//! elements keep their old attributes and children, like
+//! [`PolicyAction::Keep`](super::PolicyAction::Keep); they are still
+//! inserted and removed when they appear or disappear. Each skipped change
+//! counts toward `DiffStats::ignored`.
+
+use std::fmt;
+use std::sync::Arc;
+
+use rustc_hash::FxHashSet;
+
+use crate::core::{HasStableId, PhaseExt};
+use crate::id::StableId;
+use crate::node::Document;
+use crate::selector::Selector;
+
+use super::policy::ElementInfo;
+
+type SubtreePredicate = Arc<dyn Fn(&ElementInfo<'_>) -> bool + Send + Sync>;
+
+/// Content the diff should not patch. Empty by default.
+#[derive(Clone, Default)]
+pub struct IgnoreRules {
+    /// Attribute name patterns whose changes are dropped
+    attrs: Vec<String>,
+    /// Attribute name patterns whose values are compared without query strings
+    query_attrs: Vec<String>,
+    /// Elements left alone with their subtrees
+    elements: Vec<Selector>,
+    /// Predicates for elements left alone with their subtrees
+    subtrees: Vec<SubtreePredicate>,
+}
+
+impl IgnoreRules {
+    /// Create an empty rule set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ignore changes to attributes matching `pattern`, an attribute name
+    /// in which `*` matches any run of characters (`data-build*`).
+    pub fn attr(mut self, pattern: impl Into<String>) -> Self {
+        self.attrs.push(pattern.into());
+        self
+    }
+
+    /// Compare values of attributes matching `pattern` without their query
+    /// strings, so `app.css?v=1` and `app.css?v=2` count as equal.
+    pub fn query_string(mut self, pattern: impl Into<String>) -> Self {
+        self.query_attrs.push(pattern.into());
+        self
+    }
+
+    /// Leave elements matching `selector` and their subtrees alone. An
+    /// element is ignored if it matches in the old or the new document.
+    pub fn elements(mut self, selector: Selector) -> Self {
+        self.elements.push(selector);
+        self
+    }
+
+    /// Leave elements for which `predicate` returns true and their
+    /// subtrees alone.
+    pub fn subtree_if<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&ElementInfo<'_>) -> bool + Send + Sync + 'static,
+    {
+        self.subtrees.push(Arc::new(predicate));
+        self
+    }
+
+    /// Whether a change of attribute `name` from `old` to `new` is ignored.
+    pub(super) fn ignores_attr(&self, name: &str, old: Option<&str>, new: Option<&str>) -> bool {
+        if self.attrs.iter().any(|pattern| glob_match(pattern, name)) {
+            return true;
+        }
+        match (old, new) {
+            (Some(old), Some(new)) => {
+                self.query_attrs.iter().any(|pattern| glob_match(pattern, name))
+                    && without_query(old) == without_query(new)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the subtree of an element is ignored by a predicate.
+    pub(super) fn ignores_subtree(&self, el: &ElementInfo<'_>) -> bool {
+        self.subtrees.iter().any(|predicate| predicate(el))
+    }
+
+    /// StableIds of elements matched by the selector rules in either document.
+    pub(super) fn matched_elements<P>(&self, old: &Document<P>, new: &Document<P>) -> FxHashSet<StableId>
+    where
+        P: PhaseExt,
+        P::Ext: HasStableId,
+    {
+        let mut ids = FxHashSet::default();
+        for selector in &self.elements {
+            for doc in [old, new] {
+                ids.extend(selector.select(doc).into_iter().map(|elem| elem.ext.stable_id()));
+            }
+        }
+        ids
+    }
+}
+
+impl fmt::Debug for IgnoreRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IgnoreRules")
+            .field("attrs", &self.attrs)
+            .field("query_attrs", &self.query_attrs)
+            .field("elements", &self.elements)
+            .field("subtrees", &self.subtrees.len())
+            .finish()
+    }
+}
+
+/// Match `name` against `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No `*`: exact match
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// A URL without its query string, as (before `?`, `#fragment`).
+fn without_query(url: &str) -> (&str, &str) {
+    match url.split_once('?') {
+        Some((base, rest)) => (base, rest.find('#').map_or("", |at| &rest[at..])),
+        None => (url, ""),
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+    use crate::algo::{diff, diff_with_config, DiffConfig, PatchOp};
+    use crate::algo::test_site::doc;
+
+    #[test]
+    fn test_glob_and_query_matching() {
+        assert!(glob_match("data-build", "data-build"));
+        assert!(!glob_match("data-build", "data-builder"));
+        assert!(glob_match("data-build*", "data-build-time"));
+        assert!(glob_match("*-nonce", "csp-nonce"));
+        assert!(glob_match("data-*-at", "data-built-at"));
+        assert!(!glob_match("data-*-at", "data-at"));
+
+        assert_eq!(without_query("app.css?v=2#top"), ("app.css", "#top"));
+        assert_eq!(without_query("app.css"), ("app.css", ""));
+    }
+
+    #[test]
+    fn test_ignored_changes_are_skipped_and_counted() {
+        let old = doc(concat!(
+            r#"<link rel="stylesheet" href="app.css?v=1">"#,
+            r#"<p data-build-time="100" title="a">x</p>"#,
+            r#"<time data-build>Mon</time>"#,
+            r#"<x-clock><b>12:00</b></x-clock>"#,
+        ));
+        let new = doc(concat!(
+            r#"<link rel="stylesheet" href="app.css?v=2">"#,
+            r#"<p data-build-time="200" title="b">x</p>"#,
+            r#"<time data-build>Tue</time>"#,
+            r#"<x-clock><b>12:01</b></x-clock>"#,
+        ));
+        let result = diff(&old, &new);
+        assert!(result.ops.len() > 1, "{:?}", result.ops);
+
+        let rules = IgnoreRules::new()
+            .attr("data-build*")
+            .query_string("href")
+            .elements(Selector::parse("time[data-build]").unwrap())
+            .subtree_if(|el| el.tag == "x-clock");
+        let result = diff_with_config(&old, &new, DiffConfig::default().with_ignore(rules));
+        assert!(
+            matches!(
+                result.ops.as_slice(),
+                [PatchOp::UpdateAttrs { changes, .. }] if changes.len() == 1 && changes[0].0 == "title"
+            ),
+            "{:?}",
+            result.ops
+        );
+        // The query string, the build attribute, <time> and <x-clock>
+        assert_eq!(result.stats.ignored, 4);
+
+        // Other URL changes still go through, query string included
+        let old = doc(r#"<link rel="stylesheet" href="app.css?v=1">"#);
+        let new = doc(r#"<link rel="stylesheet" href="print.css?v=2">"#);
+        let config = DiffConfig::default().with_ignore(IgnoreRules::new().query_string("href"));
+        let result = diff_with_config(&old, &new, config);
+        assert_eq!(result.stats.ignored, 0);
+        assert!(!result.ops.is_empty());
+    }
+}
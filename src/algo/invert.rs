@@ -0,0 +1,322 @@
+//! Inverse Patches
+//!
+//! Builds the operations that undo a patch set, so a page can be rolled back
+//! to the previous build without a reload (for example when the new build
+//! fails validation after its patches went out):
+//!
+//! ```text
+//! let result = diff(&old, &new);
+//! let undo = invert(&old, &result.ops)?;
+//! apply_patches(&mut doc, &result.ops)?;  // doc matches new
+//! apply_patches(&mut doc, &undo)?;        // doc matches old again
+//! ```
+//!
+//! Each inverse is computed against the document as it stands before its
+//! forward operation runs: the old attribute values, the removed subtree and
+//! where it sat, the anchor a moved element left. The inverses run in
+//! reverse order.
+
+use crate::core::{ElementExt, ExtChange, HasStableId, PhaseExt};
+use crate::id::StableId;
+use crate::node::{Document, Element, Node};
+
+use super::apply::{apply_patch, find_path, ApplyError};
+use super::diff::{Anchor, PatchOp, PatchOpRef};
+use super::myers::DEFAULT_MAX_EDIT_DISTANCE;
+use super::reparent::{at_path, child_anchor};
+use super::text::text_splices;
+use super::tokens::token_update;
+
+/// Operations that undo `ops` when applied after them to `old`.
+///
+/// `ops` are applied to a copy of `old` along the way, so an operation that
+/// would not apply fails here with the same error.
+pub fn invert<P>(old: &Document<P>, ops: &[PatchOp<P>]) -> Result<Vec<PatchOp<P>>, ApplyError>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId + Clone,
+{
+    let mut doc = old.clone();
+    let mut inverse = Vec::with_capacity(ops.len());
+    for op in ops {
+        inverse.push(invert_op(&doc.root, op)?);
+        apply_patch(&mut doc, op)?;
+    }
+    inverse.reverse();
+    Ok(inverse)
+}
+
+/// The inverse of `op`, for a document with root `root` before `op` runs.
+fn invert_op<P>(root: &Element<P>, op: &PatchOp<P>) -> Result<PatchOp<P>, ApplyError>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId + Clone,
+{
+    let target = |id: StableId| find(root, id).ok_or(ApplyError::TargetNotFound(id));
+    let inverse = match op {
+        PatchOp::Replace { target: id, element, is_svg } => PatchOp::Replace {
+            target: element.ext.stable_id(),
+            element: Box::new(target(*id)?.clone()),
+            is_svg: *is_svg,
+        },
+        PatchOp::UpdateText { target: id, .. } | PatchOp::ReplaceChildren { target: id, .. } => {
+            restore_children(target(*id)?)
+        }
+        PatchOp::UpdateTextAt { parent, index, .. } => PatchOp::UpdateTextAt {
+            parent: *parent,
+            index: *index,
+            text: text_at(target(*parent)?, *index)?.to_string(),
+        },
+        PatchOp::SpliceText { parent, index, text, .. } => {
+            let old = text_at(target(*parent)?, *index)?;
+            PatchOp::SpliceText {
+                parent: *parent,
+                index: *index,
+                splices: text_splices(text, old, DEFAULT_MAX_EDIT_DISTANCE),
+                text: old.to_string(),
+            }
+        }
+        PatchOp::RemoveTextAt { parent, index } => {
+            let elem = target(*parent)?;
+            text_at(elem, *index)?;
+            PatchOp::Insert {
+                anchor: Anchor::ChildIndex(*parent, *index),
+                node: elem.children[*index].clone(),
+                is_svg: children_in_svg(root, *parent),
+            }
+        }
+        PatchOp::Remove { target: id } => {
+            let (parent, index) = position(root, *id)?;
+            PatchOp::Insert {
+                anchor: child_anchor(parent, index),
+                node: parent.children[index].clone(),
+                is_svg: children_in_svg(root, parent.ext.stable_id()),
+            }
+        }
+        PatchOp::Insert { anchor, node, .. } => match node {
+            Node::Element(elem) => PatchOp::Remove { target: elem.ext.stable_id() },
+            Node::Text(_) => {
+                let (parent, index) = insert_position(root, *anchor)?;
+                PatchOp::RemoveTextAt { parent: parent.ext.stable_id(), index }
+            }
+            // Comments and other markup have no id to remove them by
+            _ => restore_children(insert_position(root, *anchor)?.0),
+        },
+        PatchOp::Move { target: id, .. } => {
+            let (parent, index) = position(root, *id)?;
+            PatchOp::Move { target: *id, to: child_anchor(parent, index) }
+        }
+        PatchOp::UpdateAttrs { target: id, changes } => {
+            let elem = target(*id)?;
+            let changes = changes
+                .iter()
+                .map(|(name, _)| (name.clone(), elem.get_attr(name).map(Into::into)))
+                .collect();
+            PatchOp::UpdateAttrs { target: *id, changes }
+        }
+        PatchOp::UpdateClasses { target: id, value, .. } => restore_attr(target(*id)?, "class", value),
+        PatchOp::UpdateStyles { target: id, value, .. } => restore_attr(target(*id)?, "style", value),
+        PatchOp::UpdateExt { target: id, ext, .. } => {
+            let elem = target(*id)?;
+            match ext.diff_ext(&elem.ext) {
+                ExtChange::Update(data) => PatchOp::UpdateExt { target: *id, ext: elem.ext.clone(), data },
+                ExtChange::Unchanged | ExtChange::Replace => PatchOp::Replace {
+                    target: *id,
+                    element: Box::new(elem.clone()),
+                    is_svg: position(root, *id)
+                        .is_ok_and(|(parent, _)| children_in_svg(root, parent.ext.stable_id())),
+                },
+            }
+        }
+        PatchOp::Rekey { target: id, element } => PatchOp::Rekey {
+            target: element.ext.stable_id(),
+            element: Box::new(target(*id)?.clone()),
+        },
+        PatchOp::UpdateDoc { target, change } => PatchOp::UpdateDoc {
+            target: *target,
+            change: change.inverse(),
+        },
+    };
+    Ok(inverse)
+}
+
+/// An op setting the children of `elem` back to what they are now.
+fn restore_children<P>(elem: &Element<P>) -> PatchOp<P>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    let target = elem.ext.stable_id();
+    let is_svg = elem.tag == "svg";
+    match elem.children.as_slice() {
+        [] if !is_svg => PatchOp::UpdateText { target, text: String::new() },
+        [Node::Text(text)] if !is_svg => PatchOp::UpdateText { target, text: text.content.to_string() },
+        children => PatchOp::ReplaceChildren { target, children: children.to_vec(), is_svg },
+    }
+}
+
+/// An op setting attribute `name` of `elem` back from `value`, token by
+/// token where possible.
+fn restore_attr<P>(elem: &Element<P>, name: &str, value: &str) -> PatchOp<P>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    let target = elem.ext.stable_id();
+    let old = elem.get_attr(name);
+    old.and_then(|old| token_update(target, name, value, &old.into()))
+        .map(PatchOpRef::into_owned)
+        .unwrap_or_else(|| PatchOp::UpdateAttrs { target, changes: vec![(name.into(), old.map(Into::into))] })
+}
+
+fn find<P>(root: &Element<P>, id: StableId) -> Option<&Element<P>>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    find_path(root, id).map(|path| at_path(root, &path))
+}
+
+/// Whether nodes placed in element `id` lie inside an `<svg>` (and not in
+/// a `<foreignObject>` there).
+fn children_in_svg<P>(root: &Element<P>, id: StableId) -> bool
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    find_path(root, id).is_some_and(|path| {
+        (0..=path.len())
+            .rev()
+            .map(|len| at_path(root, &path[..len]).tag.as_str())
+            .find(|&tag| tag == "svg" || tag == "foreignObject")
+            == Some("svg")
+    })
+}
+
+fn text_at<P: PhaseExt>(parent: &Element<P>, index: usize) -> Result<&str, ApplyError>
+where
+    P::Ext: HasStableId,
+{
+    match parent.children.get(index) {
+        Some(Node::Text(text)) => Ok(&text.content),
+        _ => Err(ApplyError::NotText { parent: parent.ext.stable_id(), index }),
+    }
+}
+
+/// The parent of element `id` and its index there.
+fn position<P>(root: &Element<P>, id: StableId) -> Result<(&Element<P>, usize), ApplyError>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    let path = find_path(root, id).ok_or(ApplyError::TargetNotFound(id))?;
+    let (&index, parent) = path.split_last().ok_or(ApplyError::DetachRoot(id))?;
+    Ok((at_path(root, parent), index))
+}
+
+/// The parent a node inserted at `anchor` goes into, and its index there.
+fn insert_position<P>(root: &Element<P>, anchor: Anchor) -> Result<(&Element<P>, usize), ApplyError>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    let id = anchor.target_id();
+    let found = |id| find(root, id).ok_or(ApplyError::AnchorNotFound(id));
+    match anchor {
+        Anchor::FirstChildOf(_) => Ok((found(id)?, 0)),
+        Anchor::LastChildOf(_) => found(id).map(|parent| (parent, parent.children.len())),
+        Anchor::ChildIndex(_, index) => Ok((found(id)?, index)),
+        Anchor::Before(_) | Anchor::After(_) => {
+            let (parent, index) = position(root, id).map_err(|err| match err {
+                ApplyError::DetachRoot(id) => ApplyError::RootSibling(id),
+                _ => ApplyError::AnchorNotFound(id),
+            })?;
+            Ok((parent, index + usize::from(matches!(anchor, Anchor::After(_)))))
+        }
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+    use crate::algo::{apply_patches, diff, diff_with_config, DiffConfig};
+    use crate::algo::test_site::doc;
+    use crate::render::{render_document, RenderConfig};
+
+    /// Apply the diff from `old` to `new` and its inverse, checking both ends.
+    fn roundtrip(old: &str, new: &str, config: DiffConfig) {
+        let (old, new) = (doc(old), doc(new));
+        let html = |doc: &Document<_>| render_document(doc, &RenderConfig::DEV);
+        let result = diff_with_config(&old, &new, config);
+        assert!(!result.ops.is_empty());
+        let undo = invert(&old, &result.ops).unwrap();
+
+        let mut patched = old.clone();
+        apply_patches(&mut patched, &result.ops).unwrap();
+        assert_eq!(html(&patched), html(&new));
+        apply_patches(&mut patched, &undo).unwrap();
+        assert_eq!(html(&patched), html(&old), "undo: {:?}", undo);
+        assert_eq!(patched.content_hash(), old.content_hash());
+    }
+
+    #[test]
+    fn test_inverse_restores_old_document() {
+        let cases = [
+            // Attributes, classes and styles
+            (
+                r#"<p class="a b" style="color: red; margin: 0" title="x">t</p>"#,
+                r#"<p class="b c" style="color: blue" lang="en">t</p>"#,
+            ),
+            // Text, single and mixed
+            ("<h2>Old</h2><p>a <b>b</b> c</p>", "<h2></h2><p>a <b>b</b> d</p>"),
+            ("<p>a <b>b</b> c</p>", "<p><b>b</b> c <i>i</i></p>"),
+            (
+                "<pre>let x = 1;\nlet y = 2;\nlet z = 3;\nlet w = 4;</pre>",
+                "<pre>let x = 10;\nlet y = 2;\nlet z = 3;\nlet w = 4;</pre>",
+            ),
+            // Inserted, removed, moved and replaced elements
+            (
+                r#"<ul><li key="1">1</li><li key="2">2</li><li key="3">3</li></ul><div>x</div>"#,
+                r#"<ul><li key="3">3</li><li key="1">1</li><li key="4">4</li></ul><section>x</section>"#,
+            ),
+            // Comments are restored with their parent's children
+            ("<div><!-- a --><p>x</p></div>", "<div><!-- b --><p>x</p></div>"),
+            ("<div></div>", "<div><!-- a --><p>x</p></div>"),
+        ];
+        for (old, new) in cases {
+            roundtrip(old, new, DiffConfig::default());
+        }
+
+        // Cross-parent moves and rekeys
+        roundtrip(
+            "<main><section><h2>A</h2><p>Long text</p></section></main>",
+            "<main><div><section><h2>A</h2><p>Long text</p></section></div></main>",
+            DiffConfig::default().with_reparent_detection(true),
+        );
+    }
+
+    #[test]
+    fn test_inverse_ops() {
+        let old = doc(r#"<body><ul><li key="1">1</li><li key="2">2</li></ul></body>"#);
+        let new = doc(r#"<body><ul><li key="2">2</li></ul></body>"#);
+        let result = diff(&old, &new);
+        let undo = invert(&old, &result.ops).unwrap();
+        let ul = old.root.children[0].as_element().unwrap().children[0].as_element().unwrap();
+        assert!(
+            matches!(
+                undo.as_slice(),
+                [PatchOp::Insert { anchor: Anchor::FirstChildOf(parent), node: Node::Element(li), .. }]
+                    if *parent == ul.ext.stable_id() && li.get_attr("key") == Some("1")
+            ),
+            "{undo:?}"
+        );
+
+        // Ops that do not apply to `old` fail like `apply_patches`
+        let missing = PatchOp::Remove { target: StableId::from_raw(7) };
+        assert_eq!(invert(&old, &[missing]).unwrap_err(), ApplyError::TargetNotFound(StableId::from_raw(7)));
+    }
+}
@@ -1,14 +1,44 @@
 //! Algorithm implementations for VDOM operations.
 //!
 //! - `diff`: VDOM diff algorithm with edit operations
+//! - `apply`: Apply edit operations to a document
+//! - `invert`: Inverse operations for rolling a document back
 //! - `myers`: Myers diff algorithm for efficient LCS
+//! - `policy`: Rules for how changed elements are patched
+//! - `cost`: Rendered size estimates for patch-versus-reload decisions
+//! - `ignore`: Rules for volatile content the diff leaves alone
+//! - `reparent`: Cross-parent move detection by content hash
+//! - `text`: Character-level splices for edits to long text
+//! - `tokens`: Token-level diffing of `class` and `style`
 //! - `hash`: Stable hashing utilities
 
+mod apply;
+mod cost;
 mod diff;
 mod hash;
+mod ignore;
+mod invert;
 mod myers;
+mod policy;
+mod reparent;
+pub(crate) mod text;
+pub(crate) mod tokens;
 
-pub use diff::{diff, diff_with_config, Anchor, DiffConfig, DiffResult, DiffStats, Patch, PatchOp};
+#[cfg(all(test, feature = "macros"))]
+mod test_site;
+#[cfg(test)]
+mod test_rng;
+
+pub use apply::{apply_patch, apply_patches, ApplyError};
+pub use cost::{estimate_element_bytes, estimate_node_bytes, estimate_patch_bytes};
+pub use diff::{
+    diff, diff_ref, diff_with_config, Anchor, DiffConfig, DiffResult, DiffResultRef, DiffStats, Patch,
+    PatchOp, PatchOpRef, ReloadReason,
+};
 pub use hash::StableHasher;
+pub use ignore::IgnoreRules;
+pub use invert::invert;
+pub use policy::{AttrChange, DefaultPolicy, DiffPolicy, ElementInfo, PolicyAction};
+pub use text::TextSplice;
 // Use Myers algorithm (better for hot reload scenarios)
-pub use myers::{diff_sequences, Edit, LcsResult, LcsStats};
+pub use myers::{diff_sequences, diff_sequences_with, Edit, LcsResult, LcsStats, DEFAULT_MAX_EDIT_DISTANCE};
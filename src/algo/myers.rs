@@ -7,22 +7,31 @@
 //! | Algorithm | Time | Space | Best for |
 //! |-----------|------|-------|----------|
 //! | DP | O(n*m) | O(min(n,m)) | General |
-//! | **Myers** | O((n+m)*d) | O(d*(n+m)) | **Small diffs (hot reload)** |
-//! | Patience | O(n log n) | O(n) | Code diffs |
+//! | **Myers** | O((n+m)*d) | O(n+m) | **Small diffs (hot reload)** |
+//! | Patience | O(n log n) | O(n) | Code diffs, huge edits |
 //!
 //! For SSG hot reload:
 //! - Edit distance `d` is typically very small (1-5 edits)
 //! - O((n+m)*d) ≈ O(n+m) linear for small d
 //! - Perfect match for incremental updates
 //!
-//! # Space Complexity Note
+//! # Space Complexity
 //!
-//! This implementation stores a full trace for backtracking (`O(d)` snapshots,
-//! each of size `O(n+m)`), resulting in **O(d*(n+m))** space complexity.
-//! For large documents with many edits, this could be significant.
+//! This is the linear-space variant: instead of keeping a trace of every
+//! search frontier for backtracking, it finds the *middle snake* of the edit
+//! path with a forward and a reverse search, then splits the problem there.
+//! Only two frontiers are live at a time, each bounded by the edit distance
+//! ceiling, so space is **O(n+m)** at the cost of roughly 2x time. The split
+//! runs on an explicit work list rather than recursion.
 //!
-//! Future optimization: Use linear-space Myers variant (divide-and-conquer)
-//! which achieves O(n+m) space at the cost of 2x time.
+//! # Edit Distance Ceiling
+//!
+//! Past `max_edit_distance` edits (default [`DEFAULT_MAX_EDIT_DISTANCE`],
+//! configurable through `DiffConfig`), the O((n+m)*d) search stops and the
+//! LCS is computed by patience diff instead: the longest increasing run of
+//! elements that occur once in both lists, extended over equal neighbours.
+//! That is O(n log n) regardless of how different the lists are, and matches
+//! everything for sibling lists of unique StableIds.
 //!
 //! # References
 //!
@@ -33,6 +42,8 @@
 //! - Uses common prefix/suffix optimization for further speedup
 //! - Move detection: nodes present in both sequences but not in LCS
 
+use std::hash::Hash;
+
 use rustc_hash::FxHashMap;
 
 use crate::id::StableId;
@@ -78,6 +89,8 @@ pub struct LcsStats {
     pub inserted: usize,
     pub deleted: usize,
     pub moved: usize,
+    /// The edit distance exceeded the ceiling and patience diff was used
+    pub patience_fallback: bool,
 }
 
 impl LcsStats {
@@ -94,10 +107,19 @@ impl LcsStats {
 // Main API
 // =============================================================================
 
+/// Default edit distance ceiling for the Myers search.
+pub const DEFAULT_MAX_EDIT_DISTANCE: usize = 512;
+
 /// Compute diff between two sequences using Myers algorithm
 ///
 /// Detects: Keep, Insert, Delete, Move operations
 pub fn diff_sequences(old: &[StableId], new: &[StableId]) -> LcsResult {
+    diff_sequences_with(old, new, DEFAULT_MAX_EDIT_DISTANCE)
+}
+
+/// Like [`diff_sequences`], falling back to patience diff once the edit
+/// distance exceeds `max_edit_distance`.
+pub fn diff_sequences_with(old: &[StableId], new: &[StableId], max_edit_distance: usize) -> LcsResult {
     // Quick paths
     if old.is_empty() && new.is_empty() {
         return LcsResult::default();
@@ -122,10 +144,12 @@ pub fn diff_sequences(old: &[StableId], new: &[StableId]) -> LcsResult {
     let new_map: FxHashMap<StableId, usize> = new.iter().copied().enumerate().map(|(i, id)| (id, i)).collect();
 
     // Compute LCS using Myers algorithm
-    let lcs = myers_lcs(old, new);
+    let (lcs, patience_fallback) = myers_lcs(old, new, max_edit_distance);
 
     // Extract edit script with move detection
-    extract_edits(old, new, &lcs, &old_map, &new_map)
+    let mut result = extract_edits(old, new, &lcs, &old_map, &new_map);
+    result.stats.patience_fallback = patience_fallback;
+    result
 }
 
 // =============================================================================
@@ -133,24 +157,18 @@ pub fn diff_sequences(old: &[StableId], new: &[StableId]) -> LcsResult {
 // =============================================================================
 
 /// Compute LCS using Myers diff algorithm with prefix/suffix optimization
-fn myers_lcs(old: &[StableId], new: &[StableId]) -> Vec<(usize, usize)> {
+///
+/// Also returns whether the patience fallback was used.
+pub(super) fn myers_lcs<T: Copy + Eq + Hash>(
+    old: &[T],
+    new: &[T],
+    max_edit_distance: usize,
+) -> (Vec<(usize, usize)>, bool) {
     let n = old.len();
     let m = new.len();
 
     // Optimization: strip common prefix
-    let mut prefix_len = 0;
-    while prefix_len < n && prefix_len < m && old[prefix_len] == new[prefix_len] {
-        prefix_len += 1;
-    }
-
-    // Optimization: strip common suffix
-    let mut suffix_len = 0;
-    while suffix_len < (n - prefix_len)
-        && suffix_len < (m - prefix_len)
-        && old[n - 1 - suffix_len] == new[m - 1 - suffix_len]
-    {
-        suffix_len += 1;
-    }
+    let (prefix_len, suffix_len) = common_ends(old, new);
 
     // Build prefix pairs
     let mut lcs: Vec<(usize, usize)> = (0..prefix_len).map(|i| (i, i)).collect();
@@ -159,8 +177,15 @@ fn myers_lcs(old: &[StableId], new: &[StableId]) -> Vec<(usize, usize)> {
     let old_mid = &old[prefix_len..n - suffix_len];
     let new_mid = &new[prefix_len..m - suffix_len];
 
+    let mut patience_fallback = false;
     if !old_mid.is_empty() && !new_mid.is_empty() {
-        let mid_lcs = myers_core(old_mid, new_mid);
+        let mid_lcs = match Myers::new(old_mid, new_mid, max_edit_distance).lcs() {
+            Some(mid_lcs) => mid_lcs,
+            None => {
+                patience_fallback = true;
+                patience_lcs(old_mid, new_mid)
+            }
+        };
         for (oi, ni) in mid_lcs {
             lcs.push((oi + prefix_len, ni + prefix_len));
         }
@@ -171,153 +196,258 @@ fn myers_lcs(old: &[StableId], new: &[StableId]) -> Vec<(usize, usize)> {
         lcs.push((n - suffix_len + i, m - suffix_len + i));
     }
 
-    lcs
+    (lcs, patience_fallback)
 }
 
-/// Myers algorithm core implementation
-///
-/// Based on "An O(ND) Difference Algorithm" by Eugene W. Myers (1986)
-///
-/// The key insight: explore the edit graph by d (edit distance), not by position.
-/// For each d, we track the furthest-reaching path on each diagonal k = x - y.
-///
-/// # Optimizations
-///
-/// 1. **Early termination**: If edit distance exceeds `MAX_EDIT_DISTANCE`, returns None
-///    to signal that a full reload is more efficient than computing the full diff.
-/// 2. **Small array fast path**: For sequences ≤8 elements, uses simple O(n²) DP
-///    which is faster due to cache locality and no allocation overhead.
-fn myers_core(old: &[StableId], new: &[StableId]) -> Vec<(usize, usize)> {
-    let n = old.len();
-    let m = new.len();
+/// Lengths of the common prefix and (non-overlapping) common suffix.
+fn common_ends<T: Eq>(old: &[T], new: &[T]) -> (usize, usize) {
+    let prefix = old.iter().zip(new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+    (prefix, suffix)
+}
 
-    if n == 0 || m == 0 {
-        return Vec::new();
-    }
+/// Middle snake of an edit path: the diagonal run from `(x, y)` to `(u, v)`
+/// that the optimal path crosses halfway.
+struct Snake {
+    x: usize,
+    y: usize,
+    u: usize,
+    v: usize,
+}
 
-    // Small array optimization: use simple DP for small sequences
-    // Cache-friendly and no allocation overhead beats Myers for n,m ≤ 8
-    if n <= 8 && m <= 8 {
-        return small_lcs_dp(old, new);
-    }
+/// Linear-space Myers search (divide and conquer on the middle snake).
+///
+/// Based on section 4b of "An O(ND) Difference Algorithm" by Eugene W.
+/// Myers (1986). The forward search explores the edit graph from the start
+/// by edit distance d, tracking the furthest-reaching x on each diagonal
+/// k = x - y; the reverse search does the same from the end. Where they
+/// first overlap is the middle snake.
+struct Myers<'s, T> {
+    old: &'s [T],
+    new: &'s [T],
+    /// Furthest x per diagonal of the forward search
+    forward: Vec<isize>,
+    /// Furthest distance from the end per diagonal of the reverse search
+    reverse: Vec<isize>,
+    /// Largest d either search may reach
+    max_d: usize,
+    /// Edit distance past which the top-level search gives up
+    max_edit_distance: usize,
+}
 
-    let max_d = n + m;
-    let offset = max_d; // To handle negative k indices
+impl<'s, T: Copy + Eq + Hash> Myers<'s, T> {
+    fn new(old: &'s [T], new: &'s [T], max_edit_distance: usize) -> Self {
+        // A path with D edits has its middle snake at d = ceil(D / 2), and
+        // sub-problems never have more edits than the whole
+        let max_d = (old.len() + new.len()).div_ceil(2).min(max_edit_distance.div_ceil(2) + 1);
+        // Diagonals -(max_d + 1)..=(max_d + 1) are read
+        let len = 2 * max_d + 3;
+        Self {
+            old,
+            new,
+            forward: vec![0; len],
+            reverse: vec![0; len],
+            max_d,
+            max_edit_distance,
+        }
+    }
 
-    // Early termination threshold: if edit distance exceeds this,
-    // the diff is too large and full reload is more efficient
-    const MAX_EDIT_DISTANCE: usize = 512;
+    /// LCS index pairs in order, or `None` past the edit distance ceiling.
+    fn lcs(mut self) -> Option<Vec<(usize, usize)>> {
+        let mut lcs = Vec::new();
+        // Sub-problems as (old range, new range) offsets
+        let mut work = vec![(0, self.old.len(), 0, self.new.len())];
+        let mut limit = self.max_edit_distance;
+
+        while let Some((x0, x1, y0, y1)) = work.pop() {
+            let old = &self.old[x0..x1];
+            let new = &self.new[y0..y1];
+            let (prefix, suffix) = common_ends(old, new);
+            lcs.extend((0..prefix).map(|i| (x0 + i, y0 + i)));
+            lcs.extend((1..=suffix).map(|i| (x1 - i, y1 - i)));
+
+            let old = &old[prefix..old.len() - suffix];
+            let new = &new[prefix..new.len() - suffix];
+            let (x0, y0) = (x0 + prefix, y0 + prefix);
+            if old.is_empty() || new.is_empty() {
+                continue;
+            }
 
-    // V[k + offset] = furthest x on diagonal k
-    let mut v = vec![0usize; 2 * max_d + 1];
+            // Small array optimization: use simple DP for small sequences
+            // Cache-friendly and no allocation overhead beats Myers for n,m ≤ 8
+            if old.len() <= 8 && new.len() <= 8 {
+                lcs.extend(small_lcs_dp(old, new).into_iter().map(|(o, n)| (x0 + o, y0 + n)));
+                continue;
+            }
 
-    // Store V at each d for backtracking
-    let mut trace: Vec<Vec<usize>> = Vec::with_capacity(max_d.min(MAX_EDIT_DISTANCE) + 1);
+            // Only the whole problem can exceed the ceiling
+            let snake = self.middle_snake(old, new, limit)?;
+            limit = usize::MAX;
 
-    // Forward pass: find shortest edit script
-    'outer: for d in 0..=max_d {
-        // Early termination: if edit distance is too large, bail out
-        // The caller will fall back to full reload
-        if d > MAX_EDIT_DISTANCE {
-            // Return empty LCS to signal "too different"
-            // This triggers maximum edits in extract_edits
-            return Vec::new();
+            // With common ends stripped there are at least two edits, so
+            // both halves have fewer than the whole
+            lcs.extend((0..snake.u - snake.x).map(|i| (x0 + snake.x + i, y0 + snake.y + i)));
+            work.push((x0, x0 + snake.x, y0, y0 + snake.y));
+            work.push((x0 + snake.u, x0 + old.len(), y0 + snake.v, y0 + new.len()));
         }
 
-        trace.push(v.clone());
-
-        // Iterate over diagonals k in [-d, d] with same parity as d
-        for k in (-(d as isize)..=(d as isize)).step_by(2) {
-            let kk = (k + offset as isize) as usize;
+        lcs.sort_unstable();
+        Some(lcs)
+    }
 
-            // Decide: come from k-1 (delete) or k+1 (insert)?
-            // At k=-d, must come from k+1 (insert)
-            // At k=d, must come from k-1 (delete)
-            // Otherwise, pick whichever reaches further right
-            let mut x = if k == -(d as isize) || (k != d as isize && v[kk - 1] < v[kk + 1]) {
-                v[kk + 1] // insert: x stays same, y increases
-            } else {
-                v[kk - 1] + 1 // delete: x increases
-            };
+    /// Find the middle snake of the edit path between `old` and `new`, or
+    /// `None` if the path has more than `limit` edits.
+    fn middle_snake(&mut self, old: &[T], new: &[T], limit: usize) -> Option<Snake> {
+        let n = old.len() as isize;
+        let m = new.len() as isize;
+        let delta = n - m;
+        let odd = delta % 2 != 0;
+        let offset = self.max_d as isize + 1;
+        let at = |k: isize| (k + offset) as usize;
 
-            let mut y = (x as isize - k) as usize;
+        self.forward[at(1)] = 0;
+        self.reverse[at(1)] = 0;
 
-            // Extend snake: follow diagonal while elements match
-            while x < n && y < m && old[x] == new[y] {
-                x += 1;
-                y += 1;
+        for d in 0..=self.max_d {
+            // No overlap up to d - 1 means at least 2d - 1 edits
+            if 2 * d > limit.saturating_add(1) {
+                return None;
+            }
+            let d = d as isize;
+
+            for k in (-d..=d).step_by(2) {
+                // Come from k+1 (insert) or k-1 (delete), whichever reaches further
+                let mut x = if k == -d || (k != d && self.forward[at(k - 1)] < self.forward[at(k + 1)]) {
+                    self.forward[at(k + 1)]
+                } else {
+                    self.forward[at(k - 1)] + 1
+                };
+                let mut y = x - k;
+                let (x_start, y_start) = (x, y);
+
+                // Extend snake: follow diagonal while elements match
+                while x < n && y < m && old[x as usize] == new[y as usize] {
+                    x += 1;
+                    y += 1;
+                }
+                self.forward[at(k)] = x;
+
+                // The reverse search is on diagonal delta - k, one step behind
+                let reverse_k = delta - k;
+                if odd
+                    && (-(d - 1)..=d - 1).contains(&reverse_k)
+                    && x + self.reverse[at(reverse_k)] >= n
+                {
+                    return Some(Snake {
+                        x: x_start as usize,
+                        y: y_start as usize,
+                        u: x as usize,
+                        v: y as usize,
+                    });
+                }
             }
 
-            v[kk] = x;
-
-            // Check if we reached the end
-            if x >= n && y >= m {
-                break 'outer;
+            for k in (-d..=d).step_by(2) {
+                let mut x = if k == -d || (k != d && self.reverse[at(k - 1)] < self.reverse[at(k + 1)]) {
+                    self.reverse[at(k + 1)]
+                } else {
+                    self.reverse[at(k - 1)] + 1
+                };
+                let mut y = x - k;
+                let (x_start, y_start) = (x, y);
+
+                // Extend snake backwards from the end
+                while x < n && y < m && old[(n - 1 - x) as usize] == new[(m - 1 - y) as usize] {
+                    x += 1;
+                    y += 1;
+                }
+                self.reverse[at(k)] = x;
+
+                let forward_k = delta - k;
+                if !odd && (-d..=d).contains(&forward_k) && x + self.forward[at(forward_k)] >= n {
+                    return Some(Snake {
+                        x: (n - x) as usize,
+                        y: (m - y) as usize,
+                        u: (n - x_start) as usize,
+                        v: (m - y_start) as usize,
+                    });
+                }
             }
         }
-    }
 
-    // Backtrack to find LCS
-    backtrack(&trace, old, new, n, m, offset)
+        // Only reachable when `max_d` was capped by the ceiling
+        None
+    }
 }
 
-/// Backtrack through trace to extract LCS pairs
-fn backtrack(
-    trace: &[Vec<usize>],
-    old: &[StableId],
-    new: &[StableId],
-    n: usize,
-    m: usize,
-    offset: usize,
-) -> Vec<(usize, usize)> {
-    let mut x = n;
-    let mut y = m;
-    let mut lcs = Vec::new();
-
-    for (d, v) in trace.iter().enumerate().rev() {
-        let k = x as isize - y as isize;
-        let kk = (k + offset as isize) as usize;
-
-        // Determine previous k (before this edit)
-        let prev_k = if d == 0 {
-            // At d=0, we started at (0,0)
-            0isize
-        } else if k == -(d as isize) || (k != d as isize && v[kk - 1] < v[kk + 1]) {
-            k + 1 // came from insert
-        } else {
-            k - 1 // came from delete
-        };
-
-        let prev_kk = (prev_k + offset as isize) as usize;
-        let prev_x = if d == 0 { 0 } else { v[prev_kk] };
-        let prev_y = (prev_x as isize - prev_k) as usize;
-
-        // Collect matches along the snake (diagonal moves)
-        while x > prev_x && y > prev_y {
-            x -= 1;
-            y -= 1;
-            if old[x] == new[y] {
-                lcs.push((x, y));
-            }
+/// Patience diff: LCS over elements that occur exactly once in each list.
+///
+/// Their new positions, taken in old order, form a sequence whose longest
+/// increasing subsequence is the LCS of the unique elements. Runs between
+/// those anchors are then extended over equal neighbours. O(n log n) time
+/// and O(n) space however different the lists are.
+fn patience_lcs<T: Copy + Eq + Hash>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    // id -> (occurrences in old, occurrences in new, new index)
+    let mut counts: FxHashMap<T, (u32, u32, usize)> = FxHashMap::default();
+    for &id in old {
+        counts.entry(id).or_default().0 += 1;
+    }
+    for (new_idx, &id) in new.iter().enumerate() {
+        if let Some(entry) = counts.get_mut(&id) {
+            entry.1 += 1;
+            entry.2 = new_idx;
         }
-
-        // Step back before the edit
-        if d > 0 {
-            if prev_k < k {
-                // Was a delete: x decreased by 1
-                x = prev_x;
-            } else {
-                // Was an insert: y decreased by 1
-                y = prev_y;
-            }
+    }
+    let unique: Vec<(usize, usize)> = old
+        .iter()
+        .enumerate()
+        .filter_map(|(old_idx, id)| match counts[id] {
+            (1, 1, new_idx) => Some((old_idx, new_idx)),
+            _ => None,
+        })
+        .collect();
+    drop(counts);
+
+    // Patience sorting: `tails[i]` ends the best increasing run of length
+    // i + 1; `prev` links each candidate to its predecessor in that run
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev: Vec<Option<usize>> = Vec::with_capacity(unique.len());
+    for (i, &(_, new_idx)) in unique.iter().enumerate() {
+        let pile = tails.partition_point(|&t| unique[t].1 < new_idx);
+        prev.push(pile.checked_sub(1).map(|p| tails[p]));
+        if pile == tails.len() {
+            tails.push(i);
+        } else {
+            tails[pile] = i;
         }
-
-        if x == 0 && y == 0 {
-            break;
+    }
+    let mut anchors = Vec::with_capacity(tails.len());
+    let mut cursor = tails.last().copied();
+    while let Some(i) = cursor {
+        anchors.push(unique[i]);
+        cursor = prev[i];
+    }
+    anchors.reverse();
+
+    // Extend each anchor-delimited gap by its common prefix and suffix
+    let mut lcs = Vec::with_capacity(anchors.len());
+    let mut start = (0, 0);
+    for end in anchors.iter().copied().map(Some).chain([None]) {
+        let (x1, y1) = end.unwrap_or((old.len(), new.len()));
+        let (x0, y0) = start;
+        let (prefix, suffix) = common_ends(&old[x0..x1], &new[y0..y1]);
+        lcs.extend((0..prefix).map(|i| (x0 + i, y0 + i)));
+        lcs.extend((0..suffix).rev().map(|i| (x1 - 1 - i, y1 - 1 - i)));
+        if let Some(anchor) = end {
+            lcs.push(anchor);
+            start = (anchor.0 + 1, anchor.1 + 1);
         }
     }
-
-    lcs.reverse();
     lcs
 }
 
@@ -329,7 +459,7 @@ fn backtrack(
 /// 3. Simpler control flow (no diagonal tracking)
 ///
 /// Uses stack-allocated array for the DP table when possible.
-fn small_lcs_dp(old: &[StableId], new: &[StableId]) -> Vec<(usize, usize)> {
+fn small_lcs_dp<T: Eq>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
     let n = old.len();
     let m = new.len();
 
@@ -440,6 +570,7 @@ fn extract_edits(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::algo::test_rng::Rng;
 
     fn ids(nums: &[u64]) -> Vec<StableId> {
         nums.iter().map(|&n| StableId::from_raw(n)).collect()
@@ -561,6 +692,69 @@ mod tests {
         assert_eq!(result.stats.inserted, 1); // 200
     }
 
+    /// LCS length by full DP, as the reference.
+    fn lcs_len(old: &[StableId], new: &[StableId]) -> usize {
+        let mut dp = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+        for i in 1..=old.len() {
+            for j in 1..=new.len() {
+                dp[i][j] = if old[i - 1] == new[j - 1] {
+                    dp[i - 1][j - 1] + 1
+                } else {
+                    dp[i - 1][j].max(dp[i][j - 1])
+                };
+            }
+        }
+        dp[old.len()][new.len()]
+    }
+
+    fn assert_common_subsequence(old: &[StableId], new: &[StableId], lcs: &[(usize, usize)]) {
+        for pair in lcs.windows(2) {
+            assert!(pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1, "not increasing: {lcs:?}");
+        }
+        assert!(lcs.iter().all(|&(o, n)| old[o] == new[n]));
+    }
+
+    #[test]
+    fn test_linear_space_lcs_is_optimal() {
+        for seed in 0..500 {
+            let mut rng = Rng::new(seed);
+            let alphabet = 2 + rng.below(20);
+            let seq = |rng: &mut Rng| -> Vec<StableId> {
+                (0..rng.below(60)).map(|_| StableId::from_raw(rng.below(alphabet))).collect()
+            };
+            let old = seq(&mut rng);
+            let new = seq(&mut rng);
+
+            let (lcs, fallback) = myers_lcs(&old, &new, usize::MAX);
+            assert!(!fallback);
+            assert_common_subsequence(&old, &new, &lcs);
+            assert_eq!(lcs.len(), lcs_len(&old, &new), "seed {seed}: {old:?} -> {new:?}");
+        }
+    }
+
+    #[test]
+    fn test_edit_distance_ceiling_falls_back_to_patience() {
+        // Reversing 100 unique ids takes 198 edits
+        let old = ids(&(0..100).collect::<Vec<_>>());
+        let new: Vec<StableId> = old.iter().rev().copied().collect();
+
+        let within = diff_sequences_with(&old, &new, 200);
+        assert!(!within.stats.patience_fallback);
+        assert_eq!(within.stats.kept, 1);
+
+        let result = diff_sequences_with(&old, &new, 50);
+        assert!(result.stats.patience_fallback);
+        assert_eq!(result.stats.kept, 1);
+        assert_eq!(result.stats.moved, 99);
+
+        // Duplicates next to the unique anchors are matched too
+        let old = ids(&[1, 9, 9, 2, 3]);
+        let new = ids(&[1, 9, 9, 3, 2]);
+        let lcs = patience_lcs(&old, &new);
+        assert_common_subsequence(&old, &new, &lcs);
+        assert_eq!(lcs.len(), lcs_len(&old, &new));
+    }
+
     #[test]
     fn test_suffix_optimization() {
         // Common suffix should be detected quickly
@@ -0,0 +1,232 @@
+//! Diff Policy
+//!
+//! Decides how the diff patches elements whose attributes changed, and
+//! whether some elements should be treated specially altogether:
+//!
+//! ```text
+//! <link href>   changed  -> Replace  (the browser refetches the stylesheet)
+//! <script src>  changed  -> Reload   (re-running scripts has side effects)
+//! anything else changed  -> Update   (UpdateAttrs in place)
+//! ```
+//!
+//! That is [`DefaultPolicy`], used by `DiffConfig::default()`. Custom
+//! policies implement [`DiffPolicy`] and usually fall back to
+//! `DefaultPolicy` for everything they do not handle:
+//!
+//! ```ignore
+//! #[derive(Debug)]
+//! struct SitePolicy;
+//!
+//! impl DiffPolicy for SitePolicy {
+//!     fn element(&self, el: &ElementInfo<'_>) -> PolicyAction {
+//!         match (el.tag, el.new_attrs.get("type")) {
+//!             ("script", Some("application/ld+json")) => PolicyAction::Keep,
+//!             _ => DefaultPolicy.element(el),
+//!         }
+//!     }
+//!
+//!     fn attr(&self, el: &ElementInfo<'_>, change: &AttrChange<'_>) -> PolicyAction {
+//!         match (el.tag, change.name) {
+//!             ("meta", "content") if el.new_attrs.has("http-equiv") => {
+//!                 PolicyAction::reload(el.id, "meta http-equiv changed")
+//!             }
+//!             ("my-widget", "data-config") => PolicyAction::Replace,
+//!             _ => DefaultPolicy.attr(el, change),
+//!         }
+//!     }
+//! }
+//!
+//! let config = DiffConfig::default().with_policy(SitePolicy);
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::attr::Attrs;
+use crate::id::StableId;
+
+use super::diff::ReloadReason;
+
+/// What the diff should do with an element.
+///
+/// Variants are ordered by strength: when several attribute changes of one
+/// element get different actions, the strongest wins.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PolicyAction {
+    /// Leave the browser's copy alone. For an element this skips its
+    /// attributes and children; for an attribute it drops the change.
+    Keep,
+    /// Diff normally: patch changed attributes in place and descend.
+    Update,
+    /// Replace the whole element.
+    Replace,
+    /// Give up on patching and reload the page.
+    Reload(ReloadReason),
+}
+
+impl PolicyAction {
+    /// Reload because of element `target`, with the given reason
+    /// ([`ReloadReason::PolicyRequested`]).
+    pub fn reload(target: StableId, reason: impl Into<Cow<'static, str>>) -> Self {
+        Self::Reload(ReloadReason::PolicyRequested { target, reason: reason.into() })
+    }
+}
+
+/// An element pair the policy is consulted about.
+#[derive(Debug, Clone, Copy)]
+pub struct ElementInfo<'a> {
+    /// StableId shared by the old and new element
+    pub id: StableId,
+    /// Tag name (equal on both sides; tag changes always replace)
+    pub tag: &'a str,
+    /// Family name of the new element
+    pub family: &'static str,
+    /// Attributes before the change
+    pub old_attrs: &'a Attrs,
+    /// Attributes after the change
+    pub new_attrs: &'a Attrs,
+}
+
+/// One changed attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttrChange<'a> {
+    /// Attribute name
+    pub name: &'a str,
+    /// Old value (`None` if added)
+    pub old: Option<&'a str>,
+    /// New value (`None` if removed)
+    pub new: Option<&'a str>,
+}
+
+/// Rules deciding how changed elements are patched.
+///
+/// Both methods default to [`PolicyAction::Update`], so an empty impl diffs
+/// everything in place without the [`DefaultPolicy`] rules.
+pub trait DiffPolicy: fmt::Debug + Send + Sync {
+    /// Consulted for each matched element pair whose content differs,
+    /// before its attributes and children are diffed.
+    fn element(&self, _el: &ElementInfo<'_>) -> PolicyAction {
+        PolicyAction::Update
+    }
+
+    /// Consulted for each changed attribute of an element diffed with
+    /// [`PolicyAction::Update`].
+    fn attr(&self, _el: &ElementInfo<'_>, _change: &AttrChange<'_>) -> PolicyAction {
+        PolicyAction::Update
+    }
+}
+
+/// The built-in rules: replace `<link>` on `href` changes, reload on
+/// `<script src>` changes, update everything else in place.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultPolicy;
+
+impl DiffPolicy for DefaultPolicy {
+    fn attr(&self, el: &ElementInfo<'_>, change: &AttrChange<'_>) -> PolicyAction {
+        match (el.tag, change.name) {
+            // Swapping the element makes the browser load the new stylesheet
+            ("link", "href") => PolicyAction::Replace,
+            // Re-executing a script has side effects
+            ("script", "src") => PolicyAction::Reload(ReloadReason::ScriptSrcChanged { target: el.id }),
+            _ => PolicyAction::Update,
+        }
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+    use crate::algo::{diff, diff_with_config, DiffConfig, DiffResult, PatchOp};
+    use crate::algo::test_site::{doc, Indexed};
+
+    #[derive(Debug)]
+    struct SitePolicy;
+
+    impl DiffPolicy for SitePolicy {
+        fn element(&self, el: &ElementInfo<'_>) -> PolicyAction {
+            match (el.tag, el.new_attrs.get("type")) {
+                ("script", Some("application/ld+json")) => PolicyAction::Keep,
+                _ => DefaultPolicy.element(el),
+            }
+        }
+
+        fn attr(&self, el: &ElementInfo<'_>, change: &AttrChange<'_>) -> PolicyAction {
+            match (el.tag, change.name) {
+                ("meta", "content") if el.new_attrs.has("http-equiv") => {
+                    PolicyAction::reload(el.id, "meta http-equiv changed")
+                }
+                ("my-widget", "data-config") => PolicyAction::Replace,
+                (_, "data-build") => PolicyAction::Keep,
+                _ => DefaultPolicy.attr(el, change),
+            }
+        }
+    }
+
+    fn with_site_policy(old: &str, new: &str) -> DiffResult<Indexed> {
+        diff_with_config(&doc(old), &doc(new), DiffConfig::default().with_policy(SitePolicy))
+    }
+
+    #[test]
+    fn test_default_policy_rules() {
+        let result = diff(&doc(r#"<link href="a.css">"#), &doc(r#"<link href="b.css">"#));
+        assert!(matches!(
+            result.ops.as_slice(),
+            [PatchOp::Replace { element, .. }] if element.tag == "link"
+        ));
+
+        let new = doc("\n<script src=\"b.js\"></script>");
+        let result = diff(&doc("\n<script src=\"a.js\"></script>"), &new);
+        assert!(result.should_reload);
+        let Some(ReloadReason::ScriptSrcChanged { target }) = result.reload_reason else {
+            panic!("{:?}", result.reload_reason);
+        };
+        assert_eq!(result.reload_reason.unwrap().target(), Some(target));
+        // The report points at the new source
+        let location = new.span_of(target).and_then(|span| span.location()).unwrap();
+        assert_eq!((location.line, location.column), (2, 1));
+
+        let result = diff(&doc(r#"<p title="a">x</p>"#), &doc(r#"<p title="b">x</p>"#));
+        assert!(matches!(result.ops.as_slice(), [PatchOp::UpdateAttrs { .. }]));
+    }
+
+    #[test]
+    fn test_custom_policy_rules() {
+        let result = with_site_policy(
+            r#"<meta http-equiv="refresh" content="5">"#,
+            r#"<meta http-equiv="refresh" content="10">"#,
+        );
+        assert!(matches!(
+            result.reload_reason,
+            Some(ReloadReason::PolicyRequested { reason, .. }) if reason == "meta http-equiv changed"
+        ));
+
+        // Replacing ends the element's diff: no patches inside it
+        let result = with_site_policy(
+            r#"<my-widget data-config="a"><p>old</p></my-widget>"#,
+            r#"<my-widget data-config="b"><p>new</p></my-widget>"#,
+        );
+        assert!(matches!(result.ops.as_slice(), [PatchOp::Replace { .. }]), "{:?}", result.ops);
+
+        let result = with_site_policy(
+            r#"<script type="application/ld+json">{"a":1}</script>"#,
+            r#"<script type="application/ld+json">{"a":2}</script>"#,
+        );
+        assert!(!result.has_changes());
+
+        // Kept attribute changes are dropped from the update
+        let result = with_site_policy(
+            r#"<p data-build="1" title="a">x</p>"#,
+            r#"<p data-build="2" title="b">x</p>"#,
+        );
+        assert!(matches!(
+            result.ops.as_slice(),
+            [PatchOp::UpdateAttrs { changes, .. }] if changes.len() == 1 && changes[0].0 == "title"
+        ));
+        let result = with_site_policy(r#"<p data-build="1">x</p>"#, r#"<p data-build="2">x</p>"#);
+        assert!(!result.has_changes());
+    }
+}
@@ -0,0 +1,527 @@
+//! Reparent Detection
+//!
+//! Sibling lists are diffed one at a time, so moves are only found within
+//! one parent. Wrapping a section in a new `<div>` or moving an item to
+//! another list shows up as a `Remove` of the old subtree and an `Insert`
+//! of the same content elsewhere, under new StableIds (ids are seeded by
+//! the parent).
+//!
+//! This pass matches removed and inserted subtrees by [`ContentHash`] and
+//! turns each match into a `Move` of the live node followed by a `Rekey`
+//! giving it the ids of its new position, so element state (video
+//! playback, form input, scroll) survives:
+//!
+//! ```text
+//! Remove(s1)                          Insert(<div>)     (without the section)
+//! Insert(<div><section>…</section>)   Move(s1 -> FirstChildOf(div))
+//!                                  => Rekey(s1 -> <section>…</section>)
+//! ```
+//!
+//! Subtrees inside `<svg>` are never matched, since moving a node does not
+//! change its namespace.
+
+use std::borrow::Cow;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::core::{HasStableId, PhaseExt};
+use crate::id::{ContentHash, StableId};
+use crate::node::{Element, Node};
+
+use super::diff::{Anchor, DiffStats, PatchOpRef};
+
+/// An element of a removed subtree that may have moved.
+struct Candidate<'a, P: PhaseExt> {
+    elem: &'a Element<P>,
+    /// Index of the `Remove` op of the subtree root
+    remove_op: usize,
+    /// Path from the subtree root (empty for the root)
+    path: Vec<usize>,
+}
+
+/// A candidate matched with an element of an inserted subtree.
+struct Claim {
+    candidate: usize,
+    /// Path from the inserted root (empty for the root)
+    path: Vec<usize>,
+}
+
+/// Turn `Remove`/`Insert` pairs of equal content into `Move` plus `Rekey`.
+pub(super) fn detect_reparents<'a, P>(
+    ops: Vec<PatchOpRef<'a, P>>,
+    old: &Element<P>,
+    stats: &mut DiffStats,
+) -> Vec<PatchOpRef<'a, P>>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId + Clone,
+{
+    let removes: FxHashMap<StableId, usize> = ops
+        .iter()
+        .enumerate()
+        .filter_map(|(i, op)| match op {
+            PatchOpRef::Remove { target } => Some((*target, i)),
+            _ => None,
+        })
+        .collect();
+    let has_insert = ops
+        .iter()
+        .any(|op| matches!(op, PatchOpRef::Insert { node, .. } if node.is_element()));
+    if removes.is_empty() || !has_insert {
+        return ops;
+    }
+
+    let mut matcher = Matcher::new(&ops, collect_candidates(old, &removes));
+    if matcher.by_hash.is_empty() {
+        return ops;
+    }
+    let mut claims: FxHashMap<usize, Vec<Claim>> = FxHashMap::default();
+    for (i, op) in ops.iter().enumerate() {
+        if let PatchOpRef::Insert { node, is_svg: false, .. } = op
+            && let Node::Element(elem) = node.as_ref()
+        {
+            let found = matcher.claim_subtree(elem, i);
+            if !found.is_empty() {
+                claims.insert(i, found);
+            }
+        }
+    }
+    if claims.is_empty() {
+        return ops;
+    }
+
+    // Removes of claimed roots are dropped. Removes of subtrees that lost a
+    // descendant wait until it has been moved out.
+    let mut dropped: FxHashSet<usize> = FxHashSet::default();
+    let mut delayed: FxHashMap<usize, Vec<usize>> = FxHashMap::default();
+    let mut last_claim: FxHashMap<usize, usize> = FxHashMap::default();
+    for (&insert_op, found) in &claims {
+        for claim in found {
+            let candidate = &matcher.candidates[claim.candidate];
+            if candidate.path.is_empty() {
+                dropped.insert(candidate.remove_op);
+            } else {
+                let last = last_claim.entry(candidate.remove_op).or_insert(insert_op);
+                *last = (*last).max(insert_op);
+            }
+        }
+    }
+    for (remove_op, last) in last_claim {
+        if last > remove_op && dropped.insert(remove_op) {
+            delayed.entry(last).or_default().push(remove_op);
+        }
+    }
+
+    let mut out = Vec::with_capacity(ops.len());
+    let mut ops: Vec<Option<PatchOpRef<'a, P>>> = ops.into_iter().map(Some).collect();
+    for i in 0..ops.len() {
+        if dropped.contains(&i) {
+            continue;
+        }
+        let op = ops[i].take().expect("each op is taken once");
+        let Some(found) = claims.get(&i) else {
+            out.push(op);
+            continue;
+        };
+        let PatchOpRef::Insert { anchor, node, .. } = op else {
+            unreachable!("claims are only made for element inserts");
+        };
+        let elem = into_element(node);
+
+        let moved = |claim: &Claim| matcher.candidates[claim.candidate].elem.ext.stable_id();
+        match found.as_slice() {
+            [root] if root.path.is_empty() => {
+                let target = moved(root);
+                out.push(PatchOpRef::Move { target, to: anchor });
+                out.push(PatchOpRef::Rekey { target, element: elem });
+            }
+            _ => {
+                out.push(PatchOpRef::Insert {
+                    anchor,
+                    node: Cow::Owned(Node::Element(Box::new(without(&elem, found)))),
+                    is_svg: false,
+                });
+                for claim in found {
+                    let target = moved(claim);
+                    let (&index, parent_path) = claim.path.split_last().expect("nested claim");
+                    let parent = at_path(&elem, parent_path);
+                    out.push(PatchOpRef::Move { target, to: child_anchor(parent, index) });
+                    let element = match &elem {
+                        Cow::Borrowed(root) => Cow::Borrowed(at_path(root, &claim.path)),
+                        Cow::Owned(root) => Cow::Owned(at_path(root, &claim.path).clone()),
+                    };
+                    out.push(PatchOpRef::Rekey { target, element });
+                }
+            }
+        }
+        stats.nodes_reparented += found.len();
+
+        for remove_op in delayed.remove(&i).unwrap_or_default() {
+            out.push(ops[remove_op].take().expect("delayed removes are not emitted yet"));
+        }
+    }
+    out
+}
+
+// =============================================================================
+// Matching
+// =============================================================================
+
+struct Matcher<'a, P: PhaseExt> {
+    candidates: Vec<Candidate<'a, P>>,
+    by_hash: FxHashMap<ContentHash, Vec<usize>>,
+    claimed: Vec<bool>,
+    /// Claimed paths per removed root, to keep claims from nesting
+    claimed_paths: FxHashMap<usize, Vec<Vec<usize>>>,
+    /// Number of index-addressed ops before each position
+    addressed: Vec<usize>,
+}
+
+impl<'a, P: PhaseExt> Matcher<'a, P>
+where
+    P::Ext: HasStableId,
+{
+    fn new(ops: &[PatchOpRef<'_, P>], candidates: Vec<Candidate<'a, P>>) -> Self {
+        let mut by_hash: FxHashMap<ContentHash, Vec<usize>> = FxHashMap::default();
+        for (i, candidate) in candidates.iter().enumerate() {
+            by_hash.entry(candidate.elem.content_hash()).or_default().push(i);
+        }
+        let mut addressed = Vec::with_capacity(ops.len() + 1);
+        addressed.push(0);
+        for op in ops {
+            addressed.push(addressed.last().unwrap() + usize::from(index_addressed(op)));
+        }
+        Self {
+            claimed: vec![false; candidates.len()],
+            candidates,
+            by_hash,
+            claimed_paths: FxHashMap::default(),
+            addressed,
+        }
+    }
+
+    /// Claims for elements of a subtree inserted by op `insert_op`, in the
+    /// order their moves must run.
+    fn claim_subtree(&mut self, root: &Element<P>, insert_op: usize) -> Vec<Claim> {
+        if let Some(candidate) = self.claim(root, insert_op) {
+            return vec![Claim { candidate, path: Vec::new() }];
+        }
+
+        let mut claims = Vec::new();
+        let mut stack = vec![(root, Vec::new())];
+        while let Some((elem, path)) = stack.pop() {
+            // Markup inside SVG keeps the SVG namespace
+            if elem.tag == "svg" {
+                continue;
+            }
+
+            let mut found: Vec<(usize, usize)> = Vec::new();
+            for (i, child) in elem.children.iter().enumerate() {
+                if let Node::Element(child) = child
+                    && let Some(candidate) = self.claim(child, insert_op)
+                {
+                    found.push((i, candidate));
+                }
+            }
+            // Moved children are placed by index among the rest, which
+            // must map one-to-one onto DOM nodes
+            if !found.is_empty() && !positions_stable(&elem.children, &found) {
+                for &(_, candidate) in &found {
+                    self.unclaim(candidate);
+                }
+                found.clear();
+            }
+
+            for &(i, candidate) in &found {
+                claims.push(Claim { candidate, path: child_path(&path, i) });
+            }
+            for (i, child) in elem.children.iter().enumerate().rev() {
+                if let Node::Element(child) = child
+                    && !found.iter().any(|&(claimed, _)| claimed == i)
+                {
+                    stack.push((child, child_path(&path, i)));
+                }
+            }
+        }
+        claims
+    }
+
+    /// Claim an unused candidate with the content of `elem`, if any can
+    /// move to where op `insert_op` places it.
+    fn claim(&mut self, elem: &Element<P>, insert_op: usize) -> Option<usize> {
+        let hash = elem.content_hash();
+        let found = self.by_hash.get(&hash)?.iter().copied().find(|&i| {
+            let candidate = &self.candidates[i];
+            !self.claimed[i]
+                && candidate.elem.tag == elem.tag
+                && self.window_clean(candidate, insert_op)
+                && !self.claimed_paths.get(&candidate.remove_op).is_some_and(|paths| {
+                    paths
+                        .iter()
+                        .any(|path| path.starts_with(&candidate.path) || candidate.path.starts_with(path))
+                })
+        })?;
+        self.claimed[found] = true;
+        let candidate = &self.candidates[found];
+        self.claimed_paths
+            .entry(candidate.remove_op)
+            .or_default()
+            .push(candidate.path.clone());
+        Some(found)
+    }
+
+    fn unclaim(&mut self, i: usize) {
+        self.claimed[i] = false;
+        let candidate = &self.candidates[i];
+        if let Some(paths) = self.claimed_paths.get_mut(&candidate.remove_op) {
+            paths.retain(|path| *path != candidate.path);
+        }
+    }
+
+    /// Whether the ops between the candidate's removal and the insert can
+    /// run with the old subtree still in place (or already gone).
+    ///
+    /// A claimed root leaves its parent at the insert instead of at its
+    /// `Remove`; a subtree with a claimed descendant is removed no earlier
+    /// than the insert. Either way the old parent's child list differs
+    /// in between, which only matters to ops addressing children by index.
+    fn window_clean(&self, candidate: &Candidate<'_, P>, insert_op: usize) -> bool {
+        let remove_op = candidate.remove_op;
+        if !candidate.path.is_empty() && remove_op > insert_op {
+            return true;
+        }
+        let (lo, hi) = (remove_op.min(insert_op), remove_op.max(insert_op));
+        self.addressed[hi] == self.addressed[lo + 1]
+    }
+}
+
+/// Removed subtrees outside SVG, with their elements as candidates.
+fn collect_candidates<'a, P>(
+    old: &'a Element<P>,
+    removes: &FxHashMap<StableId, usize>,
+) -> Vec<Candidate<'a, P>>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    let mut candidates = Vec::new();
+    // (element, whether it is inside an <svg>)
+    let mut stack = vec![(old, false)];
+    while let Some((elem, in_svg)) = stack.pop() {
+        if let Some(&remove_op) = removes.get(&elem.ext.stable_id()) {
+            if !in_svg {
+                let mut subtree = vec![(elem, Vec::new())];
+                while let Some((elem, path)) = subtree.pop() {
+                    if elem.tag != "svg" {
+                        for (i, child) in elem.children.iter().enumerate() {
+                            if let Node::Element(child) = child {
+                                subtree.push((child, child_path(&path, i)));
+                            }
+                        }
+                    }
+                    candidates.push(Candidate { elem, remove_op, path });
+                }
+            }
+            continue;
+        }
+        let in_svg = in_svg || elem.tag == "svg";
+        for child in &elem.children {
+            if let Node::Element(child) = child {
+                stack.push((child, in_svg));
+            }
+        }
+    }
+    candidates
+}
+
+/// Whether an op addresses children by index.
+fn index_addressed<P: PhaseExt>(op: &PatchOpRef<'_, P>) -> bool {
+    match op {
+        PatchOpRef::UpdateTextAt { .. }
+        | PatchOpRef::RemoveTextAt { .. }
+        | PatchOpRef::SpliceText { .. } => true,
+        PatchOpRef::Insert { anchor, .. } | PatchOpRef::Move { to: anchor, .. } => {
+            matches!(anchor, Anchor::ChildIndex(..))
+        }
+        _ => false,
+    }
+}
+
+/// Whether, with the `found` children taken out, every remaining child is
+/// one DOM node: no empty text and no text nodes the browser would merge.
+fn positions_stable<P: PhaseExt>(children: &[Node<P>], found: &[(usize, usize)]) -> bool {
+    let mut prev_text = false;
+    for (i, child) in children.iter().enumerate() {
+        if found.iter().any(|&(claimed, _)| claimed == i) {
+            continue;
+        }
+        match child {
+            Node::Element(_) | Node::Comment(_) => prev_text = false,
+            Node::Text(text) if !text.content.is_empty() && !prev_text => prev_text = true,
+            _ => return false,
+        }
+    }
+    true
+}
+
+// =============================================================================
+// Helpers
+// =============================================================================
+
+fn child_path(path: &[usize], index: usize) -> Vec<usize> {
+    let mut child = path.to_vec();
+    child.push(index);
+    child
+}
+
+/// The element of an element insert, borrowed if the insert was.
+fn into_element<P: PhaseExt>(node: Cow<'_, Node<P>>) -> Cow<'_, Element<P>> {
+    match node {
+        Cow::Borrowed(Node::Element(elem)) => Cow::Borrowed(elem),
+        Cow::Owned(Node::Element(elem)) => Cow::Owned(*elem),
+        _ => unreachable!("claims are only made for element inserts"),
+    }
+}
+
+pub(super) fn at_path<'e, P: PhaseExt>(root: &'e Element<P>, path: &[usize]) -> &'e Element<P> {
+    path.iter().fold(root, |elem, &i| {
+        elem.children[i].as_element().expect("paths only pass through elements")
+    })
+}
+
+/// Where child `index` of `parent` goes once the children before it are
+/// in place.
+pub(super) fn child_anchor<P>(parent: &Element<P>, index: usize) -> Anchor
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    let parent_id = parent.ext.stable_id();
+    match index.checked_sub(1).map(|prev| &parent.children[prev]) {
+        None => Anchor::FirstChildOf(parent_id),
+        Some(Node::Element(prev)) => Anchor::After(prev.ext.stable_id()),
+        Some(_) => Anchor::ChildIndex(parent_id, index),
+    }
+}
+
+/// `root` without the claimed elements, which are moved in afterwards.
+fn without<P>(root: &Element<P>, claims: &[Claim]) -> Element<P>
+where
+    P: PhaseExt,
+    P::Ext: Clone,
+{
+    let mut pruned = root.clone();
+    let mut paths: Vec<&[usize]> = claims.iter().map(|claim| claim.path.as_slice()).collect();
+    // Later siblings first, so earlier indices stay valid
+    paths.sort_unstable_by(|a, b| b.cmp(a));
+    for path in paths {
+        let (&index, parent_path) = path.split_last().expect("nested claim");
+        pruned.invalidate_hash();
+        let parent = parent_path.iter().fold(&mut pruned, |elem, &i| {
+            let child = elem.children[i].as_element_mut().expect("claim paths only pass through elements");
+            child.invalidate_hash();
+            child
+        });
+        parent.children.remove(index);
+    }
+    pruned
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use crate::algo::{apply_patches, diff, diff_with_config, DiffConfig, DiffResult, PatchOp};
+    use crate::algo::test_site::{doc, Indexed};
+    use crate::node::Document;
+    use crate::render::{render_document, RenderConfig};
+
+    /// Diff with reparent detection, checking the ops patch `old` into `new`.
+    fn reparent(old: &str, new: &str) -> DiffResult<Indexed> {
+        let (old, new) = (doc(old), doc(new));
+        let result = diff_with_config(&old, &new, DiffConfig::default().with_reparent_detection(true));
+        let mut patched = old.clone();
+        apply_patches(&mut patched, &result.ops).unwrap();
+        let html = |doc: &Document<Indexed>| render_document(doc, &RenderConfig::DEV);
+        assert_eq!(html(&patched), html(&new), "ops: {:?}", result.ops);
+        assert_eq!(patched.content_hash(), new.content_hash());
+        result
+    }
+
+    fn summary(result: &DiffResult<Indexed>) -> Vec<&'static str> {
+        result
+            .ops
+            .iter()
+            .map(|op| match op {
+                PatchOp::Remove { .. } => "remove",
+                PatchOp::Insert { .. } => "insert",
+                PatchOp::Move { .. } => "move",
+                PatchOp::Rekey { .. } => "rekey",
+                _ => "other",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_wrapped_subtree_is_moved() {
+        let old = "<main><section><h2>Title</h2><p>Body</p></section></main>";
+        let new = r#"<main><div class="wrap"><section><h2>Title</h2><p>Body</p></section></div></main>"#;
+
+        // Without detection: the section is removed and recreated
+        assert_eq!(summary(&diff(&doc(old), &doc(new))), ["remove", "insert"]);
+
+        let result = reparent(old, new);
+        assert_eq!(summary(&result), ["insert", "move", "rekey"]);
+        assert_eq!(result.stats.nodes_reparented, 1);
+        let PatchOp::Insert { node, .. } = &result.ops[0] else { unreachable!() };
+        assert!(node.as_element().unwrap().children.is_empty(), "the wrapper is inserted empty");
+    }
+
+    #[test]
+    fn test_item_moved_between_lists() {
+        let result = reparent(
+            "<div><ul><li>a</li><li>b</li></ul><ol><li>c</li></ol></div>",
+            "<div><ul><li>a</li></ul><ol><li>c</li><li>b</li></ol></div>",
+        );
+        assert_eq!(summary(&result), ["move", "rekey"]);
+        assert_eq!(result.stats.nodes_reparented, 1);
+    }
+
+    #[test]
+    fn test_unwrapped_subtree_is_moved() {
+        let result = reparent(
+            "<main><div>text <p>kept</p></div></main>",
+            "<main><p>kept</p></main>",
+        );
+        // The kept paragraph leaves before its old parent is removed
+        assert_eq!(summary(&result), ["move", "rekey", "remove"]);
+    }
+
+    #[test]
+    fn test_changed_content_is_not_matched() {
+        let result = reparent(
+            "<main><section><p>old</p></section></main>",
+            "<main><div><section><p>new</p></section></div></main>",
+        );
+        assert_eq!(summary(&result), ["remove", "insert"]);
+        assert_eq!(result.stats.nodes_reparented, 0);
+    }
+
+    #[test]
+    fn test_svg_subtrees_are_not_matched() {
+        let result = reparent(
+            "<main><svg><g><circle r=\"1\"/></g></svg></main>",
+            "<main><div><svg><g><circle r=\"1\"/></g></svg></div></main>",
+        );
+        // The <svg> root moves as a whole; nothing is taken out of it
+        assert_eq!(summary(&result), ["insert", "move", "rekey"]);
+
+        let result = reparent(
+            "<main><svg><g><circle r=\"1\"/></g></svg><p>x</p></main>",
+            "<main><svg><rect/></svg><p>x</p><div><g><circle r=\"1\"/></g></div></main>",
+        );
+        assert_eq!(result.stats.nodes_reparented, 0);
+    }
+}
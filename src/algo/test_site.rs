@@ -0,0 +1,18 @@
+//! Shared fixture for the `algo` tests.
+
+use crate::families::LinkFamily;
+use crate::node::Document;
+use crate::transform::Transform;
+use crate::vdom;
+
+#[vdom::families]
+pub struct AlgoSite {
+    link: LinkFamily,
+}
+
+pub(crate) type Indexed = AlgoSite::Indexed;
+
+/// Parse and index `<html>{body}</html>`.
+pub(crate) fn doc(body: &str) -> Document<Indexed> {
+    AlgoSite::indexer().transform(AlgoSite::parse(&format!("<html>{body}</html>")))
+}
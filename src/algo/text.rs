@@ -0,0 +1,140 @@
+//! Text Splices
+//!
+//! An edit to a long text node (a `<pre>` block, a code listing) changes a
+//! few characters, but `UpdateText` resends all of it. For such edits the
+//! diff emits `SpliceText` instead, carrying only the changed ranges:
+//!
+//! ```text
+//! "let x = 1;\nlet y = 2;"  -> "let x = 10;\nlet y = 2;"
+//!   SpliceText { splices: [TextSplice { start: 9, delete: 0, insert: "0" }] }
+//! ```
+//!
+//! Ranges come from a character-level Myers diff and are given in UTF-16
+//! code units, as `CharacterData.replaceData` counts them. Splices separated
+//! by only a few characters are merged, since each one costs more to send
+//! than a short run of text. Whether splices are sent at all is decided by
+//! `DiffConfig::max_splice_ratio`.
+
+use super::cost::SPLICE_BYTES;
+use super::myers::myers_lcs;
+
+/// Replacement of `delete` UTF-16 code units at `start` with `insert`.
+///
+/// Offsets refer to the old text. The splices of an op are sorted and do not
+/// overlap; applying them last to first keeps every offset valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextSplice {
+    /// Offset of the replaced range, in UTF-16 code units
+    pub start: usize,
+    /// Length of the replaced range, in UTF-16 code units
+    pub delete: usize,
+    /// Replacement text
+    pub insert: String,
+}
+
+/// Splices turning `old` into `new`.
+pub(super) fn text_splices(old: &str, new: &str, max_edit_distance: usize) -> Vec<TextSplice> {
+    let old_chars: Vec<char> = old.chars().collect();
+    let new_chars: Vec<char> = new.chars().collect();
+    let (lcs, _) = myers_lcs(&old_chars, &new_chars, max_edit_distance);
+
+    // Unmatched runs as (old start, old end, new start, new end) in chars,
+    // merged across short matched runs
+    let mut runs: Vec<(usize, usize, usize, usize)> = Vec::new();
+    let (mut old_at, mut new_at) = (0, 0);
+    for (old_idx, new_idx) in lcs.into_iter().chain([(old_chars.len(), new_chars.len())]) {
+        if old_idx > old_at || new_idx > new_at {
+            match runs.last_mut() {
+                Some(last) if gap_bytes(&new_chars[last.3..new_at]) < SPLICE_BYTES => {
+                    (last.1, last.3) = (old_idx, new_idx);
+                }
+                _ => runs.push((old_at, old_idx, new_at, new_idx)),
+            }
+        }
+        (old_at, new_at) = (old_idx + 1, new_idx + 1);
+    }
+
+    // Char index to UTF-16 offset, walking forward through the old text
+    let (mut char_at, mut unit_at) = (0, 0);
+    let mut offset = |to: usize| {
+        unit_at += old_chars[char_at..to].iter().map(|c| c.len_utf16()).sum::<usize>();
+        char_at = to;
+        unit_at
+    };
+    runs.into_iter()
+        .map(|(old_start, old_end, new_start, new_end)| {
+            let start = offset(old_start);
+            TextSplice {
+                start,
+                delete: offset(old_end) - start,
+                insert: new_chars[new_start..new_end].iter().collect(),
+            }
+        })
+        .collect()
+}
+
+fn gap_bytes(chars: &[char]) -> usize {
+    chars.iter().map(|c| c.len_utf8()).sum()
+}
+
+/// Apply `splices` to `text`, or `None` if a range is out of bounds or
+/// splits a surrogate pair.
+#[cfg(test)]
+pub(crate) fn apply_splices(text: &str, splices: &[TextSplice]) -> Option<String> {
+    let mut units: Vec<u16> = text.encode_utf16().collect();
+    for splice in splices.iter().rev() {
+        let end = splice.start.checked_add(splice.delete).filter(|&end| end <= units.len())?;
+        units.splice(splice.start..end, splice.insert.encode_utf16());
+    }
+    String::from_utf16(&units).ok()
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::DEFAULT_MAX_EDIT_DISTANCE;
+
+    fn splices(old: &str, new: &str) -> Vec<TextSplice> {
+        let splices = text_splices(old, new, DEFAULT_MAX_EDIT_DISTANCE);
+        assert_eq!(apply_splices(old, &splices).as_deref(), Some(new), "{old:?} -> {new:?}");
+        splices
+    }
+
+    fn splice(start: usize, delete: usize, insert: &str) -> TextSplice {
+        TextSplice { start, delete, insert: insert.into() }
+    }
+
+    #[test]
+    fn test_splices_use_utf16_offsets() {
+        assert_eq!(splices("let x = 1;", "let x = 10;"), [splice(9, 0, "0")]);
+        assert_eq!(splices("same", "same"), []);
+        // 😀 is two UTF-16 code units, é one
+        assert_eq!(splices("😀 café 1", "😀 café 2"), [splice(8, 1, "2")]);
+        assert_eq!(splices("a😀b", "ab"), [splice(1, 2, "")]);
+        assert_eq!(splices("", "new"), [splice(0, 0, "new")]);
+    }
+
+    #[test]
+    fn test_nearby_splices_are_merged() {
+        let old = "fn main() { println!(\"one\"); }\n".repeat(20);
+        let mut new = old.clone();
+        new.replace_range(12..19, "eprintln");
+        new.push_str("// end\n");
+        assert_eq!(splices(&old, &new), [splice(12, 0, "e"), splice(619, 0, "\n// end")]);
+
+        // Far apart edits stay separate, close ones merge
+        assert_eq!(splices("a-much-longer-gap-b", "A-much-longer-gap-B").len(), 2);
+        assert_eq!(splices("ab-c", "Ab-C"), [splice(0, 4, "Ab-C")]);
+    }
+
+    #[test]
+    fn test_apply_splices_checks_bounds() {
+        assert_eq!(apply_splices("abc", &[splice(2, 2, "")]), None);
+        assert_eq!(apply_splices("😀", &[splice(1, 1, "")]), None);
+        assert_eq!(apply_splices("abc", &[splice(0, 1, "x"), splice(2, 1, "z")]).as_deref(), Some("xbz"));
+    }
+}
@@ -0,0 +1,202 @@
+//! Class and Style Tokens
+//!
+//! `class` and `style` are lists, and page scripts edit them item by item
+//! (`classList.add("is-open")`, `el.style.setProperty(...)`). Setting the
+//! whole attribute would drop those edits on every hot reload, so changes
+//! to them are patched one token or property at a time:
+//!
+//! ```text
+//! class="card active"  -> class="card featured"
+//!   UpdateClasses { add: [featured], remove: [active] }
+//!
+//! style="color: red; margin: 0"  -> style="color: blue"
+//!   UpdateStyles { set: [(color, blue)], remove: [margin] }
+//! ```
+//!
+//! Styles are only patched this way when the properties end up in the new
+//! order (set properties keep their place, new ones go last), since later
+//! declarations override earlier ones (`margin` vs `margin-top`). Values
+//! the splitter cannot take apart safely, such as ones with comments, are
+//! set whole.
+
+use std::borrow::Cow;
+
+use crate::attr::{AttrKey, AttrValue};
+use crate::core::PhaseExt;
+use crate::id::StableId;
+
+use super::diff::PatchOpRef;
+
+/// Token-level patch for a changed `class` or `style` value, or `None` if
+/// the attribute must be set whole.
+pub(super) fn token_update<'a, P: PhaseExt>(
+    target: StableId,
+    name: &str,
+    old: &str,
+    new: &AttrValue,
+) -> Option<PatchOpRef<'a, P>> {
+    match name {
+        "class" => {
+            let (old_tokens, new_tokens) = (class_tokens(old), class_tokens(new));
+            if old_tokens.is_empty() || new_tokens.is_empty() {
+                return None;
+            }
+            let missing = |from: &[&str], of: &[&str]| -> Vec<AttrValue> {
+                from.iter().filter(|token| !of.contains(token)).map(|&token| token.into()).collect()
+            };
+            Some(PatchOpRef::UpdateClasses {
+                target,
+                add: Cow::Owned(missing(&new_tokens, &old_tokens)),
+                remove: Cow::Owned(missing(&old_tokens, &new_tokens)),
+                value: Cow::Owned(new.clone()),
+            })
+        }
+        "style" => {
+            let (old_decls, new_decls) = (parse_style(old)?, parse_style(new)?);
+            if old_decls.is_empty() || new_decls.is_empty() {
+                return None;
+            }
+            let (set, remove) = style_changes(&old_decls, &new_decls)?;
+            Some(PatchOpRef::UpdateStyles {
+                target,
+                set: Cow::Owned(set),
+                remove: Cow::Owned(remove),
+                value: Cow::Owned(new.clone()),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Whether a token-level patch adds, sets and removes nothing, as when
+/// class tokens or style declarations were only respaced or reordered.
+pub(super) fn is_noop<P: PhaseExt>(op: &PatchOpRef<'_, P>) -> bool {
+    match op {
+        PatchOpRef::UpdateClasses { add, remove, .. } => add.is_empty() && remove.is_empty(),
+        PatchOpRef::UpdateStyles { set, remove, .. } => set.is_empty() && remove.is_empty(),
+        _ => false,
+    }
+}
+
+/// Distinct class tokens in order of first appearance.
+fn class_tokens(value: &str) -> Vec<&str> {
+    let mut tokens: Vec<&str> = Vec::new();
+    for token in value.split_ascii_whitespace() {
+        if !tokens.contains(&token) {
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+/// Style properties to set and to remove.
+type StyleChanges = (Vec<(AttrKey, AttrValue)>, Vec<AttrKey>);
+
+/// Changes taking `old` declarations to `new`, if applying them in place
+/// yields the new order.
+fn style_changes(old: &[(&str, &str)], new: &[(&str, &str)]) -> Option<StyleChanges> {
+    // Kept properties stay in place, added ones are appended
+    let kept = old.iter().map(|&(prop, _)| prop).filter(|prop| lookup(new, prop).is_some());
+    let added = new.iter().map(|&(prop, _)| prop).filter(|prop| lookup(old, prop).is_none());
+    if !kept.chain(added).eq(new.iter().map(|&(prop, _)| prop)) {
+        return None;
+    }
+
+    let set = new
+        .iter()
+        .filter(|&&(prop, value)| lookup(old, prop) != Some(value))
+        .map(|&(prop, value)| (prop.into(), value.into()))
+        .collect();
+    let remove = old
+        .iter()
+        .filter(|&&(prop, _)| lookup(new, prop).is_none())
+        .map(|&(prop, _)| prop.into())
+        .collect();
+    Some((set, remove))
+}
+
+fn lookup<'a>(decls: &[(&str, &'a str)], name: &str) -> Option<&'a str> {
+    decls.iter().find(|(prop, _)| *prop == name).map(|&(_, value)| value)
+}
+
+/// Split an inline style into `(property, value)` declarations.
+///
+/// Returns `None` for comments, declarations without a property or value,
+/// and repeated properties.
+pub(crate) fn parse_style(style: &str) -> Option<Vec<(&str, &str)>> {
+    if style.contains("/*") {
+        return None;
+    }
+
+    let mut decls = Vec::new();
+    // Split on `;` outside quotes and parentheses, as in `url("a;b")`
+    let (mut start, mut depth, mut quote) = (0, 0usize, None);
+    for (i, c) in style.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.checked_sub(1)?,
+            (None, ';') if depth == 0 => {
+                push_decl(&mut decls, &style[start..i])?;
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if quote.is_some() || depth > 0 {
+        return None;
+    }
+    push_decl(&mut decls, &style[start..])?;
+    Some(decls)
+}
+
+fn push_decl<'a>(decls: &mut Vec<(&'a str, &'a str)>, decl: &'a str) -> Option<()> {
+    if decl.trim().is_empty() {
+        return Some(());
+    }
+    let (prop, value) = decl.split_once(':')?;
+    let (prop, value) = (prop.trim(), value.trim());
+    if prop.is_empty() || value.is_empty() || lookup(decls, prop).is_some() {
+        return None;
+    }
+    decls.push((prop, value));
+    Some(())
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_style() {
+        assert_eq!(
+            parse_style("color: red;margin:0 ; background: url(\"a;b.png\");"),
+            Some(vec![("color", "red"), ("margin", "0"), ("background", "url(\"a;b.png\")")])
+        );
+        assert_eq!(parse_style(" "), Some(vec![]));
+        assert_eq!(parse_style("--gap: calc(1px + (2px))"), Some(vec![("--gap", "calc(1px + (2px))")]));
+        for unsafe_style in ["color: red /* x */", "color", "color:", "a: 1; a: 2", "content: 'x", "a: f(1"] {
+            assert_eq!(parse_style(unsafe_style), None, "{unsafe_style}");
+        }
+    }
+
+    #[test]
+    fn test_style_changes_keep_declaration_order() {
+        let changes = |old: &str, new: &str| {
+            style_changes(&parse_style(old).unwrap(), &parse_style(new).unwrap())
+        };
+        let (set, remove) =
+            changes("color: red; margin: 0; top: 1px", "color: blue; top: 1px; left: 0").unwrap();
+        assert_eq!(set, [("color".into(), "blue".into()), ("left".into(), "0".into())]);
+        assert_eq!(remove, [AttrKey::from("margin")]);
+
+        // Reordered declarations can change which one wins
+        assert_eq!(changes("margin-top: 1px; margin: 0", "margin: 0; margin-top: 1px"), None);
+    }
+}
@@ -24,6 +24,7 @@
 
 use std::fmt::Debug;
 
+use crate::algo::StableHasher;
 use crate::id::StableId;
 
 // =============================================================================
@@ -76,8 +77,149 @@ pub trait Family: 'static + Send + Sync {
 
     /// Transform Indexed → Processed
     fn process(indexed: &Self::Indexed) -> Self::Processed;
+
+    /// Compare the Indexed data of two elements matched by the diff.
+    ///
+    /// Families whose data drives rendering report changes here that the
+    /// element's tag, attributes and children do not show. Default: unchanged.
+    fn diff_indexed(_old: &Self::Indexed, _new: &Self::Indexed) -> ExtChange {
+        ExtChange::Unchanged
+    }
+
+    /// Compare the Processed data of two elements matched by the diff.
+    fn diff_processed(_old: &Self::Processed, _new: &Self::Processed) -> ExtChange {
+        ExtChange::Unchanged
+    }
+
+    /// Feed the Indexed data compared by `diff_indexed` into the element's
+    /// [`ContentHash`](crate::id::ContentHash).
+    ///
+    /// The diff skips subtrees with equal hashes, so a family overriding
+    /// `diff_indexed` must hash what it compares. Default: nothing.
+    fn hash_indexed(_indexed: &Self::Indexed, hasher: StableHasher) -> StableHasher {
+        hasher
+    }
+
+    /// Feed the Processed data compared by `diff_processed` into the
+    /// element's content hash.
+    fn hash_processed(_processed: &Self::Processed, hasher: StableHasher) -> StableHasher {
+        hasher
+    }
+}
+
+/// How family data changed between two elements matched by the diff.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ExtChange {
+    /// Nothing the page needs to know about
+    #[default]
+    Unchanged,
+    /// Patch in place with `PatchOp::UpdateExt`. The payload is handed to
+    /// the page runtime, which dispatches it to the element as a
+    /// `tola:update-ext` event.
+    Update(String),
+    /// Replace the whole element
+    Replace,
+}
+
+/// A document-level change found by [`DocDiff`], patched through `document`
+/// rather than an element. Each change records the old state as well, so
+/// it can be inverted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocChange {
+    /// `document.title` changed
+    Title { old: String, new: String },
+    /// The head `<meta>` with this `name` (or `property`) changed content;
+    /// `None` when absent
+    Meta {
+        name: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// A head `<link rel href>` was added
+    AddLink { rel: String, href: String },
+    /// A head `<link rel href>` was removed
+    RemoveLink { rel: String, href: String },
+}
+
+impl DocChange {
+    /// The change that undoes this one.
+    pub fn inverse(&self) -> Self {
+        match self {
+            Self::Title { old, new } => Self::Title { old: new.clone(), new: old.clone() },
+            Self::Meta { name, old, new } => Self::Meta {
+                name: name.clone(),
+                old: new.clone(),
+                new: old.clone(),
+            },
+            Self::AddLink { rel, href } => Self::RemoveLink { rel: rel.clone(), href: href.clone() },
+            Self::RemoveLink { rel, href } => Self::AddLink { rel: rel.clone(), href: href.clone() },
+        }
+    }
+
+    /// `Meta` changes taking `(name, content)` pairs from `old` to `new`.
+    pub fn metas(old: &[(String, String)], new: &[(String, String)]) -> Vec<Self> {
+        let content = |metas: &[(String, String)], name: &str| {
+            metas.iter().find(|(n, _)| n == name).map(|(_, content)| content.clone())
+        };
+        let mut changes = Vec::new();
+        for (name, _) in old.iter().chain(new) {
+            let (old, new) = (content(old, name), content(new, name));
+            let seen = changes.iter().any(|change| matches!(change, Self::Meta { name: n, .. } if n == name));
+            if old != new && !seen {
+                changes.push(Self::Meta { name: name.clone(), old, new });
+            }
+        }
+        changes
+    }
+
+    /// `RemoveLink` and `AddLink` changes taking the `(rel, href)` set `old`
+    /// to `new`.
+    pub fn links(old: &[(String, String)], new: &[(String, String)]) -> Vec<Self> {
+        let removed = old.iter().filter(|link| !new.contains(link));
+        let added = new.iter().filter(|link| !old.contains(link));
+        removed
+            .map(|(rel, href)| Self::RemoveLink { rel: rel.clone(), href: href.clone() })
+            .chain(added.map(|(rel, href)| Self::AddLink { rel: rel.clone(), href: href.clone() }))
+            .collect()
+    }
 }
 
+/// Comparison of document metadata (`PhaseExt::DocExt`) for the diff.
+///
+/// Implement it for metadata the page shows, such as the title, and hash
+/// the same fields so `Document::content_hash` tells such pages apart:
+///
+/// ```ignore
+/// impl DocDiff for PageMeta {
+///     fn diff_doc(&self, new: &Self) -> Vec<DocChange> {
+///         let mut changes = DocChange::metas(&self.metas, &new.metas);
+///         if self.title != new.title {
+///             changes.push(DocChange::Title { old: self.title.clone(), new: new.title.clone() });
+///         }
+///         changes
+///     }
+///
+///     fn hash_doc(&self, hasher: StableHasher) -> StableHasher {
+///         let hasher = hasher.update_str(&self.title).update_usize(self.metas.len());
+///         self.metas.iter().fold(hasher, |h, (name, content)| h.update_str(name).update_str(content))
+///     }
+/// }
+/// ```
+pub trait DocDiff {
+    /// Changes from `self` to the new document's metadata. Default: none.
+    fn diff_doc(&self, _new: &Self) -> Vec<DocChange> {
+        Vec::new()
+    }
+
+    /// Feed the metadata [`diff_doc`](Self::diff_doc) compares into
+    /// `Document::content_hash`. Default: nothing.
+    fn hash_doc(&self, hasher: StableHasher) -> StableHasher {
+        hasher
+    }
+}
+
+impl DocDiff for () {}
+
 /// Bounds for family data types
 pub trait FamilyData: Debug + Clone + Default + Send + Sync + 'static {}
 
@@ -106,7 +248,7 @@ pub trait PhaseExt: Phase {
     type Ext: ElementExt;
 
     /// Document metadata
-    type DocExt: Debug + Clone + Default + Send + Sync;
+    type DocExt: DocDiff + Debug + Clone + Default + Send + Sync;
 
     /// Text node extension
     type TextExt: Debug + Clone + Default + Send + Sync;
@@ -116,6 +258,19 @@ pub trait PhaseExt: Phase {
 pub trait ElementExt: Debug + Clone + Default + Send + Sync {
     /// Get family name
     fn family_name(&self) -> &'static str;
+
+    /// Compare with the extension of the element matched in the new tree.
+    ///
+    /// Generated enums dispatch to [`Family::diff_indexed`] or
+    /// [`Family::diff_processed`], and replace elements that changed family.
+    fn diff_ext(&self, _new: &Self) -> ExtChange {
+        ExtChange::Unchanged
+    }
+
+    /// Feed family data into the element's content hash.
+    fn hash_ext(&self, hasher: StableHasher) -> StableHasher {
+        hasher
+    }
 }
 
 /// Element extension with StableId (for Indexed/Processed phases)
@@ -1,7 +1,8 @@
 //! Heading family: `<h1>` through `<h6>`.
 
+use crate::algo::StableHasher;
 use crate::attr::Attrs;
-use crate::core::{Family, HasStableId};
+use crate::core::{ExtChange, Family, HasStableId};
 use crate::id::StableId;
 
 // =============================================================================
@@ -38,6 +39,21 @@ impl Family for HeadingFamily {
             in_toc: true,
         }
     }
+
+    /// A changed anchor is sent to the page; TOC fields only matter to
+    /// whatever renders the TOC.
+    fn diff_processed(old: &Self::Processed, new: &Self::Processed) -> ExtChange {
+        if old.anchor_id == new.anchor_id {
+            ExtChange::Unchanged
+        } else {
+            ExtChange::Update(new.anchor_id.clone())
+        }
+    }
+
+    fn hash_processed(processed: &Self::Processed, hasher: StableHasher) -> StableHasher {
+        let anchor = &processed.anchor_id;
+        hasher.update_usize(anchor.len()).update_str(anchor)
+    }
 }
 
 // =============================================================================
@@ -1,7 +1,8 @@
 //! Media family: `<img>`, `<video>`, `<audio>`, etc.
 
+use crate::algo::StableHasher;
 use crate::attr::Attrs;
-use crate::core::{Family, HasStableId};
+use crate::core::{ExtChange, Family, HasStableId};
 use crate::id::StableId;
 
 // =============================================================================
@@ -48,6 +49,31 @@ impl Family for MediaFamily {
             lazy_load: true,
         }
     }
+
+    /// Resolved source, size and loading strategy decide how the element
+    /// renders, so any change re-renders it.
+    fn diff_processed(old: &Self::Processed, new: &Self::Processed) -> ExtChange {
+        if old.resolved_src == new.resolved_src
+            && old.width == new.width
+            && old.height == new.height
+            && old.lazy_load == new.lazy_load
+        {
+            ExtChange::Unchanged
+        } else {
+            ExtChange::Replace
+        }
+    }
+
+    fn hash_processed(processed: &Self::Processed, hasher: StableHasher) -> StableHasher {
+        let size = |v: Option<u32>| v.map_or(u64::MAX, u64::from);
+        let src = processed.resolved_src.as_deref();
+        hasher
+            .update_usize(src.map_or(usize::MAX, str::len))
+            .update_str(src.unwrap_or_default())
+            .update(&[u8::from(processed.lazy_load)])
+            .update_u64(size(processed.width))
+            .update_u64(size(processed.height))
+    }
 }
 
 // =============================================================================
@@ -87,7 +87,7 @@ impl PageSeed {
 /// - 8 bytes (u64)
 /// - Copy, no heap allocation
 /// - Null-optimized: `Option<StableId>` is also 8 bytes
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[must_use]
 pub struct StableId(pub(crate) u64);
 
@@ -202,6 +202,47 @@ impl StableId {
             .finish())
     }
 
+    /// Create a StableId for a comment node
+    ///
+    /// Like text nodes, comment IDs are based on occurrence index only.
+    #[inline]
+    pub fn for_comment(occurrence: usize, parent_seed: u64) -> Self {
+        use crate::algo::StableHasher;
+
+        Self(StableHasher::new()
+            .update_u64(parent_seed)
+            .update_str("__comment__")
+            .update_usize(occurrence)
+            .finish())
+    }
+
+    /// Create a StableId for a doctype node
+    #[inline]
+    pub fn for_doctype(occurrence: usize, parent_seed: u64) -> Self {
+        use crate::algo::StableHasher;
+
+        Self(StableHasher::new()
+            .update_u64(parent_seed)
+            .update_str("__doctype__")
+            .update_usize(occurrence)
+            .finish())
+    }
+
+    /// Create a StableId for a processing instruction node
+    ///
+    /// The target is included so `<?xml?>` and `<?xml-stylesheet?>` differ.
+    #[inline]
+    pub fn for_processing_instruction(target: &str, occurrence: usize, parent_seed: u64) -> Self {
+        use crate::algo::StableHasher;
+
+        Self(StableHasher::new()
+            .update_u64(parent_seed)
+            .update_str("__pi__")
+            .update_str(target)
+            .update_usize(occurrence)
+            .finish())
+    }
+
     /// Create a StableId for a frame node (SVG content)
     ///
     /// # Arguments
@@ -258,6 +299,50 @@ impl Default for StableId {
     }
 }
 
+// =============================================================================
+// ContentHash
+// =============================================================================
+
+/// Merkle hash of an element subtree's content
+///
+/// Covers the tag, attributes (in order), family name and the family data
+/// each `Family` chooses to hash, and children: text, comments and
+/// processing instructions by content, child elements by their own
+/// `ContentHash`. StableIds are not included, so the same markup hashes the
+/// same on every page.
+///
+/// The `Indexer` caches one per element in `Element::hash`; `diff` skips
+/// subtrees whose hashes and StableIds match without walking them.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[must_use]
+pub struct ContentHash(pub(crate) u64);
+
+impl ContentHash {
+    /// Create a ContentHash from a raw u64 value (e.g. read from cache).
+    #[inline]
+    pub const fn from_raw(raw: u64) -> Self {
+        Self(raw)
+    }
+
+    /// Get the raw u64 representation
+    #[inline]
+    pub const fn as_raw(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Debug for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ContentHash({:016x})", self.0)
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
 // =============================================================================
 // rkyv serialization support
 // =============================================================================
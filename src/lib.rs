@@ -12,6 +12,17 @@
 //! - `transform`: Phase transformations (Indexer, Processor)
 //! - `attr`: Attribute system
 //! - `algo`: Diff algorithms
+//! - `parse`: HTML5 parser producing Raw documents
+//! - `selector`: CSS selector queries
+//! - `visit`: Visitor and Fold traversal traits
+//! - `wire`: Versioned JSON/binary encoding of patch batches
+//!
+//! ## Deep Documents
+//!
+//! Indexing, processing, rendering, serialization, selector queries, `Clone`
+//! and `Drop` walk the tree with heap-allocated stacks, so nesting depth is
+//! bounded only by memory. The diff also descends iteratively; below
+//! `DiffConfig::max_depth` (default 500) changed subtrees are replaced whole.
 //!
 //! ## Usage
 //!
@@ -75,9 +86,21 @@ pub mod prelude;
 /// HTML rendering
 pub mod render;
 
+/// HTML parsing
+pub mod parse;
+
+/// CSS selector queries
+pub mod selector;
+
+/// Tree traversal (visitors and folds)
+pub mod visit;
+
 /// Serialization support
 pub mod serialize;
 
+/// Wire format for patch batches
+pub mod wire;
+
 /// Cache types for hot reload
 pub mod cache;
 
@@ -87,12 +110,15 @@ pub mod cache;
 
 // Core traits
 pub use crate::core::{
-    Family, Phase, PhaseExt, ElementExt, HasStableId, ExtractFamily,
+    Family, Phase, PhaseExt, ElementExt, ExtChange, DocChange, DocDiff, HasStableId, ExtractFamily,
     FamilyData, FamilySet, NoneFamily, NoneIndexed,
 };
 
 // Node types
-pub use node::{Document, Element, Node, Text, TextKind, Children};
+pub use node::{
+    Children, Comment, Cursor, CursorMut, Doctype, Document, Element, Node, NodeRef,
+    ProcessingInstruction, Text, TextKind,
+};
 
 // Transform
 pub use transform::{IndexStats, Indexer, Pipeline, Processor, Transform};
@@ -104,13 +130,25 @@ pub use transform::{AsyncPipeline, ValidateError, ValidateErrors, Validator};
 pub use attr::{Attrs, AttrKey, AttrValue, Tag, TextContent};
 
 // Identity
-pub use id::{PageSeed, StableId};
+pub use id::{ContentHash, PageSeed, StableId};
 
 // Algorithms
 pub use algo::StableHasher;
 
+// Parsing
+pub use parse::{ExtFn, Parser};
+
+// Selectors
+pub use selector::{Selector, SelectorError};
+
+// Traversal
+pub use visit::{Fold, VisitControl, Visitor, VisitorMut};
+
+// Wire format
+pub use wire::{PatchBatch, WireError, WIRE_VERSION};
+
 // Span
-pub use span::SourceSpan;
+pub use span::{SourceLocation, SourceSpan, SpannedExt};
 
 // Error types
 pub use error::{VdomError, VdomResult};
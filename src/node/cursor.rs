@@ -0,0 +1,793 @@
+//! Parent-aware navigation over element trees.
+//!
+//! Elements own their children and have no parent or sibling links. A
+//! [`Cursor`] keeps the chain of enclosing elements while it moves, so
+//! context queries ("is this `<img>` inside a `<figure>`?", "what is the
+//! previous heading?") don't need to re-walk the tree.
+//!
+//! [`CursorMut`] is the mutable counterpart. It stores only the child-index
+//! path and re-resolves it from the root on each access (O(depth)), which
+//! keeps in-place edits safe without parent pointers.
+//!
+//! Cursors start at the root element; the document prolog is not part of
+//! the navigable tree.
+//!
+//! ```ignore
+//! let mut cursor = doc.cursor();
+//! while cursor.move_to_first_child() {}
+//! let in_figure = cursor.ancestors().any(|e| e.is_tag("figure"));
+//! ```
+
+use crate::core::{HasStableId, PhaseExt};
+use crate::id::StableId;
+
+use super::{Comment, Doctype, Document, Element, Node, ProcessingInstruction, Text};
+
+// =============================================================================
+// NodeRef
+// =============================================================================
+
+/// Borrowed view of a node at a cursor position.
+///
+/// Unlike `&Node<P>`, this can also refer to the root element, which is not
+/// stored inside a `Node`.
+#[derive(Debug, Clone, Copy)]
+pub enum NodeRef<'a, P: PhaseExt> {
+    Element(&'a Element<P>),
+    Text(&'a Text<P>),
+    Comment(&'a Comment<P>),
+    Doctype(&'a Doctype<P>),
+    ProcessingInstruction(&'a ProcessingInstruction<P>),
+}
+
+impl<'a, P: PhaseExt> NodeRef<'a, P> {
+    /// Check if this is an element node.
+    #[inline]
+    pub fn is_element(&self) -> bool {
+        matches!(self, NodeRef::Element(_))
+    }
+
+    /// Check if this is a text node.
+    #[inline]
+    pub fn is_text(&self) -> bool {
+        matches!(self, NodeRef::Text(_))
+    }
+
+    /// Get as element reference.
+    #[inline]
+    pub fn as_element(&self) -> Option<&'a Element<P>> {
+        match *self {
+            NodeRef::Element(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Get as text reference.
+    #[inline]
+    pub fn as_text(&self) -> Option<&'a Text<P>> {
+        match *self {
+            NodeRef::Text(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    /// Get as comment reference.
+    #[inline]
+    pub fn as_comment(&self) -> Option<&'a Comment<P>> {
+        match *self {
+            NodeRef::Comment(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Get as doctype reference.
+    #[inline]
+    pub fn as_doctype(&self) -> Option<&'a Doctype<P>> {
+        match *self {
+            NodeRef::Doctype(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// Get as processing instruction reference.
+    #[inline]
+    pub fn as_processing_instruction(&self) -> Option<&'a ProcessingInstruction<P>> {
+        match *self {
+            NodeRef::ProcessingInstruction(pi) => Some(pi),
+            _ => None,
+        }
+    }
+}
+
+impl<P: PhaseExt> NodeRef<'_, P>
+where
+    P::Ext: HasStableId,
+    P::TextExt: HasStableId,
+{
+    /// Get the stable identifier of this node.
+    pub fn stable_id(&self) -> StableId {
+        match self {
+            NodeRef::Element(e) => e.ext.stable_id(),
+            NodeRef::Text(t) => t.ext.stable_id(),
+            NodeRef::Comment(c) => c.ext.stable_id(),
+            NodeRef::Doctype(d) => d.ext.stable_id(),
+            NodeRef::ProcessingInstruction(pi) => pi.ext.stable_id(),
+        }
+    }
+}
+
+impl<'a, P: PhaseExt> From<&'a Node<P>> for NodeRef<'a, P> {
+    fn from(node: &'a Node<P>) -> Self {
+        match node {
+            Node::Element(e) => NodeRef::Element(e),
+            Node::Text(t) => NodeRef::Text(t),
+            Node::Comment(c) => NodeRef::Comment(c),
+            Node::Doctype(d) => NodeRef::Doctype(d),
+            Node::ProcessingInstruction(pi) => NodeRef::ProcessingInstruction(pi),
+        }
+    }
+}
+
+impl<'a, P: PhaseExt> From<&'a Element<P>> for NodeRef<'a, P> {
+    fn from(elem: &'a Element<P>) -> Self {
+        NodeRef::Element(elem)
+    }
+}
+
+// =============================================================================
+// Cursor
+// =============================================================================
+
+/// Read-only cursor with parent, sibling and child navigation.
+///
+/// All `move_to_*` methods return `false` and leave the cursor in place when
+/// the target does not exist.
+#[derive(Debug, Clone)]
+pub struct Cursor<'a, P: PhaseExt> {
+    root: &'a Element<P>,
+    /// Elements enclosing the current node, outermost first
+    parents: Vec<&'a Element<P>>,
+    /// `path[i]` is the index of the current branch in `parents[i].children`
+    path: Vec<usize>,
+}
+
+impl<'a, P: PhaseExt> Cursor<'a, P> {
+    /// Create a cursor positioned at `root`.
+    pub fn new(root: &'a Element<P>) -> Self {
+        Self {
+            root,
+            parents: Vec::new(),
+            path: Vec::new(),
+        }
+    }
+
+    /// Create a cursor at the node reached by following `path` from `root`.
+    ///
+    /// Returns `None` if the path is out of bounds or passes through a
+    /// non-element node.
+    pub fn at_path(root: &'a Element<P>, path: &[usize]) -> Option<Self> {
+        let mut cursor = Self::new(root);
+        for &index in path {
+            if !cursor.move_to_child(index) {
+                return None;
+            }
+        }
+        Some(cursor)
+    }
+
+    // -------------------------------------------------------------------------
+    // Position
+    // -------------------------------------------------------------------------
+
+    /// The node at the cursor.
+    pub fn node(&self) -> NodeRef<'a, P> {
+        match (self.parents.last(), self.path.last()) {
+            (Some(parent), Some(&index)) => NodeRef::from(&parent.children[index]),
+            _ => NodeRef::Element(self.root),
+        }
+    }
+
+    /// The element at the cursor, if the current node is an element.
+    pub fn element(&self) -> Option<&'a Element<P>> {
+        self.node().as_element()
+    }
+
+    /// Child indices from the root to the current node.
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    /// Number of steps from the root (0 at the root).
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    /// Check if the cursor is at the root element.
+    pub fn is_root(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    /// Index of the current node in its parent's children.
+    pub fn index(&self) -> Option<usize> {
+        self.path.last().copied()
+    }
+
+    // -------------------------------------------------------------------------
+    // Context
+    // -------------------------------------------------------------------------
+
+    /// The element containing the current node.
+    pub fn parent(&self) -> Option<&'a Element<P>> {
+        self.parents.last().copied()
+    }
+
+    /// Enclosing elements, nearest first.
+    pub fn ancestors(&self) -> impl Iterator<Item = &'a Element<P>> + '_ {
+        self.parents.iter().rev().copied()
+    }
+
+    /// Siblings before the current node, nearest first.
+    pub fn prev_siblings(&self) -> impl Iterator<Item = NodeRef<'a, P>> + use<'a, P> {
+        let siblings = match (self.parent(), self.index()) {
+            (Some(parent), Some(index)) => &parent.children[..index],
+            _ => &[],
+        };
+        siblings.iter().rev().map(NodeRef::from)
+    }
+
+    /// Siblings after the current node, nearest first.
+    pub fn next_siblings(&self) -> impl Iterator<Item = NodeRef<'a, P>> + use<'a, P> {
+        let siblings = match (self.parent(), self.index()) {
+            (Some(parent), Some(index)) => &parent.children[index + 1..],
+            _ => &[],
+        };
+        siblings.iter().map(NodeRef::from)
+    }
+
+    /// Children of the current node (empty unless it is an element).
+    pub fn children(&self) -> impl Iterator<Item = NodeRef<'a, P>> + use<'a, P> {
+        let children = match self.element() {
+            Some(elem) => &elem.children[..],
+            None => &[],
+        };
+        children.iter().map(NodeRef::from)
+    }
+
+    // -------------------------------------------------------------------------
+    // Navigation
+    // -------------------------------------------------------------------------
+
+    /// Move to the root element.
+    pub fn move_to_root(&mut self) {
+        self.parents.clear();
+        self.path.clear();
+    }
+
+    /// Move to the parent element.
+    pub fn move_to_parent(&mut self) -> bool {
+        if self.path.pop().is_none() {
+            return false;
+        }
+        self.parents.pop();
+        true
+    }
+
+    /// Move to the child at `index`.
+    pub fn move_to_child(&mut self, index: usize) -> bool {
+        match self.element() {
+            Some(elem) if index < elem.children.len() => {
+                self.parents.push(elem);
+                self.path.push(index);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Move to the first child.
+    pub fn move_to_first_child(&mut self) -> bool {
+        self.move_to_child(0)
+    }
+
+    /// Move to the last child.
+    pub fn move_to_last_child(&mut self) -> bool {
+        match self.element() {
+            Some(elem) if !elem.children.is_empty() => self.move_to_child(elem.children.len() - 1),
+            _ => false,
+        }
+    }
+
+    /// Move to the next sibling.
+    pub fn move_to_next_sibling(&mut self) -> bool {
+        match (self.parent(), self.path.last_mut()) {
+            (Some(parent), Some(index)) if *index + 1 < parent.children.len() => {
+                *index += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Move to the previous sibling.
+    pub fn move_to_prev_sibling(&mut self) -> bool {
+        match self.path.last_mut() {
+            Some(index) if *index > 0 => {
+                *index -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Move to the next sibling that is an element, skipping text and markup.
+    pub fn move_to_next_element_sibling(&mut self) -> bool {
+        let Some(offset) = self.next_siblings().position(|n| n.is_element()) else {
+            return false;
+        };
+        if let Some(index) = self.path.last_mut() {
+            *index += offset + 1;
+        }
+        true
+    }
+
+    /// Move to the previous sibling that is an element, skipping text and markup.
+    pub fn move_to_prev_element_sibling(&mut self) -> bool {
+        let Some(offset) = self.prev_siblings().position(|n| n.is_element()) else {
+            return false;
+        };
+        if let Some(index) = self.path.last_mut() {
+            *index -= offset + 1;
+        }
+        true
+    }
+}
+
+impl<P: PhaseExt> Cursor<'_, P>
+where
+    P::Ext: HasStableId,
+    P::TextExt: HasStableId,
+{
+    /// StableIds from the root down to the current node.
+    pub fn stable_id_path(&self) -> Vec<StableId> {
+        self.parents
+            .iter()
+            .map(|e| e.stable_id())
+            .chain(std::iter::once(self.node().stable_id()))
+            .collect()
+    }
+}
+
+// =============================================================================
+// CursorMut
+// =============================================================================
+
+/// Mutable cursor for in-place edits.
+///
+/// Navigation mirrors [`Cursor`]. Each access walks the stored path from the
+/// root, so operations cost O(depth).
+#[derive(Debug)]
+pub struct CursorMut<'a, P: PhaseExt> {
+    root: &'a mut Element<P>,
+    path: Vec<usize>,
+}
+
+impl<'a, P: PhaseExt> CursorMut<'a, P> {
+    /// Create a mutable cursor positioned at `root`.
+    pub fn new(root: &'a mut Element<P>) -> Self {
+        Self {
+            root,
+            path: Vec::new(),
+        }
+    }
+
+    /// Read-only cursor at the same position.
+    pub fn as_cursor(&self) -> Cursor<'_, P> {
+        Cursor::at_path(self.root, &self.path).expect("cursor path is always valid")
+    }
+
+    /// The node at the cursor.
+    pub fn node(&self) -> NodeRef<'_, P> {
+        match self.path.split_last() {
+            Some((&index, parent)) => NodeRef::from(&descend(self.root, parent).children[index]),
+            None => NodeRef::Element(self.root),
+        }
+    }
+
+    /// Child indices from the root to the current node.
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    /// Number of steps from the root (0 at the root).
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    /// Check if the cursor is at the root element.
+    pub fn is_root(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    // -------------------------------------------------------------------------
+    // Mutable access
+    // -------------------------------------------------------------------------
+
+    /// The element at the cursor, if the current node is an element.
+    pub fn element_mut(&mut self) -> Option<&mut Element<P>> {
+        match self.path.split_last() {
+            Some((&index, parent)) => {
+                let elem = descend_mut(self.root, parent).children[index].as_element_mut()?;
+                elem.invalidate_hash();
+                Some(elem)
+            }
+            None => {
+                self.root.invalidate_hash();
+                Some(self.root)
+            }
+        }
+    }
+
+    /// The text node at the cursor, if the current node is text.
+    pub fn text_mut(&mut self) -> Option<&mut Text<P>> {
+        self.node_mut()?.as_text_mut()
+    }
+
+    /// The element containing the current node.
+    pub fn parent_mut(&mut self) -> Option<&mut Element<P>> {
+        let (_, parent) = self.path.split_last()?;
+        Some(descend_mut(self.root, parent))
+    }
+
+    /// The current node as `&mut Node` (`None` at the root element).
+    fn node_mut(&mut self) -> Option<&mut Node<P>> {
+        let (&index, parent) = self.path.split_last()?;
+        Some(&mut descend_mut(self.root, parent).children[index])
+    }
+
+    // -------------------------------------------------------------------------
+    // Navigation
+    // -------------------------------------------------------------------------
+
+    /// Move to the root element.
+    pub fn move_to_root(&mut self) {
+        self.path.clear();
+    }
+
+    /// Move to the parent element.
+    pub fn move_to_parent(&mut self) -> bool {
+        self.path.pop().is_some()
+    }
+
+    /// Move to the child at `index`.
+    pub fn move_to_child(&mut self, index: usize) -> bool {
+        match self.node().as_element() {
+            Some(elem) if index < elem.children.len() => {
+                self.path.push(index);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Move to the first child.
+    pub fn move_to_first_child(&mut self) -> bool {
+        self.move_to_child(0)
+    }
+
+    /// Move to the last child.
+    pub fn move_to_last_child(&mut self) -> bool {
+        match self.node().as_element().map(|e| e.children.len()) {
+            Some(len) if len > 0 => self.move_to_child(len - 1),
+            _ => false,
+        }
+    }
+
+    /// Move to the next sibling.
+    pub fn move_to_next_sibling(&mut self) -> bool {
+        let len = self.sibling_count();
+        match self.path.last_mut() {
+            Some(index) if *index + 1 < len => {
+                *index += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Move to the previous sibling.
+    pub fn move_to_prev_sibling(&mut self) -> bool {
+        match self.path.last_mut() {
+            Some(index) if *index > 0 => {
+                *index -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn sibling_count(&self) -> usize {
+        match self.path.split_last() {
+            Some((_, parent)) => descend(self.root, parent).children.len(),
+            None => 1,
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // Editing
+    // -------------------------------------------------------------------------
+
+    /// Insert `node` before the current node. The cursor stays on the
+    /// current node. Returns `false` at the root.
+    pub fn insert_before(&mut self, node: Node<P>) -> bool {
+        let Some((&index, parent)) = self.path.split_last() else {
+            return false;
+        };
+        descend_mut(self.root, parent).children.insert(index, node);
+        *self.path.last_mut().unwrap() += 1;
+        true
+    }
+
+    /// Insert `node` after the current node. The cursor stays on the
+    /// current node. Returns `false` at the root.
+    pub fn insert_after(&mut self, node: Node<P>) -> bool {
+        let Some((&index, parent)) = self.path.split_last() else {
+            return false;
+        };
+        descend_mut(self.root, parent).children.insert(index + 1, node);
+        true
+    }
+
+    /// Append `node` to the current element's children. Returns `false` if
+    /// the current node is not an element.
+    pub fn append_child(&mut self, node: Node<P>) -> bool {
+        match self.element_mut() {
+            Some(elem) => {
+                elem.children.push(node);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace the current node, returning the old one. The cursor moves to
+    /// the new node. Returns `None` at the root.
+    pub fn replace(&mut self, node: Node<P>) -> Option<Node<P>> {
+        Some(std::mem::replace(self.node_mut()?, node))
+    }
+
+    /// Remove the current node and return it.
+    ///
+    /// The cursor moves to the next sibling, or the previous sibling if there
+    /// is none, or the parent if the node was an only child. Returns `None`
+    /// at the root.
+    pub fn remove(&mut self) -> Option<Node<P>> {
+        let (&index, parent) = self.path.split_last()?;
+        let parent = descend_mut(self.root, parent);
+        let removed = parent.children.remove(index);
+        let remaining = parent.children.len();
+
+        // Otherwise the next sibling has shifted into `index`
+        if index == remaining {
+            if remaining == 0 {
+                self.path.pop();
+            } else {
+                *self.path.last_mut().unwrap() -= 1;
+            }
+        }
+        Some(removed)
+    }
+}
+
+impl<P: PhaseExt> CursorMut<'_, P>
+where
+    P::Ext: HasStableId,
+    P::TextExt: HasStableId,
+{
+    /// StableIds from the root down to the current node.
+    pub fn stable_id_path(&self) -> Vec<StableId> {
+        self.as_cursor().stable_id_path()
+    }
+}
+
+/// Follow `path` from `elem`. Cursor paths only ever enter elements.
+fn descend<'e, P: PhaseExt>(mut elem: &'e Element<P>, path: &[usize]) -> &'e Element<P> {
+    for &index in path {
+        elem = elem.children[index]
+            .as_element()
+            .expect("cursor path only enters elements");
+    }
+    elem
+}
+
+/// Like [`descend`]; every caller edits below the elements passed, so their
+/// cached content hashes are cleared on the way.
+fn descend_mut<'e, P: PhaseExt>(mut elem: &'e mut Element<P>, path: &[usize]) -> &'e mut Element<P> {
+    elem.invalidate_hash();
+    for &index in path {
+        elem = elem.children[index]
+            .as_element_mut()
+            .expect("cursor path only enters elements");
+        elem.invalidate_hash();
+    }
+    elem
+}
+
+// =============================================================================
+// Entry points
+// =============================================================================
+
+impl<P: PhaseExt> Element<P> {
+    /// Cursor positioned at this element.
+    pub fn cursor(&self) -> Cursor<'_, P> {
+        Cursor::new(self)
+    }
+
+    /// Mutable cursor positioned at this element.
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, P> {
+        CursorMut::new(self)
+    }
+}
+
+impl<P: PhaseExt> Document<P> {
+    /// Cursor positioned at the root element.
+    pub fn cursor(&self) -> Cursor<'_, P> {
+        Cursor::new(&self.root)
+    }
+
+    /// Mutable cursor positioned at the root element.
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, P> {
+        CursorMut::new(&mut self.root)
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+    use crate::families::{HeadingFamily, LinkFamily, MediaFamily};
+    use crate::transform::Transform;
+    use crate::vdom;
+
+    #[vdom::families]
+    pub struct CursorSite {
+        link: LinkFamily,
+        heading: HeadingFamily,
+        media: MediaFamily,
+    }
+
+    const HTML: &str = "<html><body>\
+        <h1>Intro</h1><p>a</p>\
+        <h2>Usage</h2><figure><img src=\"x.png\"><figcaption>cap</figcaption></figure>\
+        </body></html>";
+
+    /// Cursor at the `<img>` inside the figure.
+    fn find_img<'a, P: PhaseExt>(cursor: &mut Cursor<'a, P>) {
+        assert!(cursor.move_to_first_child()); // body
+        assert!(cursor.move_to_last_child()); // figure
+        assert!(cursor.move_to_first_child()); // img
+    }
+
+    #[test]
+    fn test_cursor_navigation() {
+        let doc = CursorSite::parse(HTML);
+        let mut cursor = doc.cursor();
+        assert!(cursor.is_root());
+        assert!(!cursor.move_to_parent());
+        assert!(!cursor.move_to_next_sibling());
+
+        find_img(&mut cursor);
+        let img = cursor.element().unwrap();
+        assert_eq!(img.tag, "img");
+        assert_eq!(cursor.path(), &[0, 3, 0]);
+        assert!(cursor.ancestors().any(|e| e.is_tag("figure")));
+        assert_eq!(cursor.parent().unwrap().tag, "figure");
+        assert!(!cursor.move_to_prev_sibling());
+
+        assert!(cursor.move_to_next_sibling());
+        assert_eq!(cursor.element().unwrap().tag, "figcaption");
+        assert!(!cursor.move_to_next_sibling());
+
+        // Previous heading from the figure
+        assert!(cursor.move_to_parent());
+        let prev_heading = cursor
+            .prev_siblings()
+            .filter_map(|n| n.as_element())
+            .find(|e| e.family_name() == "heading")
+            .unwrap();
+        assert_eq!(prev_heading.text_content(), "Usage");
+
+        assert!(cursor.move_to_prev_element_sibling());
+        assert!(cursor.move_to_prev_element_sibling());
+        assert_eq!(cursor.element().unwrap().tag, "p");
+        assert!(cursor.move_to_first_child());
+        assert_eq!(cursor.node().as_text().unwrap().content, "a");
+        assert!(!cursor.move_to_first_child());
+        assert_eq!(cursor.children().count(), 0);
+
+        cursor.move_to_root();
+        assert_eq!(cursor.depth(), 0);
+        assert!(Cursor::at_path(&doc.root, &[0, 1, 0, 0]).is_none());
+        assert_eq!(
+            Cursor::at_path(&doc.root, &[0, 2]).unwrap().element().unwrap().tag,
+            "h2"
+        );
+    }
+
+    #[test]
+    fn test_cursor_stable_id_path() {
+        let doc = CursorSite::indexer().transform(CursorSite::parse(HTML));
+        let mut cursor = doc.cursor();
+        find_img(&mut cursor);
+
+        let body = &doc.root.children[0].as_element().unwrap();
+        let figure = body.children[3].as_element().unwrap();
+        let img = figure.children[0].as_element().unwrap();
+        assert_eq!(
+            cursor.stable_id_path(),
+            vec![
+                doc.root.stable_id(),
+                body.stable_id(),
+                figure.stable_id(),
+                img.stable_id()
+            ]
+        );
+
+        assert!(cursor.move_to_next_sibling());
+        assert!(cursor.move_to_first_child());
+        let caption_text = cursor.node();
+        assert!(caption_text.is_text());
+        assert_eq!(cursor.stable_id_path().last(), Some(&caption_text.stable_id()));
+    }
+
+    #[test]
+    fn test_cursor_mut_edits() {
+        let mut doc = CursorSite::parse("<html><body><p>a</p><p>b</p></body></html>");
+        let mut cursor = doc.cursor_mut();
+        assert!(!cursor.insert_before(Node::Text(Text::new("x"))));
+        assert!(cursor.remove().is_none());
+
+        assert!(cursor.move_to_first_child());
+        assert!(cursor.move_to_first_child());
+        assert!(cursor.insert_before(Node::Element(Box::new(Element::new("hr")))));
+        assert_eq!(cursor.path(), &[0, 1]);
+        assert!(cursor.insert_after(Node::Element(Box::new(Element::new("br")))));
+        cursor.element_mut().unwrap().set_attr("class", "first");
+
+        assert!(cursor.move_to_first_child());
+        cursor.text_mut().unwrap().content = "A".into();
+        assert!(cursor.move_to_parent());
+
+        // Remove moves to the next sibling (the <br>)
+        assert!(cursor.remove().is_some());
+        assert_eq!(cursor.node().as_element().unwrap().tag, "br");
+        let old = cursor.replace(Node::Element(Box::new(Element::new("wbr")))).unwrap();
+        assert_eq!(old.as_element().unwrap().tag, "br");
+
+        assert!(cursor.move_to_parent());
+        assert!(cursor.append_child(Node::Text(Text::new("end"))));
+        assert_eq!(cursor.parent_mut().unwrap().tag, "html");
+
+        let body = doc.root.children[0].as_element().unwrap();
+        let tags: Vec<_> = body.children.iter().filter_map(|n| n.as_element()).map(|e| &*e.tag).collect();
+        assert_eq!(tags, ["hr", "wbr", "p"]);
+        assert_eq!(body.text_content(), "bend");
+    }
+
+    #[test]
+    fn test_cursor_mut_remove_last() {
+        let mut doc = CursorSite::parse("<html><body><p>a</p></body></html>");
+        let mut cursor = doc.cursor_mut();
+        assert!(cursor.move_to_first_child());
+        assert!(cursor.move_to_first_child());
+        assert!(cursor.move_to_first_child());
+        cursor.remove();
+        // Only child removed: back at the <p>
+        assert_eq!(cursor.path(), &[0, 0]);
+        cursor.remove();
+        assert_eq!(cursor.path(), &[0]);
+        assert!(cursor.as_cursor().element().unwrap().is_empty());
+    }
+}
@@ -1,12 +1,19 @@
 //! Document type for the new PhaseExt-based system.
 
-use crate::core::PhaseExt;
+use crate::algo::StableHasher;
+use crate::core::{DocDiff, HasStableId, PhaseExt};
+use crate::id::{ContentHash, StableId};
+use crate::span::SourceSpan;
+use crate::visit::{walk, walk_mut, ElementFn, ElementFnMut, VisitControl, Visitor};
 
-use super::{Element, Node};
+use super::element::hash_leaf;
+use super::{Children, Comment, Doctype, Element, Node, ProcessingInstruction, Text};
 
 /// Root document container.
 #[derive(Debug, Clone)]
 pub struct Document<P: PhaseExt> {
+    /// Nodes before the root element (doctype, comments, processing instructions)
+    pub prolog: Children<P>,
     /// Root element
     pub root: Element<P>,
     /// Phase-specific metadata
@@ -17,6 +24,7 @@ impl<P: PhaseExt> Document<P> {
     /// Create document with root element.
     pub fn new(root: Element<P>) -> Self {
         Self {
+            prolog: Children::new(),
             root,
             meta: P::DocExt::default(),
         }
@@ -24,7 +32,22 @@ impl<P: PhaseExt> Document<P> {
 
     /// Create document with explicit metadata.
     pub fn with_meta(root: Element<P>, meta: P::DocExt) -> Self {
-        Self { root, meta }
+        Self {
+            prolog: Children::new(),
+            root,
+            meta,
+        }
+    }
+
+    /// Set the prolog (builder pattern).
+    pub fn with_prolog(mut self, prolog: impl IntoIterator<Item = Node<P>>) -> Self {
+        self.prolog = prolog.into_iter().collect();
+        self
+    }
+
+    /// Get the doctype from the prolog, if any.
+    pub fn doctype(&self) -> Option<&Doctype<P>> {
+        self.prolog.iter().find_map(|node| node.as_doctype())
     }
 
     /// Get phase name.
@@ -32,6 +55,21 @@ impl<P: PhaseExt> Document<P> {
         P::NAME
     }
 
+    /// Hash of the whole document: prolog nodes, the metadata fed by
+    /// [`DocDiff::hash_doc`] and the root's [`Element::content_hash`].
+    ///
+    /// O(1) for indexed documents beyond the (short) prolog and metadata, so
+    /// builds can compare it to skip unchanged pages without diffing, as
+    /// long as `hash_doc` covers everything `DocDiff::diff_doc` compares.
+    pub fn content_hash(&self) -> ContentHash {
+        let hasher = StableHasher::new()
+            .update_str("document")
+            .update_usize(self.prolog.len());
+        let hasher = self.prolog.iter().fold(hasher, hash_leaf);
+        let hasher = self.meta.hash_doc(hasher);
+        ContentHash::from_raw(hasher.update_u64(self.root.content_hash().as_raw()).finish())
+    }
+
     // -------------------------------------------------------------------------
     // Query API
     // -------------------------------------------------------------------------
@@ -41,24 +79,7 @@ impl<P: PhaseExt> Document<P> {
     where
         F: Fn(&Element<P>) -> bool,
     {
-        Self::find_in(&self.root, &pred)
-    }
-
-    fn find_in<'a, F>(elem: &'a Element<P>, pred: &F) -> Option<&'a Element<P>>
-    where
-        F: Fn(&Element<P>) -> bool,
-    {
-        if pred(elem) {
-            return Some(elem);
-        }
-        for child in &elem.children {
-            if let Some(e) = child.as_element() {
-                if let Some(found) = Self::find_in(e, pred) {
-                    return Some(found);
-                }
-            }
-        }
-        None
+        self.elements().find(|elem| pred(elem))
     }
 
     /// Find first element matching predicate (mutable).
@@ -66,48 +87,35 @@ impl<P: PhaseExt> Document<P> {
     where
         F: Fn(&Element<P>) -> bool + Copy,
     {
-        Self::find_in_mut(&mut self.root, pred)
-    }
-
-    fn find_in_mut<F>(elem: &mut Element<P>, pred: F) -> Option<&mut Element<P>>
-    where
-        F: Fn(&Element<P>) -> bool + Copy,
-    {
-        if pred(elem) {
-            return Some(elem);
-        }
-        for child in &mut elem.children {
-            if let Some(e) = child.as_element_mut() {
-                if let Some(found) = Self::find_in_mut(e, pred) {
-                    return Some(found);
-                }
+        let mut stack = vec![&mut self.root];
+        while let Some(elem) = stack.pop() {
+            // Every element passed may be an ancestor of the one returned
+            elem.invalidate_hash();
+            if pred(elem) {
+                return Some(elem);
             }
+            // Push children in reverse for correct DFS order
+            stack.extend(elem.children.iter_mut().rev().filter_map(Node::as_element_mut));
         }
         None
     }
 
-    /// Find all elements matching predicate.
-    pub fn find_all<F>(&self, pred: F) -> Vec<&Element<P>>
+    /// Source span of the element with StableId `id`, so diff reports such
+    /// as [`ReloadReason::target`](crate::algo::ReloadReason::target) can
+    /// point at the source.
+    pub fn span_of(&self, id: StableId) -> Option<SourceSpan>
     where
-        F: Fn(&Element<P>) -> bool,
+        P::Ext: HasStableId,
     {
-        let mut results = Vec::new();
-        Self::collect(&self.root, &pred, &mut results);
-        results
+        self.find(|elem| elem.ext.stable_id() == id).map(|elem| elem.span)
     }
 
-    fn collect<'a, F>(elem: &'a Element<P>, pred: &F, out: &mut Vec<&'a Element<P>>)
+    /// Find all elements matching predicate.
+    pub fn find_all<F>(&self, pred: F) -> Vec<&Element<P>>
     where
         F: Fn(&Element<P>) -> bool,
     {
-        if pred(elem) {
-            out.push(elem);
-        }
-        for child in &elem.children {
-            if let Some(e) = child.as_element() {
-                Self::collect(e, pred, out);
-            }
-        }
+        self.elements().filter(|elem| pred(elem)).collect()
     }
 
     /// Check if any element matches.
@@ -120,17 +128,7 @@ impl<P: PhaseExt> Document<P> {
 
     /// Count total elements.
     pub fn element_count(&self) -> usize {
-        Self::count(&self.root)
-    }
-
-    fn count(elem: &Element<P>) -> usize {
-        let mut n = 1;
-        for child in &elem.children {
-            if let Some(e) = child.as_element() {
-                n += Self::count(e);
-            }
-        }
-        n
+        self.elements().count()
     }
 
     /// Iterate all elements (DFS).
@@ -143,43 +141,19 @@ impl<P: PhaseExt> Document<P> {
     // -------------------------------------------------------------------------
 
     /// Visit each element with a closure.
-    pub fn for_each<F>(&self, mut f: F)
-    where
-        F: FnMut(&Element<P>),
-    {
-        Self::visit(&self.root, &mut f);
-    }
-
-    fn visit<F>(elem: &Element<P>, f: &mut F)
+    pub fn for_each<F>(&self, f: F)
     where
         F: FnMut(&Element<P>),
     {
-        f(elem);
-        for child in &elem.children {
-            if let Some(e) = child.as_element() {
-                Self::visit(e, f);
-            }
-        }
+        walk(&self.root, &mut ElementFn(f));
     }
 
-    /// Visit each element mutably.
-    pub fn for_each_mut<F>(&mut self, mut f: F)
+    /// Visit each element mutably (clearing their cached content hashes).
+    pub fn for_each_mut<F>(&mut self, f: F)
     where
         F: FnMut(&mut Element<P>),
     {
-        Self::visit_mut(&mut self.root, &mut f);
-    }
-
-    fn visit_mut<F>(elem: &mut Element<P>, f: &mut F)
-    where
-        F: FnMut(&mut Element<P>),
-    {
-        f(elem);
-        for child in &mut elem.children {
-            if let Some(e) = child.as_element_mut() {
-                Self::visit_mut(e, f);
-            }
-        }
+        walk_mut(&mut self.root, &mut ElementFnMut(f));
     }
 
     // -------------------------------------------------------------------------
@@ -265,26 +239,60 @@ impl<'a, P: PhaseExt> Iterator for ElementIter<'a, P> {
 pub struct Stats {
     pub elements: usize,
     pub text_nodes: usize,
+    /// Comments, doctypes and processing instructions (including the prolog)
+    pub markup_nodes: usize,
     pub max_depth: usize,
 }
 
 impl<P: PhaseExt> Document<P> {
     /// Calculate document statistics.
     pub fn stats(&self) -> Stats {
-        let mut stats = Stats::default();
-        Self::calc_stats(&self.root, 1, &mut stats);
-        stats
+        let mut counter = StatsCounter {
+            stats: Stats::default(),
+            depth: 0,
+        };
+        self.walk(&mut counter);
+        counter.stats
     }
+}
 
-    fn calc_stats(elem: &Element<P>, depth: usize, stats: &mut Stats) {
-        stats.elements += 1;
-        stats.max_depth = stats.max_depth.max(depth);
-        for child in &elem.children {
-            match child {
-                Node::Element(e) => Self::calc_stats(e, depth + 1, stats),
-                Node::Text(_) => stats.text_nodes += 1,
-            }
-        }
+/// Visitor behind [`Document::stats`].
+struct StatsCounter {
+    stats: Stats,
+    depth: usize,
+}
+
+impl<P: PhaseExt> Visitor<P> for StatsCounter {
+    fn enter_element(&mut self, _elem: &Element<P>) -> VisitControl {
+        self.depth += 1;
+        self.stats.elements += 1;
+        self.stats.max_depth = self.stats.max_depth.max(self.depth);
+        VisitControl::Continue
+    }
+
+    fn leave_element(&mut self, _elem: &Element<P>) -> VisitControl {
+        self.depth -= 1;
+        VisitControl::Continue
+    }
+
+    fn visit_text(&mut self, _text: &Text<P>) -> VisitControl {
+        self.stats.text_nodes += 1;
+        VisitControl::Continue
+    }
+
+    fn visit_comment(&mut self, _comment: &Comment<P>) -> VisitControl {
+        self.stats.markup_nodes += 1;
+        VisitControl::Continue
+    }
+
+    fn visit_doctype(&mut self, _doctype: &Doctype<P>) -> VisitControl {
+        self.stats.markup_nodes += 1;
+        VisitControl::Continue
+    }
+
+    fn visit_processing_instruction(&mut self, _pi: &ProcessingInstruction<P>) -> VisitControl {
+        self.stats.markup_nodes += 1;
+        VisitControl::Continue
     }
 }
 
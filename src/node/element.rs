@@ -4,7 +4,10 @@ use smallvec::SmallVec;
 
 use crate::attr::{Attrs, AttrKey, AttrValue, Tag, TextContent};
 use crate::core::{PhaseExt, ElementExt, HasStableId};
-use crate::id::StableId;
+use crate::algo::StableHasher;
+use crate::id::{ContentHash, StableId};
+use crate::span::SourceSpan;
+use crate::visit::{walk, VisitControl, Visitor};
 
 use super::{Node, Text, Children};
 
@@ -12,7 +15,10 @@ use super::{Node, Text, Children};
 ///
 /// The extension type `P::Ext` is a single enum containing all family variants,
 /// generated by `#[vdom::families]` macro.
-#[derive(Debug, Clone)]
+///
+/// `Clone` and `Drop` use an explicit stack, so arbitrarily deep trees can be
+/// copied and freed without overflowing the call stack.
+#[derive(Debug)]
 pub struct Element<P: PhaseExt> {
     /// Tag name
     pub tag: Tag,
@@ -22,6 +28,79 @@ pub struct Element<P: PhaseExt> {
     pub children: Children<P>,
     /// Phase-specific extension (family data)
     pub ext: P::Ext,
+    /// Source location (detached unless produced by the parser; not persisted
+    /// by `serialize`)
+    pub span: SourceSpan,
+    /// Cached [`ContentHash`] of this subtree, set by the `Indexer`
+    /// (see [`cached_hash`](Self::cached_hash)).
+    hash: Option<ContentHash>,
+}
+
+// =============================================================================
+// Clone / Drop (stack-safe)
+// =============================================================================
+
+impl<P: PhaseExt> Clone for Element<P> {
+    fn clone(&self) -> Self {
+        // (source, next child to copy, copy under construction)
+        let mut stack = vec![(self, 0, self.clone_shallow())];
+        loop {
+            let (source, next, copy) = stack.last_mut().unwrap();
+            let source: &Element<P> = source;
+            match source.children.get(*next) {
+                Some(Node::Element(child)) => {
+                    *next += 1;
+                    stack.push((child, 0, child.clone_shallow()));
+                }
+                Some(leaf) => {
+                    *next += 1;
+                    copy.children.push(leaf.clone());
+                }
+                None => {
+                    let (_, _, done) = stack.pop().unwrap();
+                    match stack.last_mut() {
+                        Some((_, _, parent)) => parent.children.push(Node::Element(Box::new(done))),
+                        None => return done,
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<P: PhaseExt> Element<P> {
+    /// Copy everything except the children.
+    fn clone_shallow(&self) -> Self {
+        Self {
+            tag: self.tag.clone(),
+            attrs: self.attrs.clone(),
+            children: Children::with_capacity(self.children.len()),
+            ext: self.ext.clone(),
+            span: self.span,
+            hash: self.hash,
+        }
+    }
+}
+
+impl<P: PhaseExt> Drop for Element<P> {
+    fn drop(&mut self) {
+        // Shallow trees drop normally without recursing more than one level
+        let nested = self
+            .children
+            .iter()
+            .any(|child| matches!(child, Node::Element(e) if !e.children.is_empty()));
+        if !nested {
+            return;
+        }
+
+        // Detach descendants onto a heap stack so each element drops childless
+        let mut stack: Vec<Node<P>> = self.children.drain(..).collect();
+        while let Some(node) = stack.pop() {
+            if let Node::Element(mut elem) = node {
+                stack.extend(elem.children.drain(..));
+            }
+        }
+    }
 }
 
 // =============================================================================
@@ -36,6 +115,8 @@ impl<P: PhaseExt> Element<P> {
             attrs: Attrs::new(),
             children: SmallVec::new(),
             ext: P::Ext::default(),
+            span: SourceSpan::detached(),
+            hash: None,
         }
     }
 
@@ -46,6 +127,23 @@ impl<P: PhaseExt> Element<P> {
             attrs: Attrs::new(),
             children: SmallVec::new(),
             ext,
+            span: SourceSpan::detached(),
+            hash: None,
+        }
+    }
+
+    /// Convert from another phase with a new extension and children.
+    ///
+    /// Tag, attributes and span carry over; `other`'s children are dropped.
+    /// The content hash does not, since `children` may differ.
+    pub fn from_other<Q: PhaseExt>(mut other: Element<Q>, ext: P::Ext, children: Children<P>) -> Self {
+        Self {
+            tag: std::mem::take(&mut other.tag),
+            attrs: std::mem::take(&mut other.attrs),
+            children,
+            ext,
+            span: other.span,
+            hash: None,
         }
     }
 
@@ -56,6 +154,8 @@ impl<P: PhaseExt> Element<P> {
             attrs,
             children: SmallVec::new(),
             ext: P::Ext::default(),
+            span: SourceSpan::detached(),
+            hash: None,
         }
     }
 }
@@ -90,6 +190,113 @@ impl<P: PhaseExt> Element<P> {
     }
 }
 
+// =============================================================================
+// Content hash
+// =============================================================================
+
+impl<P: PhaseExt> Element<P> {
+    /// The cached [`ContentHash`] of this subtree, set by the `Indexer`.
+    ///
+    /// The diff skips elements whose cached hashes and StableIds match, so
+    /// the cache is cleared by every `&mut` method that can change a subtree:
+    /// the attribute and child methods here, `Document::for_each_mut`,
+    /// `find_mut` and `modify_by`, `select_mut`, `walk_mut` and `CursorMut`.
+    /// After writing to the public fields directly, call
+    /// [`invalidate_hash`](Self::invalidate_hash) on the element and its
+    /// ancestors.
+    #[inline]
+    pub fn cached_hash(&self) -> Option<ContentHash> {
+        self.hash
+    }
+
+    /// Forget the cached content hash.
+    #[inline]
+    pub fn invalidate_hash(&mut self) {
+        self.hash = None;
+    }
+
+    /// Set the cached content hash (for the indexer and deserialization).
+    #[inline]
+    pub(crate) fn set_cached_hash(&mut self, hash: Option<ContentHash>) {
+        self.hash = hash;
+    }
+
+    /// Merkle hash of this subtree (see [`ContentHash`]).
+    ///
+    /// Returns the cached `hash` when set. Otherwise hashes the subtree on an
+    /// explicit stack, reusing the cached hashes of descendants.
+    pub fn content_hash(&self) -> ContentHash {
+        if let Some(hash) = self.hash {
+            return hash;
+        }
+
+        // (element, next child to hash, hasher fed so far)
+        let mut stack = vec![(self, 0, self.hash_head())];
+        loop {
+            let (elem, next, hasher) = stack.last_mut().unwrap();
+            let elem: &Element<P> = elem;
+            match elem.children.get(*next) {
+                Some(Node::Element(child)) => {
+                    *next += 1;
+                    match child.hash {
+                        Some(hash) => *hasher = hash_child(std::mem::take(hasher), hash),
+                        None => stack.push((child, 0, child.hash_head())),
+                    }
+                }
+                Some(leaf) => {
+                    *next += 1;
+                    *hasher = hash_leaf(std::mem::take(hasher), leaf);
+                }
+                None => {
+                    let (_, _, done) = stack.pop().unwrap();
+                    let hash = ContentHash(done.finish());
+                    match stack.last_mut() {
+                        Some((_, _, parent)) => *parent = hash_child(std::mem::take(parent), hash),
+                        None => return hash,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Hasher fed with everything but the children.
+    fn hash_head(&self) -> StableHasher {
+        let mut hasher = hash_field(StableHasher::new().update_str("element"), &self.tag)
+            .update_usize(self.attrs.len());
+        for (name, value) in &self.attrs {
+            hasher = hash_field(hash_field(hasher, name), value);
+        }
+        hasher = self.ext.hash_ext(hash_field(hasher, self.family_name()));
+        hasher.update_usize(self.children.len())
+    }
+}
+
+/// Feed a length-prefixed string, so adjacent fields cannot run together.
+fn hash_field(hasher: StableHasher, s: &str) -> StableHasher {
+    hasher.update_usize(s.len()).update_str(s)
+}
+
+/// Feed a child element's content hash.
+fn hash_child(hasher: StableHasher, hash: ContentHash) -> StableHasher {
+    hasher.update(&[0]).update_u64(hash.0)
+}
+
+/// Feed a non-element node by kind and content.
+///
+/// Element nodes are hashed by `Element::content_hash`; passing one here
+/// hashes it the same way.
+pub(super) fn hash_leaf<P: PhaseExt>(hasher: StableHasher, node: &Node<P>) -> StableHasher {
+    match node {
+        Node::Element(elem) => hash_child(hasher, elem.content_hash()),
+        Node::Text(text) => hash_field(hasher.update(&[1, text.is_raw() as u8]), &text.content),
+        Node::Comment(comment) => hash_field(hasher.update(&[2]), &comment.content),
+        Node::Doctype(doctype) => hash_field(hasher.update(&[3]), &doctype.content),
+        Node::ProcessingInstruction(pi) => {
+            hash_field(hash_field(hasher.update(&[4]), &pi.target), &pi.data)
+        }
+    }
+}
+
 // =============================================================================
 // StableId access (for Indexed/Processed phases)
 // =============================================================================
@@ -122,12 +329,14 @@ impl<P: PhaseExt> Element<P> {
     /// Set attribute.
     #[inline]
     pub fn set_attr(&mut self, name: impl Into<AttrKey>, value: impl Into<AttrValue>) {
+        self.hash = None;
         self.attrs.set(name.into(), value.into());
     }
 
     /// Remove attribute.
     #[inline]
     pub fn remove_attr(&mut self, name: &str) -> Option<AttrValue> {
+        self.hash = None;
         self.attrs.remove(name)
     }
 
@@ -179,21 +388,25 @@ impl<P: PhaseExt> Element<P> {
 impl<P: PhaseExt> Element<P> {
     /// Push a child element.
     pub fn push_elem(&mut self, elem: Element<P>) {
+        self.hash = None;
         self.children.push(Node::Element(Box::new(elem)));
     }
 
     /// Push a text node.
     pub fn push_text(&mut self, text: impl Into<TextContent>) {
+        self.hash = None;
         self.children.push(Node::Text(Text::new(text)));
     }
 
     /// Push a text node with explicit extension.
     pub fn push_text_with_ext(&mut self, text: impl Into<TextContent>, ext: P::TextExt) {
+        self.hash = None;
         self.children.push(Node::Text(Text::with_ext(text, ext)));
     }
 
     /// Push a child node.
     pub fn push(&mut self, node: Node<P>) {
+        self.hash = None;
         self.children.push(node);
     }
 
@@ -204,6 +417,7 @@ impl<P: PhaseExt> Element<P> {
 
     /// Get first child element mutably.
     pub fn first_child_mut(&mut self) -> Option<&mut Element<P>> {
+        self.hash = None;
         self.children.iter_mut().find_map(|n| n.as_element_mut())
     }
 
@@ -214,27 +428,29 @@ impl<P: PhaseExt> Element<P> {
 
     /// Iterate over child elements mutably.
     pub fn child_elements_mut(&mut self) -> impl Iterator<Item = &mut Element<P>> {
+        self.hash = None;
         self.children.iter_mut().filter_map(|n| n.as_element_mut())
     }
 
-    /// Get text content (concatenated from all text children).
+    /// Get text content (concatenated from all descendant text nodes).
     pub fn text_content(&self) -> String {
-        let mut result = String::new();
-        Self::collect_text(&self.children, &mut result);
-        result
-    }
+        struct Collect(String);
 
-    fn collect_text(children: &[Node<P>], out: &mut String) {
-        for child in children {
-            match child {
-                Node::Text(t) => out.push_str(&t.content),
-                Node::Element(e) => Self::collect_text(&e.children, out),
+        impl<P: PhaseExt> Visitor<P> for Collect {
+            fn visit_text(&mut self, text: &Text<P>) -> VisitControl {
+                self.0.push_str(&text.content);
+                VisitControl::Continue
             }
         }
+
+        let mut collect = Collect(String::new());
+        walk(self, &mut collect);
+        collect.0
     }
 
     /// Clear all children.
     pub fn clear(&mut self) {
+        self.hash = None;
         self.children.clear();
     }
 }
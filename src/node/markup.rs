@@ -0,0 +1,160 @@
+//! Comment, doctype and processing-instruction nodes.
+//!
+//! These nodes carry no content visible to the user but are needed for full
+//! pages to round-trip. They share the text extension type (`P::TextExt`), so
+//! they get StableIds and spans the same way text nodes do.
+
+use crate::attr::TextContent;
+use crate::core::PhaseExt;
+
+// =============================================================================
+// Comment
+// =============================================================================
+
+/// Comment node (`<!-- content -->`).
+#[derive(Debug, Clone)]
+pub struct Comment<P: PhaseExt> {
+    /// Content between `<!--` and `-->`
+    pub content: TextContent,
+    /// Phase-specific extension
+    pub ext: P::TextExt,
+}
+
+impl<P: PhaseExt> Comment<P> {
+    /// Create a new comment.
+    pub fn new(content: impl Into<TextContent>) -> Self {
+        Self {
+            content: content.into(),
+            ext: P::TextExt::default(),
+        }
+    }
+
+    /// Create comment with explicit extension.
+    pub fn with_ext(content: impl Into<TextContent>, ext: P::TextExt) -> Self {
+        Self {
+            content: content.into(),
+            ext,
+        }
+    }
+
+    /// Convert from another phase with a new extension.
+    pub fn from_other<Q: PhaseExt>(other: Comment<Q>, ext: P::TextExt) -> Self {
+        Self {
+            content: other.content,
+            ext,
+        }
+    }
+
+    /// Convert from another phase with default extension.
+    pub fn from_other_default<Q: PhaseExt>(other: Comment<Q>) -> Self {
+        Self::from_other(other, P::TextExt::default())
+    }
+
+    /// Check if this is a conditional comment (`<!--[if IE]>...<![endif]-->`).
+    pub fn is_conditional(&self) -> bool {
+        let content = self.content.trim_start();
+        content.starts_with("[if") || content.starts_with("<![endif]")
+    }
+}
+
+// =============================================================================
+// Doctype
+// =============================================================================
+
+/// Document type declaration (`<!DOCTYPE content>`).
+#[derive(Debug, Clone)]
+pub struct Doctype<P: PhaseExt> {
+    /// Everything after the `DOCTYPE` keyword (e.g. `html`)
+    pub content: TextContent,
+    /// Phase-specific extension
+    pub ext: P::TextExt,
+}
+
+impl<P: PhaseExt> Doctype<P> {
+    /// Create a new doctype.
+    pub fn new(content: impl Into<TextContent>) -> Self {
+        Self {
+            content: content.into(),
+            ext: P::TextExt::default(),
+        }
+    }
+
+    /// The HTML5 doctype (`<!DOCTYPE html>`).
+    pub fn html() -> Self {
+        Self::new("html")
+    }
+
+    /// Create doctype with explicit extension.
+    pub fn with_ext(content: impl Into<TextContent>, ext: P::TextExt) -> Self {
+        Self {
+            content: content.into(),
+            ext,
+        }
+    }
+
+    /// Convert from another phase with a new extension.
+    pub fn from_other<Q: PhaseExt>(other: Doctype<Q>, ext: P::TextExt) -> Self {
+        Self {
+            content: other.content,
+            ext,
+        }
+    }
+
+    /// Convert from another phase with default extension.
+    pub fn from_other_default<Q: PhaseExt>(other: Doctype<Q>) -> Self {
+        Self::from_other(other, P::TextExt::default())
+    }
+}
+
+// =============================================================================
+// ProcessingInstruction
+// =============================================================================
+
+/// Processing instruction (`<?target data?>`).
+#[derive(Debug, Clone)]
+pub struct ProcessingInstruction<P: PhaseExt> {
+    /// Target name (e.g. `xml`)
+    pub target: TextContent,
+    /// Everything after the target
+    pub data: TextContent,
+    /// Phase-specific extension
+    pub ext: P::TextExt,
+}
+
+impl<P: PhaseExt> ProcessingInstruction<P> {
+    /// Create a new processing instruction.
+    pub fn new(target: impl Into<TextContent>, data: impl Into<TextContent>) -> Self {
+        Self {
+            target: target.into(),
+            data: data.into(),
+            ext: P::TextExt::default(),
+        }
+    }
+
+    /// Create processing instruction with explicit extension.
+    pub fn with_ext(
+        target: impl Into<TextContent>,
+        data: impl Into<TextContent>,
+        ext: P::TextExt,
+    ) -> Self {
+        Self {
+            target: target.into(),
+            data: data.into(),
+            ext,
+        }
+    }
+
+    /// Convert from another phase with a new extension.
+    pub fn from_other<Q: PhaseExt>(other: ProcessingInstruction<Q>, ext: P::TextExt) -> Self {
+        Self {
+            target: other.target,
+            data: other.data,
+            ext,
+        }
+    }
+
+    /// Convert from another phase with default extension.
+    pub fn from_other_default<Q: PhaseExt>(other: ProcessingInstruction<Q>) -> Self {
+        Self::from_other(other, P::TextExt::default())
+    }
+}
@@ -12,20 +12,30 @@
 
 mod element;
 mod text;
+mod markup;
 mod document;
+mod cursor;
 
 pub use element::Element;
 pub use text::{Text, TextKind};
+pub use markup::{Comment, Doctype, ProcessingInstruction};
 pub use document::Document;
+pub use cursor::{Cursor, CursorMut, NodeRef};
 
 use smallvec::SmallVec;
 use crate::core::PhaseExt;
 
-/// Node in a VDOM tree - either Element or Text.
+/// Node in a VDOM tree.
+///
+/// Elements and text make up the content; comments, doctypes and processing
+/// instructions are kept so full pages round-trip faithfully.
 #[derive(Debug, Clone)]
 pub enum Node<P: PhaseExt> {
     Element(Box<Element<P>>),
     Text(Text<P>),
+    Comment(Comment<P>),
+    Doctype(Doctype<P>),
+    ProcessingInstruction(ProcessingInstruction<P>),
 }
 
 impl<P: PhaseExt> Node<P> {
@@ -76,6 +86,60 @@ impl<P: PhaseExt> Node<P> {
             _ => None,
         }
     }
+
+    /// Check if this is a comment node.
+    #[inline]
+    pub fn is_comment(&self) -> bool {
+        matches!(self, Node::Comment(_))
+    }
+
+    /// Get as comment reference.
+    #[inline]
+    pub fn as_comment(&self) -> Option<&Comment<P>> {
+        match self {
+            Node::Comment(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Check if this is a doctype node.
+    #[inline]
+    pub fn is_doctype(&self) -> bool {
+        matches!(self, Node::Doctype(_))
+    }
+
+    /// Get as doctype reference.
+    #[inline]
+    pub fn as_doctype(&self) -> Option<&Doctype<P>> {
+        match self {
+            Node::Doctype(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// Check if this is a processing instruction node.
+    #[inline]
+    pub fn is_processing_instruction(&self) -> bool {
+        matches!(self, Node::ProcessingInstruction(_))
+    }
+
+    /// Get as processing instruction reference.
+    #[inline]
+    pub fn as_processing_instruction(&self) -> Option<&ProcessingInstruction<P>> {
+        match self {
+            Node::ProcessingInstruction(pi) => Some(pi),
+            _ => None,
+        }
+    }
+
+    /// Check if this is a comment, doctype or processing instruction.
+    #[inline]
+    pub fn is_markup(&self) -> bool {
+        matches!(
+            self,
+            Node::Comment(_) | Node::Doctype(_) | Node::ProcessingInstruction(_)
+        )
+    }
 }
 
 /// Type alias for children collection.
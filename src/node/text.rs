@@ -2,6 +2,7 @@
 
 use crate::attr::TextContent;
 use crate::core::PhaseExt;
+use crate::span::{SourceSpan, SpannedExt};
 
 // =============================================================================
 // TextKind
@@ -120,4 +121,12 @@ impl<P: PhaseExt> Text<P> {
     pub fn trimmed(&self) -> &str {
         self.content.trim()
     }
+
+    /// Get the source span carried by the text extension.
+    pub fn span(&self) -> Option<SourceSpan>
+    where
+        P::TextExt: SpannedExt,
+    {
+        self.ext.span()
+    }
 }
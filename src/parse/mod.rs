@@ -0,0 +1,384 @@
+//! HTML5 parsing into Raw-phase documents.
+//!
+//! Turns an HTML string into a `Document<P>` / `Children<P>`, calling an
+//! extension function for every element so families are identified at
+//! parse time.
+//!
+//! - Implicit end tags (`<p>`, `<li>`, `<td>`, ...) are applied
+//! - Void elements follow [`render::is_void_element`](crate::render::is_void_element)
+//! - `<script>`/`<style>` content becomes `TextKind::Raw` text
+//! - Inside `<svg>`, camelCase tag and attribute names are restored
+//! - Comments and processing instructions are kept; a leading doctype goes
+//!   into the document prolog
+//! - Every element and text node records its [`SourceSpan`](crate::span::SourceSpan)
+//!   (byte range, line and column)
+//!
+//! Parsing never fails; malformed markup is recovered the way browsers do
+//! for the common cases.
+//!
+//! ```ignore
+//! #[vdom::families]
+//! pub struct MySite { link: LinkFamily }
+//!
+//! let doc = MySite::parse("<p>Hello <a href='/x'>world</a></p>");
+//! let indexed = doc.pipe(MySite::indexer());
+//! ```
+
+mod tokenizer;
+mod tree;
+
+use std::marker::PhantomData;
+
+use crate::attr::Attrs;
+use crate::core::PhaseExt;
+use crate::node::{Children, Document, Element, Node};
+use crate::span::SpannedExt;
+
+use tree::TreeBuilder;
+
+/// Extension function type: (tag, attrs) -> Ext
+pub trait ExtFn<P: PhaseExt>: Send + Sync {
+    fn create(&self, tag: &str, attrs: &Attrs) -> P::Ext;
+}
+
+/// Blanket implementation for function pointers
+impl<P, F> ExtFn<P> for F
+where
+    P: PhaseExt,
+    F: Fn(&str, &Attrs) -> P::Ext + Send + Sync,
+{
+    fn create(&self, tag: &str, attrs: &Attrs) -> P::Ext {
+        self(tag, attrs)
+    }
+}
+
+/// HTML parser producing nodes of phase `P`.
+///
+/// Usually obtained from the generated `MySite::parser()`.
+pub struct Parser<P: PhaseExt, F: ExtFn<P>> {
+    ext_fn: F,
+    _phase: PhantomData<fn() -> P>,
+}
+
+impl<P: PhaseExt, F: ExtFn<P>> Parser<P, F> {
+    pub fn new(ext_fn: F) -> Self {
+        Self {
+            ext_fn,
+            _phase: PhantomData,
+        }
+    }
+}
+
+impl<P, F> Parser<P, F>
+where
+    P: PhaseExt,
+    P::TextExt: SpannedExt,
+    F: ExtFn<P>,
+{
+    /// Parse a complete document.
+    ///
+    /// Leading doctypes, comments and processing instructions become the
+    /// document prolog. If the remaining input is a single element (followed
+    /// only by comments) it becomes the root; otherwise the nodes are wrapped
+    /// in a synthesized `<html>`. Whitespace-only text between top-level
+    /// nodes is dropped.
+    pub fn parse_document(&self, html: &str) -> Document<P> {
+        let mut prolog = self.parse_fragment(html);
+        prolog.retain(|node| !matches!(node, Node::Text(text) if text.is_whitespace()));
+
+        let split = prolog.iter().position(|node| !node.is_markup()).unwrap_or(prolog.len());
+        let mut nodes: Children<P> = prolog.drain(split..).collect();
+
+        let single_root = nodes.first().is_some_and(Node::is_element)
+            && nodes.iter().skip(1).all(Node::is_markup);
+
+        let root = if single_root {
+            let trailing: Children<P> = nodes.drain(1..).collect();
+            let Some(Node::Element(root)) = nodes.pop() else {
+                unreachable!()
+            };
+            let mut root = *root;
+            // Comments after the root element end up as its last children
+            root.children.extend(trailing);
+            root
+        } else {
+            let mut root = Element::with_ext("html", self.ext_fn.create("html", &Attrs::new()));
+            root.children = nodes;
+            root
+        };
+
+        Document::new(root).with_prolog(prolog)
+    }
+
+    /// Parse a fragment into a list of top-level nodes.
+    pub fn parse_fragment(&self, html: &str) -> Children<P> {
+        TreeBuilder::new(&self.ext_fn, html).build()
+    }
+}
+
+/// Parse a complete document with the given extension function.
+pub fn parse_document<P, F>(html: &str, ext_fn: F) -> Document<P>
+where
+    P: PhaseExt,
+    P::TextExt: SpannedExt,
+    F: ExtFn<P>,
+{
+    Parser::new(ext_fn).parse_document(html)
+}
+
+/// Parse a fragment with the given extension function.
+pub fn parse_fragment<P, F>(html: &str, ext_fn: F) -> Children<P>
+where
+    P: PhaseExt,
+    P::TextExt: SpannedExt,
+    F: ExtFn<P>,
+{
+    Parser::new(ext_fn).parse_fragment(html)
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+    use crate::families::{HeadingFamily, LinkFamily, SvgFamily};
+    use crate::node::{Text, TextKind};
+    use crate::render::{render_document, RenderConfig};
+    use crate::transform::Transform;
+    use crate::vdom;
+
+    #[vdom::families]
+    pub struct ParseSite {
+        link: LinkFamily,
+        heading: HeadingFamily,
+        svg: SvgFamily,
+    }
+
+    type Raw = ParseSite::Raw;
+
+    fn render(html: &str) -> String {
+        let doc = ParseSite::indexer().transform(ParseSite::parse(html));
+        render_document(&doc, &RenderConfig::PROD)
+    }
+
+    fn texts(elem: &Element<Raw>) -> Vec<&Text<Raw>> {
+        elem.children
+            .iter()
+            .filter_map(|n| n.as_text())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_single_root() {
+        let doc = ParseSite::parse("<!DOCTYPE html>\n<html><body><p>Hi</p></body></html>\n");
+        assert_eq!(doc.root.tag, "html");
+        assert_eq!(doc.root.len(), 1);
+        assert_eq!(doc.root.text_content(), "Hi");
+    }
+
+    #[test]
+    fn test_parse_multiple_roots_wrapped() {
+        let doc = ParseSite::parse("<p>a</p>\n<p>b</p>");
+        assert_eq!(doc.root.tag, "html");
+        assert_eq!(doc.root.child_elements().count(), 2);
+    }
+
+    #[test]
+    fn test_parse_families_identified() {
+        let doc = ParseSite::parse(r#"<div><a href="/x">x</a><h2 id="t">T</h2><svg></svg></div>"#);
+        let families: Vec<_> = doc.root.child_elements().map(|e| e.family_name()).collect();
+        assert_eq!(families, ["link", "heading", "svg"]);
+    }
+
+    #[test]
+    fn test_parse_implicit_end_tags() {
+        assert_eq!(
+            render("<div><p>a<p>b<div>c</div></div>"),
+            "<div><p>a</p><p>b</p><div>c</div></div>"
+        );
+        assert_eq!(
+            render("<ul><li>a<li>b<ul><li>c</ul></ul>"),
+            "<ul><li>a</li><li>b<ul><li>c</li></ul></li></ul>"
+        );
+        assert_eq!(
+            render("<table><tr><td>a<td>b<tr><td>c</table>"),
+            "<table><tr><td>a</td><td>b</td></tr><tr><td>c</td></tr></table>"
+        );
+        assert_eq!(render("<dl><dt>a<dd>b<dt>c</dl>"), "<dl><dt>a</dt><dd>b</dd><dt>c</dt></dl>");
+    }
+
+    #[test]
+    fn test_parse_void_elements() {
+        let doc = ParseSite::parse("<div>a<br>b<img src=x.png>c</div>");
+        assert_eq!(doc.root.len(), 5);
+        assert!(doc.root.child_elements().all(|e| e.is_empty()));
+        assert_eq!(render("<p>a<br></br>b</p>"), "<p>a<br /><br />b</p>");
+    }
+
+    #[test]
+    fn test_parse_raw_text_elements() {
+        let doc = ParseSite::parse("<div><script>if (a < b && c) {}</script><style>p > a {}</style></div>");
+        for elem in doc.root.child_elements() {
+            let texts = texts(elem);
+            assert_eq!(texts.len(), 1);
+            assert_eq!(texts[0].kind, TextKind::Raw);
+        }
+        assert_eq!(doc.root.children[0].as_element().unwrap().text_content(), "if (a < b && c) {}");
+    }
+
+    #[test]
+    fn test_parse_entities_decoded() {
+        let doc = ParseSite::parse(r#"<p title="a &amp; b">x &lt; y &copy;</p>"#);
+        assert_eq!(doc.root.get_attr("title"), Some("a & b"));
+        assert_eq!(doc.root.text_content(), "x < y ©");
+        assert_eq!(texts(&doc.root)[0].kind, TextKind::Escaped);
+    }
+
+    #[test]
+    fn test_parse_rcdata() {
+        let doc = ParseSite::parse("<title>a <b> &amp; c</title>");
+        assert_eq!(doc.root.text_content(), "a <b> & c");
+        assert!(doc.root.child_elements().next().is_none());
+    }
+
+    #[test]
+    fn test_parse_svg_case_and_self_closing() {
+        let doc = ParseSite::parse(
+            r#"<svg viewBox="0 0 10 10"><linearGradient id="g"/><clipPath><rect/></clipPath></svg>"#,
+        );
+        assert_eq!(doc.root.get_attr("viewBox"), Some("0 0 10 10"));
+        let tags: Vec<_> = doc.root.child_elements().map(|e| e.tag.as_str()).collect();
+        assert_eq!(tags, ["linearGradient", "clipPath"]);
+        assert!(doc.root.children[0].as_element().unwrap().is_empty());
+        assert_eq!(doc.root.children[1].as_element().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_svg_integration_point() {
+        let doc = ParseSite::parse(
+            "<svg><foreignObject><div><textarea><b></textarea></div></foreignObject><clippath/></svg>",
+        );
+        let fo = doc.root.children[0].as_element().unwrap();
+        assert_eq!(fo.tag, "foreignObject");
+        // HTML content modes apply again inside the integration point
+        assert_eq!(fo.text_content(), "<b>");
+        // and foreign content resumes once it is closed
+        assert_eq!(doc.root.children[1].as_element().unwrap().tag, "clipPath");
+    }
+
+    #[test]
+    fn test_parse_stray_end_tags_ignored() {
+        assert_eq!(render("<div>a</span>b</div></div>"), "<div>ab</div>");
+    }
+
+    #[test]
+    fn test_parse_unclosed_elements_closed_at_eof() {
+        assert_eq!(render("<div><span>a"), "<div><span>a</span></div>");
+    }
+
+    #[test]
+    fn test_parse_comments_kept() {
+        let doc = ParseSite::parse("<div>a<!-- note -->b</div>");
+        assert_eq!(doc.root.len(), 3);
+        assert_eq!(doc.root.children[1].as_comment().unwrap().content, " note ");
+        assert_eq!(doc.root.text_content(), "ab");
+    }
+
+    #[test]
+    fn test_parse_prolog() {
+        let doc = ParseSite::parse(
+            "<?xml version=\"1.0\"?>\n<!DOCTYPE html>\n<!-- top -->\n<html><body></body></html>\n<!-- end -->",
+        );
+        assert_eq!(doc.prolog.len(), 3);
+        let pi = doc.prolog[0].as_processing_instruction().unwrap();
+        assert_eq!((pi.target.as_str(), pi.data.as_str()), ("xml", "version=\"1.0\""));
+        assert_eq!(doc.doctype().unwrap().content, "html");
+        assert!(doc.prolog[2].is_comment());
+
+        assert_eq!(doc.root.tag, "html");
+        assert!(doc.root.children.last().unwrap().is_comment());
+    }
+
+    #[test]
+    fn test_parse_full_page_roundtrip() {
+        let html = "<!DOCTYPE html><html><head><!--[if IE]><p>old</p><![endif]--></head><body><!-- note --><p>x</p></body></html>";
+        let doc = ParseSite::indexer().transform(ParseSite::parse(html));
+        assert_eq!(render_document(&doc, &RenderConfig::new(false, false)), html);
+        assert_eq!(
+            render_document(&doc, &RenderConfig::PROD),
+            "<!DOCTYPE html><html><head><!--[if IE]><p>old</p><![endif]--></head><body><p>x</p></body></html>"
+        );
+    }
+
+    #[test]
+    fn test_parse_fragment() {
+        let nodes = ParseSite::parser().parse_fragment("text <b>bold</b> tail");
+        assert_eq!(nodes.len(), 3);
+        assert!(nodes[0].is_text());
+        assert!(nodes[1].is_element());
+    }
+
+    #[test]
+    fn test_parse_render_roundtrip() {
+        let html = r#"<html><head><title>T</title></head><body><h1 id="x">A</h1><p class="c">b <a href="/y">c</a></p></body></html>"#;
+        assert_eq!(render(html), html);
+    }
+
+    #[test]
+    fn test_parse_element_spans() {
+        let src = "<div>\n  <p>a<br>b</p>\n  <p>c\n</div>";
+        let doc = ParseSite::parse(src);
+
+        let div = doc.root.span.location().unwrap();
+        assert_eq!((div.start, div.end as usize), (0, src.len()));
+        assert_eq!((div.line, div.column), (1, 1));
+
+        let p = doc.root.children[1].as_element().unwrap().span.location().unwrap();
+        assert_eq!(&src[p.start as usize..p.end as usize], "<p>a<br>b</p>");
+        assert_eq!((p.line, p.column), (2, 3));
+
+        // Implicitly closed elements end where the closing token starts
+        let p2 = doc.root.children[3].as_element().unwrap().span.location().unwrap();
+        assert_eq!(&src[p2.start as usize..p2.end as usize], "<p>c\n");
+        assert_eq!(p2.to_string(), "3:3");
+    }
+
+    #[test]
+    fn test_parse_text_spans() {
+        let src = "<p>é a &amp; b</p>";
+        let doc = ParseSite::parse(src);
+        let loc = texts(&doc.root)[0].span().unwrap().location().unwrap();
+        assert_eq!(&src[loc.start as usize..loc.end as usize], "é a &amp; b");
+        assert_eq!((loc.line, loc.column), (1, 4));
+
+        // Text split by a literal `<` is merged with a single span
+        let src = "<p>a < b</p>";
+        let doc = ParseSite::parse(src);
+        let loc = texts(&doc.root)[0].span().unwrap().location().unwrap();
+        assert_eq!(&src[loc.start as usize..loc.end as usize], "a < b");
+    }
+
+    #[test]
+    fn test_parse_spans_carried_to_indexed() {
+        let src = "<div>\n<a href='/x'>link</a></div>";
+        let raw = ParseSite::parse(src);
+        let raw_span = raw.root.children[1].as_element().unwrap().span.location();
+
+        let indexed = ParseSite::indexer().transform(raw);
+        let link = indexed.root.children[1].as_element().unwrap();
+        assert_eq!(link.span.location(), raw_span);
+        assert_eq!(link.span.location().unwrap().line, 2);
+
+        let text = link.children[0].as_text().unwrap();
+        assert_eq!(text.span().unwrap().location().unwrap().column, 14);
+    }
+
+    #[test]
+    fn test_parse_pipeline() {
+        let doc = ParseSite::parse("<div><a href='https://example.com'>x</a></div>");
+        let indexed = ParseSite::indexer().transform(doc);
+        assert_eq!(indexed.root.children[0].as_element().unwrap().family_name(), "link");
+    }
+}
@@ -0,0 +1,541 @@
+//! HTML5 tokenizer.
+//!
+//! Splits source text into start tags, end tags, text, comments, doctypes
+//! and processing instructions. Character references are decoded in text
+//! and attribute values; raw-text content (`<script>`, `<style>`) is
+//! returned verbatim.
+
+use std::borrow::Cow;
+
+use crate::attr::{AttrKey, Attrs, Tag};
+
+// =============================================================================
+// Token
+// =============================================================================
+
+/// A single lexical token.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token<'a> {
+    /// `<name attr="value">` or `<name />`
+    StartTag {
+        name: Tag,
+        attrs: Box<Attrs>,
+        self_closing: bool,
+    },
+    /// `</name>`
+    EndTag { name: Tag },
+    /// Character data with references decoded
+    Text(Cow<'a, str>),
+    /// Content of a raw-text element (no decoding)
+    RawText(&'a str),
+    /// `<!-- ... -->` (also bogus comments)
+    Comment(&'a str),
+    /// `<!DOCTYPE ...>` (content after the keyword)
+    Doctype(&'a str),
+    /// `<?target data?>`
+    ProcessingInstruction(&'a str),
+}
+
+/// How the content of the element just opened should be tokenized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ContentMode {
+    /// Regular markup
+    Data,
+    /// Text with character references, no tags (`<title>`, `<textarea>`)
+    Rcdata,
+    /// Verbatim text, no tags or references (`<script>`, `<style>`)
+    RawText,
+}
+
+impl ContentMode {
+    /// Content mode for an HTML element with the given (lowercase) tag.
+    pub(crate) fn for_tag(tag: &str) -> Self {
+        match tag {
+            "script" | "style" | "xmp" | "iframe" | "noembed" | "noframes" => Self::RawText,
+            "title" | "textarea" => Self::Rcdata,
+            _ => Self::Data,
+        }
+    }
+}
+
+// =============================================================================
+// Tokenizer
+// =============================================================================
+
+/// Pull-based tokenizer over a source string.
+pub(crate) struct Tokenizer<'a> {
+    src: &'a str,
+    pos: usize,
+    /// Pending raw-text/RCDATA element: (mode, closing tag name)
+    pending: Option<(ContentMode, Tag)>,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub(crate) fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            pos: 0,
+            pending: None,
+        }
+    }
+
+    /// Switch to raw-text or RCDATA mode until `</tag>`.
+    ///
+    /// Called by the tree builder right after it receives the start tag.
+    pub(crate) fn set_content_mode(&mut self, mode: ContentMode, tag: &str) {
+        if mode != ContentMode::Data {
+            self.pending = Some((mode, Tag::from(tag)));
+        }
+    }
+
+    /// Current byte offset into the source.
+    pub(crate) fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// Produce the next token, or `None` at end of input.
+    pub(crate) fn next_token(&mut self) -> Option<Token<'a>> {
+        if let Some((mode, tag)) = self.pending.take() {
+            let content = self.consume_until_end_tag(&tag);
+            if !content.is_empty() {
+                return Some(match mode {
+                    ContentMode::RawText => Token::RawText(content),
+                    _ => Token::Text(decode_entities(content)),
+                });
+            }
+        }
+
+        let rest = self.rest();
+        if rest.is_empty() {
+            return None;
+        }
+
+        if rest.starts_with('<')
+            && let Some(token) = self.try_markup()
+        {
+            return Some(token);
+        }
+
+        Some(self.consume_text())
+    }
+
+    #[inline]
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    #[inline]
+    fn bytes(&self) -> &'a [u8] {
+        self.src.as_bytes()
+    }
+
+    /// Consume text up to the next `<` that may start markup.
+    fn consume_text(&mut self) -> Token<'a> {
+        let start = self.pos;
+        // A `<` at the current position failed to parse as markup, keep it as text
+        let mut end = start + 1;
+        let bytes = self.bytes();
+        while end < bytes.len() && bytes[end] != b'<' {
+            end += 1;
+        }
+        self.pos = end;
+        Token::Text(decode_entities(&self.src[start..end]))
+    }
+
+    /// Try to parse markup starting at `<`. Returns `None` (without consuming)
+    /// when the `<` is literal text.
+    fn try_markup(&mut self) -> Option<Token<'a>> {
+        let bytes = self.bytes();
+        let next = *bytes.get(self.pos + 1)?;
+
+        match next {
+            b'!' => Some(self.consume_declaration()),
+            b'?' => Some(self.consume_processing_instruction()),
+            b'/' => {
+                let after = *bytes.get(self.pos + 2)?;
+                if after.is_ascii_alphabetic() {
+                    Some(self.consume_end_tag())
+                } else if after == b'>' {
+                    // `</>` is ignored entirely
+                    self.pos += 3;
+                    Some(Token::Text(Cow::Borrowed("")))
+                } else {
+                    // `</ ...>` is a bogus comment
+                    self.pos += 2;
+                    Some(Token::Comment(self.consume_until(">")))
+                }
+            }
+            c if c.is_ascii_alphabetic() => Some(self.consume_start_tag()),
+            _ => None,
+        }
+    }
+
+    /// `<!-- -->`, `<!DOCTYPE>`, `<![CDATA[ ]]>` or a bogus comment.
+    fn consume_declaration(&mut self) -> Token<'a> {
+        let rest = self.rest();
+        if rest.starts_with("<!--") {
+            self.pos += 4;
+            // `<!-->` and `<!--->` are empty comments
+            let rest = self.rest();
+            if rest.starts_with('>') {
+                self.pos += 1;
+                return Token::Comment("");
+            }
+            if rest.starts_with("->") {
+                self.pos += 2;
+                return Token::Comment("");
+            }
+            return Token::Comment(self.consume_until("-->"));
+        }
+
+        if rest.len() >= 9 && rest.as_bytes()[2..9].eq_ignore_ascii_case(b"doctype") {
+            self.pos += 9;
+            let content = self.consume_until(">");
+            return Token::Doctype(content.trim());
+        }
+
+        if rest.starts_with("<![CDATA[") {
+            self.pos += 9;
+            let content = self.consume_until("]]>");
+            return Token::Text(Cow::Borrowed(content));
+        }
+
+        // Bogus comment: `<!anything>`
+        self.pos += 2;
+        Token::Comment(self.consume_until(">"))
+    }
+
+    /// `<?target data?>` (closing `?` optional, as in HTML5 bogus comments).
+    fn consume_processing_instruction(&mut self) -> Token<'a> {
+        self.pos += 2;
+        let content = self.consume_until(">");
+        Token::ProcessingInstruction(content.strip_suffix('?').unwrap_or(content))
+    }
+
+    /// Consume up to (and including) `terminator`, returning the content before it.
+    /// Unterminated constructs run to end of input.
+    fn consume_until(&mut self, terminator: &str) -> &'a str {
+        let rest = self.rest();
+        match rest.find(terminator) {
+            Some(idx) => {
+                self.pos += idx + terminator.len();
+                &rest[..idx]
+            }
+            None => {
+                self.pos = self.src.len();
+                rest
+            }
+        }
+    }
+
+    /// Consume raw-text/RCDATA content until `</tag` followed by a delimiter.
+    fn consume_until_end_tag(&mut self, tag: &str) -> &'a str {
+        let start = self.pos;
+        let bytes = self.bytes();
+        let mut i = start;
+        while let Some(off) = self.src[i..].find("</") {
+            let name_start = i + off + 2;
+            let name_end = name_start + tag.len();
+            if name_end <= bytes.len()
+                && bytes[name_start..name_end].eq_ignore_ascii_case(tag.as_bytes())
+                && matches!(
+                    bytes.get(name_end),
+                    None | Some(b'>' | b'/' | b' ' | b'\t' | b'\n' | b'\r' | b'\x0c')
+                )
+            {
+                self.pos = i + off;
+                return &self.src[start..self.pos];
+            }
+            i = name_start;
+        }
+        self.pos = self.src.len();
+        &self.src[start..]
+    }
+
+    fn consume_end_tag(&mut self) -> Token<'a> {
+        self.pos += 2;
+        let name = self.consume_name();
+        // Attributes on end tags are ignored
+        self.consume_until(">");
+        Token::EndTag { name }
+    }
+
+    fn consume_start_tag(&mut self) -> Token<'a> {
+        self.pos += 1;
+        let name = self.consume_name();
+        let mut attrs = Attrs::new();
+        let mut self_closing = false;
+
+        loop {
+            self.skip_whitespace();
+            let bytes = self.bytes();
+            match bytes.get(self.pos) {
+                None => break,
+                Some(b'>') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'/') => {
+                    self.pos += 1;
+                    if bytes.get(self.pos) == Some(&b'>') {
+                        self.pos += 1;
+                        self_closing = true;
+                        break;
+                    }
+                }
+                Some(_) => {
+                    let (key, value) = self.consume_attr();
+                    // First occurrence wins (HTML5 duplicate attribute rule)
+                    attrs.push_uniq(key, value);
+                }
+            }
+        }
+
+        Token::StartTag {
+            name,
+            attrs: Box::new(attrs),
+            self_closing,
+        }
+    }
+
+    /// Tag name: lowercased, terminated by whitespace, `/` or `>`.
+    fn consume_name(&mut self) -> Tag {
+        let start = self.pos;
+        let bytes = self.bytes();
+        while self.pos < bytes.len()
+            && !matches!(bytes[self.pos], b'/' | b'>')
+            && !bytes[self.pos].is_ascii_whitespace()
+        {
+            self.pos += 1;
+        }
+        Tag::from(self.src[start..self.pos].to_ascii_lowercase())
+    }
+
+    fn consume_attr(&mut self) -> (AttrKey, Cow<'a, str>) {
+        let start = self.pos;
+        let bytes = self.bytes();
+        // The first character may be `=` (parse error, but part of the name)
+        self.pos += 1;
+        while self.pos < bytes.len()
+            && !matches!(bytes[self.pos], b'/' | b'>' | b'=')
+            && !bytes[self.pos].is_ascii_whitespace()
+        {
+            self.pos += 1;
+        }
+        let key = AttrKey::from(self.src[start..self.pos].to_ascii_lowercase());
+
+        self.skip_whitespace();
+        if bytes.get(self.pos) != Some(&b'=') {
+            return (key, Cow::Borrowed(""));
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+
+        let value = match bytes.get(self.pos) {
+            Some(&quote @ (b'"' | b'\'')) => {
+                self.pos += 1;
+                let start = self.pos;
+                while self.pos < bytes.len() && bytes[self.pos] != quote {
+                    self.pos += 1;
+                }
+                let raw = &self.src[start..self.pos];
+                self.pos = (self.pos + 1).min(bytes.len());
+                raw
+            }
+            _ => {
+                let start = self.pos;
+                while self.pos < bytes.len()
+                    && bytes[self.pos] != b'>'
+                    && !bytes[self.pos].is_ascii_whitespace()
+                {
+                    self.pos += 1;
+                }
+                &self.src[start..self.pos]
+            }
+        };
+
+        (key, decode_entities(value))
+    }
+
+    fn skip_whitespace(&mut self) {
+        let bytes = self.bytes();
+        while self.pos < bytes.len() && bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+}
+
+// =============================================================================
+// Character references
+// =============================================================================
+
+/// Decode character references (`&amp;`, `&#60;`, `&#x3c;`).
+///
+/// Unknown or malformed references are kept verbatim.
+pub(crate) fn decode_entities(s: &str) -> Cow<'_, str> {
+    if !s.contains('&') {
+        return Cow::Borrowed(s);
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(idx) = rest.find('&') {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        match decode_reference(rest) {
+            Some((ch, len)) => {
+                out.push(ch);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// Decode a single reference at the start of `s` (which begins with `&`).
+/// Returns the character and the number of bytes consumed.
+fn decode_reference(s: &str) -> Option<(char, usize)> {
+    let body = &s[1..];
+    if let Some(num) = body.strip_prefix('#') {
+        let (digits, radix, prefix) = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16, 2),
+            None => (num, 10, 1),
+        };
+        let len = digits
+            .bytes()
+            .take_while(|b| (*b as char).is_digit(radix))
+            .count();
+        if len == 0 {
+            return None;
+        }
+        let code = u32::from_str_radix(&digits[..len], radix).ok()?;
+        let ch = match code {
+            0 => '\u{FFFD}',
+            _ => char::from_u32(code).unwrap_or('\u{FFFD}'),
+        };
+        let terminated = digits[len..].starts_with(';');
+        return Some((ch, 1 + prefix + len + usize::from(terminated)));
+    }
+
+    let end = body.find(';')?;
+    let ch = named_reference(&body[..end])?;
+    Some((ch, end + 2))
+}
+
+/// Common named character references.
+fn named_reference(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{A0}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "times" => '×',
+        "divide" => '÷',
+        "middot" => '·',
+        "bull" => '•',
+        "deg" => '°',
+        "plusmn" => '±',
+        "para" => '¶',
+        "sect" => '§',
+        "euro" => '€',
+        "pound" => '£',
+        "yen" => '¥',
+        "cent" => '¢',
+        "larr" => '←',
+        "rarr" => '→',
+        "uarr" => '↑',
+        "darr" => '↓',
+        "harr" => '↔',
+        "shy" => '\u{AD}',
+        "zwj" => '\u{200D}',
+        "zwnj" => '\u{200C}',
+        "ensp" => '\u{2002}',
+        "emsp" => '\u{2003}',
+        "thinsp" => '\u{2009}',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(src: &str) -> Vec<Token<'_>> {
+        let mut tokenizer = Tokenizer::new(src);
+        std::iter::from_fn(|| tokenizer.next_token()).collect()
+    }
+
+    #[test]
+    fn test_start_tag_attrs() {
+        let toks = tokens(r#"<A Href="/x" data-n=1 hidden class='a b'>"#);
+        match &toks[0] {
+            Token::StartTag { name, attrs, self_closing } => {
+                assert_eq!(name, "a");
+                assert_eq!(attrs.get("href"), Some("/x"));
+                assert_eq!(attrs.get("data-n"), Some("1"));
+                assert_eq!(attrs.get("hidden"), Some(""));
+                assert_eq!(attrs.get("class"), Some("a b"));
+                assert!(!self_closing);
+            }
+            other => panic!("unexpected token: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_comment_doctype_pi() {
+        let toks = tokens("<!DOCTYPE html><!-- hi --><?xml version=\"1.0\"?>");
+        assert_eq!(toks[0], Token::Doctype("html"));
+        assert_eq!(toks[1], Token::Comment(" hi "));
+        assert_eq!(toks[2], Token::ProcessingInstruction("xml version=\"1.0\""));
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(decode_entities("a &amp; b"), "a & b");
+        assert_eq!(decode_entities("&#60;&#x3E;"), "<>");
+        assert_eq!(decode_entities("&unknown; &"), "&unknown; &");
+        assert_eq!(decode_entities("AT&T"), "AT&T");
+    }
+
+    #[test]
+    fn test_lone_lt_is_text() {
+        let toks = tokens("a < b");
+        let text: String = toks
+            .iter()
+            .map(|t| match t {
+                Token::Text(s) => s.as_ref(),
+                _ => panic!("expected text"),
+            })
+            .collect();
+        assert_eq!(text, "a < b");
+    }
+
+    #[test]
+    fn test_raw_text_mode() {
+        let mut tokenizer = Tokenizer::new("<script>if (a < b) {}</script>");
+        let start = tokenizer.next_token().unwrap();
+        assert!(matches!(start, Token::StartTag { ref name, .. } if name == "script"));
+        tokenizer.set_content_mode(ContentMode::RawText, "script");
+        assert_eq!(tokenizer.next_token(), Some(Token::RawText("if (a < b) {}")));
+        assert!(matches!(tokenizer.next_token(), Some(Token::EndTag { ref name }) if name == "script"));
+    }
+}
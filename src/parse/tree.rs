@@ -0,0 +1,544 @@
+//! Tree construction from tokens.
+//!
+//! A simplified HTML5 tree builder: it keeps a stack of open elements,
+//! applies the common implicit end-tag rules (`<p>`, `<li>`, table parts,
+//! ...), never pushes void elements, and closes everything at end of input.
+//! It does not synthesize `<html>`/`<head>`/`<body>` or reparent misnested
+//! formatting elements.
+
+use crate::attr::{Attrs, Tag};
+use crate::core::PhaseExt;
+use crate::node::{Children, Comment, Doctype, Element, Node, ProcessingInstruction, Text, TextKind};
+use crate::render::is_void_element;
+use crate::span::{SourceLocation, SourceSpan, SpannedExt};
+
+use super::tokenizer::{ContentMode, Token, Tokenizer};
+use super::ExtFn;
+
+/// Builds a node list from HTML source.
+pub(crate) struct TreeBuilder<'a, P: PhaseExt, F: ExtFn<P>> {
+    ext_fn: &'a F,
+    src: &'a str,
+    lines: LineIndex,
+    /// Open elements, innermost last
+    stack: Vec<Element<P>>,
+    /// Whether the children of each open element are foreign content, parallel to `stack`
+    foreign: Vec<bool>,
+    /// Finished top-level nodes
+    top: Children<P>,
+    /// Byte range of the token being processed
+    token_start: usize,
+    token_end: usize,
+}
+
+impl<'a, P, F> TreeBuilder<'a, P, F>
+where
+    P: PhaseExt,
+    P::TextExt: SpannedExt,
+    F: ExtFn<P>,
+{
+    pub(crate) fn new(ext_fn: &'a F, src: &'a str) -> Self {
+        Self {
+            ext_fn,
+            src,
+            lines: LineIndex::new(src),
+            stack: Vec::new(),
+            foreign: Vec::new(),
+            top: Children::new(),
+            token_start: 0,
+            token_end: 0,
+        }
+    }
+
+    /// Consume all tokens and return the top-level nodes.
+    pub(crate) fn build(mut self) -> Children<P> {
+        let mut tokenizer = Tokenizer::new(self.src);
+
+        loop {
+            self.token_start = tokenizer.offset();
+            let Some(token) = tokenizer.next_token() else {
+                break;
+            };
+            self.token_end = tokenizer.offset();
+
+            match token {
+                Token::StartTag {
+                    name,
+                    attrs,
+                    self_closing,
+                } => {
+                    let in_foreign = self.in_foreign_content();
+                    if let Some(tag) = self.start_tag(name, attrs, self_closing) {
+                        if !in_foreign {
+                            tokenizer.set_content_mode(ContentMode::for_tag(&tag), &tag);
+                        }
+                    }
+                }
+                Token::EndTag { name } => self.end_tag(&name),
+                Token::Text(text) => self.push_text(&text, TextKind::Escaped),
+                Token::RawText(text) => self.push_text(text, TextKind::Raw),
+                Token::Comment(content) => {
+                    let ext = P::TextExt::from_span(self.token_span());
+                    self.append(Node::Comment(Comment::with_ext(content, ext)));
+                }
+                Token::Doctype(content) => {
+                    let ext = P::TextExt::from_span(self.token_span());
+                    self.append(Node::Doctype(Doctype::with_ext(content, ext)));
+                }
+                Token::ProcessingInstruction(content) => {
+                    let (target, data) = content
+                        .split_once(|c: char| c.is_ascii_whitespace())
+                        .unwrap_or((content, ""));
+                    let ext = P::TextExt::from_span(self.token_span());
+                    self.append(Node::ProcessingInstruction(ProcessingInstruction::with_ext(
+                        target,
+                        data.trim_start(),
+                        ext,
+                    )));
+                }
+            }
+        }
+
+        let end = self.src.len();
+        while !self.stack.is_empty() {
+            self.pop(end);
+        }
+        self.top
+    }
+
+    // -------------------------------------------------------------------------
+    // Token handlers
+    // -------------------------------------------------------------------------
+
+    /// Handle a start tag. Returns the tag name if the element was left open.
+    fn start_tag(&mut self, name: Tag, attrs: Box<Attrs>, self_closing: bool) -> Option<Tag> {
+        let mut attrs = *attrs;
+        let foreign = self.in_foreign_content() || name == "svg";
+        let name = if foreign {
+            adjust_svg_tag(name)
+        } else {
+            self.close_implied_by(&name);
+            name
+        };
+        if foreign {
+            adjust_svg_attrs(&mut attrs);
+        }
+
+        let ext = self.ext_fn.create(&name, &attrs);
+        let mut elem = Element::with_ext(name, ext);
+        elem.attrs = attrs;
+        elem.span = self.span(self.token_start, self.token_end);
+
+        // Void elements never have children; in foreign content `/>` closes
+        if is_void_element(&elem.tag) || (foreign && self_closing) {
+            self.append(Node::Element(Box::new(elem)));
+            return None;
+        }
+
+        let tag = elem.tag.clone();
+        let foreign = match tag.as_str() {
+            "svg" => true,
+            "foreignObject" | "desc" | "title" => false,
+            _ => foreign,
+        };
+        self.stack.push(elem);
+        self.foreign.push(foreign);
+        Some(tag)
+    }
+
+    fn end_tag(&mut self, name: &str) {
+        let name = if self.in_foreign_content() {
+            adjust_svg_tag(Tag::from(name))
+        } else {
+            Tag::from(name)
+        };
+
+        // `</foreignObject>` is lowercased by the tokenizer outside foreign content
+        match self.stack.iter().rposition(|e| e.tag.eq_ignore_ascii_case(&name)) {
+            Some(idx) => {
+                // Elements left open inside end where this end tag starts
+                while self.stack.len() > idx + 1 {
+                    self.pop(self.token_start);
+                }
+                self.pop(self.token_end);
+            }
+            None => match name.as_str() {
+                // `</p>` without an open `<p>` produces an empty paragraph
+                "p" => self.append(Node::Element(Box::new(self.empty_element("p")))),
+                // `</br>` is treated as `<br>`
+                "br" => self.append(Node::Element(Box::new(self.empty_element("br")))),
+                // Stray end tags are ignored
+                _ => {}
+            },
+        }
+    }
+
+    fn push_text(&mut self, text: &str, kind: TextKind) {
+        if text.is_empty() {
+            return;
+        }
+
+        let children = match self.stack.last_mut() {
+            Some(parent) => &mut parent.children,
+            None => &mut self.top,
+        };
+
+        // Merge adjacent text of the same kind (e.g. text split by a literal `<`)
+        if let Some(Node::Text(last)) = children.last_mut()
+            && last.kind == kind
+        {
+            let start = last
+                .ext
+                .span()
+                .and_then(|span| span.location())
+                .map_or(self.token_start, |loc| loc.start as usize);
+            last.content.push_str(text);
+            last.ext = P::TextExt::from_span(self.lines.span(self.src, start, self.token_end));
+            return;
+        }
+
+        let span = self.lines.span(self.src, self.token_start, self.token_end);
+        let mut node = Text::with_ext(text, P::TextExt::from_span(span));
+        node.kind = kind;
+        children.push(Node::Text(node));
+    }
+
+    // -------------------------------------------------------------------------
+    // Stack helpers
+    // -------------------------------------------------------------------------
+
+    fn empty_element(&self, tag: &str) -> Element<P> {
+        let attrs = Attrs::new();
+        let ext = self.ext_fn.create(tag, &attrs);
+        let mut elem = Element::with_ext(tag, ext);
+        elem.span = self.span(self.token_start, self.token_end);
+        elem
+    }
+
+    fn span(&self, start: usize, end: usize) -> SourceSpan {
+        self.lines.span(self.src, start, end)
+    }
+
+    fn token_span(&self) -> SourceSpan {
+        self.span(self.token_start, self.token_end)
+    }
+
+    fn append(&mut self, node: Node<P>) {
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => self.top.push(node),
+        }
+    }
+
+    /// Close the innermost open element at byte offset `end` and attach it
+    /// to its parent.
+    fn pop(&mut self, end: usize) {
+        if let Some(mut elem) = self.stack.pop() {
+            self.foreign.pop();
+            if let Some(mut loc) = elem.span.location() {
+                loc.end = end as u32;
+                elem.span = SourceSpan::from_location(loc);
+            }
+            self.append(Node::Element(Box::new(elem)));
+        }
+    }
+
+    fn current_tag(&self) -> Option<&str> {
+        self.stack.last().map(|e| e.tag.as_str())
+    }
+
+    /// Inside `<svg>` but not inside an HTML integration point.
+    fn in_foreign_content(&self) -> bool {
+        self.foreign.last().copied().unwrap_or(false)
+    }
+
+    /// Find `tag` in the stack without crossing a scope boundary.
+    fn in_scope(&self, tag: &str, boundaries: &[&str]) -> Option<usize> {
+        for (idx, elem) in self.stack.iter().enumerate().rev() {
+            if elem.tag == tag {
+                return Some(idx);
+            }
+            if boundaries.contains(&elem.tag.as_str()) {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Implicitly close elements up to and including the one at `idx`.
+    fn close_to(&mut self, idx: usize) {
+        while self.stack.len() > idx {
+            self.pop(self.token_start);
+        }
+    }
+
+    /// Apply implicit end tags triggered by a start tag.
+    fn close_implied_by(&mut self, tag: &str) {
+        if closes_paragraph(tag)
+            && let Some(idx) = self.in_scope("p", BUTTON_SCOPE)
+        {
+            self.close_to(idx);
+        }
+
+        match tag {
+            "li" => {
+                if let Some(idx) = self.in_scope("li", LIST_SCOPE) {
+                    self.close_to(idx);
+                }
+            }
+            "dt" | "dd" => {
+                for other in ["dt", "dd"] {
+                    if let Some(idx) = self.in_scope(other, DEFAULT_SCOPE) {
+                        self.close_to(idx);
+                    }
+                }
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" if self.current_tag().is_some_and(is_heading) => self.pop(self.token_start),
+            "a" => {
+                if let Some(idx) = self.in_scope("a", DEFAULT_SCOPE) {
+                    self.close_to(idx);
+                }
+            }
+            "option" if self.current_tag() == Some("option") => self.pop(self.token_start),
+            "optgroup" => {
+                if self.current_tag() == Some("option") {
+                    self.pop(self.token_start);
+                }
+                if self.current_tag() == Some("optgroup") {
+                    self.pop(self.token_start);
+                }
+            }
+            "tr" => {
+                if let Some(idx) = self.in_scope("tr", TABLE_SCOPE) {
+                    self.close_to(idx);
+                }
+            }
+            "td" | "th" => {
+                for other in ["td", "th"] {
+                    if let Some(idx) = self.in_scope(other, TABLE_ROW_SCOPE) {
+                        self.close_to(idx);
+                    }
+                }
+            }
+            "thead" | "tbody" | "tfoot" => {
+                for other in ["thead", "tbody", "tfoot"] {
+                    if let Some(idx) = self.in_scope(other, TABLE_SCOPE) {
+                        self.close_to(idx);
+                    }
+                }
+            }
+            "rb" | "rt" | "rp" | "rtc" => {
+                while self
+                    .current_tag()
+                    .is_some_and(|t| matches!(t, "rb" | "rt" | "rp" | "rtc") && (tag != "rtc" || t != "rtc"))
+                {
+                    self.pop(self.token_start);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// =============================================================================
+// Line index
+// =============================================================================
+
+/// Byte offsets of line starts, for offset to line/column conversion.
+struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    fn new(src: &str) -> Self {
+        let mut starts = vec![0];
+        starts.extend(src.bytes().enumerate().filter(|(_, b)| *b == b'\n').map(|(i, _)| i + 1));
+        Self { starts }
+    }
+
+    fn span(&self, src: &str, start: usize, end: usize) -> SourceSpan {
+        let line = self.starts.partition_point(|&s| s <= start);
+        let line_start = self.starts[line - 1];
+        let column = src[line_start..start].chars().count() + 1;
+        SourceSpan::from_location(SourceLocation {
+            start: start as u32,
+            end: end as u32,
+            line: line as u32,
+            column: column as u32,
+        })
+    }
+}
+
+// =============================================================================
+// Scopes
+// =============================================================================
+
+const DEFAULT_SCOPE: &[&str] = &[
+    "applet", "caption", "html", "table", "td", "th", "marquee", "object", "template", "svg",
+];
+
+const BUTTON_SCOPE: &[&str] = &[
+    "applet", "caption", "html", "table", "td", "th", "marquee", "object", "template", "svg",
+    "button",
+];
+
+const LIST_SCOPE: &[&str] = &[
+    "applet", "caption", "html", "table", "td", "th", "marquee", "object", "template", "svg",
+    "ol", "ul",
+];
+
+const TABLE_SCOPE: &[&str] = &["html", "table", "template"];
+
+const TABLE_ROW_SCOPE: &[&str] = &["html", "table", "template", "tr"];
+
+fn is_heading(tag: &str) -> bool {
+    matches!(tag, "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+}
+
+/// Start tags that implicitly close an open `<p>`.
+fn closes_paragraph(tag: &str) -> bool {
+    matches!(
+        tag,
+        "address"
+            | "article"
+            | "aside"
+            | "blockquote"
+            | "details"
+            | "dialog"
+            | "dir"
+            | "div"
+            | "dl"
+            | "dd"
+            | "dt"
+            | "fieldset"
+            | "figcaption"
+            | "figure"
+            | "footer"
+            | "form"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "header"
+            | "hgroup"
+            | "hr"
+            | "li"
+            | "listing"
+            | "main"
+            | "menu"
+            | "nav"
+            | "ol"
+            | "p"
+            | "pre"
+            | "search"
+            | "section"
+            | "summary"
+            | "table"
+            | "ul"
+    )
+}
+
+// =============================================================================
+// SVG name adjustments
+// =============================================================================
+
+/// Restore camelCase SVG tag names lowercased by the tokenizer.
+fn adjust_svg_tag(tag: Tag) -> Tag {
+    let adjusted = match tag.as_str() {
+        "altglyph" => "altGlyph",
+        "altglyphdef" => "altGlyphDef",
+        "altglyphitem" => "altGlyphItem",
+        "animatecolor" => "animateColor",
+        "animatemotion" => "animateMotion",
+        "animatetransform" => "animateTransform",
+        "clippath" => "clipPath",
+        "feblend" => "feBlend",
+        "fecolormatrix" => "feColorMatrix",
+        "fecomponenttransfer" => "feComponentTransfer",
+        "fecomposite" => "feComposite",
+        "feconvolvematrix" => "feConvolveMatrix",
+        "fediffuselighting" => "feDiffuseLighting",
+        "fedisplacementmap" => "feDisplacementMap",
+        "fedistantlight" => "feDistantLight",
+        "fedropshadow" => "feDropShadow",
+        "feflood" => "feFlood",
+        "fefunca" => "feFuncA",
+        "fefuncb" => "feFuncB",
+        "fefuncg" => "feFuncG",
+        "fefuncr" => "feFuncR",
+        "fegaussianblur" => "feGaussianBlur",
+        "feimage" => "feImage",
+        "femerge" => "feMerge",
+        "femergenode" => "feMergeNode",
+        "femorphology" => "feMorphology",
+        "feoffset" => "feOffset",
+        "fepointlight" => "fePointLight",
+        "fespecularlighting" => "feSpecularLighting",
+        "fespotlight" => "feSpotLight",
+        "fetile" => "feTile",
+        "feturbulence" => "feTurbulence",
+        "foreignobject" => "foreignObject",
+        "glyphref" => "glyphRef",
+        "lineargradient" => "linearGradient",
+        "radialgradient" => "radialGradient",
+        "textpath" => "textPath",
+        _ => return tag,
+    };
+    Tag::from(adjusted)
+}
+
+/// Restore camelCase SVG attribute names lowercased by the tokenizer.
+fn adjust_svg_attrs(attrs: &mut Attrs) {
+    for (key, _) in attrs.iter_mut() {
+        let adjusted = match key.as_str() {
+            "attributename" => "attributeName",
+            "attributetype" => "attributeType",
+            "basefrequency" => "baseFrequency",
+            "calcmode" => "calcMode",
+            "clippathunits" => "clipPathUnits",
+            "diffuseconstant" => "diffuseConstant",
+            "edgemode" => "edgeMode",
+            "filterunits" => "filterUnits",
+            "gradienttransform" => "gradientTransform",
+            "gradientunits" => "gradientUnits",
+            "kernelmatrix" => "kernelMatrix",
+            "keypoints" => "keyPoints",
+            "keysplines" => "keySplines",
+            "keytimes" => "keyTimes",
+            "lengthadjust" => "lengthAdjust",
+            "markerheight" => "markerHeight",
+            "markerunits" => "markerUnits",
+            "markerwidth" => "markerWidth",
+            "maskcontentunits" => "maskContentUnits",
+            "maskunits" => "maskUnits",
+            "numoctaves" => "numOctaves",
+            "pathlength" => "pathLength",
+            "patterncontentunits" => "patternContentUnits",
+            "patterntransform" => "patternTransform",
+            "patternunits" => "patternUnits",
+            "preserveaspectratio" => "preserveAspectRatio",
+            "primitiveunits" => "primitiveUnits",
+            "refx" => "refX",
+            "refy" => "refY",
+            "repeatcount" => "repeatCount",
+            "repeatdur" => "repeatDur",
+            "specularconstant" => "specularConstant",
+            "specularexponent" => "specularExponent",
+            "spreadmethod" => "spreadMethod",
+            "startoffset" => "startOffset",
+            "stddeviation" => "stdDeviation",
+            "stitchtiles" => "stitchTiles",
+            "surfacescale" => "surfaceScale",
+            "systemlanguage" => "systemLanguage",
+            "tablevalues" => "tableValues",
+            "textlength" => "textLength",
+            "viewbox" => "viewBox",
+            "xchannelselector" => "xChannelSelector",
+            "ychannelselector" => "yChannelSelector",
+            _ => continue,
+        };
+        *key = adjusted.into();
+    }
+}
@@ -6,13 +6,16 @@
 
 // Core traits
 pub use crate::core::{
-    ElementExt, ExtractFamily, Family, FamilyData, FamilySet, HasStableId, IndexedExt,
-    IndexedPhaseMarker, NoneFamily, NoneIndexed, Phase, PhaseExt, ProcessedPhaseMarker,
-    RawPhaseMarker,
+    DocChange, DocDiff, ElementExt, ExtChange, ExtractFamily, Family, FamilyData, FamilySet,
+    HasStableId, IndexedExt, IndexedPhaseMarker, NoneFamily, NoneIndexed, Phase, PhaseExt,
+    ProcessedPhaseMarker, RawPhaseMarker,
 };
 
 // Node types
-pub use crate::node::{Children, Document, Element, Node, Text, TextKind};
+pub use crate::node::{
+    Children, Comment, Cursor, CursorMut, Doctype, Document, Element, Node, NodeRef,
+    ProcessingInstruction, Text, TextKind,
+};
 
 // Transform
 pub use crate::transform::{IdentityTransform, IndexStats, Indexer, Pipeline, Processor, Transform};
@@ -24,16 +27,17 @@ pub use crate::transform::{AsyncPipeline, NoopValidator, ValidateError, Validate
 pub use crate::attr::{AttrKey, AttrValue, Attrs, Tag, TextContent};
 
 // Identity
-pub use crate::id::{PageSeed, StableId};
+pub use crate::id::{ContentHash, PageSeed, StableId};
 
 // Algorithms
 pub use crate::algo::{
-    diff, diff_with_config, Anchor, DiffConfig, DiffResult, DiffStats, Patch, PatchOp,
-    StableHasher,
+    apply_patches, diff, diff_ref, diff_with_config, invert, ApplyError, Anchor, DefaultPolicy, DiffConfig,
+    DiffPolicy, DiffResult, DiffResultRef, DiffStats, IgnoreRules, Patch, PatchOp, PatchOpRef, PolicyAction,
+    ReloadReason, StableHasher, TextSplice,
 };
 
 // Span
-pub use crate::span::SourceSpan;
+pub use crate::span::{SourceLocation, SourceSpan, SpannedExt};
 
 // Error
 pub use crate::error::{VdomError, VdomResult};
@@ -46,9 +50,22 @@ pub use crate::cache::{CacheEntry, CacheKey, SharedVdomCache, VdomCache};
 
 // Render
 pub use crate::render::{
-    render_document, render_document_bytes, render_patches, RenderConfig, DEFAULT_ID_ATTR,
+    patches_to_json, render_document, render_document_bytes, render_patches, CommentMode,
+    RenderConfig, DEFAULT_ID_ATTR, PATCH_RUNTIME_JS,
 };
 
+// Parsing
+pub use crate::parse::{parse_document, parse_fragment, ExtFn, Parser};
+
+// Selectors
+pub use crate::selector::{Selector, SelectorError};
+
+// Traversal
+pub use crate::visit::{Fold, VisitControl, Visitor, VisitorMut};
+
+// Wire format
+pub use crate::wire::{PatchBatch, WireError, WIRE_VERSION};
+
 // Serialization
 #[cfg(feature = "cache")]
 pub use crate::serialize::{from_bytes, to_bytes, SCHEMA_VERSION};
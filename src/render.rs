@@ -1,11 +1,14 @@
 //! HTML Rendering for VDOM
 //!
-//! Renders VDOM documents and patches to HTML strings.
+//! Renders VDOM documents and patches to HTML strings, and ships the
+//! browser runtime that applies rendered patches ([`PATCH_RUNTIME_JS`]).
 
-use crate::algo::{Patch, PatchOp};
+use crate::algo::{Anchor, Patch, PatchOpRef};
 use crate::attr::Attrs;
-use crate::core::{HasStableId, PhaseExt};
-use crate::node::{Document, Element, Node};
+use crate::core::{DocChange, ElementExt, HasStableId, PhaseExt};
+use crate::id::StableId;
+use crate::node::{Comment, Doctype, Document, Element, Node, ProcessingInstruction, Text};
+use crate::visit::{walk, VisitControl, Visitor};
 
 // =============================================================================
 // RenderConfig
@@ -26,6 +29,31 @@ pub struct RenderConfig {
     /// This allows users to customize the attribute name if needed
     /// to avoid conflicts with existing attributes.
     pub id_attr_name: String,
+    /// Which comments to emit.
+    pub comments: CommentMode,
+}
+
+/// Which comment nodes are emitted when rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommentMode {
+    /// Emit all comments.
+    #[default]
+    Keep,
+    /// Emit only conditional comments (`<!--[if IE]>...<![endif]-->`).
+    Conditional,
+    /// Emit no comments.
+    Strip,
+}
+
+impl CommentMode {
+    /// Check if a comment should be emitted under this mode.
+    pub fn keeps<P: PhaseExt>(self, comment: &Comment<P>) -> bool {
+        match self {
+            Self::Keep => true,
+            Self::Conditional => comment.is_conditional(),
+            Self::Strip => false,
+        }
+    }
 }
 
 impl RenderConfig {
@@ -34,13 +62,15 @@ impl RenderConfig {
         emit_ids: true,
         minify: false,
         id_attr_name: String::new(), // Will use DEFAULT_ID_ATTR
+        comments: CommentMode::Keep,
     };
 
-    /// Production config (no IDs, minify).
+    /// Production config (no IDs, minify, only conditional comments).
     pub const PROD: Self = Self {
         emit_ids: false,
         minify: true,
         id_attr_name: String::new(),
+        comments: CommentMode::Conditional,
     };
 
     /// Create a new config.
@@ -49,9 +79,16 @@ impl RenderConfig {
             emit_ids,
             minify,
             id_attr_name: DEFAULT_ID_ATTR.to_string(),
+            comments: CommentMode::Keep,
         }
     }
 
+    /// Set which comments to emit.
+    pub fn with_comments(mut self, mode: CommentMode) -> Self {
+        self.comments = mode;
+        self
+    }
+
     /// Set custom attribute name for stable IDs.
     pub fn with_id_attr(mut self, attr_name: impl Into<String>) -> Self {
         self.id_attr_name = attr_name.into();
@@ -94,6 +131,9 @@ where
     P::Ext: HasStableId,
 {
     let mut output = String::new();
+    for node in &doc.prolog {
+        render_node(node, config, &mut output);
+    }
     render_element(&doc.root, config, &mut output);
     output
 }
@@ -104,38 +144,7 @@ where
     P: PhaseExt,
     P::Ext: HasStableId,
 {
-    output.push('<');
-    output.push_str(&elem.tag);
-
-    // Render attributes
-    render_attrs(&elem.attrs, output);
-
-    // Emit stable ID if configured
-    if config.emit_ids {
-        let id = elem.ext.stable_id();
-        output.push(' ');
-        output.push_str(config.id_attr());
-        output.push_str("=\"");
-        output.push_str(&id.to_attr_value());
-        output.push('"');
-    }
-
-    // Void elements
-    if is_void_element(&elem.tag) {
-        output.push_str(" />");
-        return;
-    }
-
-    output.push('>');
-
-    // Render children
-    for child in &elem.children {
-        render_node(child, config, output);
-    }
-
-    output.push_str("</");
-    output.push_str(&elem.tag);
-    output.push('>');
+    walk(elem, &mut HtmlWriter { config, output });
 }
 
 /// Render a node to HTML.
@@ -144,17 +153,110 @@ where
     P: PhaseExt,
     P::Ext: HasStableId,
 {
+    let mut writer = HtmlWriter { config, output };
     match node {
-        Node::Element(elem) => render_element(elem, config, output),
+        Node::Element(elem) => {
+            walk(elem, &mut writer);
+        }
         Node::Text(text) => {
-            if text.is_raw() {
-                // Raw text: output as-is without escaping
-                output.push_str(&text.content);
-            } else {
-                // Normal text: escape HTML special characters
-                output.push_str(&escape_html(&text.content));
-            }
+            writer.visit_text(text);
+        }
+        Node::Comment(comment) => {
+            writer.visit_comment(comment);
+        }
+        Node::Doctype(doctype) => {
+            writer.visit_doctype(doctype);
+        }
+        Node::ProcessingInstruction(pi) => {
+            writer.visit_processing_instruction(pi);
+        }
+    }
+}
+
+/// Visitor writing HTML (stack-safe for deep documents).
+struct HtmlWriter<'a> {
+    config: &'a RenderConfig,
+    output: &'a mut String,
+}
+
+impl<P> Visitor<P> for HtmlWriter<'_>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    fn enter_element(&mut self, elem: &Element<P>) -> VisitControl {
+        let output = &mut *self.output;
+        output.push('<');
+        output.push_str(&elem.tag);
+
+        // Render attributes
+        render_attrs(&elem.attrs, output);
+
+        // Emit stable ID if configured
+        if self.config.emit_ids {
+            let id = elem.ext.stable_id();
+            output.push(' ');
+            output.push_str(self.config.id_attr());
+            output.push_str("=\"");
+            output.push_str(&id.to_attr_value());
+            output.push('"');
+        }
+
+        // Void elements
+        if is_void_element(&elem.tag) {
+            output.push_str(" />");
+            return VisitControl::SkipChildren;
+        }
+
+        output.push('>');
+        VisitControl::Continue
+    }
+
+    fn leave_element(&mut self, elem: &Element<P>) -> VisitControl {
+        if !is_void_element(&elem.tag) {
+            self.output.push_str("</");
+            self.output.push_str(&elem.tag);
+            self.output.push('>');
+        }
+        VisitControl::Continue
+    }
+
+    fn visit_text(&mut self, text: &Text<P>) -> VisitControl {
+        if text.is_raw() {
+            // Raw text: output as-is without escaping
+            self.output.push_str(&text.content);
+        } else {
+            // Normal text: escape HTML special characters
+            self.output.push_str(&escape_html(&text.content));
+        }
+        VisitControl::Continue
+    }
+
+    fn visit_comment(&mut self, comment: &Comment<P>) -> VisitControl {
+        if self.config.comments.keeps(comment) {
+            self.output.push_str("<!--");
+            self.output.push_str(&comment.content);
+            self.output.push_str("-->");
         }
+        VisitControl::Continue
+    }
+
+    fn visit_doctype(&mut self, doctype: &Doctype<P>) -> VisitControl {
+        self.output.push_str("<!DOCTYPE ");
+        self.output.push_str(&doctype.content);
+        self.output.push('>');
+        VisitControl::Continue
+    }
+
+    fn visit_processing_instruction(&mut self, pi: &ProcessingInstruction<P>) -> VisitControl {
+        self.output.push_str("<?");
+        self.output.push_str(&pi.target);
+        if !pi.data.is_empty() {
+            self.output.push(' ');
+            self.output.push_str(&pi.data);
+        }
+        self.output.push_str("?>");
+        VisitControl::Continue
     }
 }
 
@@ -199,7 +301,7 @@ fn escape_attr(s: &str) -> String {
 }
 
 /// Check if element is a void element (self-closing).
-fn is_void_element(tag: &str) -> bool {
+pub fn is_void_element(tag: &str) -> bool {
     matches!(
         tag,
         "area"
@@ -224,56 +326,119 @@ fn is_void_element(tag: &str) -> bool {
 // =============================================================================
 
 /// Render PatchOps to Patches with HTML.
-pub fn render_patches<P>(ops: &[PatchOp<P>], config: &RenderConfig) -> Vec<Patch>
+///
+/// Takes owned [`PatchOp`](crate::algo::PatchOp)s or [`PatchOpRef`]s borrowing from the new
+/// document (see [`diff_ref`](crate::algo::diff_ref)).
+pub fn render_patches<'o, P, O>(ops: &'o [O], config: &RenderConfig) -> Vec<Patch>
 where
     P: PhaseExt,
     P::Ext: HasStableId + Clone,
+    &'o O: Into<PatchOpRef<'o, P>>,
 {
     ops.iter()
-        .map(|op| render_patch_op(op, config))
+        .map(|op| render_patch_op(op.into(), config))
         .collect()
 }
 
 /// Render a single PatchOp to a Patch.
-fn render_patch_op<P>(op: &PatchOp<P>, config: &RenderConfig) -> Patch
+fn render_patch_op<P>(op: PatchOpRef<'_, P>, config: &RenderConfig) -> Patch
 where
     P: PhaseExt,
     P::Ext: HasStableId + Clone,
 {
     match op {
-        PatchOp::Replace { target, element } => Patch::Replace {
-            target: *target,
-            html: render_element_to_string(element, config),
+        PatchOpRef::Replace { target, element, is_svg } => Patch::Replace {
+            target,
+            html: render_element_to_string(&element, config),
+            is_svg,
+        },
+        PatchOpRef::UpdateText { target, text } => Patch::UpdateText {
+            target,
+            text: text.into_owned(),
         },
-        PatchOp::UpdateText { target, text } => Patch::UpdateText {
-            target: *target,
-            text: text.clone(),
+        PatchOpRef::UpdateTextAt {
+            parent,
+            index,
+            text,
+        } => Patch::UpdateTextAt {
+            parent,
+            index,
+            text: text.into_owned(),
         },
-        PatchOp::ReplaceChildren {
+        PatchOpRef::RemoveTextAt { parent, index } => Patch::RemoveTextAt { parent, index },
+        PatchOpRef::SpliceText {
+            parent,
+            index,
+            splices,
+            ..
+        } => Patch::SpliceText {
+            parent,
+            index,
+            splices: splices.into_owned(),
+        },
+        PatchOpRef::ReplaceChildren {
             target,
             children,
             is_svg,
         } => Patch::ReplaceChildren {
-            target: *target,
-            html: render_children_to_string(children, config),
-            is_svg: *is_svg,
+            target,
+            html: render_children_to_string(&children, config),
+            is_svg,
+        },
+        PatchOpRef::Remove { target } => Patch::Remove { target },
+        PatchOpRef::Insert { anchor, node, is_svg } => Patch::Insert {
+            anchor,
+            html: render_node_to_string(&node, config),
+            is_svg,
+        },
+        PatchOpRef::Move { target, to } => Patch::Move { target, to },
+        PatchOpRef::UpdateAttrs { target, changes } => Patch::UpdateAttrs {
+            target,
+            attrs: changes.into_owned(),
+        },
+        PatchOpRef::UpdateClasses { target, add, remove, .. } => Patch::UpdateClasses {
+            target,
+            add: add.into_owned(),
+            remove: remove.into_owned(),
+        },
+        PatchOpRef::UpdateStyles { target, set, remove, .. } => Patch::UpdateStyles {
+            target,
+            set: set.into_owned(),
+            remove: remove.into_owned(),
         },
-        PatchOp::Remove { target } => Patch::Remove { target: *target },
-        PatchOp::Insert { anchor, node } => Patch::Insert {
-            anchor: *anchor,
-            html: render_node_to_string(node, config),
+        PatchOpRef::UpdateExt { target, ext, data } => Patch::UpdateExt {
+            target,
+            family: ext.family_name().to_string(),
+            data: data.into_owned(),
         },
-        PatchOp::Move { target, to } => Patch::Move {
-            target: *target,
-            to: *to,
+        PatchOpRef::Rekey { target, element } => Patch::Rekey {
+            target,
+            ids: element_ids(&element),
         },
-        PatchOp::UpdateAttrs { target, changes } => Patch::UpdateAttrs {
-            target: *target,
-            attrs: changes.clone(),
+        PatchOpRef::UpdateDoc { target, change } => match change.into_owned() {
+            DocChange::Title { new, .. } => Patch::UpdateTitle { target, title: new },
+            DocChange::Meta { name, new, .. } => Patch::UpdateMeta { target, name, content: new },
+            DocChange::AddLink { rel, href } => Patch::AddLink { target, rel, href },
+            DocChange::RemoveLink { rel, href } => Patch::RemoveLink { target, rel, href },
         },
     }
 }
 
+/// StableIds of `elem` and its descendant elements, in document order.
+fn element_ids<P>(elem: &Element<P>) -> Vec<StableId>
+where
+    P: PhaseExt,
+    P::Ext: HasStableId,
+{
+    let mut ids = Vec::new();
+    let mut stack = vec![elem];
+    while let Some(elem) = stack.pop() {
+        ids.push(elem.ext.stable_id());
+        stack.extend(elem.children.iter().rev().filter_map(Node::as_element));
+    }
+    ids
+}
+
 fn render_element_to_string<P>(elem: &Element<P>, config: &RenderConfig) -> String
 where
     P: PhaseExt,
@@ -306,6 +471,234 @@ where
     output
 }
 
+// =============================================================================
+// Browser Runtime
+// =============================================================================
+
+/// Browser runtime that applies a JSON patch batch to the live DOM.
+///
+/// Embed it in a `<script>` tag (or serve it as a file). It defines
+/// `TolaPatch.apply(patches, { root, idAttr })`, where `patches` is the
+/// parsed output of [`patches_to_json`] and `idAttr` defaults to
+/// [`DEFAULT_ID_ATTR`].
+pub const PATCH_RUNTIME_JS: &str = include_str!("render/patch-runtime.js");
+
+/// Encode patches as the JSON batch understood by [`PATCH_RUNTIME_JS`].
+///
+/// Each patch is an object tagged by `op`; StableIds are encoded as their
+/// attribute value:
+///
+/// ```text
+/// [{"op":"update_text","target":"7b","text":"Hello"},
+///  {"op":"move","target":"7c","to":{"type":"after","id":"7d"}}]
+/// ```
+pub fn patches_to_json(patches: &[Patch]) -> String {
+    let mut output = String::from("[");
+    for (i, patch) in patches.iter().enumerate() {
+        if i > 0 {
+            output.push(',');
+        }
+        write_patch_json(patch, &mut output);
+    }
+    output.push(']');
+    output
+}
+
+/// Write one patch object of the JSON batch format.
+pub(crate) fn write_patch_json(patch: &Patch, output: &mut String) {
+    let (op, target) = match patch {
+        Patch::Replace { target, .. } => ("replace", Some(target)),
+        Patch::UpdateText { target, .. } => ("update_text", Some(target)),
+        Patch::UpdateTextAt { parent, .. } => ("update_text_at", Some(parent)),
+        Patch::RemoveTextAt { parent, .. } => ("remove_text_at", Some(parent)),
+        Patch::SpliceText { parent, .. } => ("splice_text", Some(parent)),
+        Patch::ReplaceChildren { target, .. } => ("replace_children", Some(target)),
+        Patch::Remove { target } => ("remove", Some(target)),
+        Patch::Insert { .. } => ("insert", None),
+        Patch::Move { target, .. } => ("move", Some(target)),
+        Patch::UpdateAttrs { target, .. } => ("update_attrs", Some(target)),
+        Patch::UpdateClasses { target, .. } => ("update_classes", Some(target)),
+        Patch::UpdateStyles { target, .. } => ("update_styles", Some(target)),
+        Patch::UpdateExt { target, .. } => ("update_ext", Some(target)),
+        Patch::Rekey { target, .. } => ("rekey", Some(target)),
+        Patch::UpdateTitle { target, .. } => ("update_title", Some(target)),
+        Patch::UpdateMeta { target, .. } => ("update_meta", Some(target)),
+        Patch::AddLink { target, .. } => ("add_link", Some(target)),
+        Patch::RemoveLink { target, .. } => ("remove_link", Some(target)),
+    };
+    output.push_str("{\"op\":\"");
+    output.push_str(op);
+    output.push('"');
+    if let Some(target) = target {
+        output.push_str(",\"target\":\"");
+        output.push_str(&target.to_attr_value());
+        output.push('"');
+    }
+
+    match patch {
+        Patch::Replace { html, is_svg, .. } => {
+            write_json_field("html", html, output);
+            write_svg_json(*is_svg, output);
+        }
+        Patch::UpdateText { text, .. } => write_json_field("text", text, output),
+        Patch::UpdateTextAt { index, text, .. } => {
+            output.push_str(&format!(",\"index\":{index}"));
+            write_json_field("text", text, output);
+        }
+        Patch::RemoveTextAt { index, .. } => output.push_str(&format!(",\"index\":{index}")),
+        Patch::SpliceText { index, splices, .. } => {
+            output.push_str(&format!(",\"index\":{index},\"splices\":["));
+            for (i, splice) in splices.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                output.push_str(&format!("[{},{},", splice.start, splice.delete));
+                write_json_str(&splice.insert, output);
+                output.push(']');
+            }
+            output.push(']');
+        }
+        Patch::ReplaceChildren { html, is_svg, .. } => {
+            write_json_field("html", html, output);
+            write_svg_json(*is_svg, output);
+        }
+        Patch::Remove { .. } => {}
+        Patch::Insert { anchor, html, is_svg } => {
+            write_anchor_json("anchor", anchor, output);
+            write_json_field("html", html, output);
+            write_svg_json(*is_svg, output);
+        }
+        Patch::Move { to, .. } => write_anchor_json("to", to, output),
+        Patch::UpdateAttrs { attrs, .. } => {
+            output.push_str(",\"attrs\":[");
+            for (i, (name, value)) in attrs.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                output.push('[');
+                write_json_str(name, output);
+                output.push(',');
+                match value {
+                    Some(value) => write_json_str(value, output),
+                    None => output.push_str("null"),
+                }
+                output.push(']');
+            }
+            output.push(']');
+        }
+        Patch::UpdateClasses { add, remove, .. } => {
+            write_json_strs("add", add, output);
+            write_json_strs("remove", remove, output);
+        }
+        Patch::UpdateStyles { set, remove, .. } => {
+            output.push_str(",\"set\":[");
+            for (i, (name, value)) in set.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                output.push('[');
+                write_json_str(name, output);
+                output.push(',');
+                write_json_str(value, output);
+                output.push(']');
+            }
+            output.push(']');
+            write_json_strs("remove", remove, output);
+        }
+        Patch::UpdateExt { family, data, .. } => {
+            write_json_field("family", family, output);
+            write_json_field("data", data, output);
+        }
+        Patch::Rekey { ids, .. } => {
+            output.push_str(",\"ids\":[");
+            for (i, id) in ids.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                write_json_str(&id.to_attr_value(), output);
+            }
+            output.push(']');
+        }
+        Patch::UpdateTitle { title, .. } => write_json_field("title", title, output),
+        Patch::UpdateMeta { name, content, .. } => {
+            write_json_field("name", name, output);
+            match content {
+                Some(content) => write_json_field("content", content, output),
+                None => output.push_str(",\"content\":null"),
+            }
+        }
+        Patch::AddLink { rel, href, .. } | Patch::RemoveLink { rel, href, .. } => {
+            write_json_field("rel", rel, output);
+            write_json_field("href", href, output);
+        }
+    }
+    output.push('}');
+}
+
+/// Write `,"svg":true` or `,"svg":false`.
+fn write_svg_json(is_svg: bool, output: &mut String) {
+    output.push_str(if is_svg { ",\"svg\":true" } else { ",\"svg\":false" });
+}
+
+/// Write `,"key":["a","b",…]`.
+fn write_json_strs(key: &str, values: &[impl AsRef<str>], output: &mut String) {
+    output.push_str(",\"");
+    output.push_str(key);
+    output.push_str("\":[");
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            output.push(',');
+        }
+        write_json_str(value.as_ref(), output);
+    }
+    output.push(']');
+}
+
+fn write_anchor_json(key: &str, anchor: &Anchor, output: &mut String) {
+    let kind = match anchor {
+        Anchor::After(_) => "after",
+        Anchor::Before(_) => "before",
+        Anchor::FirstChildOf(_) => "first_child_of",
+        Anchor::LastChildOf(_) => "last_child_of",
+        Anchor::ChildIndex(..) => "child_index",
+    };
+    output.push_str(",\"");
+    output.push_str(key);
+    output.push_str("\":{\"type\":\"");
+    output.push_str(kind);
+    output.push_str("\",\"id\":\"");
+    output.push_str(&anchor.target_id().to_attr_value());
+    output.push('"');
+    if let Anchor::ChildIndex(_, index) = anchor {
+        output.push_str(&format!(",\"index\":{index}"));
+    }
+    output.push('}');
+}
+
+fn write_json_field(key: &str, value: &str, output: &mut String) {
+    output.push_str(",\"");
+    output.push_str(key);
+    output.push_str("\":");
+    write_json_str(value, output);
+}
+
+/// Write `s` as a JSON string literal.
+pub(crate) fn write_json_str(s: &str, output: &mut String) {
+    output.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
 // =============================================================================
 // Tests
 // =============================================================================
@@ -313,6 +706,8 @@ where
 #[cfg(all(test, feature = "macros"))]
 mod tests {
     use super::*;
+    use crate::algo::tokens::parse_style;
+    use crate::algo::text::apply_splices;
     use crate::families::{HeadingFamily, LinkFamily, MediaFamily, SvgFamily};
     use crate::id::StableId;
     use crate::vdom;
@@ -376,4 +771,470 @@ mod tests {
         assert_eq!(escape_html("<script>"), "&lt;script&gt;");
         assert_eq!(escape_html("a & b"), "a &amp; b");
     }
+
+    #[test]
+    fn test_patches_to_json() {
+        let id = StableId::from_raw;
+        let patches = vec![
+            Patch::UpdateText { target: id(0x7b), text: "say \"hi\"\n".into() },
+            Patch::ReplaceChildren { target: id(1), html: "<b>x</b>".into(), is_svg: true },
+            Patch::Insert {
+                anchor: Anchor::FirstChildOf(id(2)),
+                html: "<i></i>".into(),
+                is_svg: false,
+            },
+            Patch::Move { target: id(3), to: Anchor::After(id(4)) },
+            Patch::UpdateAttrs {
+                target: id(5),
+                attrs: vec![("class".into(), Some("a".into())), ("title".into(), None)],
+            },
+            Patch::Remove { target: id(6) },
+        ];
+        assert_eq!(
+            patches_to_json(&patches),
+            concat!(
+                r#"[{"op":"update_text","target":"7b","text":"say \"hi\"\n"},"#,
+                r#"{"op":"replace_children","target":"1","html":"<b>x</b>","svg":true},"#,
+                r#"{"op":"insert","anchor":{"type":"first_child_of","id":"2"},"#,
+                r#""html":"<i></i>","svg":false},"#,
+                r#"{"op":"move","target":"3","to":{"type":"after","id":"4"}},"#,
+                r#"{"op":"update_attrs","target":"5","attrs":[["class","a"],["title",null]]},"#,
+                r#"{"op":"remove","target":"6"}]"#,
+            )
+        );
+    }
+
+    /// Only checks that the runtime has a `case` for each op and anchor name;
+    /// the behaviour is covered by the SimDom tests below.
+    #[test]
+    fn test_patch_runtime_names_every_encoded_op() {
+        let id = StableId::from_raw(1);
+        let patches = vec![
+            Patch::Replace { target: id, html: String::new(), is_svg: false },
+            Patch::UpdateText { target: id, text: String::new() },
+            Patch::ReplaceChildren { target: id, html: String::new(), is_svg: false },
+            Patch::Remove { target: id },
+            Patch::Insert { anchor: Anchor::After(id), html: String::new(), is_svg: false },
+            Patch::Insert { anchor: Anchor::Before(id), html: String::new(), is_svg: true },
+            Patch::Move { target: id, to: Anchor::FirstChildOf(id) },
+            Patch::Move { target: id, to: Anchor::LastChildOf(id) },
+            Patch::UpdateAttrs { target: id, attrs: Vec::new() },
+            Patch::UpdateClasses { target: id, add: Vec::new(), remove: Vec::new() },
+            Patch::UpdateStyles { target: id, set: Vec::new(), remove: Vec::new() },
+            Patch::UpdateExt { target: id, family: String::new(), data: String::new() },
+            Patch::Rekey { target: id, ids: vec![id] },
+            Patch::UpdateTextAt { parent: id, index: 0, text: String::new() },
+            Patch::RemoveTextAt { parent: id, index: 0 },
+            Patch::SpliceText { parent: id, index: 0, splices: Vec::new() },
+            Patch::Insert { anchor: Anchor::ChildIndex(id, 0), html: String::new(), is_svg: false },
+            Patch::UpdateTitle { target: id, title: String::new() },
+            Patch::UpdateMeta { target: id, name: String::new(), content: None },
+            Patch::AddLink { target: id, rel: String::new(), href: String::new() },
+            Patch::RemoveLink { target: id, rel: String::new(), href: String::new() },
+        ];
+        let json = patches_to_json(&patches);
+        for key in ["\"op\":\"", "\"type\":\""] {
+            for (start, _) in json.match_indices(key) {
+                let rest = &json[start + key.len()..];
+                let name = &rest[..rest.find('"').unwrap()];
+                assert!(
+                    PATCH_RUNTIME_JS.contains(&format!("case \"{name}\":")),
+                    "runtime does not handle {name}"
+                );
+            }
+        }
+    }
+
+    type Raw = RenderTestSite::Raw;
+
+    /// Rust mirror of `patch-runtime.js` over a parsed page: elements are
+    /// found by their id attribute and patch HTML is parsed in the same
+    /// (SVG or HTML) context the browser would use.
+    struct SimDom {
+        root: Element<Raw>,
+    }
+
+    impl SimDom {
+        fn parse(html: &str) -> Self {
+            Self { root: RenderTestSite::parse(html).root }
+        }
+
+        fn find(&self, id: StableId) -> Vec<usize> {
+            let value = id.to_attr_value();
+            let mut stack = vec![(&self.root, Vec::new())];
+            while let Some((elem, path)) = stack.pop() {
+                if elem.get_attr(DEFAULT_ID_ATTR) == Some(value.as_str()) {
+                    return path;
+                }
+                for (i, child) in elem.children.iter().enumerate() {
+                    if let Node::Element(child) = child {
+                        let mut child_path = path.clone();
+                        child_path.push(i);
+                        stack.push((child, child_path));
+                    }
+                }
+            }
+            panic!("element {id} not found");
+        }
+
+        fn at(&mut self, path: &[usize]) -> &mut Element<Raw> {
+            path.iter().fold(&mut self.root, |elem, &i| elem.children[i].as_element_mut().unwrap())
+        }
+
+        /// Whether children of the element at `path` are parsed as SVG.
+        fn in_svg(&self, path: &[usize]) -> bool {
+            let mut elem = &self.root;
+            let mut svg = elem.tag == "svg";
+            for &i in path {
+                elem = elem.children[i].as_element().unwrap();
+                svg = elem.tag == "svg" || (svg && elem.tag != "foreignObject");
+            }
+            svg
+        }
+
+        fn fragment(html: &str, svg: bool) -> Vec<Node<Raw>> {
+            let parser = RenderTestSite::parser();
+            if !svg {
+                return parser.parse_fragment(html).into_vec();
+            }
+            let mut wrapper = parser.parse_fragment(&format!("<svg>{html}</svg>"));
+            let Some(Node::Element(svg)) = wrapper.pop() else { unreachable!() };
+            svg.children.clone().into_vec()
+        }
+
+        fn place(&mut self, anchor: Anchor, nodes: Vec<Node<Raw>>) {
+            let path = self.find(anchor.target_id());
+            match anchor {
+                Anchor::After(_) | Anchor::Before(_) => {
+                    let (&i, parent) = path.split_last().unwrap();
+                    let i = if matches!(anchor, Anchor::After(_)) { i + 1 } else { i };
+                    self.at(parent).children.insert_many(i, nodes);
+                }
+                Anchor::FirstChildOf(_) => self.at(&path).children.insert_many(0, nodes),
+                Anchor::LastChildOf(_) => self.at(&path).children.extend(nodes),
+                Anchor::ChildIndex(_, i) => self.at(&path).children.insert_many(i, nodes),
+            }
+        }
+
+        fn apply(&mut self, patch: &Patch) {
+            match patch {
+                Patch::Replace { target, html, is_svg } => {
+                    let path = self.find(*target);
+                    let Some((&i, parent)) = path.split_last() else {
+                        let nodes = Self::fragment(html, false);
+                        self.root = nodes[0].as_element().unwrap().clone();
+                        return;
+                    };
+                    assert_eq!(*is_svg, self.in_svg(parent), "svg flag of {patch:?}");
+                    let nodes = Self::fragment(html, *is_svg);
+                    let parent = self.at(parent);
+                    parent.children.remove(i);
+                    parent.children.insert_many(i, nodes);
+                }
+                Patch::UpdateText { target, text } => {
+                    let path = self.find(*target);
+                    let elem = self.at(&path);
+                    elem.children.clear();
+                    if !text.is_empty() {
+                        elem.children.push(Node::Text(Text::new(text.as_str())));
+                    }
+                }
+                Patch::UpdateTextAt { parent, index, text } => {
+                    let path = self.find(*parent);
+                    let Node::Text(node) = &mut self.at(&path).children[*index] else {
+                        panic!("child {index} of {parent} is not text");
+                    };
+                    node.content = text.as_str().into();
+                }
+                Patch::SpliceText { parent, index, splices } => {
+                    let path = self.find(*parent);
+                    let Node::Text(node) = &mut self.at(&path).children[*index] else {
+                        panic!("child {index} of {parent} is not text");
+                    };
+                    node.content = apply_splices(&node.content, splices).expect("splice in bounds").into();
+                }
+                Patch::RemoveTextAt { parent, index } => {
+                    let path = self.find(*parent);
+                    let removed = self.at(&path).children.remove(*index);
+                    assert!(removed.is_text(), "child {index} of {parent} is not text");
+                }
+                Patch::ReplaceChildren { target, html, is_svg } => {
+                    let path = self.find(*target);
+                    let html_context = self.at(&path).tag == "foreignObject";
+                    let nodes = Self::fragment(html, (*is_svg || self.in_svg(&path)) && !html_context);
+                    self.at(&path).children = nodes.into_iter().collect();
+                }
+                Patch::Remove { target } => {
+                    let path = self.find(*target);
+                    let (&i, parent) = path.split_last().unwrap();
+                    self.at(parent).children.remove(i);
+                }
+                Patch::Insert { anchor, html, is_svg } => {
+                    let path = self.find(anchor.target_id());
+                    let context = match anchor {
+                        Anchor::After(_) | Anchor::Before(_) => &path[..path.len() - 1],
+                        _ => &path[..],
+                    };
+                    assert_eq!(*is_svg, self.in_svg(context), "svg flag of {patch:?}");
+                    let nodes = Self::fragment(html, *is_svg);
+                    self.place(*anchor, nodes);
+                }
+                Patch::Move { target, to } => {
+                    let path = self.find(*target);
+                    let (&i, parent) = path.split_last().unwrap();
+                    let node = self.at(parent).children.remove(i);
+                    self.place(*to, vec![node]);
+                }
+                Patch::UpdateAttrs { target, attrs } => {
+                    let path = self.find(*target);
+                    let elem = self.at(&path);
+                    for (name, value) in attrs {
+                        match value {
+                            Some(value) => elem.set_attr(name.clone(), value.clone()),
+                            None => {
+                                elem.remove_attr(name);
+                            }
+                        }
+                    }
+                }
+                Patch::UpdateClasses { target, add, remove } => {
+                    let path = self.find(*target);
+                    let elem = self.at(&path);
+                    let mut tokens: Vec<String> = elem
+                        .get_attr("class")
+                        .unwrap_or_default()
+                        .split_ascii_whitespace()
+                        .map(str::to_string)
+                        .collect();
+                    tokens.retain(|token| !remove.iter().any(|removed| removed == token));
+                    for token in add {
+                        if !tokens.iter().any(|t| t == token) {
+                            tokens.push(token.to_string());
+                        }
+                    }
+                    elem.set_attr("class", tokens.join(" "));
+                }
+                Patch::UpdateStyles { target, set, remove } => {
+                    let path = self.find(*target);
+                    let elem = self.at(&path);
+                    let style = elem.get_attr("style").unwrap_or_default().to_string();
+                    let mut decls: Vec<(String, String)> = parse_style(&style)
+                        .expect("a parsable style")
+                        .into_iter()
+                        .map(|(prop, value)| (prop.to_string(), value.to_string()))
+                        .collect();
+                    decls.retain(|(prop, _)| !remove.iter().any(|removed| removed == prop));
+                    for (prop, value) in set {
+                        match decls.iter_mut().find(|(p, _)| p == prop) {
+                            Some(decl) => decl.1 = value.to_string(),
+                            None => decls.push((prop.to_string(), value.to_string())),
+                        }
+                    }
+                    let decls: Vec<String> = decls.iter().map(|(p, v)| format!("{p}: {v}")).collect();
+                    elem.set_attr("style", decls.join("; "));
+                }
+                // Dispatches an event; the DOM itself is unchanged
+                Patch::UpdateExt { target, .. } => {
+                    self.find(*target);
+                }
+                Patch::Rekey { target, ids } => {
+                    let path = self.find(*target);
+                    let mut stack = vec![self.at(&path)];
+                    let mut ids = ids.iter();
+                    while let Some(elem) = stack.pop() {
+                        if elem.has_attr(DEFAULT_ID_ATTR) {
+                            let id = ids.next().expect("an id for every element");
+                            elem.set_attr(DEFAULT_ID_ATTR, id.to_attr_value());
+                        }
+                        stack.extend(elem.children.iter_mut().rev().filter_map(Node::as_element_mut));
+                    }
+                    assert!(ids.next().is_none(), "more ids than elements");
+                }
+                // Change `document`, outside the simulated tree
+                Patch::UpdateTitle { .. }
+                | Patch::UpdateMeta { .. }
+                | Patch::AddLink { .. }
+                | Patch::RemoveLink { .. } => {}
+            }
+        }
+
+        fn html(&self) -> String {
+            fn write(node: &Node<Raw>, output: &mut String) {
+                match node {
+                    Node::Element(elem) => {
+                        output.push('<');
+                        output.push_str(&elem.tag);
+                        // Attribute order does not matter in the DOM, nor does
+                        // class order or style formatting
+                        let mut attrs: Vec<_> = elem.attrs.iter().collect();
+                        attrs.sort();
+                        for (name, value) in attrs {
+                            let value = match name.as_str() {
+                                "class" => {
+                                    let mut tokens: Vec<&str> = value.split_ascii_whitespace().collect();
+                                    tokens.sort_unstable();
+                                    tokens.dedup();
+                                    tokens.join(" ")
+                                }
+                                "style" => match parse_style(value) {
+                                    Some(decls) => {
+                                        let decls: Vec<String> =
+                                            decls.iter().map(|(p, v)| format!("{p}: {v}")).collect();
+                                        decls.join("; ")
+                                    }
+                                    None => value.to_string(),
+                                },
+                                _ => value.to_string(),
+                            };
+                            output.push_str(&format!(" {name}=\"{}\"", escape_attr(&value)));
+                        }
+                        output.push('>');
+                        for child in &elem.children {
+                            write(child, output);
+                        }
+                        output.push_str("</");
+                        output.push_str(&elem.tag);
+                        output.push('>');
+                    }
+                    Node::Text(text) => output.push_str(&escape_html(&text.content)),
+                    Node::Comment(comment) => {
+                        output.push_str("<!--");
+                        output.push_str(&comment.content);
+                        output.push_str("-->");
+                    }
+                    _ => {}
+                }
+            }
+            let mut output = String::new();
+            write(&Node::Element(Box::new(self.root.clone())), &mut output);
+            output
+        }
+    }
+
+    #[test]
+    fn test_patch_runtime_agrees_with_simulated_dom() {
+        use crate::algo::{diff_with_config, invert, DiffConfig};
+        use crate::transform::Transform;
+
+        let cases = [
+            (
+                r#"<ul><li key="a">A</li><li key="b">B</li><li key="c">C</li></ul>"#,
+                r#"<ul><li key="c">C</li><li key="a">A</li><li key="d">D</li></ul>"#,
+            ),
+            (
+                r#"<div><p>Hello</p><p class="x">World</p></div>"#,
+                r#"<div><p>Hello!</p><p class="y" title="t">World</p></div>"#,
+            ),
+            (
+                "<div><section></section></div>",
+                "<div><section>text <b>bold</b><!-- note --></section></div>",
+            ),
+            ("<div>one <b>two</b> three</div>", "<div><b>two</b></div>"),
+            (
+                r#"<div><svg viewBox="0 0 1 1"><path d="M0"/></svg></div>"#,
+                r#"<div><svg viewBox="0 0 1 1"><path d="M1"/><circle r="1"/></svg></div>"#,
+            ),
+            ("<div><p>x</p></div>", "<div><h2>x</h2></div>"),
+            (
+                "<div><svg><foreignObject><div><p>a</p></div></foreignObject></svg></div>",
+                "<div><svg><foreignObject><div><p>a</p><p>b <i>c</i></p></div></foreignObject></svg></div>",
+            ),
+            (
+                r#"<div><svg><g><rect width="1"/><circle r="1"/></g><text>a</text></svg></div>"#,
+                r#"<div><svg><g><ellipse/><circle r="2"/><path d="M0"/></g><text>b</text></svg></div>"#,
+            ),
+            ("<div><p>a</p><!-- a --></div>", "<div><p>a</p><!-- b --></div>"),
+            ("<p>Hello <b>x</b> world</p>", "<p>Hello <b>x</b> there</p>"),
+            (
+                r#"<p>a <b key="1">1</b> b <i key="2">2</i> c</p>"#,
+                r#"<p><i key="2">2</i> c <b key="1">1</b> b <u>new</u> d</p>"#,
+            ),
+            (
+                r#"<p><b key="1">1</b> tail</p>"#,
+                r#"<p>head <b key="1">1</b></p>"#,
+            ),
+            (
+                r#"<div class="card active" style="color: red; margin: 0">x</div>"#,
+                r#"<div class="featured card" style="color: blue;top:1px">x</div>"#,
+            ),
+            (
+                r#"<p style="margin-top: 1px; margin: 0">x</p>"#,
+                r#"<p style="margin: 0; margin-top: 1px">x</p>"#,
+            ),
+            (
+                "<pre>fn main() {\n    println!(\"😀 one\");\n    println!(\"two\");\n    return;\n}</pre>",
+                "<pre>fn main() {\n    eprintln!(\"😀 one\");\n    println!(\"three\");\n    return;\n}</pre>",
+            ),
+            (
+                "<p>Lead-in <b>x</b> and then a long tail which keeps on going and going and going</p>",
+                "<p>Lead-in <b>x</b> and then a long tail which keeps on going and going and going!</p>",
+            ),
+        ];
+
+        // Cross-parent moves, diffed with and without reparent detection
+        let reparent_cases = [
+            (
+                "<main><section><h2>Title</h2><p>Body</p></section></main>",
+                "<main><div class=\"wrap\"><section><h2>Title</h2><p>Body</p></section></div></main>",
+            ),
+            (
+                "<div><ul><li>a</li><li>b</li></ul><ol><li>c</li></ol></div>",
+                "<div><ul><li>a</li></ul><ol><li>c</li><li>b</li></ol></div>",
+            ),
+            (
+                "<main><div>text <p>kept</p></div></main>",
+                "<main><p>kept</p></main>",
+            ),
+        ];
+
+        let index = |html: &str| RenderTestSite::indexer().transform(RenderTestSite::parse(html));
+        let configs = [DiffConfig::default(), DiffConfig::default().with_reparent_detection(true)];
+        for (old_html, new_html) in cases.into_iter().chain(reparent_cases) {
+            for config in &configs {
+                let (old, new) = (index(old_html), index(new_html));
+                let result = diff_with_config(&old, &new, config.clone());
+                assert!(!result.should_reload && !result.ops.is_empty(), "{old_html}");
+
+                let mut dom = SimDom::parse(&render_document(&old, &RenderConfig::DEV));
+                for patch in render_patches(&result.ops, &RenderConfig::DEV) {
+                    dom.apply(&patch);
+                }
+                let expected = SimDom::parse(&render_document(&new, &RenderConfig::DEV));
+                assert_eq!(dom.html(), expected.html(), "{old_html} -> {new_html}");
+
+                // Inverse patches take the page back
+                let undo = invert(&old, &result.ops).unwrap();
+                for patch in render_patches(&undo, &RenderConfig::DEV) {
+                    dom.apply(&patch);
+                }
+                let expected = SimDom::parse(&render_document(&old, &RenderConfig::DEV));
+                assert_eq!(dom.html(), expected.html(), "{old_html} -> {new_html} -> back");
+            }
+        }
+    }
+
+    #[test]
+    fn test_runtime_classes_and_styles_survive_patches() {
+        use crate::algo::diff;
+        use crate::transform::Transform;
+
+        let index = |html: &str| RenderTestSite::indexer().transform(RenderTestSite::parse(html));
+        let old = index(r#"<nav class="menu dark" style="color: red">x</nav>"#);
+        let new = index(r#"<nav class="menu light" style="color: blue">x</nav>"#);
+
+        let ops = diff(&old, &new).ops;
+        let mut dom = SimDom::parse(&render_document(&old, &RenderConfig::DEV));
+        let path = dom.find(ops[0].target());
+        // Page scripts opened the menu and positioned it
+        let nav = dom.at(&path);
+        nav.set_attr("class", "menu dark is-open");
+        nav.set_attr("style", "color: red; top: 10px");
+
+        for patch in render_patches(&ops, &RenderConfig::DEV) {
+            dom.apply(&patch);
+        }
+        let nav = dom.at(&path);
+        assert_eq!(nav.get_attr("class"), Some("menu is-open light"));
+        assert_eq!(nav.get_attr("style"), Some("color: blue; top: 10px"));
+    }
 }
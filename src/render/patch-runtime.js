@@ -0,0 +1,262 @@
+// tola-vdom patch runtime
+//
+// Applies patches produced by `tola_vdom::render::patches_to_json`, or whole
+// batches produced by `tola_vdom::wire::to_json`, to the live DOM. Elements
+// are located by their stable ID attribute (`data-tola-id` unless configured
+// otherwise).
+//
+//   TolaPatch.applyBatch(JSON.parse(message), { page: "9f2c..." });
+//   TolaPatch.apply(patches, { root: document, idAttr: "data-my-id" });
+//
+// The simulated DOM in the `render.rs` tests mirrors this file operation
+// by operation; change both together.
+(function (global) {
+  "use strict";
+
+  var WIRE_VERSION = 9;
+  var SVG_NS = "http://www.w3.org/2000/svg";
+  var DEFAULT_ID_ATTR = "data-tola-id";
+  var IMPORTANT = /\s*!\s*important\s*$/i;
+
+  function find(root, idAttr, id) {
+    var selector = "[" + idAttr + '="' + id + '"]';
+    if (root.nodeType === 1 && root.matches(selector)) {
+      return root;
+    }
+    var el = root.querySelector(selector);
+    if (!el) {
+      throw new Error("tola-vdom: element " + id + " not found");
+    }
+    return el;
+  }
+
+  // Parse `html` as children of `context`. Inside SVG the markup must be
+  // parsed in the SVG namespace, which a <template> would not do; the
+  // children of a <foreignObject> are HTML again.
+  function fragment(html, context, isSvg) {
+    var doc = context.ownerDocument || document;
+    var frag = doc.createDocumentFragment();
+    var htmlContext = context.localName === "foreignObject";
+    if ((isSvg || context.namespaceURI === SVG_NS) && !htmlContext) {
+      var svg = doc.createElementNS(SVG_NS, "svg");
+      svg.innerHTML = html;
+      while (svg.firstChild) {
+        frag.appendChild(svg.firstChild);
+      }
+      return frag;
+    }
+    var template = doc.createElement("template");
+    template.innerHTML = html;
+    frag.appendChild(template.content);
+    return frag;
+  }
+
+  function textAt(parent, index) {
+    var node = parent.childNodes[index];
+    if (!node || node.nodeType !== 3) {
+      throw new Error("tola-vdom: child " + index + " is not a text node");
+    }
+    return node;
+  }
+
+  // The head <meta> named `name`, matched by `name` or `property`.
+  function headMeta(doc, name) {
+    var metas = doc.head.getElementsByTagName("meta");
+    for (var i = 0; i < metas.length; i++) {
+      if (metas[i].getAttribute("name") === name || metas[i].getAttribute("property") === name) {
+        return metas[i];
+      }
+    }
+    return null;
+  }
+
+  // The element new content is parsed in, for an anchor resolved to `ref`.
+  function anchorContext(ref, anchor) {
+    return anchor.type === "after" || anchor.type === "before" ? ref.parentNode : ref;
+  }
+
+  function place(ref, anchor, node) {
+    switch (anchor.type) {
+      case "after":
+        ref.parentNode.insertBefore(node, ref.nextSibling);
+        break;
+      case "before":
+        ref.parentNode.insertBefore(node, ref);
+        break;
+      case "first_child_of":
+        ref.insertBefore(node, ref.firstChild);
+        break;
+      case "last_child_of":
+        ref.appendChild(node);
+        break;
+      case "child_index":
+        ref.insertBefore(node, ref.childNodes[anchor.index] || null);
+        break;
+      default:
+        throw new Error("tola-vdom: unknown anchor " + anchor.type);
+    }
+  }
+
+  function applyPatch(patch, root, idAttr) {
+    var el, ref, nodes, doc;
+    switch (patch.op) {
+      case "replace":
+        el = find(root, idAttr, patch.target);
+        el.replaceWith(fragment(patch.html, el.parentNode, patch.svg));
+        break;
+      case "update_text":
+        find(root, idAttr, patch.target).textContent = patch.text;
+        break;
+      case "update_text_at":
+        textAt(find(root, idAttr, patch.target), patch.index).data = patch.text;
+        break;
+      case "splice_text":
+        // Offsets refer to the old text, so apply the last splice first
+        ref = textAt(find(root, idAttr, patch.target), patch.index);
+        patch.splices.slice().reverse().forEach(function (splice) {
+          ref.replaceData(splice[0], splice[1], splice[2]);
+        });
+        break;
+      case "remove_text_at":
+        textAt(find(root, idAttr, patch.target), patch.index).remove();
+        break;
+      case "replace_children":
+        el = find(root, idAttr, patch.target);
+        el.replaceChildren(fragment(patch.html, el, patch.svg));
+        break;
+      case "remove":
+        find(root, idAttr, patch.target).remove();
+        break;
+      case "insert":
+        ref = find(root, idAttr, patch.anchor.id);
+        place(ref, patch.anchor, fragment(patch.html, anchorContext(ref, patch.anchor), patch.svg));
+        break;
+      case "move":
+        // Detach first: `child_index` counts children without the moved node
+        el = find(root, idAttr, patch.target);
+        el.remove();
+        place(find(root, idAttr, patch.to.id), patch.to, el);
+        break;
+      case "update_attrs":
+        el = find(root, idAttr, patch.target);
+        patch.attrs.forEach(function (attr) {
+          if (attr[1] === null) {
+            el.removeAttribute(attr[0]);
+          } else {
+            el.setAttribute(attr[0], attr[1]);
+          }
+        });
+        break;
+      case "update_classes":
+        // Token by token, so classes added by page scripts survive
+        el = find(root, idAttr, patch.target);
+        patch.remove.forEach(function (token) {
+          el.classList.remove(token);
+        });
+        patch.add.forEach(function (token) {
+          el.classList.add(token);
+        });
+        break;
+      case "update_styles":
+        el = find(root, idAttr, patch.target);
+        patch.remove.forEach(function (name) {
+          el.style.removeProperty(name);
+        });
+        patch.set.forEach(function (decl) {
+          var important = IMPORTANT.test(decl[1]);
+          el.style.setProperty(decl[0], decl[1].replace(IMPORTANT, ""), important ? "important" : "");
+        });
+        break;
+      case "update_ext":
+        // Family data is not markup: let page scripts react to it
+        find(root, idAttr, patch.target).dispatchEvent(
+          new CustomEvent("tola:update-ext", {
+            bubbles: true,
+            detail: { family: patch.family, data: patch.data },
+          })
+        );
+        break;
+      case "rekey":
+        // The element and its descendants, in document order
+        el = find(root, idAttr, patch.target);
+        nodes = [el].concat(Array.prototype.slice.call(el.querySelectorAll("[" + idAttr + "]")));
+        if (nodes.length !== patch.ids.length) {
+          throw new Error("tola-vdom: rekey of " + patch.target + " has the wrong id count");
+        }
+        nodes.forEach(function (node, i) {
+          node.setAttribute(idAttr, patch.ids[i]);
+        });
+        break;
+      // Document-level patches; `target` is the root element
+      case "update_title":
+        (root.ownerDocument || root).title = patch.title;
+        break;
+      case "update_meta":
+        doc = root.ownerDocument || root;
+        el = headMeta(doc, patch.name);
+        if (patch.content === null) {
+          if (el) {
+            el.parentNode.removeChild(el);
+          }
+          break;
+        }
+        if (!el) {
+          // Open Graph style names ("og:title") go in `property`
+          el = doc.createElement("meta");
+          el.setAttribute(patch.name.indexOf(":") >= 0 ? "property" : "name", patch.name);
+          doc.head.appendChild(el);
+        }
+        el.setAttribute("content", patch.content);
+        break;
+      case "add_link":
+        doc = root.ownerDocument || root;
+        el = doc.createElement("link");
+        el.setAttribute("rel", patch.rel);
+        el.setAttribute("href", patch.href);
+        doc.head.appendChild(el);
+        break;
+      case "remove_link":
+        nodes = (root.ownerDocument || root).head.getElementsByTagName("link");
+        Array.prototype.slice.call(nodes).forEach(function (link) {
+          if (link.getAttribute("rel") === patch.rel && link.getAttribute("href") === patch.href) {
+            link.parentNode.removeChild(link);
+          }
+        });
+        break;
+      default:
+        throw new Error("tola-vdom: unknown patch op " + patch.op);
+    }
+  }
+
+  // Apply a batch in order. Options: `root` (default `document`) and
+  // `idAttr` (default "data-tola-id").
+  function apply(patches, options) {
+    options = options || {};
+    var root = options.root || document;
+    var idAttr = options.idAttr || DEFAULT_ID_ATTR;
+    for (var i = 0; i < patches.length; i++) {
+      applyPatch(patches[i], root, idAttr);
+    }
+  }
+
+  // Apply a wire batch. Batches for another page (when `options.page` is
+  // set) are ignored; reload batches reload the page. Returns whether the
+  // batch was for this page.
+  function applyBatch(batch, options) {
+    options = options || {};
+    if (batch.v !== WIRE_VERSION) {
+      throw new Error("tola-vdom: unsupported wire version " + batch.v);
+    }
+    if (options.page !== undefined && batch.page !== options.page) {
+      return false;
+    }
+    if (batch.reload !== null) {
+      global.location.reload();
+    } else {
+      apply(batch.patches, options);
+    }
+    return true;
+  }
+
+  global.TolaPatch = { apply: apply, applyBatch: applyBatch, applyPatch: applyPatch };
+})(typeof globalThis !== "undefined" ? globalThis : window);
@@ -0,0 +1,704 @@
+//! CSS selector queries over documents.
+//!
+//! Supports a practical subset of CSS selectors:
+//!
+//! - Type, universal, `#id` and `.class` selectors
+//! - Attribute selectors: `[a]`, `[a=v]`, `[a~=v]`, `[a|=v]`, `[a^=v]`,
+//!   `[a$=v]`, `[a*=v]`, with an optional `i` flag
+//! - Combinators: descendant (` `), child (`>`), next sibling (`+`),
+//!   subsequent sibling (`~`)
+//! - Pseudo-classes: `:first-child`, `:last-child`, `:only-child`,
+//!   `:nth-child(an+b)`, `:nth-last-child(an+b)`, `:empty`, `:root`, `:not(...)`
+//! - `:family(name)` to match elements of a family (e.g. `:family(link)`)
+//! - Selector lists (`a, b`)
+//!
+//! ```ignore
+//! let links = doc.select("nav > a.external[href^='https']")?;
+//! let title = doc.select_first("h1:family(heading)")?;
+//! doc.select_mut("img:not([alt])", |img| img.set_attr("alt", ""))?;
+//! ```
+
+mod parser;
+
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::core::PhaseExt;
+use crate::node::{Document, Element, Node};
+
+// =============================================================================
+// Error
+// =============================================================================
+
+/// Error from parsing a selector.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SelectorError {
+    /// Selector ended where more input was expected
+    #[error("unexpected end of selector")]
+    UnexpectedEnd,
+
+    /// Character not valid at this position
+    #[error("unexpected '{found}' at position {position}")]
+    UnexpectedChar {
+        /// The offending character
+        found: char,
+        /// Byte offset in the selector
+        position: usize,
+    },
+
+    /// Pseudo-class not supported by this engine
+    #[error("unknown pseudo-class ':{name}' at position {position}")]
+    UnknownPseudoClass {
+        /// Pseudo-class name (lowercased)
+        name: String,
+        /// Byte offset in the selector
+        position: usize,
+    },
+
+    /// Malformed `:nth-child()` argument
+    #[error("invalid nth expression '{expr}' at position {position}")]
+    InvalidNth {
+        /// The argument as written
+        expr: String,
+        /// Byte offset in the selector
+        position: usize,
+    },
+}
+
+// =============================================================================
+// Selector AST
+// =============================================================================
+
+/// A parsed selector list.
+///
+/// Parse once with [`Selector::parse`] and reuse it across documents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    list: Vec<Complex>,
+}
+
+/// Compound selectors joined by combinators, e.g. `nav > a.external`.
+#[derive(Debug, Clone, PartialEq)]
+struct Complex {
+    /// Left to right
+    compounds: Vec<Compound>,
+    /// `combinators[i]` sits between `compounds[i]` and `compounds[i + 1]`
+    combinators: Vec<Combinator>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    /// `a b`
+    Descendant,
+    /// `a > b`
+    Child,
+    /// `a + b`
+    NextSibling,
+    /// `a ~ b`
+    SubsequentSibling,
+}
+
+/// Simple selectors that must all match one element, e.g. `a.external[href]`.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Compound {
+    tag: Option<String>,
+    ids: Vec<String>,
+    classes: Vec<String>,
+    attrs: Vec<AttrSelector>,
+    pseudos: Vec<Pseudo>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AttrSelector {
+    name: String,
+    op: AttrOp,
+    value: String,
+    case_insensitive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttrOp {
+    /// `[a]`
+    Exists,
+    /// `[a=v]`
+    Equals,
+    /// `[a~=v]` (whitespace-separated word)
+    Includes,
+    /// `[a|=v]` (`v` or `v-...`)
+    DashMatch,
+    /// `[a^=v]`
+    Prefix,
+    /// `[a$=v]`
+    Suffix,
+    /// `[a*=v]`
+    Substring,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Pseudo {
+    NthChild(Nth),
+    NthLastChild(Nth),
+    OnlyChild,
+    Empty,
+    Root,
+    Family(String),
+    Not(Vec<Complex>),
+}
+
+/// `an+b` (1-based positions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Nth {
+    a: i64,
+    b: i64,
+}
+
+impl Nth {
+    fn matches(self, position: usize) -> bool {
+        let diff = position as i64 - self.b;
+        match self.a {
+            0 => diff == 0,
+            a => diff % a == 0 && diff / a >= 0,
+        }
+    }
+}
+
+// =============================================================================
+// Selector API
+// =============================================================================
+
+impl Selector {
+    /// Parse a selector list.
+    pub fn parse(selector: &str) -> Result<Self, SelectorError> {
+        let list = parser::Parser::new(selector).parse_list()?;
+        Ok(Self { list })
+    }
+
+    /// All matching elements in document order.
+    pub fn select<'a, P: PhaseExt>(&self, doc: &'a Document<P>) -> Vec<&'a Element<P>> {
+        let mut out = Vec::new();
+        self.walk(&doc.root, &mut |elem, _| {
+            out.push(elem);
+            true
+        });
+        out
+    }
+
+    /// First matching element in document order.
+    pub fn select_first<'a, P: PhaseExt>(&self, doc: &'a Document<P>) -> Option<&'a Element<P>> {
+        let mut found = None;
+        self.walk(&doc.root, &mut |elem, _| {
+            found = Some(elem);
+            false
+        });
+        found
+    }
+
+    /// Call `f` on every matching element, returning the number of matches.
+    ///
+    /// Matches are visited in reverse document order, so `f` may freely
+    /// restructure the subtree of the element it receives without affecting
+    /// the remaining matches.
+    pub fn select_mut<P, F>(&self, doc: &mut Document<P>, mut f: F) -> usize
+    where
+        P: PhaseExt,
+        F: FnMut(&mut Element<P>),
+    {
+        // Collect child-index paths first, then resolve them mutably
+        let mut paths: Vec<Vec<usize>> = Vec::new();
+        self.walk(&doc.root, &mut |_, path| {
+            paths.push(path.indices[1..].to_vec());
+            true
+        });
+
+        for indices in paths.iter().rev() {
+            // Cached hashes along the path go stale once `f` edits the match
+            let mut elem = &mut doc.root;
+            elem.invalidate_hash();
+            for &idx in indices {
+                elem = match &mut elem.children[idx] {
+                    Node::Element(child) => child,
+                    _ => unreachable!("selector paths only point at elements"),
+                };
+                elem.invalidate_hash();
+            }
+            f(elem);
+        }
+        paths.len()
+    }
+
+    /// DFS from `root`, calling `on_match` for each match until it returns
+    /// `false`. Iterative, so deep documents don't overflow the stack.
+    fn walk<'a, P: PhaseExt>(
+        &self,
+        root: &'a Element<P>,
+        on_match: &mut dyn FnMut(&'a Element<P>, &Path<'a, P>) -> bool,
+    ) {
+        let mut path = Path::default();
+        // Position of the next child to visit at each level of `path`
+        let mut next: Vec<usize> = Vec::new();
+        let mut pending = Some((root, 0));
+
+        loop {
+            if let Some((elem, index)) = pending.take() {
+                path.ancestors.push(elem);
+                path.indices.push(index);
+                next.push(0);
+
+                let depth = path.ancestors.len() - 1;
+                if self
+                    .list
+                    .iter()
+                    .any(|c| path.matches_complex(c, c.compounds.len() - 1, depth, index, elem))
+                    && !on_match(elem, &path)
+                {
+                    return;
+                }
+            }
+
+            let (Some(parent), Some(cursor)) = (path.ancestors.last(), next.last_mut()) else {
+                return;
+            };
+            match parent.children[*cursor..].iter().position(Node::is_element) {
+                Some(offset) => {
+                    let index = *cursor + offset;
+                    *cursor = index + 1;
+                    pending = parent.children[index].as_element().map(|child| (child, index));
+                }
+                None => {
+                    path.ancestors.pop();
+                    path.indices.pop();
+                    next.pop();
+                }
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Selector {
+    type Err = SelectorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, complex) in self.list.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", complex)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, compound) in self.compounds.iter().enumerate() {
+            if i > 0 {
+                f.write_str(match self.combinators[i - 1] {
+                    Combinator::Descendant => " ",
+                    Combinator::Child => " > ",
+                    Combinator::NextSibling => " + ",
+                    Combinator::SubsequentSibling => " ~ ",
+                })?;
+            }
+            write!(f, "{}", compound)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Compound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tag {
+            Some(tag) => f.write_str(tag)?,
+            None if self.ids.is_empty()
+                && self.classes.is_empty()
+                && self.attrs.is_empty()
+                && self.pseudos.is_empty() =>
+            {
+                f.write_str("*")?
+            }
+            None => {}
+        }
+        for id in &self.ids {
+            write!(f, "#{}", id)?;
+        }
+        for class in &self.classes {
+            write!(f, ".{}", class)?;
+        }
+        for attr in &self.attrs {
+            let op = match attr.op {
+                AttrOp::Exists => {
+                    write!(f, "[{}]", attr.name)?;
+                    continue;
+                }
+                AttrOp::Equals => "=",
+                AttrOp::Includes => "~=",
+                AttrOp::DashMatch => "|=",
+                AttrOp::Prefix => "^=",
+                AttrOp::Suffix => "$=",
+                AttrOp::Substring => "*=",
+            };
+            let flag = if attr.case_insensitive { " i" } else { "" };
+            write!(f, "[{}{}{:?}{}]", attr.name, op, attr.value, flag)?;
+        }
+        for pseudo in &self.pseudos {
+            match pseudo {
+                Pseudo::NthChild(Nth { a: 0, b: 1 }) => f.write_str(":first-child")?,
+                Pseudo::NthLastChild(Nth { a: 0, b: 1 }) => f.write_str(":last-child")?,
+                Pseudo::NthChild(nth) => write!(f, ":nth-child({}n{:+})", nth.a, nth.b)?,
+                Pseudo::NthLastChild(nth) => write!(f, ":nth-last-child({}n{:+})", nth.a, nth.b)?,
+                Pseudo::OnlyChild => f.write_str(":only-child")?,
+                Pseudo::Empty => f.write_str(":empty")?,
+                Pseudo::Root => f.write_str(":root")?,
+                Pseudo::Family(name) => write!(f, ":family({})", name)?,
+                Pseudo::Not(list) => {
+                    f.write_str(":not(")?;
+                    for (i, complex) in list.iter().enumerate() {
+                        if i > 0 {
+                            f.write_str(", ")?;
+                        }
+                        write!(f, "{}", complex)?;
+                    }
+                    f.write_str(")")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Document API
+// =============================================================================
+
+impl<P: PhaseExt> Document<P> {
+    /// Find all elements matching a CSS selector, in document order.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let links = doc.select("nav > a.external[href^='https']")?;
+    /// ```
+    pub fn select(&self, selector: &str) -> Result<Vec<&Element<P>>, SelectorError> {
+        Ok(Selector::parse(selector)?.select(self))
+    }
+
+    /// Find the first element matching a CSS selector.
+    pub fn select_first(&self, selector: &str) -> Result<Option<&Element<P>>, SelectorError> {
+        Ok(Selector::parse(selector)?.select_first(self))
+    }
+
+    /// Modify all elements matching a CSS selector, returning the match count.
+    ///
+    /// See [`Selector::select_mut`] for the visiting order.
+    pub fn select_mut<F>(&mut self, selector: &str, f: F) -> Result<usize, SelectorError>
+    where
+        F: FnMut(&mut Element<P>),
+    {
+        Ok(Selector::parse(selector)?.select_mut(self, f))
+    }
+}
+
+// =============================================================================
+// Matching
+// =============================================================================
+
+/// Root-to-current chain of the element being matched.
+struct Path<'a, P: PhaseExt> {
+    ancestors: Vec<&'a Element<P>>,
+    /// `indices[d]` is the index of `ancestors[d]` in its parent's children
+    indices: Vec<usize>,
+}
+
+impl<P: PhaseExt> Default for Path<'_, P> {
+    fn default() -> Self {
+        Self {
+            ancestors: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+}
+
+impl<'a, P: PhaseExt> Path<'a, P> {
+    /// Match `complex.compounds[..=k]` with `compounds[k]` against `elem`.
+    ///
+    /// `elem` is a child of `ancestors[depth - 1]` at child index `index`
+    /// (the root when `depth == 0`). It is `ancestors[depth]` itself unless
+    /// reached through a sibling combinator.
+    fn matches_complex(
+        &self,
+        complex: &Complex,
+        k: usize,
+        depth: usize,
+        index: usize,
+        elem: &'a Element<P>,
+    ) -> bool {
+        if !self.matches_compound(&complex.compounds[k], depth, index, elem) {
+            return false;
+        }
+        if k == 0 {
+            return true;
+        }
+
+        match complex.combinators[k - 1] {
+            Combinator::Child => {
+                depth > 0
+                    && self.matches_complex(
+                        complex,
+                        k - 1,
+                        depth - 1,
+                        self.indices[depth - 1],
+                        self.ancestors[depth - 1],
+                    )
+            }
+            Combinator::Descendant => (0..depth).rev().any(|d| {
+                self.matches_complex(complex, k - 1, d, self.indices[d], self.ancestors[d])
+            }),
+            Combinator::NextSibling => self
+                .preceding_siblings(depth, index)
+                .next()
+                .is_some_and(|(i, sib)| self.matches_complex(complex, k - 1, depth, i, sib)),
+            Combinator::SubsequentSibling => self
+                .preceding_siblings(depth, index)
+                .any(|(i, sib)| self.matches_complex(complex, k - 1, depth, i, sib)),
+        }
+    }
+
+    fn matches_compound(&self, compound: &Compound, depth: usize, index: usize, elem: &Element<P>) -> bool {
+        if let Some(tag) = &compound.tag
+            && !elem.tag.eq_ignore_ascii_case(tag)
+        {
+            return false;
+        }
+        if !compound.ids.iter().all(|id| elem.id() == Some(id.as_str())) {
+            return false;
+        }
+        if !compound.classes.iter().all(|class| elem.has_class(class)) {
+            return false;
+        }
+        if !compound.attrs.iter().all(|attr| attr.matches(elem)) {
+            return false;
+        }
+        compound
+            .pseudos
+            .iter()
+            .all(|pseudo| self.matches_pseudo(pseudo, depth, index, elem))
+    }
+
+    fn matches_pseudo(&self, pseudo: &Pseudo, depth: usize, index: usize, elem: &'a Element<P>) -> bool {
+        match pseudo {
+            Pseudo::NthChild(nth) => nth.matches(self.preceding_siblings(depth, index).count() + 1),
+            Pseudo::NthLastChild(nth) => nth.matches(self.following_siblings(depth, index).count() + 1),
+            Pseudo::OnlyChild => {
+                self.preceding_siblings(depth, index).next().is_none()
+                    && self.following_siblings(depth, index).next().is_none()
+            }
+            Pseudo::Empty => elem.children.iter().all(|child| match child {
+                Node::Element(_) => false,
+                Node::Text(text) => text.is_empty(),
+                _ => true,
+            }),
+            Pseudo::Root => depth == 0,
+            Pseudo::Family(name) => elem.family_name() == name,
+            Pseudo::Not(list) => !list
+                .iter()
+                .any(|c| self.matches_complex(c, c.compounds.len() - 1, depth, index, elem)),
+        }
+    }
+
+    /// Element siblings before child `index` of `ancestors[depth - 1]`, nearest first.
+    fn preceding_siblings(&self, depth: usize, index: usize) -> impl Iterator<Item = (usize, &'a Element<P>)> {
+        let siblings: &'a [Node<P>] = match depth {
+            0 => &[],
+            _ => &self.ancestors[depth - 1].children[..index],
+        };
+        siblings
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(i, node)| node.as_element().map(|e| (i, e)))
+    }
+
+    /// Element siblings after child `index` of `ancestors[depth - 1]`.
+    fn following_siblings(&self, depth: usize, index: usize) -> impl Iterator<Item = &'a Element<P>> {
+        let siblings: &'a [Node<P>] = match depth {
+            0 => &[],
+            _ => &self.ancestors[depth - 1].children[index + 1..],
+        };
+        siblings.iter().filter_map(Node::as_element)
+    }
+}
+
+impl AttrSelector {
+    fn matches<P: PhaseExt>(&self, elem: &Element<P>) -> bool {
+        let Some(actual) = elem
+            .attrs
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(&self.name))
+            .map(|(_, v)| v.as_str())
+        else {
+            return false;
+        };
+
+        let (actual, expected) = if self.case_insensitive {
+            (actual.to_lowercase(), self.value.to_lowercase())
+        } else {
+            (actual.to_string(), self.value.clone())
+        };
+
+        match self.op {
+            AttrOp::Exists => true,
+            AttrOp::Equals => actual == expected,
+            AttrOp::Includes => {
+                !expected.is_empty() && actual.split_ascii_whitespace().any(|word| word == expected)
+            }
+            AttrOp::DashMatch => {
+                actual == expected
+                    || actual
+                        .strip_prefix(expected.as_str())
+                        .is_some_and(|rest| rest.starts_with('-'))
+            }
+            AttrOp::Prefix => !expected.is_empty() && actual.starts_with(&expected),
+            AttrOp::Suffix => !expected.is_empty() && actual.ends_with(&expected),
+            AttrOp::Substring => !expected.is_empty() && actual.contains(&expected),
+        }
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+    use crate::families::{HeadingFamily, LinkFamily};
+    use crate::vdom;
+
+    #[vdom::families]
+    pub struct SelectSite {
+        link: LinkFamily,
+        heading: HeadingFamily,
+    }
+
+    fn doc() -> Document<SelectSite::Raw> {
+        SelectSite::parse(
+            r#"<html><body>
+                <nav id="main">
+                    <a class="external nav" href="https://example.com">one</a>
+                    <a class="nav" href="/local">two</a>
+                    <span><a class="external" href="http://example.org">three</a></span>
+                </nav>
+                <h1 lang="en-US">Title</h1>
+                <p>first</p><p>second</p><p>third</p>
+                <div></div>
+            </body></html>"#,
+        )
+    }
+
+    fn texts(elems: Vec<&Element<SelectSite::Raw>>) -> Vec<String> {
+        elems.iter().map(|e| e.text_content()).collect()
+    }
+
+    #[test]
+    fn test_select_type_class_attr() {
+        let doc = doc();
+        assert_eq!(texts(doc.select("nav > a.external[href^='https']").unwrap()), ["one"]);
+        assert_eq!(texts(doc.select("nav a.external").unwrap()), ["one", "three"]);
+        assert_eq!(texts(doc.select("a[href$=local]").unwrap()), ["two"]);
+        assert_eq!(texts(doc.select("a[href*=\"example\"]").unwrap()), ["one", "three"]);
+        assert_eq!(texts(doc.select("[class~=nav]").unwrap()), ["one", "two"]);
+        assert_eq!(texts(doc.select("[lang|=en]").unwrap()), ["Title"]);
+        assert_eq!(texts(doc.select("A[HREF^=HTTPS i]").unwrap()), ["one"]);
+        assert_eq!(doc.select("#main").unwrap()[0].tag, "nav");
+    }
+
+    #[test]
+    fn test_select_camel_case_attr() {
+        let doc = SelectSite::parse(r#"<div><svg viewBox="0 0 8 8"><path/></svg></div>"#);
+        assert_eq!(doc.select("svg[viewBox]").unwrap().len(), 1);
+        assert_eq!(doc.select("svg[viewbox^='0 0']").unwrap().len(), 1);
+        assert_eq!(Selector::parse("svg[viewBox]").unwrap().to_string(), "svg[viewBox]");
+    }
+
+    #[test]
+    fn test_select_combinators() {
+        let doc = doc();
+        assert_eq!(texts(doc.select("h1 + p").unwrap()), ["first"]);
+        assert_eq!(texts(doc.select("h1 ~ p").unwrap()), ["first", "second", "third"]);
+        assert_eq!(texts(doc.select("nav > span > a").unwrap()), ["three"]);
+        assert!(doc.select("body > a").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_select_nth_child() {
+        let doc = doc();
+        assert_eq!(texts(doc.select("p:nth-child(odd)").unwrap()), ["first", "third"]);
+        assert_eq!(texts(doc.select("p:nth-child(2n+3)").unwrap()), ["first", "third"]);
+        assert_eq!(texts(doc.select("p:nth-last-child(-n+2)").unwrap()), ["third"]);
+        assert_eq!(texts(doc.select("nav > :first-child").unwrap()), ["one"]);
+        assert_eq!(texts(doc.select("span > a:only-child").unwrap()), ["three"]);
+        assert_eq!(doc.select("div:empty:last-child").unwrap().len(), 1);
+        assert_eq!(doc.select(":root").unwrap()[0].tag, "html");
+    }
+
+    #[test]
+    fn test_select_family() {
+        let doc = doc();
+        assert_eq!(doc.select(":family(link)").unwrap().len(), 3);
+        assert_eq!(texts(doc.select("body > :family(heading)").unwrap()), ["Title"]);
+        assert_eq!(texts(doc.select("a:not(.external), h1").unwrap()), ["two", "Title"]);
+    }
+
+    #[test]
+    fn test_select_first_and_mut() {
+        let mut doc = doc();
+        assert_eq!(doc.select_first("a").unwrap().unwrap().text_content(), "one");
+        assert!(doc.select_first("table").unwrap().is_none());
+
+        let count = doc
+            .select_mut("a.external", |a| a.set_attr("rel", "noopener"))
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(doc.select("a[rel=noopener]").unwrap().len(), 2);
+
+        // Restructuring a match does not disturb the others
+        let count = doc.select_mut("nav, span", |e| e.children.clear()).unwrap();
+        assert_eq!(count, 2);
+        assert!(doc.select("a").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_selector_errors() {
+        assert_eq!(Selector::parse(""), Err(SelectorError::UnexpectedEnd));
+        assert_eq!(Selector::parse("a >"), Err(SelectorError::UnexpectedEnd));
+        assert!(matches!(
+            Selector::parse("a:hover"),
+            Err(SelectorError::UnknownPseudoClass { ref name, position: 2 }) if name == "hover"
+        ));
+        assert!(matches!(
+            Selector::parse("li:nth-child(x)"),
+            Err(SelectorError::InvalidNth { .. })
+        ));
+        assert!(matches!(
+            Selector::parse("a[href"),
+            Err(SelectorError::UnexpectedEnd)
+        ));
+        assert!(matches!(
+            Selector::parse("a)"),
+            Err(SelectorError::UnexpectedChar { found: ')', position: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_selector_display() {
+        let selector = Selector::parse("nav>a.x[href^='h'] , p:first-child").unwrap();
+        assert_eq!(selector.to_string(), r#"nav > a.x[href^="h"], p:first-child"#);
+    }
+}
@@ -0,0 +1,363 @@
+//! Selector parser.
+//!
+//! Grammar (subset of Selectors Level 4):
+//!
+//! ```text
+//! list     := complex ( ',' complex )*
+//! complex  := compound ( combinator compound )*
+//! compound := ( type | '*' )? ( '#' id | '.' class | '[' attr ']' | ':' pseudo )*
+//! ```
+
+use super::{AttrOp, AttrSelector, Combinator, Complex, Compound, Nth, Pseudo, SelectorError};
+
+pub(super) struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub(super) fn new(src: &'a str) -> Self {
+        Self { src, pos: 0 }
+    }
+
+    /// Parse a comma-separated selector list.
+    pub(super) fn parse_list(&mut self) -> Result<Vec<Complex>, SelectorError> {
+        let mut list = Vec::new();
+        loop {
+            self.skip_whitespace();
+            list.push(self.parse_complex()?);
+            self.skip_whitespace();
+            match self.peek() {
+                None => return Ok(list),
+                Some(',') => self.pos += 1,
+                Some(c) => return Err(self.unexpected(c)),
+            }
+        }
+    }
+
+    fn parse_complex(&mut self) -> Result<Complex, SelectorError> {
+        let mut compounds = vec![self.parse_compound()?];
+        let mut combinators = Vec::new();
+
+        loop {
+            let had_space = self.skip_whitespace();
+            let combinator = match self.peek() {
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::SubsequentSibling,
+                Some(',') | None => break,
+                Some(_) if had_space => Combinator::Descendant,
+                Some(c) => return Err(self.unexpected(c)),
+            };
+            if combinator != Combinator::Descendant {
+                self.pos += 1;
+                self.skip_whitespace();
+            }
+            combinators.push(combinator);
+            compounds.push(self.parse_compound()?);
+        }
+
+        Ok(Complex {
+            compounds,
+            combinators,
+        })
+    }
+
+    fn parse_compound(&mut self) -> Result<Compound, SelectorError> {
+        let start = self.pos;
+        let mut compound = Compound::default();
+
+        match self.peek() {
+            Some('*') => self.pos += 1,
+            Some(c) if is_ident_start(c) => compound.tag = Some(self.parse_ident()?),
+            _ => {}
+        }
+
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.pos += 1;
+                    compound.ids.push(self.parse_ident()?);
+                }
+                Some('.') => {
+                    self.pos += 1;
+                    compound.classes.push(self.parse_ident()?);
+                }
+                Some('[') => {
+                    self.pos += 1;
+                    compound.attrs.push(self.parse_attr()?);
+                }
+                Some(':') => {
+                    self.pos += 1;
+                    compound.pseudos.push(self.parse_pseudo()?);
+                }
+                _ => break,
+            }
+        }
+
+        if self.pos == start {
+            return Err(match self.peek() {
+                Some(c) => self.unexpected(c),
+                None => SelectorError::UnexpectedEnd,
+            });
+        }
+        Ok(compound)
+    }
+
+    /// `[name]`, `[name=value]`, `[name^="value" i]` (after the `[`)
+    fn parse_attr(&mut self) -> Result<AttrSelector, SelectorError> {
+        self.skip_whitespace();
+        // Kept as written: SVG attributes like `viewBox` are stored camelCase
+        let name = self.parse_ident()?;
+        self.skip_whitespace();
+
+        let op = match self.peek() {
+            Some(']') => {
+                self.pos += 1;
+                return Ok(AttrSelector {
+                    name,
+                    op: AttrOp::Exists,
+                    value: String::new(),
+                    case_insensitive: false,
+                });
+            }
+            Some('=') => AttrOp::Equals,
+            Some(c @ ('~' | '|' | '^' | '$' | '*')) => {
+                self.pos += 1;
+                if self.peek() != Some('=') {
+                    return Err(self.unexpected_or_end());
+                }
+                match c {
+                    '~' => AttrOp::Includes,
+                    '|' => AttrOp::DashMatch,
+                    '^' => AttrOp::Prefix,
+                    '$' => AttrOp::Suffix,
+                    _ => AttrOp::Substring,
+                }
+            }
+            _ => return Err(self.unexpected_or_end()),
+        };
+        self.pos += 1;
+        self.skip_whitespace();
+
+        let value = match self.peek() {
+            Some(quote @ ('"' | '\'')) => self.parse_string(quote)?,
+            Some(_) => self.parse_ident()?,
+            None => return Err(SelectorError::UnexpectedEnd),
+        };
+        self.skip_whitespace();
+
+        let mut case_insensitive = false;
+        if let Some(flag @ ('i' | 'I' | 's' | 'S')) = self.peek() {
+            case_insensitive = flag.eq_ignore_ascii_case(&'i');
+            self.pos += 1;
+            self.skip_whitespace();
+        }
+
+        if self.peek() != Some(']') {
+            return Err(self.unexpected_or_end());
+        }
+        self.pos += 1;
+
+        Ok(AttrSelector {
+            name,
+            op,
+            value,
+            case_insensitive,
+        })
+    }
+
+    /// Pseudo-class name and optional argument (after the `:`)
+    fn parse_pseudo(&mut self) -> Result<Pseudo, SelectorError> {
+        let start = self.pos;
+        let name = self.parse_ident()?.to_ascii_lowercase();
+
+        let pseudo = match name.as_str() {
+            "first-child" => Pseudo::NthChild(Nth { a: 0, b: 1 }),
+            "last-child" => Pseudo::NthLastChild(Nth { a: 0, b: 1 }),
+            "only-child" => Pseudo::OnlyChild,
+            "empty" => Pseudo::Empty,
+            "root" => Pseudo::Root,
+            "nth-child" => Pseudo::NthChild(self.parse_argument(Self::parse_nth)?),
+            "nth-last-child" => Pseudo::NthLastChild(self.parse_argument(Self::parse_nth)?),
+            "family" => Pseudo::Family(self.parse_argument(Self::parse_ident)?),
+            "not" => Pseudo::Not(self.parse_argument(Self::parse_list)?),
+            _ => {
+                return Err(SelectorError::UnknownPseudoClass {
+                    name,
+                    position: start,
+                });
+            }
+        };
+        Ok(pseudo)
+    }
+
+    /// `( ... )` around a pseudo-class argument.
+    fn parse_argument<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, SelectorError>,
+    ) -> Result<T, SelectorError> {
+        if self.peek() != Some('(') {
+            return Err(self.unexpected_or_end());
+        }
+        self.pos += 1;
+
+        // Parse only up to the matching `)`
+        let close = self.find_closing_paren().ok_or(SelectorError::UnexpectedEnd)?;
+        let mut inner = Parser {
+            src: &self.src[..close],
+            pos: self.pos,
+        };
+        inner.skip_whitespace();
+        let value = parse(&mut inner)?;
+        inner.skip_whitespace();
+        if let Some(c) = inner.peek() {
+            return Err(inner.unexpected(c));
+        }
+
+        self.pos = close + 1;
+        Ok(value)
+    }
+
+    fn find_closing_paren(&self) -> Option<usize> {
+        let mut depth = 0usize;
+        let mut quote = None;
+        for (i, c) in self.src[self.pos..].char_indices() {
+            match (c, quote) {
+                ('"' | '\'', None) => quote = Some(c),
+                (c, Some(q)) if c == q => quote = None,
+                (_, Some(_)) => {}
+                ('(', None) => depth += 1,
+                (')', None) if depth == 0 => return Some(self.pos + i),
+                (')', None) => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// `odd`, `even`, `3`, `2n+1`, `-n+3`, `n`
+    fn parse_nth(&mut self) -> Result<Nth, SelectorError> {
+        let start = self.pos;
+        let rest = &self.src[self.pos..];
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | ' ')))
+            .unwrap_or(rest.len());
+        let expr: String = rest[..end].chars().filter(|c| *c != ' ').collect();
+        self.pos += end;
+
+        let invalid = || SelectorError::InvalidNth {
+            expr: expr.clone(),
+            position: start,
+        };
+        let expr_lower = expr.to_ascii_lowercase();
+
+        let nth = match expr_lower.as_str() {
+            "odd" => Nth { a: 2, b: 1 },
+            "even" => Nth { a: 2, b: 0 },
+            _ => match expr_lower.split_once('n') {
+                Some((a, b)) => {
+                    let a = match a {
+                        "" | "+" => 1,
+                        "-" => -1,
+                        _ => a.parse().map_err(|_| invalid())?,
+                    };
+                    let b = match b {
+                        "" => 0,
+                        _ if b.starts_with(['+', '-']) => b.parse().map_err(|_| invalid())?,
+                        _ => return Err(invalid()),
+                    };
+                    Nth { a, b }
+                }
+                None => Nth {
+                    a: 0,
+                    b: expr_lower.parse().map_err(|_| invalid())?,
+                },
+            },
+        };
+        Ok(nth)
+    }
+
+    /// Identifier with `\` escapes.
+    fn parse_ident(&mut self) -> Result<String, SelectorError> {
+        let mut ident = String::new();
+        while let Some(c) = self.peek() {
+            if c == '\\' {
+                self.pos += 1;
+                let escaped = self.peek().ok_or(SelectorError::UnexpectedEnd)?;
+                ident.push(escaped);
+                self.pos += escaped.len_utf8();
+            } else if is_ident_char(c) {
+                ident.push(c);
+                self.pos += c.len_utf8();
+            } else {
+                break;
+            }
+        }
+
+        if ident.is_empty() {
+            return Err(self.unexpected_or_end());
+        }
+        Ok(ident)
+    }
+
+    /// Quoted string (the opening quote is at the current position).
+    fn parse_string(&mut self, quote: char) -> Result<String, SelectorError> {
+        self.pos += 1;
+        let mut value = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+            match c {
+                '\\' => {
+                    let escaped = self.peek().ok_or(SelectorError::UnexpectedEnd)?;
+                    value.push(escaped);
+                    self.pos += escaped.len_utf8();
+                }
+                c if c == quote => return Ok(value),
+                c => value.push(c),
+            }
+        }
+        Err(SelectorError::UnexpectedEnd)
+    }
+
+    // -------------------------------------------------------------------------
+    // Helpers
+    // -------------------------------------------------------------------------
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    /// Skip whitespace, returning whether any was skipped.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while let Some(c) = self.peek()
+            && c.is_whitespace()
+        {
+            self.pos += c.len_utf8();
+        }
+        self.pos > start
+    }
+
+    fn unexpected(&self, found: char) -> SelectorError {
+        SelectorError::UnexpectedChar {
+            found,
+            position: self.pos,
+        }
+    }
+
+    fn unexpected_or_end(&self) -> SelectorError {
+        match self.peek() {
+            Some(c) => self.unexpected(c),
+            None => SelectorError::UnexpectedEnd,
+        }
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '-' || c == '\\' || !c.is_ascii()
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || !c.is_ascii()
+}
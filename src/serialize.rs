@@ -5,14 +5,26 @@
 
 use crate::core::{HasStableId, PhaseExt};
 use crate::node::Document;
+#[cfg(feature = "cache")]
+use crate::visit::{VisitControl, Visitor};
 
 /// Current schema version for cache validation.
 /// Increment this when making breaking changes to SerDocument structure.
-pub const SCHEMA_VERSION: u32 = 1;
+pub const SCHEMA_VERSION: u32 = 4;
 
 /// Magic bytes for tola-vdom cache files.
 const MAGIC: [u8; 4] = *b"TOLA";
 
+/// `SerText::kind` values.
+#[cfg(feature = "cache")]
+mod leaf_kind {
+    pub const TEXT: u8 = 0;
+    pub const RAW_TEXT: u8 = 1;
+    pub const COMMENT: u8 = 2;
+    pub const DOCTYPE: u8 = 3;
+    pub const PROCESSING_INSTRUCTION: u8 = 4;
+}
+
 #[cfg(feature = "cache")]
 mod concrete {
     //! Concrete serialization types using a flat structure.
@@ -31,8 +43,10 @@ mod concrete {
         pub schema_version: u32,
         /// All elements in the document (flattened)
         pub elements: Vec<SerElement>,
-        /// All text nodes in the document (flattened)
+        /// All text, comment, doctype and PI nodes in the document (flattened)
         pub texts: Vec<SerText>,
+        /// Prolog nodes as (is_element, index) pairs
+        pub prolog: Vec<(bool, u32)>,
         /// Root element index (always 0)
         pub root_idx: u32,
         /// Document metadata
@@ -55,13 +69,19 @@ mod concrete {
         /// is_element=true means index into elements[], false means index into texts[]
         pub children: Vec<(bool, u32)>,
         pub ext: SerExt,
+        /// Cached content hash, if the element had one
+        pub hash: Option<u64>,
     }
 
-    /// Serializable text node.
+    /// Serializable non-element node (text, comment, doctype or PI).
     #[derive(Archive, RkyvSerialize, RkyvDeserialize, Debug)]
     pub struct SerText {
+        /// Node kind (see `leaf_kind`)
+        pub kind: u8,
+        /// Text/comment/doctype content, or PI data
         pub content: String,
-        pub is_raw: bool,
+        /// PI target (empty for other kinds)
+        pub target: String,
         pub stable_id: u64,
     }
 
@@ -215,68 +235,151 @@ where
     P::TextExt: SerializableTextExt,
     P::DocExt: SerializableDocExt,
 {
-    let mut elements = Vec::new();
-    let mut texts = Vec::new();
+    let mut flattener = Flattener::default();
 
-    // Recursively flatten the tree
-    flatten_element(&doc.root, &mut elements, &mut texts);
+    // Root first so it always lands at index 0
+    let root_idx = flattener.flatten_element(&doc.root);
+    let prolog = flattener.flatten_children(&doc.prolog);
 
     SerDocument {
         magic: MAGIC,
         schema_version: SCHEMA_VERSION,
-        elements,
-        texts,
-        root_idx: 0,
+        elements: flattener.elements,
+        texts: flattener.texts,
+        prolog,
+        root_idx,
         meta: doc.meta.to_ser_doc_meta(),
     }
 }
 
+/// Visitor flattening a tree into index-linked `SerElement`/`SerText` lists.
+///
+/// Elements get indices in pre-order, so a child's index is always greater
+/// than its parent's.
+#[cfg(feature = "cache")]
+#[derive(Default)]
+struct Flattener {
+    elements: Vec<SerElement>,
+    texts: Vec<SerText>,
+    /// Child lists being collected: one per open element, plus the bottom
+    /// entry for top-level nodes
+    open: Vec<Vec<(bool, u32)>>,
+}
+
 #[cfg(feature = "cache")]
-fn flatten_element<P>(
-    elem: &crate::node::Element<P>,
-    elements: &mut Vec<SerElement>,
-    texts: &mut Vec<SerText>,
-) -> u32
+impl Flattener {
+    fn flatten_element<P>(&mut self, elem: &crate::node::Element<P>) -> u32
+    where
+        P: PhaseExt,
+        P::Ext: SerializableExt,
+        P::TextExt: SerializableTextExt,
+    {
+        let elem_idx = self.elements.len() as u32;
+        self.open.push(Vec::new());
+        crate::visit::walk(elem, self);
+        self.open.pop();
+        elem_idx
+    }
+
+    fn flatten_children<P>(&mut self, nodes: &[crate::node::Node<P>]) -> Vec<(bool, u32)>
+    where
+        P: PhaseExt,
+        P::Ext: SerializableExt,
+        P::TextExt: SerializableTextExt,
+    {
+        use crate::node::Node;
+
+        self.open.push(Vec::with_capacity(nodes.len()));
+        for node in nodes {
+            match node {
+                Node::Element(elem) => {
+                    crate::visit::walk(elem, self);
+                }
+                Node::Text(text) => {
+                    self.visit_text(text);
+                }
+                Node::Comment(comment) => {
+                    self.visit_comment(comment);
+                }
+                Node::Doctype(doctype) => {
+                    self.visit_doctype(doctype);
+                }
+                Node::ProcessingInstruction(pi) => {
+                    self.visit_processing_instruction(pi);
+                }
+            }
+        }
+        self.open.pop().unwrap_or_default()
+    }
+
+    fn push_leaf(&mut self, kind: u8, content: &str, target: &str, stable_id: u64) -> VisitControl {
+        let text_idx = self.texts.len() as u32;
+        self.texts.push(SerText {
+            kind,
+            content: content.to_string(),
+            target: target.to_string(),
+            stable_id,
+        });
+        if let Some(siblings) = self.open.last_mut() {
+            siblings.push((false, text_idx));
+        }
+        VisitControl::Continue
+    }
+}
+
+#[cfg(feature = "cache")]
+impl<P> Visitor<P> for Flattener
 where
     P: PhaseExt,
     P::Ext: SerializableExt,
     P::TextExt: SerializableTextExt,
 {
-    // Reserve our index
-    let elem_idx = elements.len() as u32;
-
-    // Create placeholder element (children will be filled in)
-    elements.push(SerElement {
-        tag: elem.tag.to_string(),
-        attrs: elem.attrs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
-        children: Vec::new(),
-        ext: elem.ext.to_ser_ext(),
-    });
-
-    // Process children
-    let mut children = Vec::new();
-    for child in &elem.children {
-        match child {
-            crate::node::Node::Element(child_elem) => {
-                let child_idx = flatten_element(child_elem, elements, texts);
-                children.push((true, child_idx));
-            }
-            crate::node::Node::Text(text) => {
-                let text_idx = texts.len() as u32;
-                texts.push(SerText {
-                    content: text.content.to_string(),
-                    is_raw: text.is_raw(),
-                    stable_id: text.ext.stable_id(),
-                });
-                children.push((false, text_idx));
-            }
+    fn enter_element(&mut self, elem: &crate::node::Element<P>) -> VisitControl {
+        let elem_idx = self.elements.len() as u32;
+        if let Some(siblings) = self.open.last_mut() {
+            siblings.push((true, elem_idx));
         }
+
+        // Children are filled in by leave_element
+        self.elements.push(SerElement {
+            tag: elem.tag.to_string(),
+            attrs: elem.attrs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            children: Vec::new(),
+            ext: elem.ext.to_ser_ext(),
+            hash: elem.cached_hash().map(|hash| hash.as_raw()),
+        });
+        self.open.push(Vec::with_capacity(elem.children.len()));
+        VisitControl::Continue
     }
 
-    // Update children
-    elements[elem_idx as usize].children = children;
+    fn leave_element(&mut self, _elem: &crate::node::Element<P>) -> VisitControl {
+        let children = self.open.pop().unwrap_or_default();
+        // The parent's list ends with this element's entry
+        if let Some(&(true, elem_idx)) = self.open.last().and_then(|s| s.last()) {
+            self.elements[elem_idx as usize].children = children;
+        }
+        VisitControl::Continue
+    }
+
+    fn visit_text(&mut self, text: &crate::node::Text<P>) -> VisitControl {
+        let kind = if text.is_raw() { leaf_kind::RAW_TEXT } else { leaf_kind::TEXT };
+        self.push_leaf(kind, &text.content, "", text.ext.stable_id())
+    }
 
-    elem_idx
+    fn visit_comment(&mut self, comment: &crate::node::Comment<P>) -> VisitControl {
+        self.push_leaf(leaf_kind::COMMENT, &comment.content, "", comment.ext.stable_id())
+    }
+
+    fn visit_doctype(&mut self, doctype: &crate::node::Doctype<P>) -> VisitControl {
+        self.push_leaf(leaf_kind::DOCTYPE, &doctype.content, "", doctype.ext.stable_id())
+    }
+
+    fn visit_processing_instruction(
+        &mut self,
+        pi: &crate::node::ProcessingInstruction<P>,
+    ) -> VisitControl {
+        self.push_leaf(leaf_kind::PROCESSING_INSTRUCTION, &pi.data, &pi.target, pi.ext.stable_id())
+    }
 }
 
 #[cfg(feature = "cache")]
@@ -289,10 +392,12 @@ where
 {
     let root_idx: u32 = archived.root_idx.into();
     let root = unflatten_element::<P>(root_idx as usize, &archived.elements, &archived.texts)?;
+    let prolog = unflatten_children::<P>(&archived.prolog, &archived.elements, &archived.texts)?;
     let meta = P::DocExt::from_ser_doc_meta(&archived.meta)?;
-    Ok(Document::with_meta(root, meta))
+    Ok(Document::with_meta(root, meta).with_prolog(prolog))
 }
 
+/// Rebuild the element at `idx` and its subtree (iteratively).
 #[cfg(feature = "cache")]
 fn unflatten_element<P>(
     idx: usize,
@@ -303,11 +408,61 @@ where
     P: PhaseExt,
     P::Ext: DeserializableExt + Default,
     P::TextExt: DeserializableTextExt + Default,
+{
+    use crate::node::Node;
+
+    // (element index, next child position, element under construction)
+    let mut stack = vec![(idx, 0, unflatten_shallow::<P>(idx, elements)?)];
+    loop {
+        let (elem_idx, next, elem) = stack.last_mut().unwrap();
+        let elem_idx = *elem_idx;
+
+        let Some(pair) = elements[elem_idx].children.get(*next) else {
+            let (_, _, done) = stack.pop().unwrap();
+            match stack.last_mut() {
+                Some((_, _, parent)) => parent.children.push(Node::Element(Box::new(done))),
+                None => return Ok(done),
+            }
+            continue;
+        };
+        *next += 1;
+
+        let is_element: bool = pair.0;
+        let child_idx: u32 = pair.1.into();
+        let child_idx = child_idx as usize;
+
+        if is_element {
+            // Pre-order indices rule out cycles in corrupt files
+            if child_idx <= elem_idx {
+                return Err(format!(
+                    "Element {} listed as child of element {} in cache file",
+                    child_idx, elem_idx
+                ));
+            }
+            stack.push((child_idx, 0, unflatten_shallow::<P>(child_idx, elements)?));
+        } else {
+            elem.children.push(unflatten_leaf::<P>(child_idx, texts)?);
+        }
+    }
+}
+
+/// Rebuild the element at `idx` without its children.
+#[cfg(feature = "cache")]
+fn unflatten_shallow<P>(
+    idx: usize,
+    elements: &rkyv::vec::ArchivedVec<ArchivedSerElement>,
+) -> Result<crate::node::Element<P>, String>
+where
+    P: PhaseExt,
+    P::Ext: DeserializableExt + Default,
 {
     use crate::attr::{AttrKey, AttrValue, Attrs};
-    use crate::node::{Element, Node, Text, TextKind};
+    use crate::id::ContentHash;
+    use crate::node::{Children, Element};
 
-    let archived = &elements[idx];
+    let archived = elements
+        .get(idx)
+        .ok_or_else(|| format!("Element index {} out of bounds in cache file", idx))?;
 
     let tag = archived.tag.as_str();
     let attrs = Attrs::from_pairs(archived.attrs.iter().map(|pair| {
@@ -317,27 +472,71 @@ where
 
     let mut elem = Element::with_ext(tag, ext);
     elem.attrs = attrs;
+    elem.children = Children::with_capacity(archived.children.len());
+    elem.set_cached_hash(archived.hash.as_ref().map(|hash| ContentHash::from_raw((*hash).into())));
+    Ok(elem)
+}
+
+#[cfg(feature = "cache")]
+fn unflatten_leaf<P>(
+    idx: usize,
+    texts: &rkyv::vec::ArchivedVec<ArchivedSerText>,
+) -> Result<crate::node::Node<P>, String>
+where
+    P: PhaseExt,
+    P::TextExt: DeserializableTextExt + Default,
+{
+    use crate::node::{Comment, Doctype, Node, ProcessingInstruction, Text, TextKind};
+
+    let text = texts
+        .get(idx)
+        .ok_or_else(|| format!("Text index {} out of bounds in cache file", idx))?;
+    let ext = P::TextExt::from_stable_id(text.stable_id.into());
+    let content = text.content.as_str();
+    let node = match text.kind {
+        leaf_kind::TEXT => Node::Text(Text::with_ext(content, ext)),
+        leaf_kind::RAW_TEXT => {
+            let mut t = Text::with_ext(content, ext);
+            t.kind = TextKind::Raw;
+            Node::Text(t)
+        }
+        leaf_kind::COMMENT => Node::Comment(Comment::with_ext(content, ext)),
+        leaf_kind::DOCTYPE => Node::Doctype(Doctype::with_ext(content, ext)),
+        leaf_kind::PROCESSING_INSTRUCTION => Node::ProcessingInstruction(
+            ProcessingInstruction::with_ext(text.target.as_str(), content, ext),
+        ),
+        kind => return Err(format!("Unknown node kind {} in cache file", kind)),
+    };
+    Ok(node)
+}
+
+#[cfg(feature = "cache")]
+fn unflatten_children<P>(
+    pairs: &rkyv::vec::ArchivedVec<rkyv::tuple::ArchivedTuple2<bool, rkyv::rend::u32_le>>,
+    elements: &rkyv::vec::ArchivedVec<ArchivedSerElement>,
+    texts: &rkyv::vec::ArchivedVec<ArchivedSerText>,
+) -> Result<crate::node::Children<P>, String>
+where
+    P: PhaseExt,
+    P::Ext: DeserializableExt + Default,
+    P::TextExt: DeserializableTextExt + Default,
+{
+    use crate::node::{Children, Node};
 
-    for pair in archived.children.iter() {
+    let mut children = Children::with_capacity(pairs.len());
+    for pair in pairs.iter() {
         let is_element: bool = pair.0;
         let child_idx: u32 = pair.1.into();
         let child_idx = child_idx as usize;
 
-        if is_element {
-            let child_elem = unflatten_element::<P>(child_idx, elements, texts)?;
-            elem.children.push(Node::Element(Box::new(child_elem)));
+        let node = if is_element {
+            Node::Element(Box::new(unflatten_element::<P>(child_idx, elements, texts)?))
         } else {
-            let text = &texts[child_idx];
-            let ext = P::TextExt::from_stable_id(text.stable_id.into());
-            let mut t = Text::with_ext(text.content.as_str(), ext);
-            if text.is_raw {
-                t.kind = TextKind::Raw;
-            }
-            elem.children.push(Node::Text(t));
-        }
+            unflatten_leaf::<P>(child_idx, texts)?
+        };
+        children.push(node);
     }
-
-    Ok(elem)
+    Ok(children)
 }
 
 // =============================================================================
@@ -370,3 +569,43 @@ where
 {
     Err("Deserialization requires 'cache' feature".to_string())
 }
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+    use crate::core::HasStableId;
+    use crate::families::LinkFamily;
+    use crate::node::Node;
+    use crate::render::{render_document, RenderConfig};
+    use crate::transform::Transform;
+    use crate::vdom;
+
+    #[vdom::families]
+    pub struct SerializeSite {
+        link: LinkFamily,
+    }
+
+    #[test]
+    fn test_roundtrip_markup_nodes() {
+        let html = "<?xml version=\"1.0\"?><!DOCTYPE html><html><body><!-- note --><p>a<script>1 < 2</script></p></body></html>";
+        let doc = SerializeSite::indexer().transform(SerializeSite::parse(html));
+
+        let bytes = to_bytes(&doc).unwrap();
+        let restored: Document<SerializeSite::Indexed> = from_bytes(&bytes).unwrap();
+
+        let config = RenderConfig::new(true, false);
+        assert_eq!(render_document(&restored, &config), render_document(&doc, &config));
+
+        let ids = |doc: &Document<SerializeSite::Indexed>| match &doc.prolog[1] {
+            Node::Doctype(d) => HasStableId::stable_id(&d.ext),
+            _ => panic!("expected doctype"),
+        };
+        assert_eq!(ids(&restored), ids(&doc));
+        assert!(restored.root.cached_hash().is_some());
+        assert_eq!(restored.content_hash(), doc.content_hash());
+    }
+}
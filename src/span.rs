@@ -9,6 +9,11 @@
 //! When `typst` is enabled, `SourceSpan` wraps the real `typst::syntax::Span`.
 //! When disabled, `SourceSpan` is a simple u64 that can store span data
 //! or be "detached" (None).
+//!
+//! Spans produced by the HTML parser additionally carry a [`SourceLocation`]
+//! (byte range plus line/column) so diagnostics can point at the source.
+
+use std::fmt;
 
 /// Source location span abstraction
 ///
@@ -20,17 +25,22 @@ pub struct SourceSpan {
     /// - When typst enabled: stores the raw u64 from typst::syntax::Span
     /// - When disabled: stores any u64 value, or None for detached
     inner: Option<u64>,
+    /// Resolved location in the source text (set by the HTML parser)
+    location: Option<SourceLocation>,
 }
 
 impl SourceSpan {
     /// Create a detached span (no source location)
     pub const fn detached() -> Self {
-        Self { inner: None }
+        Self {
+            inner: None,
+            location: None,
+        }
     }
 
     /// Check if this span is detached (has no source location)
     pub fn is_detached(&self) -> bool {
-        self.inner.is_none()
+        self.inner.is_none() && self.location.is_none()
     }
 
     /// Get the raw u64 value (if any)
@@ -40,7 +50,78 @@ impl SourceSpan {
 
     /// Create from a raw u64 value
     pub const fn from_raw(value: u64) -> Self {
-        Self { inner: Some(value) }
+        Self {
+            inner: Some(value),
+            location: None,
+        }
+    }
+
+    /// Create from a resolved source location
+    pub const fn from_location(location: SourceLocation) -> Self {
+        Self {
+            inner: None,
+            location: Some(location),
+        }
+    }
+
+    /// Get the resolved source location (if any)
+    pub fn location(&self) -> Option<SourceLocation> {
+        self.location
+    }
+}
+
+/// Byte range and line/column of a node in its source text.
+///
+/// Offsets are in bytes; `line` and `column` are 1-based, with the column
+/// counted in characters from the start of the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SourceLocation {
+    /// Byte offset of the first byte
+    pub start: u32,
+    /// Byte offset one past the last byte
+    pub end: u32,
+    /// Line of `start` (1-based)
+    pub line: u32,
+    /// Column of `start` (1-based, in characters)
+    pub column: u32,
+}
+
+impl SourceLocation {
+    /// Length of the range in bytes.
+    pub fn len(&self) -> usize {
+        (self.end - self.start) as usize
+    }
+
+    /// Check if the range is empty.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Text extension that can carry a source span.
+///
+/// Implemented by the macro-generated `RawTextExt` and `IndexedTextExt` so the
+/// parser can attach spans to text nodes and the indexer can carry them over.
+/// Phases without text spans use `()`.
+pub trait SpannedExt {
+    /// Create an extension holding `span`.
+    fn from_span(span: SourceSpan) -> Self;
+
+    /// The attached span, if any.
+    fn span(&self) -> Option<SourceSpan>;
+}
+
+impl SpannedExt for () {
+    fn from_span(_span: SourceSpan) -> Self {}
+
+    fn span(&self) -> Option<SourceSpan> {
+        None
     }
 }
 
@@ -5,10 +5,11 @@
 use thiserror::Error;
 
 use crate::id::StableId;
+use crate::span::SourceSpan;
 
 /// Error from a failed validation.
 #[derive(Debug, Error)]
-#[error("{validator}: {message}{}", format_context(.node_id, .source_hint))]
+#[error("{validator}: {message}{}", format_context(.node_id, .span, .source_hint))]
 pub struct ValidateError {
     /// Name/description of the validator that failed.
     pub validator: String,
@@ -18,13 +19,22 @@ pub struct ValidateError {
     pub node_id: Option<StableId>,
     /// Source hint for locating the error (e.g., line number, file path).
     pub source_hint: Option<String>,
+    /// Source span of the node that caused the error (if known).
+    pub span: Option<SourceSpan>,
 }
 
-fn format_context(node_id: &Option<StableId>, source_hint: &Option<String>) -> String {
+fn format_context(
+    node_id: &Option<StableId>,
+    span: &Option<SourceSpan>,
+    source_hint: &Option<String>,
+) -> String {
     let mut ctx = String::new();
     if let Some(id) = node_id {
         ctx.push_str(&format!(" [node: {}]", id));
     }
+    if let Some(loc) = span.and_then(|s| s.location()) {
+        ctx.push_str(&format!(" at {}", loc));
+    }
     if let Some(hint) = source_hint {
         ctx.push_str(&format!(" ({})", hint));
     }
@@ -39,6 +49,7 @@ impl ValidateError {
             message: message.into(),
             node_id: None,
             source_hint: None,
+            span: None,
         }
     }
 
@@ -53,6 +64,16 @@ impl ValidateError {
         self.source_hint = Some(hint.into());
         self
     }
+
+    /// Attach the source span of the node that caused this error.
+    ///
+    /// Detached spans are ignored.
+    pub fn with_span(mut self, span: SourceSpan) -> Self {
+        if !span.is_detached() {
+            self.span = Some(span);
+        }
+        self
+    }
 }
 
 /// Errors collected from all failed validations.
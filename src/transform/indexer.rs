@@ -7,7 +7,8 @@ use rustc_hash::FxHashMap;
 use crate::algo::StableHasher;
 use crate::core::{HasStableId, PhaseExt};
 use crate::id::{PageSeed, StableId};
-use crate::node::{Children, Document, Element, Node, Text};
+use crate::node::{Children, Comment, Document, Doctype, Element, ProcessingInstruction, Text};
+use crate::visit::Fold;
 
 use super::Transform;
 
@@ -65,11 +66,17 @@ where
 pub struct IndexStats {
     pub element_count: usize,
     pub text_count: usize,
+    /// Comments, doctypes and processing instructions
+    pub markup_count: usize,
 }
 
 /// Generic Indexer: Raw → Indexed
 ///
 /// Works with any phase system generated by `#[vdom::families]`.
+///
+/// Indexing is a [`Fold`], so it runs on an explicit stack and handles
+/// documents of any depth. Each element also gets its
+/// [`ContentHash`](crate::id::ContentHash) cached in `Element::hash`.
 pub struct Indexer<Raw, Indexed, F, TF, DF>
 where
     Raw: PhaseExt,
@@ -82,11 +89,30 @@ where
     index_fn: F,
     text_index_fn: TF,
     doc_index_fn: DF,
-    page_seed: u64,
     stats: IndexStats,
+    /// One scope per open element, above the page-level scope
+    scopes: Vec<IndexScope>,
     _marker: std::marker::PhantomData<(Raw, Indexed)>,
 }
 
+/// Seed and sibling occurrence counts for the children of one element.
+struct IndexScope {
+    /// StableId of the element owning this scope (unused for the page scope)
+    id: StableId,
+    seed: u64,
+    occurrences: FxHashMap<ContentKey, usize>,
+}
+
+impl IndexScope {
+    fn new(id: StableId, seed: u64) -> Self {
+        Self {
+            id,
+            seed,
+            occurrences: FxHashMap::default(),
+        }
+    }
+}
+
 impl<Raw, Indexed, F, TF, DF> Indexer<Raw, Indexed, F, TF, DF>
 where
     Raw: PhaseExt,
@@ -102,80 +128,103 @@ where
             index_fn,
             text_index_fn,
             doc_index_fn,
-            page_seed: 0,
             stats: IndexStats::default(),
+            scopes: vec![IndexScope::new(StableId::default(), 0)],
             _marker: std::marker::PhantomData,
         }
     }
 
     /// Set page seed for globally unique IDs.
     pub fn with_page_seed(mut self, seed: PageSeed) -> Self {
-        self.page_seed = seed.as_u64();
+        self.scopes = vec![IndexScope::new(StableId::default(), seed.as_u64())];
         self
     }
 
-    fn index_document(&mut self, doc: Document<Raw>) -> Document<Indexed> {
-        let root = self.index_element(doc.root, 0, self.page_seed);
-        let meta = self.doc_index_fn.index(doc.meta, self.stats.clone());
-
-        Document::with_meta(root, meta)
+    /// Occurrence of `key` among the current siblings, and the parent seed.
+    fn next_occurrence(&mut self, key: ContentKey) -> (usize, u64) {
+        let scope = self.scopes.last_mut().expect("page scope is never popped");
+        let occurrence = scope.occurrences.entry(key).or_insert(0);
+        let current = *occurrence;
+        *occurrence += 1;
+        (current, scope.seed)
     }
 
-    fn index_element(
+    /// Index a text-like node's extension, counting it in `stats`.
+    fn index_leaf(
         &mut self,
-        elem: Element<Raw>,
-        occurrence: usize,
-        parent_seed: u64,
-    ) -> Element<Indexed> {
+        key: ContentKey,
+        ext: Raw::TextExt,
+        make_id: impl FnOnce(usize, u64) -> StableId,
+    ) -> Indexed::TextExt {
+        match key {
+            ContentKey::Text { .. } => self.stats.text_count += 1,
+            _ => self.stats.markup_count += 1,
+        }
+        let (occurrence, parent_seed) = self.next_occurrence(key);
+        self.text_index_fn.index(ext, make_id(occurrence, parent_seed))
+    }
+}
+
+impl<Raw, Indexed, F, TF, DF> Fold<Raw, Indexed> for Indexer<Raw, Indexed, F, TF, DF>
+where
+    Raw: PhaseExt,
+    Indexed: PhaseExt,
+    Indexed::Ext: HasStableId,
+    F: IndexFn<Raw, Indexed>,
+    TF: TextIndexFn<Raw, Indexed>,
+    DF: DocIndexFn<Raw, Indexed>,
+{
+    fn enter_element(&mut self, elem: &Element<Raw>) {
         self.stats.element_count += 1;
 
+        let (occurrence, parent_seed) = self.next_occurrence(ContentKey::from_element(elem));
         let id = StableId::for_element(&elem.tag, &elem.attrs, &[], occurrence, parent_seed);
-        let my_seed = id.0;
-
-        let indexed_ext = self.index_fn.index(elem.ext, id);
-        let children = self.index_children(elem.children, my_seed);
+        self.scopes.push(IndexScope::new(id, id.0));
+    }
 
-        let mut indexed = Element::with_ext(elem.tag, indexed_ext);
-        indexed.attrs = elem.attrs;
-        indexed.children = children;
+    fn fold_element(&mut self, mut elem: Element<Raw>, children: Children<Indexed>) -> Element<Indexed> {
+        let scope = self.scopes.pop().expect("enter_element pushed a scope");
+        let indexed_ext = self.index_fn.index(std::mem::take(&mut elem.ext), scope.id);
+        let mut indexed = Element::from_other(elem, indexed_ext, children);
+        // Children are folded first, so this only hashes their cached hashes
+        indexed.set_cached_hash(Some(indexed.content_hash()));
         indexed
     }
 
-    fn index_children(
+    fn fold_text(&mut self, text: Text<Raw>) -> Text<Indexed> {
+        let key = ContentKey::text(&text.content);
+        let indexed_ext = self.index_leaf(key, text.ext, StableId::for_text);
+        // Preserve TextKind (Raw/Escaped) when transforming
+        let mut indexed_text = Text::with_ext(text.content, indexed_ext);
+        indexed_text.kind = text.kind;
+        indexed_text
+    }
+
+    fn fold_comment(&mut self, comment: Comment<Raw>) -> Comment<Indexed> {
+        let key = ContentKey::comment(&comment.content);
+        let indexed_ext = self.index_leaf(key, comment.ext, StableId::for_comment);
+        Comment::with_ext(comment.content, indexed_ext)
+    }
+
+    fn fold_doctype(&mut self, doctype: Doctype<Raw>) -> Doctype<Indexed> {
+        let indexed_ext = self.index_leaf(ContentKey::Doctype, doctype.ext, StableId::for_doctype);
+        Doctype::with_ext(doctype.content, indexed_ext)
+    }
+
+    fn fold_processing_instruction(
         &mut self,
-        children: Children<Raw>,
-        parent_seed: u64,
-    ) -> Children<Indexed> {
-        let mut occurrence_counts: FxHashMap<ContentKey, usize> = FxHashMap::default();
-
-        children
-            .into_iter()
-            .map(|child| {
-                let key = ContentKey::from_node(&child);
-                let occurrence = occurrence_counts.entry(key).or_insert(0);
-                let current = *occurrence;
-                *occurrence += 1;
-
-                self.index_node(child, current, parent_seed)
-            })
-            .collect()
-    }
-
-    fn index_node(&mut self, node: Node<Raw>, occurrence: usize, parent_seed: u64) -> Node<Indexed> {
-        match node {
-            Node::Element(elem) => {
-                Node::Element(Box::new(self.index_element(*elem, occurrence, parent_seed)))
-            }
-            Node::Text(text) => {
-                self.stats.text_count += 1;
-                let id = StableId::for_text(occurrence, parent_seed);
-                let indexed_ext = self.text_index_fn.index(text.ext, id);
-                // Preserve TextKind (Raw/Escaped) when transforming
-                let mut indexed_text = Text::with_ext(text.content, indexed_ext);
-                indexed_text.kind = text.kind;
-                Node::Text(indexed_text)
-            }
-        }
+        pi: ProcessingInstruction<Raw>,
+    ) -> ProcessingInstruction<Indexed> {
+        let key = ContentKey::processing_instruction(&pi.target);
+        let target = &pi.target;
+        let indexed_ext = self.index_leaf(key, pi.ext, |occurrence, parent_seed| {
+            StableId::for_processing_instruction(target, occurrence, parent_seed)
+        });
+        ProcessingInstruction::with_ext(pi.target, pi.data, indexed_ext)
+    }
+
+    fn fold_meta(&mut self, meta: Raw::DocExt) -> Indexed::DocExt {
+        self.doc_index_fn.index(meta, self.stats.clone())
     }
 }
 
@@ -191,7 +240,7 @@ where
     type To = Indexed;
 
     fn transform(mut self, doc: Document<Raw>) -> Document<Indexed> {
-        self.index_document(doc)
+        doc.fold(&mut self)
     }
 }
 
@@ -203,29 +252,42 @@ where
 enum ContentKey {
     Element { tag_hash: u64, key_hash: u64 },
     Text { content_hash: u64 },
+    Comment { content_hash: u64 },
+    Doctype,
+    ProcessingInstruction { target_hash: u64 },
 }
 
 impl ContentKey {
-    fn from_node<P: PhaseExt>(node: &Node<P>) -> Self {
-        match node {
-            Node::Element(elem) => {
-                let tag_hash = StableHasher::new().update_str(&elem.tag).finish();
-                let mut attr_hasher = StableHasher::new();
-                for (k, v) in &elem.attrs {
-                    if k == "id" || k == "key" || k.starts_with("data-key") {
-                        attr_hasher = attr_hasher.update_str(k).update_str(v);
-                    }
-                }
-                ContentKey::Element {
-                    tag_hash,
-                    key_hash: attr_hasher.finish(),
-                }
-            }
-            Node::Text(text) => {
-                let content_hash = StableHasher::new().update_str(&text.content).finish();
-                ContentKey::Text { content_hash }
+    fn from_element<P: PhaseExt>(elem: &Element<P>) -> Self {
+        let tag_hash = StableHasher::new().update_str(&elem.tag).finish();
+        let mut attr_hasher = StableHasher::new();
+        for (k, v) in &elem.attrs {
+            if k == "id" || k == "key" || k.starts_with("data-key") {
+                attr_hasher = attr_hasher.update_str(k).update_str(v);
             }
         }
+        ContentKey::Element {
+            tag_hash,
+            key_hash: attr_hasher.finish(),
+        }
+    }
+
+    fn text(content: &str) -> Self {
+        ContentKey::Text {
+            content_hash: StableHasher::new().update_str(content).finish(),
+        }
+    }
+
+    fn comment(content: &str) -> Self {
+        ContentKey::Comment {
+            content_hash: StableHasher::new().update_str(content).finish(),
+        }
+    }
+
+    fn processing_instruction(target: &str) -> Self {
+        ContentKey::ProcessingInstruction {
+            target_hash: StableHasher::new().update_str(target).finish(),
+        }
     }
 }
 
@@ -239,7 +301,7 @@ impl ContentKey {
 /// ```ignore
 /// let indexer = Indexer::simple(
 ///     MySite::index_ext,
-///     |_, id| IndexedTextExt { stable_id: id },
+///     |raw, id| IndexedTextExt { stable_id: id, span: raw.span },
 ///     |raw, stats| IndexedDocExt { source_path: raw.source_path, node_count: stats.element_count + stats.text_count },
 /// );
 /// ```
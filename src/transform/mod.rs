@@ -78,7 +78,7 @@ mod tests {
     fn make_indexer() -> impl crate::transform::Transform<TransformTestSite::Raw, To = TransformTestSite::Indexed> {
         Indexer::new(
             TransformTestSite::index_ext,
-            |_: TransformTestSite::RawTextExt, id| TransformTestSite::IndexedTextExt { stable_id: id },
+            |raw: TransformTestSite::RawTextExt, id| TransformTestSite::IndexedTextExt { stable_id: id, span: raw.span },
             |raw: TransformTestSite::RawDocExt, stats: IndexStats| TransformTestSite::IndexedDocExt {
                 source_path: raw.source_path,
                 node_count: stats.element_count + stats.text_count,
@@ -126,6 +126,52 @@ mod tests {
         assert_eq!(link_data.href.as_deref(), Some("https://example.com"));
     }
 
+    #[test]
+    fn test_deep_document_is_stack_safe() {
+        use crate::algo::{diff, PatchOp};
+        use crate::render::{render_document, render_patches, RenderConfig};
+
+        const DEPTH: usize = 100_000;
+
+        fn deep_doc(leaf: &str) -> Document<TransformTestSite::Raw> {
+            let mut elem = Element::new("div").text(leaf);
+            for _ in 0..DEPTH {
+                elem = Element::new("div").child(elem);
+            }
+            Document::new(elem)
+        }
+
+        let old = make_indexer().transform(deep_doc("old"));
+        let new = make_indexer().transform(deep_doc("new"));
+        assert_eq!(old.meta.node_count, DEPTH + 2);
+        assert_eq!(old.stats().max_depth, DEPTH + 1);
+        assert_eq!(old.root.text_content(), "old");
+        assert_eq!(old.select("div:only-child").unwrap().len(), DEPTH + 1);
+
+        let config = RenderConfig::new(false, false);
+        let html = render_document(&old, &config);
+        assert_eq!(html.matches("</div>").count(), DEPTH + 1);
+
+        #[cfg(feature = "cache")]
+        {
+            let bytes = crate::serialize::to_bytes(&old).unwrap();
+            let restored: Document<TransformTestSite::Indexed> =
+                crate::serialize::from_bytes(&bytes).unwrap();
+            assert_eq!(render_document(&restored, &config), html);
+        }
+
+        // Below max_depth the changed subtree is replaced whole
+        let result = diff(&old, &new);
+        assert!(!result.should_reload);
+        assert_eq!(result.ops.len(), 1);
+        assert!(matches!(result.ops[0], PatchOp::Replace { .. }));
+        assert_eq!(render_patches(&result.ops, &config).len(), 1);
+        assert!(diff(&old, &old.clone()).ops.is_empty());
+
+        let processed = make_processor().transform(old);
+        assert_eq!(render_document(&processed, &config), html);
+    }
+
     #[test]
     fn test_processor_basic() {
         // First, create and index a document
@@ -136,6 +182,8 @@ mod tests {
         let doc = Document::new(root);
         let indexed = make_indexer().transform(doc);
         let indexed_id = indexed.root.ext.stable_id();
+        let indexed_hash = indexed.root.cached_hash();
+        assert!(indexed_hash.is_some());
 
         // Now process
         let processed = make_processor().transform(indexed);
@@ -143,6 +191,11 @@ mod tests {
         // Verify processing
         assert_eq!(processed.root.family_name(), "heading");
         assert_eq!(processed.root.ext.stable_id(), indexed_id);
+        // Rehashed over the processed heading data
+        let mut unhashed = processed.root.clone();
+        unhashed.invalidate_hash();
+        assert_eq!(processed.root.cached_hash(), Some(unhashed.content_hash()));
+        assert_ne!(processed.root.cached_hash(), indexed_hash);
 
         // Verify processed heading data
         let heading_data = ExtractFamily::<HeadingFamily>::get(&processed.root.ext).unwrap();
@@ -328,7 +381,7 @@ mod async_tests {
         fn make_indexer() -> impl Transform<AsyncTestSite::Raw, To = AsyncTestSite::Indexed> {
             Indexer::new(
                 AsyncTestSite::index_ext,
-                |_: AsyncTestSite::RawTextExt, id| AsyncTestSite::IndexedTextExt { stable_id: id },
+                |raw: AsyncTestSite::RawTextExt, id| AsyncTestSite::IndexedTextExt { stable_id: id, span: raw.span },
                 |raw: AsyncTestSite::RawDocExt, stats: IndexStats| AsyncTestSite::IndexedDocExt {
                     source_path: raw.source_path,
                     node_count: stats.element_count + stats.text_count,
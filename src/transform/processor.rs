@@ -3,7 +3,8 @@
 //! Transforms Indexed → Processed using a user-provided processing function.
 
 use crate::core::{HasStableId, PhaseExt};
-use crate::node::{Children, Document, Element, Node, Text};
+use crate::node::{Children, Document, Element, Text};
+use crate::visit::Fold;
 
 use super::Transform;
 
@@ -42,6 +43,8 @@ where
 /// Generic Processor: Indexed → Processed
 ///
 /// Works with any phase system generated by `#[vdom::families]`.
+///
+/// Processing is a [`Fold`], so it handles documents of any depth.
 pub struct Processor<Indexed, Processed, F, DF>
 where
     Indexed: PhaseExt,
@@ -71,41 +74,35 @@ where
             _marker: std::marker::PhantomData,
         }
     }
+}
 
-    fn process_document(&self, doc: Document<Indexed>) -> Document<Processed> {
-        let root = self.process_element(doc.root);
-        let meta = self.doc_process_fn.process(&doc.meta);
-
-        Document::with_meta(root, meta)
-    }
-
-    fn process_element(&self, elem: Element<Indexed>) -> Element<Processed> {
-        let children = self.process_children(elem.children);
+impl<Indexed, Processed, F, DF> Fold<Indexed, Processed> for Processor<Indexed, Processed, F, DF>
+where
+    Indexed: PhaseExt,
+    Indexed::Ext: HasStableId,
+    Processed: PhaseExt,
+    F: ProcessFn<Indexed, Processed>,
+    DF: DocProcessFn<Indexed, Processed>,
+{
+    fn fold_element(&mut self, elem: Element<Indexed>, children: Children<Processed>) -> Element<Processed> {
         let processed_ext = self.process_fn.process(&elem.ext);
-
-        let mut processed = Element::with_ext(elem.tag, processed_ext);
-        processed.attrs = elem.attrs;
-        processed.children = children;
+        // Processed family data may hash differently; rehashing only feeds
+        // this element, as the children's hashes are already cached
+        let hashed = elem.cached_hash().is_some();
+        let mut processed = Element::from_other(elem, processed_ext, children);
+        if hashed {
+            processed.set_cached_hash(Some(processed.content_hash()));
+        }
         processed
     }
 
-    fn process_children(&self, children: Children<Indexed>) -> Children<Processed> {
-        children
-            .into_iter()
-            .map(|child| self.process_node(child))
-            .collect()
+    fn fold_text(&mut self, text: Text<Indexed>) -> Text<Processed> {
+        // Preserve TextKind (Raw/Escaped) when transforming
+        Text::from_other_default(text)
     }
 
-    fn process_node(&self, node: Node<Indexed>) -> Node<Processed> {
-        match node {
-            Node::Element(elem) => {
-                Node::Element(Box::new(self.process_element(*elem)))
-            }
-            Node::Text(text) => {
-                // Preserve TextKind (Raw/Escaped) when transforming
-                Node::Text(Text::from_other_default(text))
-            }
-        }
+    fn fold_meta(&mut self, meta: Indexed::DocExt) -> Processed::DocExt {
+        self.doc_process_fn.process(&meta)
     }
 }
 
@@ -119,7 +116,7 @@ where
 {
     type To = Processed;
 
-    fn transform(self, doc: Document<Indexed>) -> Document<Processed> {
-        self.process_document(doc)
+    fn transform(mut self, doc: Document<Indexed>) -> Document<Processed> {
+        doc.fold(&mut self)
     }
 }
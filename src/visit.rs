@@ -0,0 +1,700 @@
+//! Tree traversal: visitors and folds.
+//!
+//! - [`Visitor`] / [`VisitorMut`]: pre/post-order hooks over an existing tree,
+//!   with [`VisitControl`] to skip subtrees or stop early.
+//! - [`Fold`]: rebuild a tree into another phase (`Element<From>` →
+//!   `Element<To>`), bottom-up.
+//!
+//! All drivers use an explicit stack instead of recursion, so they stay
+//! stack-safe on arbitrarily deep documents.
+//!
+//! # Example
+//!
+//! ```ignore
+//! struct LinkCounter(usize);
+//!
+//! impl<P: PhaseExt> Visitor<P> for LinkCounter {
+//!     fn enter_element(&mut self, elem: &Element<P>) -> VisitControl {
+//!         if elem.is_tag("a") {
+//!             self.0 += 1;
+//!         }
+//!         VisitControl::Continue
+//!     }
+//! }
+//!
+//! let mut counter = LinkCounter(0);
+//! doc.walk(&mut counter);
+//! ```
+
+use crate::core::PhaseExt;
+use crate::node::{Children, Comment, Doctype, Document, Element, Node, ProcessingInstruction, Text};
+
+// =============================================================================
+// VisitControl
+// =============================================================================
+
+/// What a traversal should do after a hook returns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum VisitControl {
+    /// Keep going
+    #[default]
+    Continue,
+    /// Don't descend into this element's children (`leave_element` still
+    /// runs). Same as `Continue` when returned from a leaf or leave hook.
+    SkipChildren,
+    /// End the traversal immediately; no further hooks run
+    Stop,
+}
+
+use VisitControl::{Continue, SkipChildren, Stop};
+
+// =============================================================================
+// Visitor
+// =============================================================================
+
+/// Read-only tree visitor. All hooks default to `Continue`.
+///
+/// Driven by [`walk`] or [`Document::walk`].
+pub trait Visitor<P: PhaseExt> {
+    /// Called before an element's children.
+    fn enter_element(&mut self, _elem: &Element<P>) -> VisitControl {
+        Continue
+    }
+
+    /// Called after an element's children (also when they were skipped).
+    fn leave_element(&mut self, _elem: &Element<P>) -> VisitControl {
+        Continue
+    }
+
+    /// Called for each text node.
+    fn visit_text(&mut self, _text: &Text<P>) -> VisitControl {
+        Continue
+    }
+
+    /// Called for each comment.
+    fn visit_comment(&mut self, _comment: &Comment<P>) -> VisitControl {
+        Continue
+    }
+
+    /// Called for each doctype.
+    fn visit_doctype(&mut self, _doctype: &Doctype<P>) -> VisitControl {
+        Continue
+    }
+
+    /// Called for each processing instruction.
+    fn visit_processing_instruction(&mut self, _pi: &ProcessingInstruction<P>) -> VisitControl {
+        Continue
+    }
+}
+
+/// Mutable tree visitor. All hooks default to `Continue`.
+///
+/// `enter_element` sees the element with its children attached, so it may
+/// rewrite them before they are visited. Driven by [`walk_mut`] or
+/// [`Document::walk_mut`].
+pub trait VisitorMut<P: PhaseExt> {
+    /// Called before an element's children.
+    fn enter_element(&mut self, _elem: &mut Element<P>) -> VisitControl {
+        Continue
+    }
+
+    /// Called after an element's children (also when they were skipped).
+    fn leave_element(&mut self, _elem: &mut Element<P>) -> VisitControl {
+        Continue
+    }
+
+    /// Called for each text node.
+    fn visit_text(&mut self, _text: &mut Text<P>) -> VisitControl {
+        Continue
+    }
+
+    /// Called for each comment.
+    fn visit_comment(&mut self, _comment: &mut Comment<P>) -> VisitControl {
+        Continue
+    }
+
+    /// Called for each doctype.
+    fn visit_doctype(&mut self, _doctype: &mut Doctype<P>) -> VisitControl {
+        Continue
+    }
+
+    /// Called for each processing instruction.
+    fn visit_processing_instruction(&mut self, _pi: &mut ProcessingInstruction<P>) -> VisitControl {
+        Continue
+    }
+}
+
+/// Walk the subtree at `root`. Returns `false` if the visitor stopped early.
+pub fn walk<P, V>(root: &Element<P>, visitor: &mut V) -> bool
+where
+    P: PhaseExt,
+    V: Visitor<P> + ?Sized,
+{
+    match visitor.enter_element(root) {
+        Continue => {}
+        SkipChildren => return visitor.leave_element(root) != Stop,
+        Stop => return false,
+    }
+
+    // (element, index of the next child to visit)
+    let mut stack: Vec<(&Element<P>, usize)> = vec![(root, 0)];
+    while let Some((elem, next)) = stack.last_mut() {
+        let elem = *elem;
+        let Some(child) = elem.children.get(*next) else {
+            stack.pop();
+            if visitor.leave_element(elem) == Stop {
+                return false;
+            }
+            continue;
+        };
+        *next += 1;
+
+        let control = match child {
+            Node::Element(child) => match visitor.enter_element(child) {
+                Continue => {
+                    stack.push((child, 0));
+                    Continue
+                }
+                SkipChildren => visitor.leave_element(child),
+                Stop => Stop,
+            },
+            Node::Text(text) => visitor.visit_text(text),
+            Node::Comment(comment) => visitor.visit_comment(comment),
+            Node::Doctype(doctype) => visitor.visit_doctype(doctype),
+            Node::ProcessingInstruction(pi) => visitor.visit_processing_instruction(pi),
+        };
+        if control == Stop {
+            return false;
+        }
+    }
+    true
+}
+
+/// Walk the subtree at `root` mutably. Returns `false` if the visitor
+/// stopped early; the tree is left intact either way.
+///
+/// The cached content hashes of the elements entered are cleared, since the
+/// visitor may edit them or their descendants.
+pub fn walk_mut<P, V>(root: &mut Element<P>, visitor: &mut V) -> bool
+where
+    P: PhaseExt,
+    V: VisitorMut<P> + ?Sized,
+{
+    root.invalidate_hash();
+    match visitor.enter_element(root) {
+        Continue => {}
+        SkipChildren => return visitor.leave_element(root) != Stop,
+        Stop => return false,
+    }
+
+    // Children are detached while being visited and reattached on leave, so
+    // each frame owns its element and no `&mut` chain is held.
+    let mut stack = vec![OpenElement::open(Box::new(std::mem::replace(root, Element::new(""))))];
+    let mut stopped = false;
+
+    while let Some(frame) = stack.last_mut() {
+        let next = if stopped { None } else { frame.pending.next() };
+        let Some(mut node) = next else {
+            let mut elem = stack.pop().unwrap().close();
+            if !stopped && visitor.leave_element(&mut elem) == Stop {
+                stopped = true;
+            }
+            match stack.last_mut() {
+                Some(parent) => parent.done.push(Node::Element(elem)),
+                None => *root = *elem,
+            }
+            continue;
+        };
+
+        let control = match &mut node {
+            Node::Element(child) => {
+                child.invalidate_hash();
+                match visitor.enter_element(child) {
+                    Continue => {
+                        let Node::Element(child) = node else { unreachable!() };
+                        stack.push(OpenElement::open(child));
+                        continue;
+                    }
+                    SkipChildren => visitor.leave_element(child),
+                    Stop => Stop,
+                }
+            }
+            Node::Text(text) => visitor.visit_text(text),
+            Node::Comment(comment) => visitor.visit_comment(comment),
+            Node::Doctype(doctype) => visitor.visit_doctype(doctype),
+            Node::ProcessingInstruction(pi) => visitor.visit_processing_instruction(pi),
+        };
+        frame.done.push(node);
+        if control == Stop {
+            stopped = true;
+        }
+    }
+    !stopped
+}
+
+/// Element whose children are being visited by [`walk_mut`].
+struct OpenElement<P: PhaseExt> {
+    /// Element with `children` detached
+    elem: Box<Element<P>>,
+    /// Children not yet visited
+    pending: smallvec::IntoIter<[Node<P>; 8]>,
+    /// Children already visited
+    done: Children<P>,
+}
+
+impl<P: PhaseExt> OpenElement<P> {
+    fn open(mut elem: Box<Element<P>>) -> Self {
+        let children = std::mem::take(&mut elem.children);
+        Self {
+            done: Children::with_capacity(children.len()),
+            pending: children.into_iter(),
+            elem,
+        }
+    }
+
+    /// Reattach children (including any left unvisited after a stop).
+    fn close(mut self) -> Box<Element<P>> {
+        self.done.extend(self.pending);
+        self.elem.children = self.done;
+        self.elem
+    }
+}
+
+// =============================================================================
+// Fold
+// =============================================================================
+
+/// Phase-changing tree rebuild.
+///
+/// Elements are folded bottom-up: `fold_element` receives the element with
+/// its children removed, together with the already-folded children. Use
+/// `enter_element` for top-down context (it runs before the children are
+/// folded). Markup nodes default to a fresh extension.
+///
+/// Driven by [`fold`] or [`Document::fold`].
+pub trait Fold<From: PhaseExt, To: PhaseExt> {
+    /// Called before an element's children are folded.
+    fn enter_element(&mut self, _elem: &Element<From>) {}
+
+    /// Build the output element. `elem.children` is empty.
+    fn fold_element(&mut self, elem: Element<From>, children: Children<To>) -> Element<To>;
+
+    /// Convert a text node.
+    fn fold_text(&mut self, text: Text<From>) -> Text<To>;
+
+    /// Convert a comment.
+    fn fold_comment(&mut self, comment: Comment<From>) -> Comment<To> {
+        Comment::from_other_default(comment)
+    }
+
+    /// Convert a doctype.
+    fn fold_doctype(&mut self, doctype: Doctype<From>) -> Doctype<To> {
+        Doctype::from_other_default(doctype)
+    }
+
+    /// Convert a processing instruction.
+    fn fold_processing_instruction(
+        &mut self,
+        pi: ProcessingInstruction<From>,
+    ) -> ProcessingInstruction<To> {
+        ProcessingInstruction::from_other_default(pi)
+    }
+
+    /// Convert document metadata.
+    fn fold_meta(&mut self, _meta: From::DocExt) -> To::DocExt {
+        To::DocExt::default()
+    }
+}
+
+/// Fold the subtree at `root` into phase `To`.
+pub fn fold<From, To, F>(root: Element<From>, folder: &mut F) -> Element<To>
+where
+    From: PhaseExt,
+    To: PhaseExt,
+    F: Fold<From, To> + ?Sized,
+{
+    let mut stack = vec![FoldFrame::open(root, folder)];
+
+    loop {
+        let frame = stack.last_mut().unwrap();
+        let Some(node) = frame.pending.next() else {
+            let FoldFrame { elem, done, .. } = stack.pop().unwrap();
+            let folded = folder.fold_element(elem, done);
+            match stack.last_mut() {
+                Some(parent) => parent.done.push(Node::Element(Box::new(folded))),
+                None => return folded,
+            }
+            continue;
+        };
+
+        let folded = match node {
+            Node::Element(child) => {
+                stack.push(FoldFrame::open(*child, folder));
+                continue;
+            }
+            leaf => fold_leaf(leaf, folder),
+        };
+        frame.done.push(folded);
+    }
+}
+
+/// Fold a single node (elements fold their whole subtree).
+fn fold_node<From, To, F>(node: Node<From>, folder: &mut F) -> Node<To>
+where
+    From: PhaseExt,
+    To: PhaseExt,
+    F: Fold<From, To> + ?Sized,
+{
+    match node {
+        Node::Element(elem) => Node::Element(Box::new(fold(*elem, folder))),
+        leaf => fold_leaf(leaf, folder),
+    }
+}
+
+fn fold_leaf<From, To, F>(node: Node<From>, folder: &mut F) -> Node<To>
+where
+    From: PhaseExt,
+    To: PhaseExt,
+    F: Fold<From, To> + ?Sized,
+{
+    match node {
+        Node::Element(_) => unreachable!("elements are folded by the caller"),
+        Node::Text(text) => Node::Text(folder.fold_text(text)),
+        Node::Comment(comment) => Node::Comment(folder.fold_comment(comment)),
+        Node::Doctype(doctype) => Node::Doctype(folder.fold_doctype(doctype)),
+        Node::ProcessingInstruction(pi) => {
+            Node::ProcessingInstruction(folder.fold_processing_instruction(pi))
+        }
+    }
+}
+
+/// Element whose children are being folded by [`fold`].
+struct FoldFrame<From: PhaseExt, To: PhaseExt> {
+    elem: Element<From>,
+    pending: smallvec::IntoIter<[Node<From>; 8]>,
+    done: Children<To>,
+}
+
+impl<From: PhaseExt, To: PhaseExt> FoldFrame<From, To> {
+    fn open<F: Fold<From, To> + ?Sized>(mut elem: Element<From>, folder: &mut F) -> Self {
+        folder.enter_element(&elem);
+        let children = std::mem::take(&mut elem.children);
+        Self {
+            done: Children::with_capacity(children.len()),
+            pending: children.into_iter(),
+            elem,
+        }
+    }
+}
+
+// =============================================================================
+// Document API
+// =============================================================================
+
+impl<P: PhaseExt> Document<P> {
+    /// Walk the prolog and then the root element.
+    ///
+    /// Returns `false` if the visitor stopped early.
+    pub fn walk<V: Visitor<P> + ?Sized>(&self, visitor: &mut V) -> bool {
+        for node in &self.prolog {
+            let control = match node {
+                Node::Element(elem) => {
+                    if !walk(elem, visitor) {
+                        return false;
+                    }
+                    Continue
+                }
+                Node::Text(text) => visitor.visit_text(text),
+                Node::Comment(comment) => visitor.visit_comment(comment),
+                Node::Doctype(doctype) => visitor.visit_doctype(doctype),
+                Node::ProcessingInstruction(pi) => visitor.visit_processing_instruction(pi),
+            };
+            if control == Stop {
+                return false;
+            }
+        }
+        walk(&self.root, visitor)
+    }
+
+    /// Walk the prolog and then the root element mutably.
+    ///
+    /// Returns `false` if the visitor stopped early.
+    pub fn walk_mut<V: VisitorMut<P> + ?Sized>(&mut self, visitor: &mut V) -> bool {
+        for node in &mut self.prolog {
+            let control = match node {
+                Node::Element(elem) => {
+                    if !walk_mut(elem, visitor) {
+                        return false;
+                    }
+                    Continue
+                }
+                Node::Text(text) => visitor.visit_text(text),
+                Node::Comment(comment) => visitor.visit_comment(comment),
+                Node::Doctype(doctype) => visitor.visit_doctype(doctype),
+                Node::ProcessingInstruction(pi) => visitor.visit_processing_instruction(pi),
+            };
+            if control == Stop {
+                return false;
+            }
+        }
+        walk_mut(&mut self.root, visitor)
+    }
+
+    /// Rebuild this document into phase `To`.
+    pub fn fold<To, F>(self, folder: &mut F) -> Document<To>
+    where
+        To: PhaseExt,
+        F: Fold<P, To> + ?Sized,
+    {
+        let prolog: Children<To> = self
+            .prolog
+            .into_iter()
+            .map(|node| fold_node(node, folder))
+            .collect();
+        let root = fold(self.root, folder);
+        let meta = folder.fold_meta(self.meta);
+        Document {
+            prolog,
+            root,
+            meta,
+        }
+    }
+}
+
+// =============================================================================
+// Closure adapters
+// =============================================================================
+
+/// Visitor calling a closure on each element (pre-order).
+pub(crate) struct ElementFn<F>(pub(crate) F);
+
+impl<P: PhaseExt, F: FnMut(&Element<P>)> Visitor<P> for ElementFn<F> {
+    fn enter_element(&mut self, elem: &Element<P>) -> VisitControl {
+        (self.0)(elem);
+        Continue
+    }
+}
+
+/// Mutable visitor calling a closure on each element (pre-order).
+pub(crate) struct ElementFnMut<F>(pub(crate) F);
+
+impl<P: PhaseExt, F: FnMut(&mut Element<P>)> VisitorMut<P> for ElementFnMut<F> {
+    fn enter_element(&mut self, elem: &mut Element<P>) -> VisitControl {
+        (self.0)(elem);
+        Continue
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+    use crate::core::HasStableId;
+    use crate::families::{HeadingFamily, LinkFamily};
+    use crate::id::StableId;
+    use crate::vdom;
+
+    #[vdom::families]
+    pub struct VisitSite {
+        link: LinkFamily,
+        heading: HeadingFamily,
+    }
+
+    const HTML: &str = "<!DOCTYPE html><html><body>\
+        <h1>Title</h1><!-- note --><p>text <a href=\"/x\">link</a></p><nav><a href=\"/y\">y</a></nav>\
+        </body></html>";
+
+    /// Records events as strings.
+    #[derive(Default)]
+    struct Trace {
+        events: Vec<String>,
+        skip: Option<&'static str>,
+        stop_at: Option<&'static str>,
+    }
+
+    impl<P: PhaseExt> Visitor<P> for Trace {
+        fn enter_element(&mut self, elem: &Element<P>) -> VisitControl {
+            self.events.push(format!("<{}>", elem.tag));
+            if self.stop_at == Some(&*elem.tag) {
+                Stop
+            } else if self.skip == Some(&*elem.tag) {
+                SkipChildren
+            } else {
+                Continue
+            }
+        }
+
+        fn leave_element(&mut self, elem: &Element<P>) -> VisitControl {
+            self.events.push(format!("</{}>", elem.tag));
+            Continue
+        }
+
+        fn visit_text(&mut self, text: &Text<P>) -> VisitControl {
+            self.events.push(text.content.to_string());
+            Continue
+        }
+
+        fn visit_comment(&mut self, _comment: &Comment<P>) -> VisitControl {
+            self.events.push("comment".into());
+            Continue
+        }
+
+        fn visit_doctype(&mut self, _doctype: &Doctype<P>) -> VisitControl {
+            self.events.push("doctype".into());
+            Continue
+        }
+    }
+
+    #[test]
+    fn test_walk_order() {
+        let doc = VisitSite::parse(HTML);
+        let mut trace = Trace::default();
+        assert!(doc.walk(&mut trace));
+        assert_eq!(
+            trace.events.join(" "),
+            "doctype <html> <body> <h1> Title </h1> comment <p> text  <a> link </a> </p> \
+             <nav> <a> y </a> </nav> </body> </html>"
+        );
+    }
+
+    #[test]
+    fn test_walk_skip_and_stop() {
+        let doc = VisitSite::parse(HTML);
+
+        let mut trace = Trace {
+            skip: Some("p"),
+            ..Default::default()
+        };
+        assert!(walk(&doc.root, &mut trace));
+        assert!(trace.events.join(" ").contains("<p> </p> <nav>"));
+
+        let mut trace = Trace {
+            stop_at: Some("a"),
+            ..Default::default()
+        };
+        assert!(!doc.walk(&mut trace));
+        assert_eq!(trace.events.last().unwrap(), "<a>");
+        assert!(!trace.events.contains(&"</p>".to_string()));
+    }
+
+    struct Rewrite {
+        stop_at: Option<&'static str>,
+    }
+
+    impl<P: PhaseExt> VisitorMut<P> for Rewrite {
+        fn enter_element(&mut self, elem: &mut Element<P>) -> VisitControl {
+            if self.stop_at == Some(&*elem.tag) {
+                return Stop;
+            }
+            if elem.is_tag("nav") {
+                // Children replaced before descent are visited
+                elem.children.push(Node::Text(Text::new("added")));
+            }
+            Continue
+        }
+
+        fn leave_element(&mut self, elem: &mut Element<P>) -> VisitControl {
+            elem.set_attr("data-seen", "");
+            Continue
+        }
+
+        fn visit_text(&mut self, text: &mut Text<P>) -> VisitControl {
+            text.content = text.content.to_uppercase();
+            Continue
+        }
+    }
+
+    #[test]
+    fn test_walk_mut() {
+        let mut doc = VisitSite::parse(HTML);
+        assert!(doc.walk_mut(&mut Rewrite { stop_at: None }));
+        assert_eq!(doc.root.text_content(), "TITLETEXT LINKYADDED");
+        assert!(doc.elements().all(|e| e.has_attr("data-seen")));
+
+        // Stopping keeps the tree intact
+        let mut doc = VisitSite::parse(HTML);
+        assert!(!doc.walk_mut(&mut Rewrite { stop_at: Some("a") }));
+        assert_eq!(doc.root.text_content(), "TITLETEXT linky");
+        assert_eq!(doc.elements().count(), 7);
+        assert!(!doc.root.has_attr("data-seen"));
+    }
+
+    /// Raw → Indexed with sequential ids.
+    #[derive(Default)]
+    struct Numbering {
+        next: u64,
+        depth: usize,
+        max_depth: usize,
+    }
+
+    impl Numbering {
+        fn id(&mut self) -> StableId {
+            self.next += 1;
+            StableId::from_raw(self.next)
+        }
+    }
+
+    impl Fold<VisitSite::Raw, VisitSite::Indexed> for Numbering {
+        fn enter_element(&mut self, _elem: &Element<VisitSite::Raw>) {
+            self.depth += 1;
+            self.max_depth = self.max_depth.max(self.depth);
+        }
+
+        fn fold_element(
+            &mut self,
+            elem: Element<VisitSite::Raw>,
+            children: Children<VisitSite::Indexed>,
+        ) -> Element<VisitSite::Indexed> {
+            self.depth -= 1;
+            let ext = VisitSite::index_ext(elem.ext.clone(), self.id());
+            Element::from_other(elem, ext, children)
+        }
+
+        fn fold_text(&mut self, text: Text<VisitSite::Raw>) -> Text<VisitSite::Indexed> {
+            let stable_id = self.id();
+            Text::from_other(text, VisitSite::IndexedTextExt { stable_id, span: None })
+        }
+    }
+
+    #[test]
+    fn test_fold_changes_phase() {
+        let doc = VisitSite::parse(HTML);
+        let mut numbering = Numbering::default();
+        let indexed: Document<VisitSite::Indexed> = doc.fold(&mut numbering);
+
+        assert_eq!(numbering.max_depth, 4);
+        assert_eq!(indexed.doctype().unwrap().content, "html");
+        // Bottom-up: root is numbered last
+        assert_eq!(indexed.root.stable_id().as_raw(), numbering.next);
+        let h1 = indexed.find(|e| e.is_tag("h1")).unwrap();
+        assert_eq!(h1.family_name(), "heading");
+        assert_eq!(h1.children[0].as_text().unwrap().ext.stable_id().as_raw(), 1);
+        assert_eq!(h1.stable_id().as_raw(), 2);
+    }
+
+    #[test]
+    fn test_deep_tree_is_stack_safe() {
+        const DEPTH: usize = 5_000;
+
+        let mut root: Element<VisitSite::Raw> = Element::new("div").text("leaf");
+        for _ in 0..DEPTH {
+            root = Element::new("div").child(root);
+        }
+        let mut doc = Document::new(root);
+
+        let mut trace = Trace::default();
+        assert!(doc.walk(&mut trace));
+        assert_eq!(trace.events.len(), 2 * (DEPTH + 1) + 1);
+
+        assert!(doc.walk_mut(&mut Rewrite { stop_at: None }));
+        assert_eq!(doc.root.text_content(), "LEAF");
+
+        let mut numbering = Numbering::default();
+        let indexed: Document<VisitSite::Indexed> = doc.fold(&mut numbering);
+        assert_eq!(numbering.max_depth, DEPTH + 1);
+        assert_eq!(indexed.root.stable_id().as_raw(), DEPTH as u64 + 2);
+    }
+}
@@ -0,0 +1,1421 @@
+//! Wire format for patch batches.
+//!
+//! A [`PatchBatch`] carries rendered [`Patch`]es for one page together with
+//! the page's [`PageSeed`], the cached document version and, when the diff
+//! gave up, the [`ReloadReason`]. Two encodings are provided; both start
+//! with [`WIRE_VERSION`], and decoders reject any other version.
+//!
+//! # JSON
+//!
+//! For WebSocket text frames; understood by [`PATCH_RUNTIME_JS`]
+//! (`TolaPatch.applyBatch`):
+//!
+//! ```text
+//! {"v":9,"page":"9f2c…","version":7,"reload":null,"patches":[…]}
+//! ```
+//!
+//! - `page`: PageSeed as a hex string (u64 does not fit a JS number)
+//! - `version`: `CacheEntry::version`
+//! - `reload`: `null` when `patches` should be applied, otherwise the reason
+//!   as an object with a `type` and the variant's fields, e.g.
+//!   `{"type":"ops_limit_exceeded","limit":1000,"reached":1001}` or
+//!   `{"type":"script_src_changed","target":"1f"}`
+//! - `patches`: patch objects as produced by [`patches_to_json`]
+//!
+//! Unknown object fields are ignored when decoding.
+//!
+//! # Binary
+//!
+//! For binary frames. Integers are little-endian; `len` is an unsigned
+//! LEB128 varint; `str` is `len` followed by UTF-8 bytes; `id` is a u64.
+//!
+//! ```text
+//! magic "TVPB" | version u16 | page u64 | doc version u64
+//! | reload | count len | patch*
+//!
+//! reload = kind u8, then by kind:
+//!   0 none
+//!   1 DoctypeChanged
+//!   2 PrologChanged
+//!   3 ScriptSrcChanged  id
+//!   4 PolicyRequested   id str(reason)
+//!   5 OpsLimitExceeded  len(limit) len(reached)
+//!   6 PatchTooLarge     len(patch_bytes) len(page_bytes)
+//!
+//! patch  = tag u8, then by tag:
+//!   0 Replace          id str(html) svg u8
+//!   1 UpdateText       id str(text)
+//!   2 ReplaceChildren  id str(html) svg u8
+//!   3 Remove           id
+//!   4 Insert           anchor str(html) svg u8
+//!   5 Move             id anchor
+//!   6 UpdateAttrs      id len (str(name) u8 [str(value)])*
+//!   7 UpdateTextAt     id len(index) str(text)
+//!   8 RemoveTextAt     id len(index)
+//!   9 UpdateExt        id str(family) str(data)
+//!  10 Rekey            id len id*
+//!  11 UpdateClasses    id len str(add)* len str(remove)*
+//!  12 UpdateStyles     id len (str(name) str(value))* len str(remove)*
+//!  13 SpliceText       id len(index) len (len(start) len(delete) str(insert))*
+//!  14 UpdateTitle      id str(title)
+//!  15 UpdateMeta       id str(name) u8 [str(content)]
+//!  16 AddLink          id str(rel) str(href)
+//!  17 RemoveLink       id str(rel) str(href)
+//! anchor = kind u8 (0 After, 1 Before, 2 FirstChildOf, 3 LastChildOf,
+//!          4 ChildIndex) id [len(index) for ChildIndex]
+//! ```
+//!
+//! [`PATCH_RUNTIME_JS`]: crate::render::PATCH_RUNTIME_JS
+//! [`ReloadReason`]: crate::algo::ReloadReason
+//! [`patches_to_json`]: crate::render::patches_to_json
+
+use thiserror::Error;
+
+use std::borrow::Cow;
+
+use crate::algo::{Anchor, DiffResult, DiffResultRef, Patch, ReloadReason, TextSplice};
+use crate::attr::{AttrKey, AttrValue};
+use crate::core::{HasStableId, PhaseExt};
+use crate::id::{PageSeed, StableId};
+use crate::render::{render_patches, write_json_str, write_patch_json, RenderConfig};
+
+/// Current wire format version.
+/// Increment this when changing either encoding.
+pub const WIRE_VERSION: u16 = 9;
+
+/// Magic bytes starting a binary patch batch.
+const MAGIC: [u8; 4] = *b"TVPB";
+
+/// JSON nesting accepted when decoding (a batch needs 5).
+const MAX_JSON_DEPTH: usize = 16;
+
+/// Error decoding a patch batch.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum WireError {
+    /// The batch was encoded with a different wire version.
+    #[error("unsupported wire version {found} (expected {WIRE_VERSION})")]
+    UnsupportedVersion {
+        /// Version found in the batch
+        found: u64,
+    },
+
+    /// Binary batch does not start with the magic bytes.
+    #[error("invalid patch batch: expected magic bytes {MAGIC:?}, found {found:?}")]
+    InvalidMagic {
+        /// Bytes found instead
+        found: Vec<u8>,
+    },
+
+    /// The batch is truncated or not well-formed.
+    #[error("malformed patch batch at byte {position}: {message}")]
+    Malformed {
+        /// Byte offset where decoding failed
+        position: usize,
+        /// What was wrong
+        message: String,
+    },
+}
+
+impl WireError {
+    fn malformed(position: usize, message: impl Into<String>) -> Self {
+        Self::Malformed {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+// =============================================================================
+// PatchBatch
+// =============================================================================
+
+/// Rendered patches for one page, as sent to the browser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchBatch {
+    /// Page the patches apply to.
+    pub page: PageSeed,
+    /// Document version after applying the patches (`CacheEntry::version`).
+    pub version: u64,
+    /// Why the page must reload instead; `patches` is empty when set.
+    pub reload: Option<ReloadReason>,
+    /// Patches to apply in order.
+    pub patches: Vec<Patch>,
+}
+
+impl PatchBatch {
+    /// Create a batch of patches to apply.
+    pub fn new(page: PageSeed, version: u64, patches: Vec<Patch>) -> Self {
+        Self {
+            page,
+            version,
+            reload: None,
+            patches,
+        }
+    }
+
+    /// Create a batch telling the page to reload.
+    pub fn reload(page: PageSeed, version: u64, reason: ReloadReason) -> Self {
+        Self {
+            page,
+            version,
+            reload: Some(reason),
+            patches: Vec::new(),
+        }
+    }
+
+    /// Build a batch from a diff, rendering its operations with `config`.
+    pub fn from_diff<P>(
+        page: PageSeed,
+        version: u64,
+        result: &DiffResult<P>,
+        config: &RenderConfig,
+    ) -> Self
+    where
+        P: PhaseExt,
+        P::Ext: HasStableId + Clone,
+    {
+        if let Some(reason) = result.reload_reason.as_ref().filter(|_| result.should_reload) {
+            Self::reload(page, version, reason.clone())
+        } else {
+            Self::new(page, version, render_patches(&result.ops, config))
+        }
+    }
+
+    /// Build a batch from a [`diff_ref`](crate::algo::diff_ref) result, like
+    /// [`from_diff`](Self::from_diff).
+    pub fn from_diff_ref<P>(
+        page: PageSeed,
+        version: u64,
+        result: &DiffResultRef<'_, P>,
+        config: &RenderConfig,
+    ) -> Self
+    where
+        P: PhaseExt,
+        P::Ext: HasStableId + Clone,
+    {
+        if let Some(reason) = result.reload_reason.as_ref().filter(|_| result.should_reload) {
+            Self::reload(page, version, reason.clone())
+        } else {
+            Self::new(page, version, render_patches(&result.ops, config))
+        }
+    }
+
+    /// Check if the batch asks for a full reload.
+    pub fn is_reload(&self) -> bool {
+        self.reload.is_some()
+    }
+}
+
+// =============================================================================
+// JSON
+// =============================================================================
+
+/// Encode a batch as JSON.
+pub fn to_json(batch: &PatchBatch) -> String {
+    let mut output = format!(
+        "{{\"v\":{},\"page\":\"{:x}\",\"version\":{},\"reload\":",
+        WIRE_VERSION,
+        batch.page.as_u64(),
+        batch.version
+    );
+    match &batch.reload {
+        Some(reason) => write_reload_json(reason, &mut output),
+        None => output.push_str("null"),
+    }
+    output.push_str(",\"patches\":[");
+    for (i, patch) in batch.patches.iter().enumerate() {
+        if i > 0 {
+            output.push(',');
+        }
+        write_patch_json(patch, &mut output);
+    }
+    output.push_str("]}");
+    output
+}
+
+/// Decode a batch from JSON.
+pub fn from_json(json: &str) -> Result<PatchBatch, WireError> {
+    let mut parser = JsonParser { input: json, pos: 0 };
+    let batch = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.pos != json.len() {
+        return Err(WireError::malformed(parser.pos, "trailing characters"));
+    }
+
+    let found = field(&batch, "v")?.as_u64()?;
+    if found != u64::from(WIRE_VERSION) {
+        return Err(WireError::UnsupportedVersion { found });
+    }
+    let page = PageSeed(field(&batch, "page")?.as_hex()?);
+    let version = field(&batch, "version")?.as_u64()?;
+    let reload = match field(&batch, "reload")? {
+        (_, Json::Null) => None,
+        value => Some(reload_from_json(value)?),
+    };
+    let patches = field(&batch, "patches")?
+        .as_array()?
+        .iter()
+        .map(patch_from_json)
+        .collect::<Result<_, _>>()?;
+
+    Ok(PatchBatch {
+        page,
+        version,
+        reload,
+        patches,
+    })
+}
+
+fn write_reload_json(reason: &ReloadReason, output: &mut String) {
+    let kind = match reason {
+        ReloadReason::DoctypeChanged => "doctype_changed",
+        ReloadReason::PrologChanged => "prolog_changed",
+        ReloadReason::ScriptSrcChanged { .. } => "script_src_changed",
+        ReloadReason::PolicyRequested { .. } => "policy_requested",
+        ReloadReason::OpsLimitExceeded { .. } => "ops_limit_exceeded",
+        ReloadReason::PatchTooLarge { .. } => "patch_too_large",
+    };
+    output.push_str("{\"type\":\"");
+    output.push_str(kind);
+    output.push('"');
+    if let Some(target) = reason.target() {
+        output.push_str(",\"target\":\"");
+        output.push_str(&target.to_attr_value());
+        output.push('"');
+    }
+    match reason {
+        ReloadReason::PolicyRequested { reason, .. } => {
+            output.push_str(",\"reason\":");
+            write_json_str(reason, output);
+        }
+        ReloadReason::OpsLimitExceeded { limit, reached } => {
+            output.push_str(&format!(",\"limit\":{limit},\"reached\":{reached}"));
+        }
+        ReloadReason::PatchTooLarge { patch_bytes, page_bytes } => {
+            output.push_str(&format!(",\"patch_bytes\":{patch_bytes},\"page_bytes\":{page_bytes}"));
+        }
+        _ => {}
+    }
+    output.push('}');
+}
+
+fn reload_from_json(object: &Spanned) -> Result<ReloadReason, WireError> {
+    let target = || Ok::<_, WireError>(StableId::from_raw(field(object, "target")?.as_hex()?));
+    let size = |key: &str| offset(field(object, key)?);
+
+    let kind = field(object, "type")?;
+    let reason = match kind.as_str()? {
+        "doctype_changed" => ReloadReason::DoctypeChanged,
+        "prolog_changed" => ReloadReason::PrologChanged,
+        "script_src_changed" => ReloadReason::ScriptSrcChanged { target: target()? },
+        "policy_requested" => ReloadReason::PolicyRequested {
+            target: target()?,
+            reason: Cow::Owned(field(object, "reason")?.as_str()?.to_string()),
+        },
+        "ops_limit_exceeded" => ReloadReason::OpsLimitExceeded {
+            limit: size("limit")?,
+            reached: size("reached")?,
+        },
+        "patch_too_large" => ReloadReason::PatchTooLarge {
+            patch_bytes: size("patch_bytes")?,
+            page_bytes: size("page_bytes")?,
+        },
+        other => return Err(WireError::malformed(kind.0, format!("unknown reload type '{other}'"))),
+    };
+    Ok(reason)
+}
+
+fn patch_from_json(object: &Spanned) -> Result<Patch, WireError> {
+    let op = field(object, "op")?;
+    let target = || Ok::<_, WireError>(StableId::from_raw(field(object, "target")?.as_hex()?));
+    let string = |key: &str| field(object, key)?.as_str().map(str::to_string);
+
+    let patch = match op.as_str()? {
+        "replace" => Patch::Replace {
+            target: target()?,
+            html: string("html")?,
+            is_svg: field(object, "svg")?.as_bool()?,
+        },
+        "update_text" => Patch::UpdateText {
+            target: target()?,
+            text: string("text")?,
+        },
+        "replace_children" => Patch::ReplaceChildren {
+            target: target()?,
+            html: string("html")?,
+            is_svg: field(object, "svg")?.as_bool()?,
+        },
+        "update_text_at" => Patch::UpdateTextAt {
+            parent: target()?,
+            index: index(object)?,
+            text: string("text")?,
+        },
+        "remove_text_at" => Patch::RemoveTextAt {
+            parent: target()?,
+            index: index(object)?,
+        },
+        "splice_text" => {
+            let splices = field(object, "splices")?
+                .as_array()?
+                .iter()
+                .map(|splice| match splice.as_array()? {
+                    [start, delete, insert] => Ok(TextSplice {
+                        start: offset(start)?,
+                        delete: offset(delete)?,
+                        insert: insert.as_str()?.to_string(),
+                    }),
+                    _ => Err(WireError::malformed(splice.0, "expected [start, delete, insert]")),
+                })
+                .collect::<Result<_, _>>()?;
+            Patch::SpliceText {
+                parent: target()?,
+                index: index(object)?,
+                splices,
+            }
+        }
+        "remove" => Patch::Remove { target: target()? },
+        "insert" => Patch::Insert {
+            anchor: anchor_from_json(field(object, "anchor")?)?,
+            html: string("html")?,
+            is_svg: field(object, "svg")?.as_bool()?,
+        },
+        "move" => Patch::Move {
+            target: target()?,
+            to: anchor_from_json(field(object, "to")?)?,
+        },
+        "update_attrs" => {
+            let attrs = field(object, "attrs")?
+                .as_array()?
+                .iter()
+                .map(|pair| match pair.as_array()? {
+                    [name, (_, Json::Null)] => Ok((AttrKey::from(name.as_str()?), None)),
+                    [name, value] => Ok((
+                        AttrKey::from(name.as_str()?),
+                        Some(AttrValue::from(value.as_str()?)),
+                    )),
+                    _ => Err(WireError::malformed(pair.0, "expected [name, value]")),
+                })
+                .collect::<Result<_, _>>()?;
+            Patch::UpdateAttrs {
+                target: target()?,
+                attrs,
+            }
+        }
+        "update_classes" => Patch::UpdateClasses {
+            target: target()?,
+            add: strings(field(object, "add")?)?,
+            remove: strings(field(object, "remove")?)?,
+        },
+        "update_styles" => {
+            let set = field(object, "set")?
+                .as_array()?
+                .iter()
+                .map(|pair| match pair.as_array()? {
+                    [name, value] => Ok((AttrKey::from(name.as_str()?), AttrValue::from(value.as_str()?))),
+                    _ => Err(WireError::malformed(pair.0, "expected [name, value]")),
+                })
+                .collect::<Result<_, _>>()?;
+            Patch::UpdateStyles {
+                target: target()?,
+                set,
+                remove: strings(field(object, "remove")?)?,
+            }
+        }
+        "update_ext" => Patch::UpdateExt {
+            target: target()?,
+            family: string("family")?,
+            data: string("data")?,
+        },
+        "rekey" => Patch::Rekey {
+            target: target()?,
+            ids: field(object, "ids")?
+                .as_array()?
+                .iter()
+                .map(|id| Ok(StableId::from_raw(id.as_hex()?)))
+                .collect::<Result<_, WireError>>()?,
+        },
+        "update_title" => Patch::UpdateTitle {
+            target: target()?,
+            title: string("title")?,
+        },
+        "update_meta" => Patch::UpdateMeta {
+            target: target()?,
+            name: string("name")?,
+            content: match field(object, "content")? {
+                (_, Json::Null) => None,
+                content => Some(content.as_str()?.to_string()),
+            },
+        },
+        "add_link" => Patch::AddLink {
+            target: target()?,
+            rel: string("rel")?,
+            href: string("href")?,
+        },
+        "remove_link" => Patch::RemoveLink {
+            target: target()?,
+            rel: string("rel")?,
+            href: string("href")?,
+        },
+        other => {
+            return Err(WireError::malformed(op.0, format!("unknown patch op '{other}'")));
+        }
+    };
+    Ok(patch)
+}
+
+/// An array of class tokens or style property names.
+fn strings(array: &Spanned) -> Result<Vec<AttrValue>, WireError> {
+    array.as_array()?.iter().map(|item| Ok(AttrValue::from(item.as_str()?))).collect()
+}
+
+fn anchor_from_json(object: &Spanned) -> Result<Anchor, WireError> {
+    let id = StableId::from_raw(field(object, "id")?.as_hex()?);
+    let kind = field(object, "type")?;
+    match kind.as_str()? {
+        "after" => Ok(Anchor::After(id)),
+        "before" => Ok(Anchor::Before(id)),
+        "first_child_of" => Ok(Anchor::FirstChildOf(id)),
+        "last_child_of" => Ok(Anchor::LastChildOf(id)),
+        "child_index" => Ok(Anchor::ChildIndex(id, index(object)?)),
+        other => Err(WireError::malformed(kind.0, format!("unknown anchor type '{other}'"))),
+    }
+}
+
+/// The `index` member of an object value.
+fn index(object: &Spanned) -> Result<usize, WireError> {
+    let value = field(object, "index")?;
+    usize::try_from(value.as_u64()?).map_err(|_| WireError::malformed(value.0, "index out of range"))
+}
+
+fn offset(value: &Spanned) -> Result<usize, WireError> {
+    usize::try_from(value.as_u64()?).map_err(|_| WireError::malformed(value.0, "offset out of range"))
+}
+
+/// JSON value with the byte offset it starts at, for error positions.
+type Spanned = (usize, Json);
+
+/// Object member: key and value.
+type Member = (String, Spanned);
+
+/// Parsed JSON value. Only non-negative integers are supported as numbers,
+/// which is all the format uses.
+#[derive(Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Spanned>),
+    Object(Vec<Member>),
+}
+
+/// Typed accessors failing with the value's position.
+trait JsonAt {
+    fn as_object(&self) -> Result<&[Member], WireError>;
+    fn as_array(&self) -> Result<&[Spanned], WireError>;
+    fn as_str(&self) -> Result<&str, WireError>;
+    fn as_u64(&self) -> Result<u64, WireError>;
+    fn as_bool(&self) -> Result<bool, WireError>;
+    /// Hex string encoding of a u64 (StableId, PageSeed).
+    fn as_hex(&self) -> Result<u64, WireError>;
+}
+
+impl JsonAt for Spanned {
+    fn as_object(&self) -> Result<&[Member], WireError> {
+        match &self.1 {
+            Json::Object(members) => Ok(members),
+            _ => Err(WireError::malformed(self.0, "expected object")),
+        }
+    }
+
+    fn as_array(&self) -> Result<&[Spanned], WireError> {
+        match &self.1 {
+            Json::Array(items) => Ok(items),
+            _ => Err(WireError::malformed(self.0, "expected array")),
+        }
+    }
+
+    fn as_str(&self) -> Result<&str, WireError> {
+        match &self.1 {
+            Json::String(s) => Ok(s),
+            _ => Err(WireError::malformed(self.0, "expected string")),
+        }
+    }
+
+    fn as_u64(&self) -> Result<u64, WireError> {
+        match self.1 {
+            Json::Number(n) => Ok(n),
+            _ => Err(WireError::malformed(self.0, "expected integer")),
+        }
+    }
+
+    fn as_bool(&self) -> Result<bool, WireError> {
+        match self.1 {
+            Json::Bool(b) => Ok(b),
+            _ => Err(WireError::malformed(self.0, "expected boolean")),
+        }
+    }
+
+    fn as_hex(&self) -> Result<u64, WireError> {
+        u64::from_str_radix(self.as_str()?, 16)
+            .map_err(|_| WireError::malformed(self.0, "expected hex id"))
+    }
+}
+
+/// Member `key` of an object value.
+fn field<'a>(object: &'a Spanned, key: &str) -> Result<&'a Spanned, WireError> {
+    object
+        .as_object()?
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value)
+        .ok_or_else(|| WireError::malformed(object.0, format!("missing field '{key}'")))
+}
+
+struct JsonParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), WireError> {
+        self.skip_whitespace();
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(WireError::malformed(self.pos, format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Spanned, WireError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if depth > MAX_JSON_DEPTH {
+            return Err(WireError::malformed(start, "nesting too deep"));
+        }
+        let value = match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                } else {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        members.push((key, self.value(depth + 1)?));
+                        if !self.list_continues(b'}')? {
+                            break;
+                        }
+                    }
+                }
+                Json::Object(members)
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                } else {
+                    loop {
+                        items.push(self.value(depth + 1)?);
+                        if !self.list_continues(b']')? {
+                            break;
+                        }
+                    }
+                }
+                Json::Array(items)
+            }
+            Some(b'"') => Json::String(self.string()?),
+            Some(b'0'..=b'9') => {
+                let digits = self.input[start..]
+                    .bytes()
+                    .take_while(u8::is_ascii_digit)
+                    .count();
+                self.pos += digits;
+                if matches!(self.peek(), Some(b'.' | b'e' | b'E')) {
+                    return Err(WireError::malformed(start, "expected integer"));
+                }
+                let n = self.input[start..self.pos]
+                    .parse()
+                    .map_err(|_| WireError::malformed(start, "integer out of range"))?;
+                Json::Number(n)
+            }
+            _ => {
+                let rest = &self.input[start..];
+                let (value, len) = if rest.starts_with("null") {
+                    (Json::Null, 4)
+                } else if rest.starts_with("true") {
+                    (Json::Bool(true), 4)
+                } else if rest.starts_with("false") {
+                    (Json::Bool(false), 5)
+                } else {
+                    return Err(WireError::malformed(start, "expected value"));
+                };
+                self.pos += len;
+                value
+            }
+        };
+        Ok((start, value))
+    }
+
+    /// After a list element: `true` on `,`, `false` on the closing byte.
+    fn list_continues(&mut self, close: u8) -> Result<bool, WireError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b',') => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(byte) if byte == close => {
+                self.pos += 1;
+                Ok(false)
+            }
+            _ => Err(WireError::malformed(
+                self.pos,
+                format!("expected ',' or '{}'", close as char),
+            )),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, WireError> {
+        if self.peek() != Some(b'"') {
+            return Err(WireError::malformed(self.pos, "expected string"));
+        }
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let rest = &self.input[self.pos..];
+            let Some(end) = rest.find(['"', '\\']) else {
+                return Err(WireError::malformed(self.input.len(), "unterminated string"));
+            };
+            out.push_str(&rest[..end]);
+            self.pos += end + 1;
+            if rest.as_bytes()[end] == b'"' {
+                return Ok(out);
+            }
+
+            let escape_pos = self.pos - 1;
+            let c = match self.peek() {
+                Some(b'"') => '"',
+                Some(b'\\') => '\\',
+                Some(b'/') => '/',
+                Some(b'b') => '\u{8}',
+                Some(b'f') => '\u{c}',
+                Some(b'n') => '\n',
+                Some(b'r') => '\r',
+                Some(b't') => '\t',
+                Some(b'u') => {
+                    self.pos += 1;
+                    let high = self.hex4()?;
+                    let code = if (0xD800..0xDC00).contains(&high) {
+                        // Surrogate pair: a low surrogate escape must follow
+                        if !self.input[self.pos..].starts_with("\\u") {
+                            return Err(WireError::malformed(escape_pos, "unpaired surrogate"));
+                        }
+                        self.pos += 2;
+                        let low = self.hex4()?;
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return Err(WireError::malformed(escape_pos, "unpaired surrogate"));
+                        }
+                        0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                    } else {
+                        high
+                    };
+                    out.push(
+                        char::from_u32(code)
+                            .ok_or_else(|| WireError::malformed(escape_pos, "invalid unicode escape"))?,
+                    );
+                    continue;
+                }
+                _ => return Err(WireError::malformed(escape_pos, "invalid escape")),
+            };
+            self.pos += 1;
+            out.push(c);
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, WireError> {
+        let digits = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| WireError::malformed(self.pos, "expected 4 hex digits"))?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).expect("validated hex digits"))
+    }
+}
+
+// =============================================================================
+// Binary
+// =============================================================================
+
+mod tag {
+    pub const REPLACE: u8 = 0;
+    pub const UPDATE_TEXT: u8 = 1;
+    pub const REPLACE_CHILDREN: u8 = 2;
+    pub const REMOVE: u8 = 3;
+    pub const INSERT: u8 = 4;
+    pub const MOVE: u8 = 5;
+    pub const UPDATE_ATTRS: u8 = 6;
+    pub const UPDATE_TEXT_AT: u8 = 7;
+    pub const REMOVE_TEXT_AT: u8 = 8;
+    pub const UPDATE_EXT: u8 = 9;
+    pub const REKEY: u8 = 10;
+    pub const UPDATE_CLASSES: u8 = 11;
+    pub const UPDATE_STYLES: u8 = 12;
+    pub const SPLICE_TEXT: u8 = 13;
+    pub const UPDATE_TITLE: u8 = 14;
+    pub const UPDATE_META: u8 = 15;
+    pub const ADD_LINK: u8 = 16;
+    pub const REMOVE_LINK: u8 = 17;
+}
+
+/// Encode a batch in the compact binary format.
+pub fn to_bytes(batch: &PatchBatch) -> Vec<u8> {
+    let mut out = Vec::with_capacity(64);
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&WIRE_VERSION.to_le_bytes());
+    out.extend_from_slice(&batch.page.as_u64().to_le_bytes());
+    out.extend_from_slice(&batch.version.to_le_bytes());
+    put_reload(&mut out, batch.reload.as_ref());
+
+    put_len(&mut out, batch.patches.len());
+    for patch in &batch.patches {
+        match patch {
+            Patch::Replace { target, html, is_svg } => {
+                out.push(tag::REPLACE);
+                put_id(&mut out, *target);
+                put_str(&mut out, html);
+                out.push(u8::from(*is_svg));
+            }
+            Patch::UpdateText { target, text } => {
+                out.push(tag::UPDATE_TEXT);
+                put_id(&mut out, *target);
+                put_str(&mut out, text);
+            }
+            Patch::UpdateTextAt { parent, index, text } => {
+                out.push(tag::UPDATE_TEXT_AT);
+                put_id(&mut out, *parent);
+                put_len(&mut out, *index);
+                put_str(&mut out, text);
+            }
+            Patch::RemoveTextAt { parent, index } => {
+                out.push(tag::REMOVE_TEXT_AT);
+                put_id(&mut out, *parent);
+                put_len(&mut out, *index);
+            }
+            Patch::SpliceText { parent, index, splices } => {
+                out.push(tag::SPLICE_TEXT);
+                put_id(&mut out, *parent);
+                put_len(&mut out, *index);
+                put_len(&mut out, splices.len());
+                for splice in splices {
+                    put_len(&mut out, splice.start);
+                    put_len(&mut out, splice.delete);
+                    put_str(&mut out, &splice.insert);
+                }
+            }
+            Patch::ReplaceChildren { target, html, is_svg } => {
+                out.push(tag::REPLACE_CHILDREN);
+                put_id(&mut out, *target);
+                put_str(&mut out, html);
+                out.push(u8::from(*is_svg));
+            }
+            Patch::Remove { target } => {
+                out.push(tag::REMOVE);
+                put_id(&mut out, *target);
+            }
+            Patch::Insert { anchor, html, is_svg } => {
+                out.push(tag::INSERT);
+                put_anchor(&mut out, anchor);
+                put_str(&mut out, html);
+                out.push(u8::from(*is_svg));
+            }
+            Patch::Move { target, to } => {
+                out.push(tag::MOVE);
+                put_id(&mut out, *target);
+                put_anchor(&mut out, to);
+            }
+            Patch::UpdateAttrs { target, attrs } => {
+                out.push(tag::UPDATE_ATTRS);
+                put_id(&mut out, *target);
+                put_len(&mut out, attrs.len());
+                for (name, value) in attrs {
+                    put_str(&mut out, name);
+                    match value {
+                        Some(value) => {
+                            out.push(1);
+                            put_str(&mut out, value);
+                        }
+                        None => out.push(0),
+                    }
+                }
+            }
+            Patch::UpdateClasses { target, add, remove } => {
+                out.push(tag::UPDATE_CLASSES);
+                put_id(&mut out, *target);
+                put_strs(&mut out, add);
+                put_strs(&mut out, remove);
+            }
+            Patch::UpdateStyles { target, set, remove } => {
+                out.push(tag::UPDATE_STYLES);
+                put_id(&mut out, *target);
+                put_len(&mut out, set.len());
+                for (name, value) in set {
+                    put_str(&mut out, name);
+                    put_str(&mut out, value);
+                }
+                put_strs(&mut out, remove);
+            }
+            Patch::UpdateExt { target, family, data } => {
+                out.push(tag::UPDATE_EXT);
+                put_id(&mut out, *target);
+                put_str(&mut out, family);
+                put_str(&mut out, data);
+            }
+            Patch::Rekey { target, ids } => {
+                out.push(tag::REKEY);
+                put_id(&mut out, *target);
+                put_len(&mut out, ids.len());
+                for id in ids {
+                    put_id(&mut out, *id);
+                }
+            }
+            Patch::UpdateTitle { target, title } => {
+                out.push(tag::UPDATE_TITLE);
+                put_id(&mut out, *target);
+                put_str(&mut out, title);
+            }
+            Patch::UpdateMeta { target, name, content } => {
+                out.push(tag::UPDATE_META);
+                put_id(&mut out, *target);
+                put_str(&mut out, name);
+                match content {
+                    Some(content) => {
+                        out.push(1);
+                        put_str(&mut out, content);
+                    }
+                    None => out.push(0),
+                }
+            }
+            Patch::AddLink { target, rel, href } => {
+                out.push(tag::ADD_LINK);
+                put_id(&mut out, *target);
+                put_str(&mut out, rel);
+                put_str(&mut out, href);
+            }
+            Patch::RemoveLink { target, rel, href } => {
+                out.push(tag::REMOVE_LINK);
+                put_id(&mut out, *target);
+                put_str(&mut out, rel);
+                put_str(&mut out, href);
+            }
+        }
+    }
+    out
+}
+
+/// Decode a batch from the compact binary format.
+pub fn from_bytes(bytes: &[u8]) -> Result<PatchBatch, WireError> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    let magic = bytes.get(..MAGIC.len()).unwrap_or(bytes);
+    if magic != MAGIC {
+        return Err(WireError::InvalidMagic {
+            found: magic.to_vec(),
+        });
+    }
+    reader.pos = MAGIC.len();
+
+    let found = u16::from_le_bytes(reader.array()?);
+    if found != WIRE_VERSION {
+        return Err(WireError::UnsupportedVersion {
+            found: u64::from(found),
+        });
+    }
+    let page = PageSeed(reader.u64()?);
+    let version = reader.u64()?;
+    let reload = reader.reload()?;
+
+    let count = reader.len()?;
+    // Every patch takes at least 9 bytes, so a bogus count cannot over-allocate
+    let mut patches = Vec::with_capacity(count.min(bytes.len() / 9));
+    for _ in 0..count {
+        let tag_pos = reader.pos;
+        let patch = match reader.byte()? {
+            tag::REPLACE => Patch::Replace {
+                target: reader.id()?,
+                html: reader.string()?,
+                is_svg: reader.flag()?,
+            },
+            tag::UPDATE_TEXT => Patch::UpdateText {
+                target: reader.id()?,
+                text: reader.string()?,
+            },
+            tag::UPDATE_TEXT_AT => Patch::UpdateTextAt {
+                parent: reader.id()?,
+                index: reader.len()?,
+                text: reader.string()?,
+            },
+            tag::REMOVE_TEXT_AT => Patch::RemoveTextAt {
+                parent: reader.id()?,
+                index: reader.len()?,
+            },
+            tag::SPLICE_TEXT => {
+                let parent = reader.id()?;
+                let index = reader.len()?;
+                let len = reader.len()?;
+                let mut splices = Vec::with_capacity(len.min(bytes.len() / 3));
+                for _ in 0..len {
+                    splices.push(TextSplice {
+                        start: reader.len()?,
+                        delete: reader.len()?,
+                        insert: reader.string()?,
+                    });
+                }
+                Patch::SpliceText { parent, index, splices }
+            }
+            tag::REPLACE_CHILDREN => Patch::ReplaceChildren {
+                target: reader.id()?,
+                html: reader.string()?,
+                is_svg: reader.flag()?,
+            },
+            tag::REMOVE => Patch::Remove {
+                target: reader.id()?,
+            },
+            tag::INSERT => Patch::Insert {
+                anchor: reader.anchor()?,
+                html: reader.string()?,
+                is_svg: reader.flag()?,
+            },
+            tag::MOVE => Patch::Move {
+                target: reader.id()?,
+                to: reader.anchor()?,
+            },
+            tag::UPDATE_ATTRS => {
+                let target = reader.id()?;
+                let len = reader.len()?;
+                let mut attrs = Vec::with_capacity(len.min(bytes.len()));
+                for _ in 0..len {
+                    let name = AttrKey::from(reader.string()?);
+                    let value = if reader.flag()? {
+                        Some(AttrValue::from(reader.string()?))
+                    } else {
+                        None
+                    };
+                    attrs.push((name, value));
+                }
+                Patch::UpdateAttrs { target, attrs }
+            }
+            tag::UPDATE_CLASSES => Patch::UpdateClasses {
+                target: reader.id()?,
+                add: reader.strings()?,
+                remove: reader.strings()?,
+            },
+            tag::UPDATE_STYLES => {
+                let target = reader.id()?;
+                let len = reader.len()?;
+                let mut set = Vec::with_capacity(len.min(bytes.len()));
+                for _ in 0..len {
+                    set.push((AttrKey::from(reader.string()?), AttrValue::from(reader.string()?)));
+                }
+                Patch::UpdateStyles { target, set, remove: reader.strings()? }
+            }
+            tag::UPDATE_EXT => Patch::UpdateExt {
+                target: reader.id()?,
+                family: reader.string()?,
+                data: reader.string()?,
+            },
+            tag::REKEY => {
+                let target = reader.id()?;
+                let len = reader.len()?;
+                let mut ids = Vec::with_capacity(len.min(bytes.len() / 8));
+                for _ in 0..len {
+                    ids.push(reader.id()?);
+                }
+                Patch::Rekey { target, ids }
+            }
+            tag::UPDATE_TITLE => Patch::UpdateTitle {
+                target: reader.id()?,
+                title: reader.string()?,
+            },
+            tag::UPDATE_META => Patch::UpdateMeta {
+                target: reader.id()?,
+                name: reader.string()?,
+                content: if reader.flag()? { Some(reader.string()?) } else { None },
+            },
+            tag::ADD_LINK => Patch::AddLink {
+                target: reader.id()?,
+                rel: reader.string()?,
+                href: reader.string()?,
+            },
+            tag::REMOVE_LINK => Patch::RemoveLink {
+                target: reader.id()?,
+                rel: reader.string()?,
+                href: reader.string()?,
+            },
+            other => {
+                return Err(WireError::malformed(tag_pos, format!("unknown patch tag {other}")));
+            }
+        };
+        patches.push(patch);
+    }
+
+    if reader.pos != bytes.len() {
+        return Err(WireError::malformed(reader.pos, "trailing bytes"));
+    }
+    Ok(PatchBatch {
+        page,
+        version,
+        reload,
+        patches,
+    })
+}
+
+fn put_len(out: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let byte = (len & 0x7F) as u8;
+        len >>= 7;
+        if len == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn put_strs(out: &mut Vec<u8>, strs: &[AttrValue]) {
+    put_len(out, strs.len());
+    for s in strs {
+        put_str(out, s);
+    }
+}
+
+fn put_id(out: &mut Vec<u8>, id: StableId) {
+    out.extend_from_slice(&id.as_raw().to_le_bytes());
+}
+
+fn put_reload(out: &mut Vec<u8>, reason: Option<&ReloadReason>) {
+    let Some(reason) = reason else {
+        out.push(0);
+        return;
+    };
+    match reason {
+        ReloadReason::DoctypeChanged => out.push(1),
+        ReloadReason::PrologChanged => out.push(2),
+        ReloadReason::ScriptSrcChanged { target } => {
+            out.push(3);
+            put_id(out, *target);
+        }
+        ReloadReason::PolicyRequested { target, reason } => {
+            out.push(4);
+            put_id(out, *target);
+            put_str(out, reason);
+        }
+        ReloadReason::OpsLimitExceeded { limit, reached } => {
+            out.push(5);
+            put_len(out, *limit);
+            put_len(out, *reached);
+        }
+        ReloadReason::PatchTooLarge { patch_bytes, page_bytes } => {
+            out.push(6);
+            put_len(out, *patch_bytes);
+            put_len(out, *page_bytes);
+        }
+    }
+}
+
+fn put_anchor(out: &mut Vec<u8>, anchor: &Anchor) {
+    out.push(match anchor {
+        Anchor::After(_) => 0,
+        Anchor::Before(_) => 1,
+        Anchor::FirstChildOf(_) => 2,
+        Anchor::LastChildOf(_) => 3,
+        Anchor::ChildIndex(..) => 4,
+    });
+    put_id(out, anchor.target_id());
+    if let Anchor::ChildIndex(_, index) = anchor {
+        put_len(out, *index);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], WireError> {
+        let slice = self
+            .bytes
+            .get(self.pos..self.pos.saturating_add(n))
+            .ok_or_else(|| WireError::malformed(self.pos, "unexpected end of input"))?;
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], WireError> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn byte(&mut self) -> Result<u8, WireError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn flag(&mut self) -> Result<bool, WireError> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(WireError::malformed(self.pos - 1, "expected 0 or 1")),
+        }
+    }
+
+    fn u64(&mut self) -> Result<u64, WireError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn id(&mut self) -> Result<StableId, WireError> {
+        Ok(StableId::from_raw(self.u64()?))
+    }
+
+    fn len(&mut self) -> Result<usize, WireError> {
+        let start = self.pos;
+        let mut len: usize = 0;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.byte()?;
+            len |= usize::from(byte & 0x7F)
+                .checked_shl(shift)
+                .filter(|bits| bits >> shift == usize::from(byte & 0x7F))
+                .ok_or_else(|| WireError::malformed(start, "length overflow"))?;
+            if byte & 0x80 == 0 {
+                return Ok(len);
+            }
+        }
+        Err(WireError::malformed(start, "length overflow"))
+    }
+
+    fn string(&mut self) -> Result<String, WireError> {
+        let len = self.len()?;
+        let start = self.pos;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| WireError::malformed(start, "invalid UTF-8"))
+    }
+
+    fn strings(&mut self) -> Result<Vec<AttrValue>, WireError> {
+        let len = self.len()?;
+        let mut strs = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+        for _ in 0..len {
+            strs.push(AttrValue::from(self.string()?));
+        }
+        Ok(strs)
+    }
+
+    fn reload(&mut self) -> Result<Option<ReloadReason>, WireError> {
+        let pos = self.pos;
+        let reason = match self.byte()? {
+            0 => return Ok(None),
+            1 => ReloadReason::DoctypeChanged,
+            2 => ReloadReason::PrologChanged,
+            3 => ReloadReason::ScriptSrcChanged { target: self.id()? },
+            4 => ReloadReason::PolicyRequested {
+                target: self.id()?,
+                reason: Cow::Owned(self.string()?),
+            },
+            5 => ReloadReason::OpsLimitExceeded {
+                limit: self.len()?,
+                reached: self.len()?,
+            },
+            6 => ReloadReason::PatchTooLarge {
+                patch_bytes: self.len()?,
+                page_bytes: self.len()?,
+            },
+            kind => return Err(WireError::malformed(pos, format!("unknown reload kind {kind}"))),
+        };
+        Ok(Some(reason))
+    }
+
+    fn anchor(&mut self) -> Result<Anchor, WireError> {
+        let pos = self.pos;
+        let kind = self.byte()?;
+        let id = self.id()?;
+        match kind {
+            0 => Ok(Anchor::After(id)),
+            1 => Ok(Anchor::Before(id)),
+            2 => Ok(Anchor::FirstChildOf(id)),
+            3 => Ok(Anchor::LastChildOf(id)),
+            4 => Ok(Anchor::ChildIndex(id, self.len()?)),
+            _ => Err(WireError::malformed(pos, format!("unknown anchor kind {kind}"))),
+        }
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> PatchBatch {
+        let id = StableId::from_raw;
+        PatchBatch::new(
+            PageSeed::from_path("/blog/post.html"),
+            7,
+            vec![
+                Patch::Replace { target: id(u64::MAX), html: "<p>a &amp; b</p>".into(), is_svg: false },
+                Patch::UpdateText { target: id(0x7b), text: "quote \" slash \\ tab\t nul\0 ü 🎉".into() },
+                Patch::ReplaceChildren { target: id(1), html: "<path d=\"M0\"/>".into(), is_svg: true },
+                Patch::Remove { target: id(2) },
+                Patch::Insert { anchor: Anchor::Before(id(3)), html: "<!-- c -->".into(), is_svg: false },
+                Patch::Move { target: id(4), to: Anchor::LastChildOf(id(5)) },
+                Patch::Insert {
+                    anchor: Anchor::ChildIndex(id(5), 300),
+                    html: "<rect/>".into(),
+                    is_svg: true,
+                },
+                Patch::UpdateTextAt { parent: id(8), index: 2, text: "word".into() },
+                Patch::RemoveTextAt { parent: id(8), index: 0 },
+                Patch::SpliceText {
+                    parent: id(8),
+                    index: 1,
+                    splices: vec![
+                        TextSplice { start: 0, delete: 2, insert: "é😀\"".into() },
+                        TextSplice { start: 300, delete: 0, insert: String::new() },
+                    ],
+                },
+                Patch::UpdateAttrs {
+                    target: id(6),
+                    attrs: vec![("class".into(), Some("x".into())), ("hidden".into(), None)],
+                },
+                Patch::UpdateExt { target: id(9), family: "math".into(), data: "{\"tex\":\"x^2\"}".into() },
+                Patch::Rekey { target: id(10), ids: vec![id(11), id(u64::MAX)] },
+                Patch::UpdateClasses {
+                    target: id(12),
+                    add: vec!["is-new".into()],
+                    remove: vec!["a".into(), "b".into()],
+                },
+                Patch::UpdateStyles {
+                    target: id(13),
+                    set: vec![("color".into(), "red !important".into())],
+                    remove: vec!["--gap".into()],
+                },
+                Patch::UpdateTitle { target: id(14), title: "Tola \"docs\"".into() },
+                Patch::UpdateMeta {
+                    target: id(14),
+                    name: "og:description".into(),
+                    content: Some("A page".into()),
+                },
+                Patch::UpdateMeta { target: id(14), name: "robots".into(), content: None },
+                Patch::AddLink { target: id(14), rel: "stylesheet".into(), href: "/a.css".into() },
+                Patch::RemoveLink { target: id(14), rel: "icon".into(), href: "/old.png".into() },
+            ],
+        )
+    }
+
+    fn reasons() -> Vec<ReloadReason> {
+        let target = StableId::from_raw(u64::MAX);
+        vec![
+            ReloadReason::DoctypeChanged,
+            ReloadReason::PrologChanged,
+            ReloadReason::ScriptSrcChanged { target },
+            ReloadReason::PolicyRequested { target, reason: "math \"display\" changed".into() },
+            ReloadReason::OpsLimitExceeded { limit: 1000, reached: 1001 },
+            ReloadReason::PatchTooLarge { patch_bytes: 300, page_bytes: 200 },
+        ]
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let batch = sample();
+        assert_eq!(from_json(&to_json(&batch)).unwrap(), batch);
+
+        let target = StableId::from_raw(0x1f);
+        let reload = PatchBatch::reload(PageSeed::zero(), 3, ReloadReason::ScriptSrcChanged { target });
+        let json = to_json(&reload);
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"v":9,"page":"0","version":3,"#,
+                r#""reload":{"type":"script_src_changed","target":"1f"},"patches":[]}"#
+            )
+        );
+        assert_eq!(from_json(&json).unwrap(), reload);
+
+        for reason in reasons() {
+            let reload = PatchBatch::reload(PageSeed(1), 0, reason);
+            assert_eq!(from_json(&to_json(&reload)).unwrap(), reload);
+        }
+    }
+
+    #[test]
+    fn test_json_decode_tolerates_whitespace_and_unknown_fields() {
+        let json = r#" { "patches" : [ {"op":"remove","target":"ff","extra":[1,{}]} ],
+            "reload": null, "v": 9, "version": 2, "page": "a", "note": "ü🎉" } "#;
+        let batch = from_json(json).unwrap();
+        assert_eq!(batch.page, PageSeed(10));
+        assert_eq!(batch.patches, vec![Patch::Remove { target: StableId::from_raw(0xff) }]);
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let batch = sample();
+        let bytes = to_bytes(&batch);
+        assert_eq!(&bytes[..4], b"TVPB");
+        assert!(bytes.len() < to_json(&batch).len());
+        assert_eq!(from_bytes(&bytes).unwrap(), batch);
+
+        for reason in reasons() {
+            let reload = PatchBatch::reload(PageSeed(1), 0, reason);
+            assert_eq!(from_bytes(&to_bytes(&reload)).unwrap(), reload);
+        }
+    }
+
+    #[test]
+    fn test_runtime_wire_version_matches() {
+        let declaration = format!("var WIRE_VERSION = {WIRE_VERSION};");
+        assert!(crate::render::PATCH_RUNTIME_JS.contains(&declaration));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let json = to_json(&sample()).replacen("\"v\":9", "\"v\":1", 1);
+        assert_eq!(from_json(&json), Err(WireError::UnsupportedVersion { found: 1 }));
+        assert!(matches!(from_json("{\"v\":1"), Err(WireError::Malformed { .. })));
+        assert!(matches!(from_json(&"[".repeat(100)), Err(WireError::Malformed { .. })));
+        let unknown = r#"{"v":9,"page":"0","version":0,"reload":null,"patches":[{"op":"morph"}]}"#;
+        assert!(from_json(unknown).unwrap_err().to_string().contains("unknown patch op 'morph'"));
+        let unknown = r#"{"v":9,"page":"0","version":0,"reload":{"type":"restart"},"patches":[]}"#;
+        assert!(from_json(unknown).unwrap_err().to_string().contains("unknown reload type 'restart'"));
+
+        let mut bytes = to_bytes(&sample());
+        bytes[4] = 8;
+        assert_eq!(from_bytes(&bytes), Err(WireError::UnsupportedVersion { found: 8 }));
+        assert!(matches!(from_bytes(b"TOLA"), Err(WireError::InvalidMagic { .. })));
+
+        // Every truncation is rejected without panicking
+        let bytes = to_bytes(&sample());
+        for len in 0..bytes.len() {
+            assert!(from_bytes(&bytes[..len]).is_err(), "accepted {len} bytes");
+        }
+    }
+}
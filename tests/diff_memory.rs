@@ -0,0 +1,151 @@
+//! Benchmarks-as-tests: peak heap use of sibling-list diffs on 100k-child
+//! lists, measured with a counting global allocator.
+//!
+//! Everything runs in a single test so that no other test thread allocates
+//! while a measurement is in progress. Timings are printed, not asserted;
+//! see them with `cargo test --test diff_memory -- --nocapture`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use tola_vdom::algo::{diff, diff_ref, diff_sequences, DiffConfig, LcsResult};
+use tola_vdom::families::LinkFamily;
+use tola_vdom::transform::Transform;
+use tola_vdom::{vdom, Document, Element, Node, StableId};
+
+struct CountingAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let now = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(now, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+            let now = CURRENT.fetch_add(new_size, Ordering::Relaxed) + new_size;
+            PEAK.fetch_max(now, Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Run `f`, returning its result and the peak heap growth while it ran.
+fn measure<R>(name: &str, f: impl FnOnce() -> R) -> (R, usize) {
+    let baseline = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+    let start = Instant::now();
+    let result = f();
+    let peak = PEAK.load(Ordering::Relaxed) - baseline;
+    println!("{name}: {:?}, peak {} KiB", start.elapsed(), peak / 1024);
+    (result, peak)
+}
+
+const CHILDREN: u64 = 100_000;
+
+/// Heap budget per list entry (old plus new). A Myers trace of even 512
+/// frontiers would need over 8 KiB per entry.
+const BYTES_PER_ENTRY: usize = 160;
+
+fn ids(range: impl Iterator<Item = u64>) -> Vec<StableId> {
+    range.map(|n| StableId::from_raw(n + 1)).collect()
+}
+
+fn check_sequences(name: &str, old: &[StableId], new: &[StableId]) -> LcsResult {
+    let (result, peak) = measure(name, || diff_sequences(old, new));
+    let budget = BYTES_PER_ENTRY * (old.len() + new.len());
+    assert!(peak < budget, "{name}: peak {peak} bytes over budget {budget}");
+    result
+}
+
+#[vdom::families]
+pub struct MemorySite {
+    link: LinkFamily,
+}
+
+fn list_doc(items: impl Iterator<Item = u64>) -> Document<MemorySite::Indexed> {
+    let mut root: Element<MemorySite::Raw> = Element::new("ul");
+    for item in items {
+        root.push_elem(Element::new("li").attr("key", item.to_string()).text(item.to_string()));
+    }
+    MemorySite::indexer().transform(Document::new(root))
+}
+
+#[test]
+fn test_long_sibling_lists_use_bounded_memory() {
+    let old = ids(0..CHILDREN);
+
+    // Scattered small edits: well within the edit distance ceiling
+    let new: Vec<StableId> = old
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i % 1000 != 500)
+        .map(|(_, id)| *id)
+        .chain(ids(CHILDREN..CHILDREN + 100))
+        .collect();
+    let result = check_sequences("100k, 200 edits", &old, &new);
+    assert!(!result.stats.patience_fallback);
+    assert_eq!((result.stats.deleted, result.stats.inserted), (100, 100));
+
+    // Many edits: past the ceiling, handled by patience diff
+    let new: Vec<StableId> = old
+        .iter()
+        .enumerate()
+        .map(|(i, id)| if i % 50 == 0 { StableId::from_raw(CHILDREN * 2 + i as u64) } else { *id })
+        .collect();
+    let result = check_sequences("100k, 4000 edits", &old, &new);
+    assert!(result.stats.patience_fallback);
+    assert_eq!(result.stats.kept, CHILDREN as usize - 2000);
+
+    // Reversal: worst case for Myers, a plain move list for patience
+    let new: Vec<StableId> = old.iter().rev().copied().collect();
+    let result = check_sequences("100k, reversed", &old, &new);
+    assert!(result.stats.patience_fallback);
+    assert_eq!(result.stats.kept + result.stats.moved, CHILDREN as usize);
+
+    // Whole documents: a 100k-item list with a few items removed
+    let old_doc = list_doc(0..CHILDREN);
+    let new_doc = list_doc((0..CHILDREN).filter(|i| i % 10_000 != 0));
+    let (result, peak) = measure("100k-child document", || diff(&old_doc, &new_doc));
+    assert!(!result.should_reload);
+    assert_eq!(result.ops.len(), 10);
+    let budget = BYTES_PER_ENTRY * 2 * CHILDREN as usize;
+    assert!(peak < budget, "document diff: peak {peak} bytes over budget {budget}");
+
+    // A large insertion: owned ops copy the subtree, borrowed ones do not
+    let page = |list: Option<Element<MemorySite::Raw>>| {
+        let mut root: Element<MemorySite::Raw> = Element::new("main");
+        root.children.extend(list.map(|list| Node::Element(Box::new(list))));
+        MemorySite::indexer().transform(Document::new(root))
+    };
+    let mut list: Element<MemorySite::Raw> = Element::new("ul");
+    for item in 0..CHILDREN {
+        list.push_elem(Element::new("li").attr("key", item.to_string()).text(item.to_string()));
+    }
+    let (old_doc, new_doc) = (page(None), page(Some(list)));
+    let (result, owned_peak) = measure("100k-child insertion", || diff(&old_doc, &new_doc));
+    assert_eq!(result.ops.len(), 1);
+    drop(result);
+    let (result, borrowed_peak) =
+        measure("100k-child insertion, borrowed", || diff_ref(&old_doc, &new_doc, DiffConfig::default()));
+    assert_eq!(result.ops.len(), 1);
+    assert!(borrowed_peak * 100 < owned_peak, "borrowed peak {borrowed_peak}, owned peak {owned_peak}");
+}